@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use clap::Parser;
+use clap::ValueEnum;
+use verdist::network::latency::Latency;
+use vstd::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Mode {
+    /// Each client issues its next operation as soon as the previous one completes
+    Closed,
+    /// Each client issues operations following a Poisson arrival process at `--rate`
+    Open,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LatencyDist {
+    Constant,
+    Normal,
+    LogNormal,
+    /// Heavy tailed, with the given mean and standard deviation (messages are delivered
+    /// immediately if the mean is zero)
+    Pareto,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum PoolKind {
+    /// Poll the channels in turn (`FlawlessPool`)
+    Sequential,
+    /// Poll the channels concurrently, with a reader thread per channel (`ParallelPool`)
+    Parallel,
+}
+
+#[derive(Parser)]
+#[command(author, version, about, long_about=None)]
+pub(crate) struct Args {
+    #[arg(short, long, default_value_t = 5)]
+    pub(crate) n_servers: u64,
+
+    #[arg(short = 'c', long, default_value_t = 4)]
+    pub(crate) n_clients: u64,
+
+    /// Number of operations issued by each client
+    #[arg(long, default_value_t = 1000)]
+    pub(crate) n_ops: u64,
+
+    /// Stop issuing operations after this many seconds (overrides --n-ops when reached first)
+    #[arg(long)]
+    pub(crate) duration_secs: Option<u64>,
+
+    /// Fraction of operations which are reads (the remaining are writes)
+    #[arg(long, default_value_t = 0.9)]
+    pub(crate) read_ratio: f64,
+
+    #[arg(long, value_enum, default_value_t = Mode::Closed)]
+    pub(crate) mode: Mode,
+
+    /// Target operations per second per client (open loop only)
+    #[arg(long, default_value_t = 100.0)]
+    pub(crate) rate: f64,
+
+    /// Mean one-way message latency in microseconds
+    #[arg(long, default_value_t = 0)]
+    pub(crate) latency_us: u64,
+
+    /// Standard deviation of the one-way message latency in microseconds
+    #[arg(long, default_value_t = 0)]
+    pub(crate) stddev_us: u64,
+
+    /// Distribution of the one-way message latency
+    #[arg(long, value_enum, default_value_t = LatencyDist::Normal)]
+    pub(crate) latency_dist: LatencyDist,
+
+    /// Contact the fastest quorum first, and every server only if it has not replied after this
+    /// many milliseconds (by default, every server is contacted right away)
+    #[arg(long)]
+    pub(crate) thrifty_fallback_ms: Option<u64>,
+
+    /// How the clients poll their channels for replies
+    #[arg(long, value_enum, default_value_t = PoolKind::Sequential)]
+    pub(crate) pool: PoolKind,
+
+    /// Always write back on reads, even when the first round is unanimous
+    #[arg(long)]
+    pub(crate) no_unanimous_reads: bool,
+
+    /// Keep the diagnostics of the clients and servers (printing them slows every operation down)
+    #[arg(long)]
+    pub(crate) verbose: bool,
+
+    /// Id of the first client (clients get consecutive ids); allocated when absent
+    #[arg(long)]
+    pub(crate) first_client_id: Option<u64>,
+
+    /// Await the clients as futures on a single runtime, rather than running a thread for each
+    #[cfg(feature = "async")]
+    #[arg(long)]
+    pub(crate) async_clients: bool,
+}
+
+/// Latency of each link, as configured by the arguments
+pub(crate) fn latency(args: &Args) -> Latency {
+    let mean = Duration::from_micros(args.latency_us);
+    let stddev = Duration::from_micros(args.stddev_us);
+    match args.latency_dist {
+        LatencyDist::Constant => Latency::Constant(mean),
+        LatencyDist::Normal => Latency::Normal { mean, stddev },
+        LatencyDist::LogNormal => Latency::LogNormal { mean, stddev },
+        LatencyDist::Pareto if stddev.is_zero() => Latency::Constant(mean),
+        LatencyDist::Pareto => {
+            // mean = shape * scale / (shape - 1) and cv^2 = 1 / (shape * (shape - 2))
+            let cv = stddev.as_secs_f64() / mean.as_secs_f64();
+            let shape = 1.0 + (1.0 + 1.0 / (cv * cv)).sqrt();
+            let scale = mean.mul_f64((shape - 1.0) / shape);
+            Latency::Pareto { scale, shape }
+        }
+    }
+}
+
+/// Delay after which a thrifty broadcast falls back to every server, if thrifty broadcasts are on
+pub(crate) fn thrifty_fallback(args: &Args) -> Option<Duration> {
+    args.thrifty_fallback_ms.map(Duration::from_millis)
+}
+
+/// Whether the clients poll their channels concurrently
+pub(crate) fn parallel_pool(args: &Args) -> bool {
+    args.pool == PoolKind::Parallel
+}
+
+verus! {
+
+#[allow(unused)]
+#[verifier::external_type_specification]
+pub(crate) struct ExArgs(crate::cli::Args);
+
+pub(crate) assume_specification[ latency ](args: &Args) -> Latency
+;
+
+pub(crate) assume_specification[ thrifty_fallback ](args: &Args) -> Option<Duration>
+;
+
+pub(crate) assume_specification[ parallel_pool ](args: &Args) -> bool
+;
+
+} // verus!
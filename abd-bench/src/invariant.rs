@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use vstd::atomic::PermissionU64;
+use vstd::logatom::MutLinearizer;
+use vstd::logatom::ReadLinearizer;
+use vstd::prelude::*;
+
+use verdist::network::channel::Channel;
+#[cfg(verus_only)]
+use verdist::pool::connection_pool::channel_seq_to_map;
+#[cfg(verus_only)]
+use verdist::pool::connection_pool::lemma_channel_seq_to_map;
+use verdist::pool::ConnectionPool;
+
+use specs::abd::RegisterRead;
+use specs::abd::RegisterWrite;
+
+#[cfg(verus_only)]
+use abd::channel::ChannelInv;
+use abd::invariants::committed_to::ClientCtrToken;
+use abd::invariants::requests::RequestCtrToken;
+#[cfg(verus_only)]
+use abd::invariants::server_ids;
+#[cfg(verus_only)]
+use abd::invariants::StatePredicate;
+use abd::invariants::StateInvariant;
+
+use vlib::client_ids::ClientIdToken;
+
+verus! {
+
+#[allow(unused)]
+pub(crate) fn get_invariant_state<Pool, C, ML, RL>(
+    pool: &Pool,
+    client_id: u64,
+    client_id_token: Tracked<ClientIdToken>,
+    client_perm: Tracked<PermissionU64>,
+    request_perm: Tracked<PermissionU64>,
+    system_inv: &Tracked<Arc<StateInvariant<ML, RL>>>,
+) -> (r: (Tracked<ClientCtrToken>, Tracked<RequestCtrToken>, Tracked<Arc<StateInvariant<ML, RL>>>))
+    where
+    Pool: ConnectionPool<C = C>,
+    C: Channel<R = abd::proto::Response, S = abd::proto::Request, Id = (u64, u64)>,
+    ML: MutLinearizer<RegisterWrite>,
+    RL: ReadLinearizer<RegisterRead>,
+
+    requires
+        forall|cid: (u64, u64)| #[trigger]
+            pool.spec_channels().contains_key(cid) ==> {
+                &&& cid.0 == client_id
+                &&& system_inv@.constant().server_locs.contains_key(cid.1)
+            },
+        forall|server_id| #[trigger]
+            system_inv@.constant().server_locs.contains_key(server_id) ==> {
+                pool.spec_channels().contains_key((client_id, server_id))
+            },
+        client_perm@.value() == 0,
+        request_perm@.value() == 0,
+        client_id_token@.key() == client_id,
+        client_id_token@.id() == system_inv@.constant().client_ids_ids.fresh_id,
+        system_inv@.namespace() == abd::invariants::state_inv_id(),
+        system_inv@.constant().server_locs.len() == pool.spec_len(),
+    ensures
+        r.0@.key() == client_id,
+        r.0@.value().0 == 0,
+        r.0@.value().1 == client_perm@.id(),
+        r.0@.id() == r.2@.constant().commitments_ids.client_ctr_id,
+        r.1@.key() == client_id,
+        r.1@.value().0 == 0,
+        r.1@.value().1 == request_perm@.id(),
+        r.1@.id() == r.2@.constant().request_map_ids.request_ctr_id,
+        r.2@.namespace() == abd::invariants::state_inv_id(),
+        forall|cid: (u64, u64)| #[trigger]
+            pool.spec_channels().contains_key(cid) ==> {
+                &&& cid.0 == client_id
+                &&& r.2@.constant().server_locs.contains_key(cid.1)
+            },
+        forall|server_id| #[trigger]
+            r.2@.constant().server_locs.contains_key(server_id) ==> {
+                pool.spec_channels().contains_key((client_id, server_id))
+            },
+        r.2@ == system_inv@,
+        pool.spec_len() == r.2@.constant().server_locs.len(),  // TODO: superfluous
+{
+    let tracked state_inv;
+    proof {
+        state_inv = system_inv.borrow().clone();
+    }
+
+    let tracked mut client_ctr_token;
+    let tracked mut request_ctr_token;
+    vstd::open_atomic_invariant!(&state_inv => state => {
+        proof {
+            let tracked Tracked(client_id_token) = client_id_token;
+            let tracked Tracked(client_p) = client_perm;
+            let tracked Tracked(request_p) = request_perm;
+            let tracked (c, r) = state.login(client_id_token, client_p, request_p);
+            client_ctr_token = c;
+            request_ctr_token = r;
+        }
+
+        // XXX: not load bearing but good for debugging
+        assert(<abd::invariants::StatePredicate as vstd::invariant::InvariantPredicate<_, _>>::inv(state_inv.constant(), state));
+    });
+
+    (Tracked(client_ctr_token), Tracked(request_ctr_token), Tracked(state_inv))
+}
+
+/// The channels set up by `connect_all` connect to exactly the servers in the system, and carry
+/// its invariant
+pub(crate) proof fn lemma_connected_pool<C>(
+    channels: Seq<C>,
+    pool_channels: Map<(u64, u64), C>,
+    client_id: u64,
+    state_pred: StatePredicate,
+) where C: Channel<Id = (u64, u64), K = ChannelInv>
+    requires
+        pool_channels == channel_seq_to_map(channels),
+        channels.map_values(|c: C| c.spec_id()).no_duplicates(),
+        state_pred.server_locs.dom() == server_ids(channels.len() as u64),
+        forall|idx|
+            0 <= idx < channels.len() ==> #[trigger] channels[idx].spec_id() == (
+                client_id,
+                idx as u64,
+            ),
+        forall|idx|
+            0 <= idx < channels.len() ==> #[trigger] channels[idx].constant()
+                == ChannelInv::from_state_pred(state_pred),
+    ensures
+        forall|cid: (u64, u64)| #[trigger]
+            pool_channels.contains_key(cid) ==> {
+                &&& cid.0 == client_id
+                &&& state_pred.server_locs.contains_key(cid.1)
+                &&& pool_channels[cid].constant() == ChannelInv::from_state_pred(state_pred)
+            },
+        forall|server_id| #[trigger]
+            state_pred.server_locs.contains_key(server_id) ==> {
+                pool_channels.contains_key((client_id, server_id))
+            },
+{
+    lemma_channel_seq_to_map(channels, pool_channels);
+    let ids = channels.map_values(|c: C| c.spec_id());
+    assert forall|cid: (u64, u64)| #[trigger] pool_channels.contains_key(cid) implies {
+        &&& cid.0 == client_id
+        &&& state_pred.server_locs.contains_key(cid.1)
+        &&& pool_channels[cid].constant() == ChannelInv::from_state_pred(state_pred)
+    } by {
+        assert(ids.to_set().contains(cid));
+        let idx = choose|idx: int| 0 <= idx < ids.len() && ids[idx] == cid;
+        assert(channels[idx].spec_id() == cid);
+        assert(server_ids(channels.len() as u64).contains(cid.1));
+    }
+    assert forall|server_id| #[trigger] state_pred.server_locs.contains_key(server_id) implies {
+        pool_channels.contains_key((client_id, server_id))
+    } by {
+        assert(server_ids(channels.len() as u64).contains(server_id));
+        assert(channels[server_id as int].spec_id() == (client_id, server_id));
+    }
+}
+
+} // verus!
@@ -0,0 +1,478 @@
+use std::sync::Arc;
+
+use clap::Parser;
+use vstd::atomic::PAtomicU64;
+use vstd::invariant::AtomicInvariant;
+#[cfg(verus_only)]
+use vstd::logatom::ReadLinearizer;
+use vstd::prelude::*;
+
+#[cfg(feature = "async")]
+use verdist::futures;
+use verdist::network::channel::BufChannel;
+use verdist::network::channel::BufferPolicy;
+use verdist::network::channel::Channel;
+use verdist::network::channel::Connector;
+use verdist::network::error::ConnectError;
+use verdist::pool::ConnectionPool;
+use verdist::pool::FlawlessPool;
+use verdist::pool::ParallelPool;
+use verdist::pool::Thrifty;
+
+use specs::abd::AbdRegisterClient;
+use specs::abd::RegisterViewPredicate;
+use specs::abd::SharedReadPerm;
+use specs::abd::SharedRegisterView;
+use specs::abd::SharedWritePerm;
+#[cfg(verus_only)]
+use specs::abd::RegisterRead;
+#[cfg(verus_only)]
+use specs::abd::RegisterWrite;
+
+use abd::channel::ChannelInv;
+use abd::client::AbdPool;
+use abd::invariants::initialize_system;
+#[cfg(verus_only)]
+use abd::invariants::server_ids;
+use abd::invariants::RegisterView;
+use abd::invariants::StateInvariant;
+use abd::server::run_modelled_server;
+
+use vlib::client_ids::ClientIdToken;
+
+mod cli;
+mod error;
+mod invariant;
+mod stats;
+mod workload;
+
+use cli::Args;
+use error::Error;
+use invariant::get_invariant_state;
+#[cfg(verus_only)]
+use invariant::lemma_connected_pool;
+use stats::Stats;
+use workload::Op;
+use workload::Workload;
+
+verus! {
+
+fn connect<C, Conn>(
+    args: &Args,
+    connector: &Conn,
+    client_id: u64,
+    channel_inv: Ghost<ChannelInv>,
+) -> (r: Result<BufChannel<C>, ConnectError>) where
+    Conn: Connector<C>,
+    C: Channel<Id = (u64, u64), K = ChannelInv, R = abd::proto::Response, S = abd::proto::Request>,
+
+    ensures
+        r is Ok ==> r->Ok_0.constant() == channel_inv@,
+{
+    let ghost k = channel_inv@;
+    let mut channel = connector.connect(
+        client_id,
+        |_connector: &Conn, _client_id: u64| -> (r: Ghost<ChannelInv>)
+            ensures
+                r@ == k,
+            { Ghost(k) },
+    )?;
+    if args.latency_us > 0 || args.stddev_us > 0 {
+        channel.set_latency(cli::latency(args));
+    }
+    // the client has one request in flight at a time
+    Ok(BufChannel::with_policy(channel, BufferPolicy::LowWater))
+}
+
+/// Connects to every server, the one with id `server_id` through `connectors[server_id]`
+fn connect_all<C, Conn>(
+    args: &Args,
+    connectors: &[Conn],
+    client_id: u64,
+    channel_inv: Ghost<ChannelInv>,
+) -> (r: Result<Vec<BufChannel<C>>, ConnectError>) where
+    Conn: Connector<C>,
+    C: Channel<Id = (u64, u64), K = ChannelInv, R = abd::proto::Response, S = abd::proto::Request>,
+
+    ensures
+        r is Ok ==> {
+            let v = r->Ok_0;
+            &&& connectors.len() == v.len()
+            &&& forall|idx|
+                0 <= idx < v@.len() ==> #[trigger] v@[idx].spec_id() == (client_id, idx as u64)
+            &&& forall|idx| 0 <= idx < v@.len() ==> #[trigger] v@[idx].constant() == channel_inv@
+            &&& v@.map_values(|c: BufChannel<C>| c.spec_id()).no_duplicates()
+        },
+{
+    let mut v = Vec::with_capacity(connectors.len());
+    for server_id in 0..connectors.len()
+        invariant
+            v@.len() == server_id,
+            forall|idx|
+                0 <= idx < v@.len() ==> #[trigger] v@[idx].spec_id() == (client_id, idx as u64),
+            forall|idx| 0 <= idx < v@.len() ==> #[trigger] v@[idx].constant() == channel_inv@,
+    {
+        let conn = connect(args, &connectors[server_id], client_id, channel_inv)?;
+        // the connectors are expected in server id order
+        let (local_id, remote_id) = conn.id();
+        if local_id != client_id || remote_id != server_id as u64 {
+            return Err(ConnectError);
+        }
+        v.push(conn);
+    }
+
+    proof {
+        let ids = v@.map_values(|c: BufChannel<C>| c.spec_id());
+        assert forall|i, j| 0 <= i < ids.len() && 0 <= j < ids.len() && i != j implies ids[i]
+            != ids[j] by {
+            assert(ids[i] == (client_id, i as u64));
+            assert(ids[j] == (client_id, j as u64));
+        }
+    }
+    Ok(v)
+}
+
+/// Namespace of the register view shared by the clients (see [`share_view`])
+pub open spec fn register_view_id() -> int {
+    2int
+}
+
+/// Shares the register view handed out by the bootstrap between the clients
+fn share_view(view: Tracked<RegisterView>) -> (r: Tracked<Arc<SharedRegisterView>>)
+    ensures
+        r@.constant() == view@.id(),
+        r@.namespace() == register_view_id(),
+{
+    let tracked shared;
+    proof {
+        let tracked view = view.get();
+        let tracked inv = AtomicInvariant::<_, _, RegisterViewPredicate>::new(
+            view.id(),
+            view,
+            register_view_id(),
+        );
+        shared = Arc::new(inv);
+    }
+    Tracked(shared)
+}
+
+/// Runs a single benchmark client until the workload is exhausted
+///
+/// The client stops at the first error
+fn run_client<C, Conn>(
+    args: &Args,
+    connectors: &[Conn],
+    client_id: u64,
+    client_id_token: Tracked<ClientIdToken>,
+    workload: &mut Workload,
+    state_inv: &Tracked<Arc<StateInvariant<SharedWritePerm, SharedReadPerm>>>,
+    view: &Tracked<Arc<SharedRegisterView>>,
+) -> Result<(), Error<SharedWritePerm, (), SharedReadPerm, ()>> where
+    Conn: Connector<C> + Send + Sync,
+    C: Channel<
+        K = abd::channel::ChannelInv,
+        R = abd::proto::Response,
+        S = abd::proto::Request,
+        Id = (u64, u64),
+    >,
+    C: Sync + Send + 'static,
+
+    requires
+        connectors.len() > 0,
+        state_inv@.namespace() == abd::invariants::state_inv_id(),
+        state_inv@.constant().register_id == view@.constant(),
+        state_inv@.constant().server_locs.dom() == server_ids(connectors.len() as u64),
+        state_inv@.constant().server_locs.len() == connectors.len(),
+        view@.namespace() == register_view_id(),
+        client_id_token@.key() == client_id,
+        client_id_token@.id() == state_inv@.constant().client_ids_ids.fresh_id,
+{
+    let ghost state_pred = state_inv@.constant();
+    let channels = connect_all(
+        args,
+        connectors,
+        client_id,
+        Ghost(ChannelInv::from_state_pred(state_pred)),
+    )?;
+    let ghost channel_seq = channels@;
+    if cli::parallel_pool(args) {
+        let pool = ParallelPool::new(channels);
+        assert(pool.spec_len() == connectors.len());
+        proof {
+            lemma_connected_pool(channel_seq, pool.spec_channels(), client_id, state_pred);
+        }
+        run_workload(args, pool, client_id, client_id_token, workload, state_inv, view)
+    } else {
+        let pool = FlawlessPool::new(channels);
+        assert(pool.spec_len() == connectors.len());
+        proof {
+            lemma_connected_pool(channel_seq, pool.spec_channels(), client_id, state_pred);
+        }
+        run_workload(args, pool, client_id, client_id_token, workload, state_inv, view)
+    }
+}
+
+/// Runs the workload of a client over `pool`, the channels connecting it to every server
+fn run_workload<Pool, C>(
+    args: &Args,
+    pool: Pool,
+    client_id: u64,
+    client_id_token: Tracked<ClientIdToken>,
+    workload: &mut Workload,
+    state_inv: &Tracked<Arc<StateInvariant<SharedWritePerm, SharedReadPerm>>>,
+    view: &Tracked<Arc<SharedRegisterView>>,
+) -> Result<(), Error<SharedWritePerm, (), SharedReadPerm, ()>> where
+    Pool: ConnectionPool<C = C>,
+    C: Channel<
+        K = abd::channel::ChannelInv,
+        R = abd::proto::Response,
+        S = abd::proto::Request,
+        Id = (u64, u64),
+    >,
+
+    requires
+        pool.spec_len() > 0,
+        state_inv@.namespace() == abd::invariants::state_inv_id(),
+        state_inv@.constant().register_id == view@.constant(),
+        state_inv@.constant().server_locs.len() == pool.spec_len(),
+        view@.namespace() == register_view_id(),
+        client_id_token@.key() == client_id,
+        client_id_token@.id() == state_inv@.constant().client_ids_ids.fresh_id,
+        forall|cid: (u64, u64)| #[trigger]
+            pool.spec_channels().contains_key(cid) ==> {
+                &&& cid.0 == client_id
+                &&& state_inv@.constant().server_locs.contains_key(cid.1)
+                &&& pool.spec_channels()[cid].constant() == ChannelInv::from_state_pred(
+                    state_inv@.constant(),
+                )
+            },
+        forall|server_id| #[trigger]
+            state_inv@.constant().server_locs.contains_key(server_id) ==> {
+                pool.spec_channels().contains_key((client_id, server_id))
+            },
+{
+    let (client_ctr, client_ctr_perm) = PAtomicU64::new(0);
+    let (request_ctr, request_ctr_perm) = PAtomicU64::new(0);
+
+    let (client_ctr_token, request_ctr_token, state_inv) = get_invariant_state::<
+        _,
+        _,
+        SharedWritePerm,
+        SharedReadPerm,
+    >(&pool, client_id, client_id_token, client_ctr_perm, request_ctr_perm, state_inv);
+    let mut client = AbdPool::<_, SharedWritePerm, SharedReadPerm>::new(
+        pool,
+        client_id,
+        client_ctr,
+        client_ctr_token,
+        request_ctr,
+        request_ctr_token,
+        state_inv,
+    );
+    if let Some(fallback_after) = cli::thrifty_fallback(args) {
+        client.set_thrifty(Some(Thrifty::new(fallback_after)));
+    }
+    if args.no_unanimous_reads {
+        client.set_unanimous_reads(false);
+    }
+    assert(client.inv()) by { abd::client::lemma_inv(client) };
+
+    loop
+        invariant
+            client.inv(),
+            view@.constant() == client.register_loc(),
+            view@.namespace() == register_view_id(),
+    {
+        let op = match workload.next_op() {
+            Some(op) => op,
+            None => break,
+        };
+
+        match op {
+            Op::Read => {
+                let tracked read_perm = SharedReadPerm { view: view.borrow().clone() };
+                match client.read(Tracked(read_perm)) {
+                    Ok(_) => workload.complete(),
+                    Err(e) => {
+                        workload.fail();
+                        return Err(Error::AbdRead(e));
+                    },
+                }
+            },
+            Op::Write(value) => {
+                let tracked write_perm = SharedWritePerm {
+                    view: view.borrow().clone(),
+                    value: Some(value),
+                };
+                match client.write(Some(value), Tracked(write_perm)) {
+                    Ok(_) => workload.complete(),
+                    Err(e) => {
+                        workload.fail();
+                        return Err(Error::AbdWrite(e));
+                    },
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+} // verus!
+fn main() {
+    let args = Args::parse();
+    vlib::print::set_quiet(!args.verbose);
+
+    if args.n_servers == 0 {
+        eprintln!("need at least one server");
+        return;
+    }
+
+    if !(0.0..=1.0).contains(&args.read_ratio) {
+        eprintln!("read ratio should be in [0, 1]");
+        return;
+    }
+
+    let (state_inv, view, client_ids) = initialize_system::<SharedWritePerm, SharedReadPerm>(
+        args.n_servers,
+    );
+    let view = share_view(view);
+    let connectors: Vec<_> = (0..args.n_servers)
+        .map(|server_id| run_modelled_server(server_id, &state_inv))
+        .collect();
+
+    println!(
+        "{:?} loop: {} servers, {} clients, {} ops/client, {:.0}% reads, latency {}us ± {}us ({:?})",
+        args.mode,
+        args.n_servers,
+        args.n_clients,
+        args.n_ops,
+        args.read_ratio * 100.0,
+        args.latency_us,
+        args.stddev_us,
+        args.latency_dist,
+    );
+    println!(
+        "{:?} pool, unanimous reads {}",
+        args.pool,
+        if args.no_unanimous_reads { "off" } else { "on" },
+    );
+
+    // ids are reserved upfront and in order: reserving an id takes every id below it
+    let mut clients = Vec::new();
+    for idx in 0..args.n_clients {
+        match args.first_client_id {
+            Some(first_client_id) => {
+                let client_id = first_client_id.saturating_add(idx);
+                match client_ids.reserve(client_id) {
+                    Some(client_id_token) => clients.push((client_id, client_id_token)),
+                    None => {
+                        eprintln!("client id {client_id} is not available");
+                        return;
+                    }
+                }
+            }
+            None => match client_ids.allocate() {
+                Ok(allocated) => clients.push(allocated),
+                Err(e) => {
+                    eprintln!("{e}");
+                    return;
+                }
+            },
+        }
+    }
+
+    let start = std::time::Instant::now();
+    #[cfg(feature = "async")]
+    if args.async_clients {
+        run_async(args, connectors, state_inv, view, clients).report(start.elapsed());
+        return;
+    }
+
+    let stats = std::thread::scope(|s| {
+        let handles: Vec<_> = clients
+            .into_iter()
+            .map(|(client_id, client_id_token)| {
+                let args = &args;
+                let connectors = &connectors;
+                let state_inv = &state_inv;
+                let view = &view;
+                s.spawn(move || {
+                    let mut workload = Workload::new(args, client_id);
+                    if let Err(e) = run_client(
+                        args,
+                        connectors,
+                        client_id,
+                        client_id_token,
+                        &mut workload,
+                        state_inv,
+                        view,
+                    ) {
+                        eprintln!("client {client_id} stopped: {e}");
+                    }
+                    workload.into_stats()
+                })
+            })
+            .collect();
+
+        let mut stats = Stats::default();
+        for handle in handles {
+            stats.merge(handle.join().expect("client thread panicked"));
+        }
+        stats
+    });
+
+    stats.report(start.elapsed());
+}
+
+/// Runs every client as a future, and awaits them all on the current thread
+///
+/// The clients block on the network, so each one runs on the threads of [`futures::unblock`]
+#[cfg(feature = "async")]
+fn run_async<C, Conn>(
+    args: Args,
+    connectors: Vec<Conn>,
+    state_inv: Tracked<Arc<StateInvariant<SharedWritePerm, SharedReadPerm>>>,
+    view: Tracked<Arc<SharedRegisterView>>,
+    clients: Vec<(u64, Tracked<ClientIdToken>)>,
+) -> Stats
+where
+    Conn: Connector<C> + Send + Sync + 'static,
+    C: Channel<
+        K = abd::channel::ChannelInv,
+        R = abd::proto::Response,
+        S = abd::proto::Request,
+        Id = (u64, u64),
+    >,
+    C: Sync + Send + 'static,
+{
+    let shared = Arc::new((args, connectors, state_inv, view));
+    let tasks: Vec<_> = clients
+        .into_iter()
+        .map(|(client_id, client_id_token)| {
+            let shared = shared.clone();
+            futures::unblock(move || {
+                let (args, connectors, state_inv, view) = &*shared;
+                let mut workload = Workload::new(args, client_id);
+                if let Err(e) = run_client(
+                    args,
+                    connectors,
+                    client_id,
+                    client_id_token,
+                    &mut workload,
+                    state_inv,
+                    view,
+                ) {
+                    eprintln!("client {client_id} stopped: {e}");
+                }
+                workload.into_stats()
+            })
+        })
+        .collect();
+
+    let mut stats = Stats::default();
+    for client_stats in futures::block_on(futures::join_all(tasks)) {
+        stats.merge(client_stats);
+    }
+    stats
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+/// Latency samples for a single kind of operation
+#[derive(Default)]
+pub(crate) struct Samples {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+impl Samples {
+    pub(crate) fn record(&mut self, latency: Duration) {
+        self.latencies.push(latency);
+    }
+
+    pub(crate) fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub(crate) fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    fn report(&mut self, name: &str, elapsed: Duration) {
+        self.latencies.sort_unstable();
+        let n = self.latencies.len();
+        let throughput = n as f64 / elapsed.as_secs_f64();
+        println!("{name:>6}: {n:>8} ops {:>6} errors {throughput:>10.1} ops/s", self.errors);
+        if n == 0 {
+            return;
+        }
+
+        let mean = Duration::from_secs_f64(
+            self.latencies.iter().map(Duration::as_secs_f64).sum::<f64>() / n as f64,
+        );
+        println!(
+            "        mean {:>10.3?} p50 {:>10.3?} p90 {:>10.3?} p99 {:>10.3?} p99.9 {:>10.3?} max {:>10.3?}",
+            mean,
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.latencies[n - 1],
+        );
+    }
+
+    /// Nearest-rank percentile; assumes the samples are sorted
+    fn percentile(&self, p: f64) -> Duration {
+        let n = self.latencies.len();
+        let rank = ((p / 100.0) * n as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, n) - 1]
+    }
+}
+
+/// Latency samples split by operation type
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub(crate) reads: Samples,
+    pub(crate) writes: Samples,
+}
+
+impl Stats {
+    pub(crate) fn merge(&mut self, other: Stats) {
+        self.reads.merge(other.reads);
+        self.writes.merge(other.writes);
+    }
+
+    pub(crate) fn report(mut self, elapsed: Duration) {
+        let mut all = Samples::default();
+        all.latencies.extend_from_slice(&self.reads.latencies);
+        all.latencies.extend_from_slice(&self.writes.latencies);
+        all.errors = self.reads.errors + self.writes.errors;
+
+        println!("elapsed: {elapsed:.3?}");
+        self.reads.report("reads", elapsed);
+        self.writes.report("writes", elapsed);
+        all.report("total", elapsed);
+    }
+}
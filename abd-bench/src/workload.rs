@@ -0,0 +1,127 @@
+use std::time::Duration;
+use std::time::Instant;
+
+use rand::rngs::ThreadRng;
+use rand_distr::Bernoulli;
+use rand_distr::Distribution;
+use rand_distr::Exp;
+
+use vstd::prelude::*;
+
+use crate::cli::Args;
+use crate::cli::Mode;
+use crate::stats::Stats;
+
+verus! {
+
+pub(crate) enum Op {
+    Read,
+    Write(u64),
+}
+
+/// Drives the operations of a single client and records their latencies
+///
+/// This is unverified: it only decides what to issue next and keeps time
+#[verifier::external_body]
+pub(crate) struct Workload {
+    client_id: u64,
+    remaining: u64,
+    deadline: Option<Instant>,
+    is_read: Bernoulli,
+    interarrival: Option<Exp<f64>>,
+    rng: ThreadRng,
+    next_arrival: Instant,
+    next_value: u64,
+    in_flight: Option<(bool, Instant)>,
+    stats: Stats,
+}
+
+impl Workload {
+    #[verifier::external_body]
+    pub(crate) fn new(args: &Args, client_id: u64) -> Self {
+        let interarrival = match args.mode {
+            Mode::Closed => None,
+            Mode::Open => Some(Exp::new(args.rate).expect("rate should be positive")),
+        };
+        let now = Instant::now();
+        Workload {
+            client_id,
+            remaining: args.n_ops,
+            deadline: args.duration_secs.map(|s| now + Duration::from_secs(s)),
+            is_read: Bernoulli::new(args.read_ratio).expect("read ratio should be in [0, 1]"),
+            interarrival,
+            rng: rand::rng(),
+            next_arrival: now,
+            next_value: 0,
+            in_flight: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Returns the next operation to issue, or `None` if the client is done
+    ///
+    /// In open loop mode this blocks until the scheduled arrival of the operation.
+    /// Latency is measured from the scheduled arrival (not from when the previous operation
+    /// completed), so queueing behind a slow operation is accounted for.
+    #[verifier::external_body]
+    pub(crate) fn next_op(&mut self) -> Option<Op> {
+        let now = Instant::now();
+        if self.remaining == 0 || self.deadline.is_some_and(|d| now >= d) {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let start = match &self.interarrival {
+            None => now,
+            Some(exp) => {
+                let arrival = self.next_arrival;
+                self.next_arrival += Duration::from_secs_f64(exp.sample(&mut self.rng));
+                if arrival > now {
+                    std::thread::sleep(arrival - now);
+                }
+                arrival
+            },
+        };
+
+        let is_read = self.is_read.sample(&mut self.rng);
+        self.in_flight = Some((is_read, start));
+        if is_read {
+            Some(Op::Read)
+        } else {
+            self.next_value += 1;
+            Some(Op::Write((self.client_id << 32) | (self.next_value & 0xffff_ffff)))
+        }
+    }
+
+    /// Marks the in-flight operation as completed
+    #[verifier::external_body]
+    pub(crate) fn complete(&mut self) {
+        if let Some((is_read, start)) = self.in_flight.take() {
+            let latency = start.elapsed();
+            if is_read {
+                self.stats.reads.record(latency);
+            } else {
+                self.stats.writes.record(latency);
+            }
+        }
+    }
+
+    /// Marks the in-flight operation as failed
+    #[verifier::external_body]
+    pub(crate) fn fail(&mut self) {
+        if let Some((is_read, _)) = self.in_flight.take() {
+            if is_read {
+                self.stats.reads.record_error();
+            } else {
+                self.stats.writes.record_error();
+            }
+        }
+    }
+}
+
+} // verus!
+impl Workload {
+    pub(crate) fn into_stats(self) -> Stats {
+        self.stats
+    }
+}
@@ -16,8 +16,9 @@ pub(crate) struct Args {
     #[arg(long)]
     pub(crate) no_delay: bool,
 
-    #[arg(long, default_value_t = 1)]
-    pub(crate) client_id: u64,
+    /// Log in with this client id instead of allocating one
+    #[arg(long)]
+    pub(crate) client_id: Option<u64>,
 }
 
 verus! {
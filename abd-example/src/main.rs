@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use clap::Parser;
 use vstd::atomic::PAtomicU64;
 #[cfg(verus_only)]
@@ -6,6 +8,7 @@ use vstd::prelude::*;
 use vstd::resource::ghost_var::GhostVar;
 
 use verdist::network::channel::BufChannel;
+use verdist::network::channel::BufferPolicy;
 use verdist::network::channel::Channel;
 use verdist::network::channel::Connector;
 use verdist::network::error::ConnectError;
@@ -23,8 +26,15 @@ use specs::abd::RegisterWrite;
 
 use abd::channel::ChannelInv;
 use abd::client::AbdPool;
+use abd::invariants::initialize_system;
+#[cfg(verus_only)]
+use abd::invariants::server_ids;
+use abd::invariants::RegisterView;
+use abd::invariants::StateInvariant;
 use abd::server::run_modelled_server;
 
+use vlib::client_ids::ClientIdToken;
+
 mod cli;
 mod error;
 mod invariant;
@@ -33,6 +43,8 @@ mod trace;
 use cli::Args;
 use error::Error;
 use invariant::get_invariant_state;
+#[cfg(verus_only)]
+use invariant::lemma_connected_pool;
 
 verus! {
 
@@ -43,24 +55,25 @@ const REQUEST_LATENCY_DEFAULT_MS: u64 = 1000;
 
 const REQUEST_STDDEV_DEFAULT_MS: u64 = 2000;
 
-fn connect<C, Conn>(args: &Args, connector: &Conn, client_id: u64) -> Result<
-    BufChannel<C>,
-    ConnectError,
-> where
+fn connect<C, Conn>(
+    args: &Args,
+    connector: &Conn,
+    client_id: u64,
+    channel_inv: Ghost<ChannelInv>,
+) -> (r: Result<BufChannel<C>, ConnectError>) where
     Conn: Connector<C>,
     C: Channel<Id = (u64, u64), K = ChannelInv, R = abd::proto::Response, S = abd::proto::Request>,
- {
+
+    ensures
+        r is Ok ==> r->Ok_0.constant() == channel_inv@,
+{
+    let ghost k = channel_inv@;
     let mut channel = connector.connect(
         client_id,
-        |_connector, _client_id|
-            Ghost(
-                ChannelInv {
-                    commitment_id: arbitrary(),
-                    request_map_id: arbitrary(),
-                    server_locs: arbitrary(),
-                    server_tokens_id: arbitrary(),
-                },
-            ),
+        |_connector: &Conn, _client_id: u64| -> (r: Ghost<ChannelInv>)
+            ensures
+                r@ == k,
+            { Ghost(k) },
     )?;
     if !args.no_delay {
         channel.add_latency(
@@ -68,13 +81,17 @@ fn connect<C, Conn>(args: &Args, connector: &Conn, client_id: u64) -> Result<
             std::time::Duration::from_millis(REQUEST_STDDEV_DEFAULT_MS),
         );
     }
-    Ok(BufChannel::new(channel))
+    // the client has one request in flight at a time
+    Ok(BufChannel::with_policy(channel, BufferPolicy::LowWater))
 }
 
-fn connect_all<C, Conn>(args: &Args, connectors: &[Conn], client_id: u64) -> (r: Result<
-    Vec<BufChannel<C>>,
-    ConnectError,
->) where
+/// Connects to every server, the one with id `server_id` through `connectors[server_id]`
+fn connect_all<C, Conn>(
+    args: &Args,
+    connectors: &[Conn],
+    client_id: u64,
+    channel_inv: Ghost<ChannelInv>,
+) -> (r: Result<Vec<BufChannel<C>>, ConnectError>) where
     Conn: Connector<C>,
     C: Channel<Id = (u64, u64), K = ChannelInv, R = abd::proto::Response, S = abd::proto::Request>,
 
@@ -82,24 +99,48 @@ fn connect_all<C, Conn>(args: &Args, connectors: &[Conn], client_id: u64) -> (r:
         r is Ok ==> {
             let v = r->Ok_0;
             &&& connectors.len() == v.len()
-            &&& forall|idx| 0 <= idx < v@.len() ==> #[trigger] v@[idx].spec_id().0 == client_id
-            &&& forall|i, j|
-                0 <= i < j < v@.len() ==> #[trigger] v@[i].spec_id() != #[trigger] v@[j].spec_id()
+            &&& forall|idx|
+                0 <= idx < v@.len() ==> #[trigger] v@[idx].spec_id() == (client_id, idx as u64)
+            &&& forall|idx| 0 <= idx < v@.len() ==> #[trigger] v@[idx].constant() == channel_inv@
+            &&& v@.map_values(|c: BufChannel<C>| c.spec_id()).no_duplicates()
         },
 {
     let mut v = Vec::with_capacity(connectors.len());
-    for connector in connectors.iter() {
-        let conn = connect(args, connector, client_id)?;
+    for server_id in 0..connectors.len()
+        invariant
+            v@.len() == server_id,
+            forall|idx|
+                0 <= idx < v@.len() ==> #[trigger] v@[idx].spec_id() == (client_id, idx as u64),
+            forall|idx| 0 <= idx < v@.len() ==> #[trigger] v@[idx].constant() == channel_inv@,
+    {
+        let conn = connect(args, &connectors[server_id], client_id, channel_inv)?;
+        // the connectors are expected in server id order
+        let (local_id, remote_id) = conn.id();
+        if local_id != client_id || remote_id != server_id as u64 {
+            return Err(ConnectError);
+        }
         v.push(conn);
     }
 
     proof {
-        admit();  // XXX(assume): this is trivial but seems like something should be able to get
+        let ids = v@.map_values(|c: BufChannel<C>| c.spec_id());
+        assert forall|i, j| 0 <= i < ids.len() && 0 <= j < ids.len() && i != j implies ids[i]
+            != ids[j] by {
+            assert(ids[i] == (client_id, i as u64));
+            assert(ids[j] == (client_id, j as u64));
+        }
     }
     Ok(v)
 }
 
-fn run_client<C, Conn, 'a>(args: Args, connectors: &[Conn]) -> Result<
+fn run_client<C, Conn, 'a>(
+    args: Args,
+    connectors: &[Conn],
+    state_inv: &Tracked<Arc<StateInvariant<OwnedWritePerm, OwnedReadPerm>>>,
+    view: Tracked<RegisterView>,
+    client_id: u64,
+    client_id_token: Tracked<ClientIdToken>,
+) -> Result<
     (),
     Error<OwnedWritePerm, GhostVar<Option<u64>>, OwnedReadPerm, GhostVar<Option<u64>>>,
 > where
@@ -114,34 +155,40 @@ fn run_client<C, Conn, 'a>(args: Args, connectors: &[Conn]) -> Result<
 
     requires
         connectors.len() > 0,
+        state_inv@.namespace() == abd::invariants::state_inv_id(),
+        state_inv@.constant().register_id == view@.id(),
+        state_inv@.constant().server_locs.dom() == server_ids(connectors.len() as u64),
+        state_inv@.constant().server_locs.len() == connectors.len(),
+        client_id_token@.key() == client_id,
+        client_id_token@.id() == state_inv@.constant().client_ids_ids.fresh_id,
 {
-    let pool = connect_all(&args, connectors, args.client_id)?;
-    let pool = FlawlessPool::new(pool);
+    let ghost state_pred = state_inv@.constant();
+    let channels = connect_all(
+        &args,
+        connectors,
+        client_id,
+        Ghost(ChannelInv::from_state_pred(state_pred)),
+    )?;
+    let ghost channel_seq = channels@;
+    let pool = FlawlessPool::new(channels);
     assert(pool.spec_len() == connectors.len());
+    proof {
+        lemma_connected_pool(channel_seq, pool.spec_channels(), client_id, state_pred);
+    }
 
     let (client_ctr, client_ctr_perm) = PAtomicU64::new(0);
     let (request_ctr, request_ctr_perm) = PAtomicU64::new(0);
 
     #[allow(unused)]
-    let (client_ctr_token, request_ctr_token, state_inv, view) = get_invariant_state::<
+    let (client_ctr_token, request_ctr_token, state_inv) = get_invariant_state::<
         _,
         _,
         OwnedWritePerm,
         OwnedReadPerm,
-    >(&pool, args.client_id, client_ctr_perm, request_ctr_perm);
-    assume(forall|cid| #[trigger]
-        pool.spec_channels().dom().contains(cid) ==> {
-            let c = pool.spec_channels()[cid];
-            &&& cid.0 == args.client_id
-            &&& state_inv.constant().server_locs.contains_key(cid.1)
-            &&& state_inv.constant().request_map_ids.request_auth_id == c.constant().request_map_id
-            &&& state_inv.constant().commitments_ids.commitment_id == c.constant().commitment_id
-            &&& state_inv.constant().server_tokens_id == c.constant().server_tokens_id
-            &&& state_inv.constant().server_locs == c.constant().server_locs
-        });
+    >(&pool, client_id, client_id_token, client_ctr_perm, request_ctr_perm, state_inv);
     let mut client = AbdPool::<_, OwnedWritePerm, OwnedReadPerm>::new(
         pool,
-        args.client_id,
+        client_id,
         client_ctr,
         client_ctr_token,
         request_ctr,
@@ -155,11 +202,11 @@ fn run_client<C, Conn, 'a>(args: Args, connectors: &[Conn]) -> Result<
     #[allow(unused)]
     let (v, ts, view2) = match client.read(Tracked(read_perm)) {
         Ok((v, ts, view)) => {
-            vlib::veprintln!("[client|{:>3}]: read completed: {:?} @ {:?}", args.client_id, v, ts);
+            vlib::veprintln!("[client|{:>3}]: read completed: {:?} @ {:?}", client_id, v, ts);
             (v, ts, view)
         },
         Err(e) => {
-            vlib::veprintln!("[client|{:>3}]: read error: {}", args.client_id, e);
+            vlib::veprintln!("[client|{:>3}]: read error: {}", client_id, e);
             return Err(Error::Empty);
         },
     };
@@ -171,11 +218,11 @@ fn run_client<C, Conn, 'a>(args: Args, connectors: &[Conn]) -> Result<
     #[allow(unused_variables)]
     let view3 = match client.write(Some(42), Tracked(write_perm)) {
         Ok(comp) => {
-            vlib::veprintln!("[client|{:>3}]: write completed: {:?}", args.client_id, value);
+            vlib::veprintln!("[client|{:>3}]: write completed: {:?}", client_id, value);
             comp
         },
         Err(e) => {
-            vlib::veprintln!("[client|{:>3}]: write error: {}", args.client_id, e);
+            vlib::veprintln!("[client|{:>3}]: write error: {}", client_id, e);
             return Err(Error::Empty);
         },
     };
@@ -185,11 +232,11 @@ fn run_client<C, Conn, 'a>(args: Args, connectors: &[Conn]) -> Result<
     #[allow(unused)]
     let (v, ts, view4) = match client.read(Tracked(read_perm)) {
         Ok((v, ts, comp)) => {
-            vlib::veprintln!("[client|{:>3}]: read completed: {:?} @ {:?}", args.client_id, v, ts);
+            vlib::veprintln!("[client|{:>3}]: read completed: {:?} @ {:?}", client_id, v, ts);
             (v, ts, comp)
         },
         Err(e) => {
-            vlib::veprintln!("[client|{:>3}]: read error: {}", args.client_id, e);
+            vlib::veprintln!("[client|{:>3}]: read error: {}", client_id, e);
             return Err(Error::Empty);
         },
     };
@@ -207,9 +254,31 @@ fn main() {
         return;
     }
 
-    let connectors: Vec<_> = (0..args.n_servers).map(run_modelled_server).collect();
+    let (state_inv, view, client_ids) = initialize_system::<OwnedWritePerm, OwnedReadPerm>(
+        args.n_servers,
+    );
+    let connectors: Vec<_> = (0..args.n_servers)
+        .map(|server_id| run_modelled_server(server_id, &state_inv))
+        .collect();
+
+    let (client_id, client_id_token) = match args.client_id {
+        Some(client_id) => match client_ids.reserve(client_id) {
+            Some(client_id_token) => (client_id, client_id_token),
+            None => {
+                eprintln!("client id {client_id} is not available");
+                return;
+            }
+        },
+        None => match client_ids.allocate() {
+            Ok(allocated) => allocated,
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        },
+    };
 
-    run_client(args, &connectors).expect("error");
+    run_client(args, &connectors, &state_inv, view, client_id, client_id_token).expect("error");
 
     // let realtime_order = realtime(&trace);
     // println!("realtime ordering:\n{realtime_order:?}");
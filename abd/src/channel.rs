@@ -70,12 +70,25 @@ pub open spec fn chan_response_inv(
         &&& k.server_locs.contains_key(get_resp.server_id())
         &&& k.server_locs[get_resp.server_id()] == get_resp.loc()
     }
+    &&& r.req_type() is GetAt ==> {
+        let get_at_resp = r.get_at();
+        &&& get_at_resp.spec_commitment() is Some ==> get_at_resp.commitment_id()
+            == k.commitment_id
+        &&& get_at_resp.server_token_id() == k.server_tokens_id
+        &&& k.server_locs.contains_key(get_at_resp.server_id())
+    }
     &&& r.req_type() is GetTimestamp ==> {
         let get_ts_resp = r.get_timestamp();
         &&& get_ts_resp.server_token_id() == k.server_tokens_id
         &&& k.server_locs.contains_key(get_ts_resp.server_id())
         &&& k.server_locs[get_ts_resp.server_id()] == get_ts_resp.loc()
     }
+    &&& r.req_type() is Subscribe ==> {
+        let sub_resp = r.subscribe();
+        &&& sub_resp.server_token_id() == k.server_tokens_id
+        &&& k.server_locs.contains_key(sub_resp.server_id())
+        &&& k.server_locs[sub_resp.server_id()] == sub_resp.loc()
+    }
     &&& r.req_type() is Write ==> {
         let write_resp = r.write();
         &&& write_resp.server_token_id() == k.server_tokens_id
@@ -4,6 +4,7 @@ use crate::invariants::lin_queue::MaybeReadLinearized;
 use crate::invariants::lin_queue::MaybeWriteLinearized;
 use crate::timestamp::Timestamp;
 
+use specs::abd::AbdConditionalError;
 use specs::abd::AbdError;
 use specs::abd::RegisterRead;
 use specs::abd::RegisterWrite;
@@ -16,9 +17,11 @@ verus! {
 
 /// ABD read related errors
 ///
-/// The only way an ABD read fails is when a quorum is known to be unatainable
+/// An ABD read fails when a quorum is known to be unatainable
 /// This happens when a connection reset happens
 /// In this case, the error is exposed to the client
+///
+/// It can also fail up front, if the client has run out of request ids
 pub enum ReadError<RL, RC> {
     // The first read quorum failed
     FailedFirstQuorum {
@@ -32,13 +35,18 @@ pub enum ReadError<RL, RC> {
         required: usize,
         lincomp: Tracked<MaybeReadLinearized<RL, RC>>,
     },
+    // The request counter would overflow; the read was not attempted
+    CounterExhausted { lincomp: Tracked<MaybeReadLinearized<RL, RC>> },
 }
 
 /// ABD write related errors
 ///
-/// The only way an ABD write fails is when a quorum is known to be unatainable
+/// An ABD write fails when a quorum is known to be unatainable
 /// This happens when a connection reset happens
 /// In this case, the error is exposed to the client
+///
+/// It can also fail before physically starting, if one of the counters it draws from (request
+/// ids, client counter or the sequence number of the timestamp) would overflow
 pub enum WriteError<ML, MC> {
     // The first phase of the write failed
     // In this case the write never physicially started, so we can get the MaybeLinearized
@@ -56,12 +64,53 @@ pub enum WriteError<ML, MC> {
         token: Tracked<LinWriteToken<ML>>,
         commitment: Tracked<WriteCommitment>,
     },
+    // A counter would overflow
+    // The write never physically started, so we can get the MaybeLinearized
+    CounterExhausted { lincomp: Tracked<MaybeWriteLinearized<ML, MC>> },
+}
+
+/// ABD conditional write related errors
+///
+/// A conditional write fails like a write, or because the register was not at the expected
+/// timestamp. In the latter case the write never physically started, so we can get the
+/// MaybeLinearized back
+pub enum ConditionalWriteError<ML, MC> {
+    Conflict {
+        expected: Timestamp,
+        timestamp: Timestamp,
+        lincomp: Tracked<MaybeWriteLinearized<ML, MC>>,
+    },
+    Write(WriteError<ML, MC>),
+}
+
+/// ABD timestamp read related errors
+///
+/// Like a read, this fails when a quorum is known to be unatainable or when the client has run
+/// out of request ids
+pub enum ReadTimestampError {
+    FailedQuorum { obtained: usize, required: usize },
+    CounterExhausted,
+}
+
+pub enum WatchError {
+    FailedQuorum { obtained: usize, required: usize },
+    CounterExhausted,
+}
+
+pub enum ReadAtError {
+    /// None of the servers which replied still holds the value for the timestamp
+    ///
+    /// Only `replies` out of `servers` may have replied, if the others disconnected
+    NotFound { timestamp: Timestamp, replies: usize, servers: usize },
+    /// The client has run out of request ids
+    CounterExhausted,
 }
 
 impl<ML> WriteError<ML, ML::Completion> where ML: MutLinearizer<RegisterWrite> {
     pub open spec fn inv(self) -> bool {
         match self {
             WriteError::FailedFirstQuorum { lincomp, .. } => { lincomp@.inv() },
+            WriteError::CounterExhausted { lincomp } => { lincomp@.inv() },
             WriteError::FailedSecondQuorum { token, commitment, timestamp, .. } => {
                 &&& token@.key() == timestamp
                 &&& commitment@.key() == timestamp
@@ -69,6 +118,18 @@ impl<ML> WriteError<ML, ML::Completion> where ML: MutLinearizer<RegisterWrite> {
             },
         }
     }
+
+    /// Whether the write physically started (and can be completed with
+    /// [`crate::client::AbdPool::complete_write`])
+    pub fn is_in_flight(&self) -> (r: bool)
+        ensures
+            r == self is FailedSecondQuorum,
+    {
+        match self {
+            WriteError::FailedSecondQuorum { .. } => true,
+            _ => false,
+        }
+    }
 }
 
 impl<RL, RC> std::error::Error for ReadError<RL, RC> {
@@ -79,18 +140,41 @@ impl<ML, MC> std::error::Error for WriteError<ML, MC> {
 
 }
 
+impl<ML, MC> std::error::Error for ConditionalWriteError<ML, MC> {
+
+}
+
+impl std::error::Error for ReadTimestampError {
+
+}
+
+impl std::error::Error for WatchError {
+
+}
+
+impl std::error::Error for ReadAtError {
+
+}
+
 impl<RL> AbdError<RL, RegisterRead> for ReadError<RL, RL::Completion> where
     RL: ReadLinearizer<RegisterRead>,
  {
     open spec fn err_ensures(self, op: RegisterRead, lin: RL) -> bool {
         &&& self is FailedFirstQuorum ==> ({
+            &&& self->FailedFirstQuorum_lincomp@.inv()
             &&& self->FailedFirstQuorum_lincomp@.lin() == lin
             &&& self->FailedFirstQuorum_lincomp@.op() == op
         })
         &&& self is FailedSecondQuorum ==> ({
+            &&& self->FailedSecondQuorum_lincomp@.inv()
             &&& self->FailedSecondQuorum_lincomp@.lin() == lin
             &&& self->FailedSecondQuorum_lincomp@.op() == op
         })
+        &&& self is CounterExhausted ==> ({
+            &&& self->CounterExhausted_lincomp@.inv()
+            &&& self->CounterExhausted_lincomp@.lin() == lin
+            &&& self->CounterExhausted_lincomp@.op() == op
+        })
     }
 }
 
@@ -100,8 +184,12 @@ impl<ML> AbdError<ML, RegisterWrite> for WriteError<ML, ML::Completion> where
     open spec fn err_ensures(self, op: RegisterWrite, lin: ML) -> bool {
         &&& self.inv()
         &&& self is FailedFirstQuorum ==> ({
-            &&& self->lincomp@.lin() == lin
-            &&& self->lincomp@.op() == op
+            &&& self->FailedFirstQuorum_lincomp@.lin() == lin
+            &&& self->FailedFirstQuorum_lincomp@.op() == op
+        })
+        &&& self is CounterExhausted ==> ({
+            &&& self->CounterExhausted_lincomp@.lin() == lin
+            &&& self->CounterExhausted_lincomp@.op() == op
         })
         &&& self is FailedSecondQuorum ==> ({
             &&& self->token@.value().lin == lin
@@ -110,6 +198,33 @@ impl<ML> AbdError<ML, RegisterWrite> for WriteError<ML, ML::Completion> where
     }
 }
 
+impl<ML> AbdError<ML, RegisterWrite> for ConditionalWriteError<ML, ML::Completion> where
+    ML: MutLinearizer<RegisterWrite>,
+ {
+    open spec fn err_ensures(self, op: RegisterWrite, lin: ML) -> bool {
+        match self {
+            ConditionalWriteError::Conflict { lincomp, .. } => {
+                &&& lincomp@.inv()
+                &&& lincomp@.lin() == lin
+                &&& lincomp@.op() == op
+            },
+            ConditionalWriteError::Write(e) => e.err_ensures(op, lin),
+        }
+    }
+}
+
+impl<ML> AbdConditionalError<ML, RegisterWrite, Timestamp> for ConditionalWriteError<
+    ML,
+    ML::Completion,
+> where ML: MutLinearizer<RegisterWrite> {
+    open spec fn conflict(self) -> Option<Timestamp> {
+        match self {
+            ConditionalWriteError::Conflict { timestamp, .. } => Some(timestamp),
+            ConditionalWriteError::Write(_) => None,
+        }
+    }
+}
+
 } // verus!
 impl<RL, RC> std::fmt::Debug for ReadError<RL, RC> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -128,6 +243,7 @@ impl<RL, RC> std::fmt::Debug for ReadError<RL, RC> {
                 .field("obtained", &obtained)
                 .field("required", &required)
                 .finish(),
+            ReadError::CounterExhausted { .. } => f.debug_struct("CounterExhausted").finish(),
         }
     }
 }
@@ -141,6 +257,9 @@ impl<RL, RC> std::fmt::Display for ReadError<RL, RC> {
             ReadError::FailedSecondQuorum { obtained, required, .. } => {
                 f.write_fmt(format_args!("failed to obtain a quorum for the writeback phase of the read; got {obtained} of {required} required responses"))
             },
+            ReadError::CounterExhausted { .. } => {
+                f.write_str("the client ran out of request ids")
+            },
         }
     }
 }
@@ -162,6 +281,7 @@ impl<ML, MC> std::fmt::Debug for WriteError<ML, MC> {
                 .field("obtained", &obtained)
                 .field("required", &required)
                 .finish(),
+            WriteError::CounterExhausted { .. } => f.debug_struct("CounterExhausted").finish(),
         }
     }
 }
@@ -175,6 +295,108 @@ impl<ML, MC> std::fmt::Display for WriteError<ML, MC> {
             WriteError::FailedSecondQuorum { obtained, required, .. } => {
                 f.write_fmt(format_args!("failed to obtain a quorum for the second phase of the write; got {obtained} of {required} required responses"))
             },
+            WriteError::CounterExhausted { .. } => {
+                f.write_str("the client ran out of request ids, client counters or sequence numbers")
+            },
+        }
+    }
+}
+
+impl<ML, MC> std::fmt::Debug for ConditionalWriteError<ML, MC> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionalWriteError::Conflict {
+                expected, timestamp, ..
+            } => f
+                .debug_struct("Conflict")
+                .field("expected", &expected)
+                .field("timestamp", &timestamp)
+                .finish(),
+            ConditionalWriteError::Write(e) => f.debug_tuple("Write").field(e).finish(),
+        }
+    }
+}
+
+impl<ML, MC> std::fmt::Display for ConditionalWriteError<ML, MC> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionalWriteError::Conflict { expected, timestamp, .. } => {
+                f.write_fmt(format_args!("conditional write conflict: expected the register at {expected:?}, found it at {timestamp:?}"))
+            },
+            ConditionalWriteError::Write(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::fmt::Debug for ReadTimestampError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadTimestampError::FailedQuorum { obtained, required } => f
+                .debug_struct("FailedQuorum")
+                .field("obtained", &obtained)
+                .field("required", &required)
+                .finish(),
+            ReadTimestampError::CounterExhausted => f.debug_struct("CounterExhausted").finish(),
+        }
+    }
+}
+
+impl std::fmt::Display for ReadTimestampError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadTimestampError::FailedQuorum { obtained, required } => {
+                f.write_fmt(format_args!("failed to obtain a quorum for the timestamp read; got {obtained} of {required} required responses"))
+            },
+            ReadTimestampError::CounterExhausted => f.write_str("the client ran out of request ids"),
+        }
+    }
+}
+
+impl std::fmt::Debug for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchError::FailedQuorum { obtained, required } => f
+                .debug_struct("FailedQuorum")
+                .field("obtained", &obtained)
+                .field("required", &required)
+                .finish(),
+            WatchError::CounterExhausted => f.debug_struct("CounterExhausted").finish(),
+        }
+    }
+}
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchError::FailedQuorum { obtained, required } => {
+                f.write_fmt(format_args!("failed to obtain a quorum of notifications; got {obtained} of {required} required responses"))
+            },
+            WatchError::CounterExhausted => f.write_str("the client ran out of request ids"),
+        }
+    }
+}
+
+impl std::fmt::Debug for ReadAtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadAtError::NotFound { timestamp, replies, servers } => f
+                .debug_struct("NotFound")
+                .field("timestamp", &timestamp)
+                .field("replies", &replies)
+                .field("servers", &servers)
+                .finish(),
+            ReadAtError::CounterExhausted => f.debug_struct("CounterExhausted").finish(),
+        }
+    }
+}
+
+impl std::fmt::Display for ReadAtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadAtError::NotFound { timestamp, replies, servers } => {
+                f.write_fmt(format_args!("none of the {replies} out of {servers} servers which replied holds the value for timestamp {timestamp:?}"))
+            },
+            ReadAtError::CounterExhausted => f.write_str("the client ran out of request ids"),
         }
     }
 }
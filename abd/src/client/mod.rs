@@ -2,11 +2,13 @@ use crate::channel::ChannelInv;
 #[cfg(verus_only)]
 use crate::invariants;
 use crate::invariants::committed_to::ClientCtrToken;
+use crate::invariants::committed_to::WriteCommitment;
 #[cfg(verus_only)]
 use crate::invariants::lin_queue::InsertError;
-#[cfg(verus_only)]
 use crate::invariants::lin_queue::LinWriteToken;
 #[cfg(verus_only)]
+use crate::invariants::lin_queue::MaybeReadLinearized;
+#[cfg(verus_only)]
 use crate::invariants::lin_queue::MaybeWriteLinearized;
 #[cfg(verus_only)]
 use crate::invariants::quorum::Quorum;
@@ -15,6 +17,7 @@ use crate::invariants::quorum::ServerUniverse;
 use crate::invariants::requests::RequestCtrToken;
 #[cfg(verus_only)]
 use crate::invariants::RegisterView;
+use crate::resource::monotonic_timestamp::MonotonicTimestampResource;
 use crate::invariants::StateInvariant;
 use crate::proto::Request;
 use crate::proto::RequestInner;
@@ -24,18 +27,25 @@ use crate::timestamp::Timestamp;
 #[cfg(verus_only)]
 use specs::abd::AbdError;
 use specs::abd::AbdRegisterClient;
+use specs::abd::ConditionalWriteLinearizer;
+#[cfg(verus_only)]
+use specs::abd::RegisterConditionalWrite;
 use specs::abd::RegisterRead;
 use specs::abd::RegisterWrite;
 
 use verdist::network::channel::Channel;
 use verdist::pool::BroadcastPool;
+use verdist::pool::ChannelId;
 use verdist::pool::ConnectionPool;
+use verdist::pool::Thrifty;
+use verdist::rpc::proto::Cancellable;
 #[cfg(verus_only)]
 use verdist::rpc::proto::TaggedMessage;
 use verdist::rpc::replies::ReplyAccumulator;
 
 pub mod error;
 mod net_invs;
+pub mod retry;
 
 use net_invs::*;
 
@@ -44,6 +54,7 @@ use vstd::atomic::PAtomicU64;
 use vstd::invariant::InvariantPredicate;
 use vstd::logatom::MutLinearizer;
 use vstd::logatom::ReadLinearizer;
+use vstd::pervasive::unreached;
 use vstd::prelude::*;
 use vstd::proph::Prophecy;
 #[cfg(verus_only)]
@@ -57,6 +68,7 @@ verus! {
 
 #[allow(dead_code)]
 pub struct AbdPool<Pool, ML, RL> where
+    Pool: ConnectionPool,
     ML: MutLinearizer<RegisterWrite>,
     RL: ReadLinearizer<RegisterRead>,
  {
@@ -68,6 +80,15 @@ pub struct AbdPool<Pool, ML, RL> where
     client_ctr: PAtomicU64,
     request_ctr_token: Tracked<RequestCtrToken>,
     request_ctr: PAtomicU64,
+    /// Next value of `client_ctr` (its permission lives in the invariant, so it cannot be loaded
+    /// before committing to the operation)
+    next_client_ctr: u64,
+    /// Next value of `request_ctr`
+    next_request_id: u64,
+    /// Fan-out policy for the quorum phases: everyone gets the requests if unset
+    thrifty: Option<Thrifty<ChannelId<Pool>>>,
+    /// Whether a read whose first round is unanimous returns right away (skipping the write-back)
+    unanimous_reads: bool,
 }
 
 impl<Pool, C, ML, RL> AbdPool<Pool, ML, RL> where
@@ -123,9 +144,37 @@ impl<Pool, C, ML, RL> AbdPool<Pool, ML, RL> where
             client_ctr,
             request_ctr_token,
             request_ctr,
+            next_client_ctr: 0,
+            next_request_id: 0,
+            thrifty: None,
+            unanimous_reads: true,
         }
     }
 
+    /// Contact a preferred quorum first, rather than every server (see [`Thrifty`])
+    pub fn set_thrifty(&mut self, thrifty: Option<Thrifty<ChannelId<Pool>>>)
+        requires
+            old(self)._inv(),
+        ensures
+            final(self)._inv(),
+            final(self).register_loc() == old(self).register_loc(),
+    {
+        self.thrifty = thrifty;
+    }
+
+    /// Whether reads may skip the write-back when their first round is unanimous (on by default)
+    ///
+    /// The write-back is always safe, so turning this off only makes reads slower
+    pub fn set_unanimous_reads(&mut self, unanimous_reads: bool)
+        requires
+            old(self)._inv(),
+        ensures
+            final(self)._inv(),
+            final(self).register_loc() == old(self).register_loc(),
+    {
+        self.unanimous_reads = unanimous_reads;
+    }
+
     closed spec fn spec_len(self) -> nat {
         self.pool.spec_len()
     }
@@ -145,6 +194,8 @@ impl<Pool, C, ML, RL> AbdPool<Pool, ML, RL> where
         &&& self.client_ctr_token@.value().1 == self.client_ctr.id()
         &&& self.request_ctr_token@.key() == self.id()
         &&& self.request_ctr_token@.value().1 == self.request_ctr.id()
+        &&& self.client_ctr_token@.value().0 == self.next_client_ctr
+        &&& self.request_ctr_token@.value().0 == self.next_request_id
         &&& self.pool.spec_len() == self.state_inv@.constant().server_locs.len()
         &&& forall|c_id| #[trigger]
             self.pool.spec_channels().contains_key(c_id) ==> {
@@ -172,6 +223,32 @@ impl<Pool, C, ML, RL> AbdPool<Pool, ML, RL> where
         self.spec_len() / 2 + 1
     }
 
+    /// Location of the linearization queue watermark
+    ///
+    /// Every write that has completed is at or below the watermark
+    pub closed spec fn watermark_loc(self) -> Loc {
+        self.state_inv@.constant().lin_queue_ids.watermark_id
+    }
+
+    /// Location of the map of committed values
+    pub closed spec fn commitment_id(self) -> Loc {
+        self.state_inv@.constant().commitments_ids.commitment_id
+    }
+
+    /// Location of the write tokens of the linearization queue
+    pub closed spec fn write_token_id(self) -> Loc {
+        self.state_inv@.constant().lin_queue_ids.write_token_map_id
+    }
+
+    /// A write that failed in its second phase can be completed by this client
+    /// (see [`AbdPool::complete_write`])
+    pub open spec fn in_flight(self, err: error::WriteError<ML, ML::Completion>) -> bool {
+        err is FailedSecondQuorum ==> {
+            &&& err->FailedSecondQuorum_token@.id() == self.write_token_id()
+            &&& err->FailedSecondQuorum_commitment@.id() == self.commitment_id()
+        }
+    }
+
     proof fn lemma_quorum_nonzero(self)
         requires
             self.spec_len() > 0,
@@ -192,6 +269,8 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
 
     type WriteErr = error::WriteError<ML, ML::Completion>;
 
+    type CondWriteErr = error::ConditionalWriteError<ML, ML::Completion>;
+
     type Timestamp = Timestamp;
 
     open spec fn read_lin_requires(lin: RL) -> bool {
@@ -221,6 +300,14 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
         error::ReadError<RL, RL::Completion>,
     >) {
         let tracked op = RegisterRead { id: Ghost(self.register_loc()) };
+        // the read and its writeback each take a request id
+        if self.next_request_id > u64::MAX - 2 {
+            let tracked lincomp;
+            proof {
+                lincomp = MaybeReadLinearized::linearizer(lin, op, None);
+            }
+            return Err(error::ReadError::CounterExhausted { lincomp: Tracked(lincomp) });
+        }
         // NOTE: IMPORTANT: We need to add the linearizer to the queue at this point -- see
         // discussion on `write`
         let proph_val = Prophecy::<Option<u64>>::new();
@@ -254,7 +341,6 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             proof {
                 perm = state.request_map.take_permission(self.request_ctr_token.borrow());
             }
-            assume(perm.value() < u64::MAX); // XXX: integer overflow
             request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
             proof {
                 request_proof = state.request_map.issue_request_proof(
@@ -267,6 +353,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             // XXX: debug assert
             assert(state.inv());
         });
+        self.next_request_id = request_id + 1;
 
         let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
 
@@ -291,6 +378,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             );
             server_lbs.lemma_leq_quorums(server_lbs_cpy, read_pred@.min_timestamp);
         }
+        let cancellation = req.cancellation();
         #[allow(unused_parens)]
         let accum = ReadAccumGetPhase::new(
             Tracked(server_lbs_cpy),
@@ -298,7 +386,13 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             Tracked(request_proof),
             read_pred,
         );
-        let quorum_res = bpool.broadcast(req, read_pred, accum).wait_for(
+        let quorum_res = bpool.broadcast_thrifty(
+            req,
+            read_pred,
+            accum,
+            self.thrifty.as_ref(),
+            self.quorum_size(),
+        ).wait_for(
             |s| -> (r: bool)
                 ensures
                     r ==> s.spec_len() >= qsize,
@@ -306,7 +400,10 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
         );
 
         let replies = match quorum_res {
-            Ok(replies) => replies.into_accumulator().destruct(),
+            Ok(replies) => {
+                BroadcastPool::new(&self.pool).cancel(&cancellation);
+                replies.into_accumulator().destruct()
+            },
             Err(e) => {
                 let tracked lincomp;
                 vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
@@ -357,7 +454,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
         assert(replies.spec_min_timestamp() <= replies.spec_max_timestamp());
         vlib::veprintln!("\n[client|{:>3}]: got first round reads quorum_size: {} agree_with_max: {:?}\n", self.id, self.quorum_size(), replies.agree_with_max());
         // check early return
-        if replies.agree_with_max().len() >= self.quorum_size() {
+        if self.unanimous_reads && replies.agree_with_max().len() >= self.quorum_size() {
             vlib::veprintln!("[client|{:>3}]: first round is unanimous", self.id);
             replies.lemma_quorum();
             replies.lemma_max_timestamp();
@@ -381,6 +478,8 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
                     state.servers.lemma_quorum_lb(replies.quorum(), max_ts);
 
                     let tracked (mut register, _view) = GhostVarAuth::<Option<u64>>::new(None);
+                    // the read can only linearize above the retired values
+                    state.linearization_queue.lemma_read_token(&token);
                     let tracked watermark = state.linearization_queue.apply_linearizers_up_to(
                             &mut state.register,
                             max_ts,
@@ -395,6 +494,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
 
                     // XXX: load bearing
                     assert(state.linearization_queue.known_timestamps() == old_known);
+                    state.compact_history();
                 }
 
                 // XXX: debug assert
@@ -418,7 +518,6 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             proof {
                 perm = state.request_map.take_permission(self.request_ctr_token.borrow());
             }
-            assume(perm.value() < u64::MAX); // XXX: integer overflow
             request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
             proof {
                 request_proof = state.request_map.issue_request_proof(
@@ -432,6 +531,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             // XXX: debug assert
             assert(state.inv());
         });
+        self.next_request_id = request_id + 1;
 
         let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
         let read_wb_pred = Ghost(
@@ -445,7 +545,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
         let accum = ReadAccumWbPhase::new(replies, Tracked(request_proof));
         #[allow(unused_parens)]
         let replies_result = bpool.broadcast_filter(
-            req,
+            &req,
             read_wb_pred,
             accum,
             |id: (u64, u64)| !agree_with_max.contains(&id.1),
@@ -503,6 +603,8 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
                 state.servers.lemma_quorum_lb(wb_replies.quorum(), max_ts);
 
                 let tracked (mut register, _view) = GhostVarAuth::<Option<u64>>::new(None);
+                // the read can only linearize above the retired values
+                state.linearization_queue.lemma_read_token(&token);
                 let tracked watermark = state.linearization_queue.apply_linearizers_up_to(
                         &mut state.register,
                         max_ts,
@@ -517,6 +619,7 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
 
                 // XXX: load bearing
                 assert(state.linearization_queue.known_timestamps() == old_known);
+                state.compact_history();
             }
 
             // XXX: debug assert
@@ -525,11 +628,122 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
         return Ok((value, max_ts, Tracked(comp)));
     }
 
-    fn write(&mut self, value: Option<u64>, Tracked(lin): Tracked<ML>) -> (r: Result<
+    fn write(&mut self, value: Option<u64>, lin: Tracked<ML>) -> (r: Result<
         Tracked<ML::Completion>,
         error::WriteError<ML, ML::Completion>,
-    >) {
+    >)
+        ensures
+            r is Err ==> final(self).in_flight(r->Err_0),
+    {
+        match self.write_impl(value, lin, None, None) {
+            Ok((comp, _observed)) => Ok(comp),
+            Err(error::ConditionalWriteError::Write(e)) => Err(e),
+            Err(error::ConditionalWriteError::Conflict { .. }) => {
+                assert(false);
+                unreached()
+            },
+        }
+    }
+
+    fn write_if_unchanged(
+        &mut self,
+        expected: Timestamp,
+        value: Option<u64>,
+        lin: Tracked<ML>,
+    ) -> (r: Result<
+        Tracked<<ML as MutLinearizer<RegisterWrite>>::Completion>,
+        Self::CondWriteErr,
+    >) where
+        ML: ConditionalWriteLinearizer<Timestamp>,
+
+        ensures
+            r is Err && r->Err_0 is Write ==> final(self).in_flight(r->Err_0->Write_0),
+    {
+        let ghost op = RegisterConditionalWrite {
+            id: Ghost(self.register_loc()),
+            expected,
+            new_value: value,
+        };
+        proof {
+            lin@.lemma_conditional_pre(op);
+        }
+
+        match self.write_impl(value, lin, Some(expected), None) {
+            Ok((comp, observed)) => {
+                proof {
+                    lin@.lemma_conditional_post(op, observed@, comp@);
+                }
+                Ok(comp)
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<Pool, C, ML, RL> AbdPool<Pool, ML, RL> where
+    Pool: ConnectionPool<C = C>,
+    C: Channel<R = Response, S = Request, Id = (u64, u64), K = ChannelInv>,
+    C::Id: Eq + Hash,
+    ML: MutLinearizer<RegisterWrite>,
+    RL: ReadLinearizer<RegisterRead>,
+ {
+    /// Write `value`, optionally only if the quorum timestamp is `expected`
+    ///
+    /// See `AbdRegisterClient::write_if_unchanged` for the semantics of the condition: on success,
+    /// this also gives back the timestamp of the quorum the write was issued over.
+    /// With a `policy`, the first phase is retried until it reaches a quorum (see
+    /// [`AbdPool::write_with_retry`])
+    fn write_impl(
+        &mut self,
+        value: Option<u64>,
+        Tracked(lin): Tracked<ML>,
+        expected: Option<Timestamp>,
+        policy: Option<&retry::RetryPolicy>,
+    ) -> (r: Result<
+        (Tracked<ML::Completion>, Ghost<Timestamp>),
+        error::ConditionalWriteError<ML, ML::Completion>,
+    >)
+        requires
+            old(self).inv(),
+            lin.pre(RegisterWrite { id: Ghost(old(self).register_loc()), new_value: value }),
+            Self::write_lin_requires(lin),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> ({
+                let (comp, observed) = r->Ok_0;
+                &&& lin.post(
+                    RegisterWrite { id: Ghost(final(self).register_loc()), new_value: value },
+                    (),
+                    comp@,
+                )
+                &&& expected is Some ==> observed@ == expected->Some_0
+            }),
+            r is Err ==> ({
+                let err = r->Err_0;
+                let op = RegisterWrite { id: Ghost(final(self).register_loc()), new_value: value };
+                &&& err.err_ensures(op, lin)
+                &&& err is Conflict ==> {
+                    &&& expected is Some
+                    &&& err->Conflict_expected == expected->Some_0
+                    &&& err->Conflict_timestamp != expected->Some_0
+                }
+                &&& err is Write ==> final(self).in_flight(err->Write_0)
+            }),
+    {
         let tracked op = RegisterWrite { id: Ghost(self.register_loc()), new_value: value };
+        // both phases of the write take a request id
+        if self.next_client_ctr == u64::MAX || self.next_request_id > u64::MAX - 2 {
+            let tracked lincomp;
+            proof {
+                lincomp = MaybeWriteLinearized::linearizer(lin, op, arbitrary());
+            }
+            return Err(
+                error::ConditionalWriteError::Write(
+                    error::WriteError::CounterExhausted { lincomp: Tracked(lincomp) },
+                ),
+            );
+        }
         // NOTE: IMPORTANT: We need to add the linearizer to the queue at this point
         //
         // Imagine if we added this after the read quorum is achieved
@@ -563,7 +777,6 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
                 perm = state.commitments.take_permission(self.client_ctr_token.borrow());
             }
 
-            assume(perm.value() < u64::MAX); // XXX: integer overflow
             client_ctr = self.client_ctr.fetch_add(Tracked(&mut perm), 1);
             let ghost proph_ts = Timestamp { seqno: proph_seqno@, client_id: self.client_id(), client_ctr };
 
@@ -600,53 +813,87 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             // XXX: debug assert
             assert(state.inv());
         });
+        self.next_client_ctr = client_ctr + 1;
         let ghost proph_ts = Timestamp {
             seqno: proph_seqno@,
             client_id: self.client_id(),
             client_ctr,
         };
 
-        let req_inner = RequestInner::new_get_timestamp(Tracked(server_lbs.extract_lbs()));
-        let tracked request_proof;
-        let request_id;
-        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
-            let ghost old_dom = state.request_map.request_ctr_map().dom();
-            let tracked mut perm;
-            proof {
-                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
-            }
-            assume(perm.value() < u64::MAX); // XXX: integer overflow
-            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
-            proof {
-                request_proof = state.request_map.issue_request_proof(
-                    self.request_ctr_token.borrow_mut(),
-                    request_id,
-                    req_inner,
-                    perm
-                );
-                request_proof.value()->GetTimestamp_0.servers().lemma_eq(server_lbs);
-                assert(state.request_map.request_ctr_map().dom() == old_dom);
-            }
-            // XXX: debug assert
-            assert(state.inv());
-        });
+        // A failed timestamp round is retried in place, as per `policy`: the linearizer stays in
+        // the queue at the prophesied timestamp, so the write is linearized at most once however
+        // many rounds it takes
+        let mut attempt: u32 = 1;
+        let mut get_ts_pred;
+        let get_ts_replies;
+        #[verifier::loop_isolation(false)]
+        loop
+            invariant
+                self.inv(),
+                self.register_loc() == old(self).register_loc(),
+                self.next_request_id <= u64::MAX - 2,
+                1 <= attempt,
+            decreases u32::MAX - attempt,
+        {
+            let req_inner = RequestInner::new_get_timestamp(Tracked(server_lbs.extract_lbs()));
+            let tracked request_proof;
+            let request_id;
+            vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+                let ghost old_dom = state.request_map.request_ctr_map().dom();
+                let tracked mut perm;
+                proof {
+                    perm = state.request_map.take_permission(self.request_ctr_token.borrow());
+                }
+                request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
+                proof {
+                    request_proof = state.request_map.issue_request_proof(
+                        self.request_ctr_token.borrow_mut(),
+                        request_id,
+                        req_inner,
+                        perm
+                    );
+                    request_proof.value()->GetTimestamp_0.servers().lemma_eq(server_lbs);
+                    assert(state.request_map.request_ctr_map().dom() == old_dom);
+                }
+                // XXX: debug assert
+                assert(state.inv());
+            });
+            self.next_request_id = request_id + 1;
 
-        let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
+            let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
 
-        let bpool = BroadcastPool::new(&self.pool);
-        let get_ts_pred = Ghost(
-            GetTimestampPred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
-        );
-        let get_ts_replies = {
+            let bpool = BroadcastPool::new(&self.pool);
+            get_ts_pred = Ghost(
+                GetTimestampPred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
+            );
             let ghost qsize = self.spec_quorum_size();
+            let tracked server_lbs_cpy;
+            let tracked server_tokens_lb_cpy;
+            proof {
+                server_lbs_cpy = server_lbs.extract_lbs();
+                ServerUniverse::lemma_eq_timestamp_trans(
+                    request_proof.value()->GetTimestamp_0.servers(),
+                    server_lbs,
+                    server_lbs_cpy,
+                );
+                server_lbs.lemma_leq_quorums(server_lbs_cpy, old_watermark);
+                server_tokens_lb_cpy = server_tokens_lb.duplicate();
+            }
             let accum = GetTimestampAccumulator::new(
-                Tracked(server_lbs),
-                Tracked(server_tokens_lb),
+                Tracked(server_lbs_cpy),
+                Tracked(server_tokens_lb_cpy),
                 Tracked(request_proof),
                 get_ts_pred,
             );
+            let cancellation = req.cancellation();
             #[allow(unused_parens)]
-            let quorum_res = bpool.broadcast(req, get_ts_pred, accum).wait_for(
+            let quorum_res = bpool.broadcast_thrifty(
+                req,
+                get_ts_pred,
+                accum,
+                self.thrifty.as_ref(),
+                self.quorum_size(),
+            ).wait_for(
                 (|s| -> (r: bool)
                     ensures
                         r ==> s.spec_len() >= qsize,
@@ -654,8 +901,21 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             );
 
             match quorum_res {
-                Ok(q) => q.into_accumulator(),
+                Ok(q) => {
+                    BroadcastPool::new(&self.pool).cancel(&cancellation);
+                    get_ts_replies = q.into_accumulator();
+                    break ;
+                },
                 Err(e) => {
+                    if let Some(policy) = policy {
+                        if attempt < policy.max_attempts && attempt < u32::MAX
+                            && self.next_request_id <= u64::MAX - 2 {
+                            vlib::veprintln!("[client|{:>3}]: retrying write get timestamp round (attempt {})", self.id, attempt + 1);
+                            retry::backoff(policy, attempt);
+                            attempt = attempt + 1;
+                            continue ;
+                        }
+                    }
                     let tracked lincomp;
                     vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
                         let ghost old_dom = state.commitments.client_map().dom();
@@ -672,7 +932,9 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
                                     state.commitments.remove_allocation(allocation, self.client_ctr_token.borrow());
                                     // XXX: load bearing
                                     assert(state.commitments.client_map().dom() == old_dom);
-                                    assert(state.linearization_queue.known_timestamps() == state.commitments.allocated().dom());
+                                    assert(state.linearization_queue.known_timestamps() == state.commitments.allocated().dom().filter(
+                                        |ts: Timestamp| state.linearization_queue.retired_below() <= ts,
+                                    ));
                                 }
                                 lincomp = lc;
                             } else {
@@ -686,15 +948,17 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
                     });
 
                     return Err(
-                        error::WriteError::FailedFirstQuorum {
-                            obtained: e.into_accumulator().n_replies(),
-                            required: self.quorum_size(),
-                            lincomp: Tracked(lincomp),
-                        },
+                        error::ConditionalWriteError::Write(
+                            error::WriteError::FailedFirstQuorum {
+                                obtained: e.into_accumulator().n_replies(),
+                                required: self.quorum_size(),
+                                lincomp: Tracked(lincomp),
+                            },
+                        ),
                     );
                 },
             }
-        };
+        }
 
         vlib::veprintln!("\n[client|{:>3}]: got write get timestamp round quorum_size: {} quorum: {:?} max_timestamp: {:?}\n", self.id, self.quorum_size()
             , get_ts_replies.get_ts_replies(), get_ts_replies.max_resp().timestamp() );
@@ -705,8 +969,64 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
         get_ts_replies.lemma_quorum();
         get_ts_replies.lemma_max_timestamp();
 
+        let conflict = match expected {
+            Some(expected) => max_ts != expected,
+            None => false,
+        };
         // XXX: timestamp recycling would be interesting
-        assume(max_ts.seqno < u64::MAX - 1);  // XXX: integer overflow
+        if conflict || max_ts.seqno == u64::MAX {
+            // the write never physically started: take the linearizer out of the queue
+            let tracked lincomp;
+            vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+                let ghost old_dom = state.commitments.client_map().dom();
+                proof {
+                    if &token_res is Ok {
+                        let tracked token = token_res.tracked_unwrap();
+
+                        let tracked (lc, allocation_opt) = state.linearization_queue.remove_write_lin(token);
+
+                        // if this write had not been committed we need to remove it from
+                        // the commitment map
+                        if &allocation_opt is Some {
+                            let tracked allocation = allocation_opt.tracked_unwrap();
+                            state.commitments.remove_allocation(allocation, self.client_ctr_token.borrow());
+                            // XXX: load bearing
+                            assert(state.commitments.client_map().dom() == old_dom);
+                            assert(state.linearization_queue.known_timestamps() == state.commitments.allocated().dom().filter(
+                                |ts: Timestamp| state.linearization_queue.retired_below() <= ts,
+                            ));
+                        }
+                        lincomp = lc;
+                    } else {
+                        let tracked err = token_res.tracked_unwrap_err();
+                        let tracked err_lin = err.tracked_write_destruct();
+                        lincomp = MaybeWriteLinearized::linearizer(err_lin, op, proph_ts);
+                    }
+                }
+                // XXX: debug assert
+                assert(state.inv());
+            });
+
+            if let Some(expected) = expected {
+                if conflict {
+                    vlib::veprintln!("[client|{:>3}]: conditional write conflict: expected {:?} found {:?}", self.id, expected, max_ts);
+                    return Err(
+                        error::ConditionalWriteError::Conflict {
+                            expected,
+                            timestamp: max_ts,
+                            lincomp: Tracked(lincomp),
+                        },
+                    );
+                }
+            }
+            vlib::veprintln!("[client|{:>3}]: sequence numbers exhausted at {:?}", self.id, max_ts);
+            return Err(
+                error::ConditionalWriteError::Write(
+                    error::WriteError::CounterExhausted { lincomp: Tracked(lincomp) },
+                ),
+            );
+        }
+
         let exec_seqno = max_ts.seqno + 1;
         let exec_ts = Timestamp { seqno: exec_seqno, client_id: self.id, client_ctr };
         proph_seqno.resolve(&exec_seqno);
@@ -746,6 +1066,10 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
 
                     commitment = state.linearization_queue.commit_value(&mut tk);
                     token = tk;
+
+                    // XXX: load bearing
+                    assert(token.id() == self.write_token_id());
+                    assert(commitment.id() == self.commitment_id());
                 }
 
                 // XXX: debug assert
@@ -753,6 +1077,50 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             });
         }
 
+        // the quorum timestamp the write was issued over (`expected`, for a conditional write)
+        match self.write_phase(value, exec_ts, Tracked(token), Tracked(commitment)) {
+            Ok(comp) => Ok((comp, Ghost(max_ts))),
+            Err(e) => Err(error::ConditionalWriteError::Write(e)),
+        }
+    }
+
+    /// Second phase of a write: broadcast the committed value at `exec_ts` until a quorum has it
+    ///
+    /// On failure the write remains physically in flight, and the token and commitment are handed
+    /// back in a `FailedSecondQuorum`
+    fn write_phase(
+        &mut self,
+        value: Option<u64>,
+        exec_ts: Timestamp,
+        Tracked(token): Tracked<LinWriteToken<ML>>,
+        Tracked(commitment): Tracked<WriteCommitment>,
+    ) -> (r: Result<Tracked<ML::Completion>, error::WriteError<ML, ML::Completion>>)
+        requires
+            old(self).inv(),
+            old(self).next_request_id < u64::MAX,
+            token.id() == old(self).write_token_id(),
+            token.key() == exec_ts,
+            token.value().op.new_value == value,
+            commitment.id() == old(self).commitment_id(),
+            commitment.key() == exec_ts,
+            commitment.value() == value,
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> ({
+                let comp = r->Ok_0;
+                token.value().lin.post(token.value().op, (), comp@)
+            }),
+            r is Err ==> ({
+                let err = r->Err_0;
+                &&& err is FailedSecondQuorum
+                &&& err.inv()
+                &&& final(self).in_flight(err)
+                &&& err->FailedSecondQuorum_token@.value().lin == token.value().lin
+                &&& err->FailedSecondQuorum_token@.value().op == token.value().op
+            }),
+    {
+        let ghost state_constant = self.state_inv@.constant();
         let tracked server_lbs;
         let tracked server_tokens_lb;
         vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
@@ -762,136 +1130,466 @@ impl<Pool, C, ML, RL> AbdRegisterClient<C, ML, RL> for AbdPool<Pool, ML, RL> whe
             }
         });
 
-        {
-            let req_inner = RequestInner::new_write(
-                value,
-                exec_ts,
-                Tracked(commitment.duplicate()),
-                Tracked(server_lbs.extract_lbs()),
+        let req_inner = RequestInner::new_write(
+            value,
+            exec_ts,
+            Tracked(commitment.duplicate()),
+            Tracked(server_lbs.extract_lbs()),
+        );
+        let tracked request_proof;
+        let request_id;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let ghost old_dom = state.request_map.request_ctr_map().dom();
+            let tracked mut perm;
+            proof {
+                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
+            }
+            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
+            proof {
+                request_proof = state.request_map.issue_request_proof(
+                    self.request_ctr_token.borrow_mut(),
+                    request_id,
+                    req_inner,
+                    perm
+                );
+                assert(state.request_map.request_ctr_map().dom() == old_dom);
+            }
+            // XXX: debug assert
+            assert(state.inv());
+        });
+        self.next_request_id = request_id + 1;
+
+        let req = Request::new(
+            self.id,
+            request_id,
+            req_inner,
+            Tracked(request_proof.duplicate()),
+        );
+
+        let bpool = BroadcastPool::new(&self.pool);
+        let write_pred = Ghost(
+            WritePred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
+        );
+        let tracked server_lbs_cpy;
+        proof {
+            server_lbs_cpy = server_lbs.extract_lbs();
+            ServerUniverse::lemma_eq_timestamp_trans(
+                request_proof.value()->Write_0.servers(),
+                server_lbs,
+                server_lbs_cpy,
             );
-            let tracked request_proof;
-            let request_id;
-            vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
-                let ghost old_dom = state.request_map.request_ctr_map().dom();
-                let tracked mut perm;
-                proof {
-                    perm = state.request_map.take_permission(self.request_ctr_token.borrow());
-                }
-                assume(perm.value() < u64::MAX); // XXX: integer overflow
-                request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
-                proof {
-                    request_proof = state.request_map.issue_request_proof(
-                        self.request_ctr_token.borrow_mut(),
-                        request_id,
-                        req_inner,
-                        perm
-                    );
-                    assert(state.request_map.request_ctr_map().dom() == old_dom);
+        }
+        let accum = WriteAccumulator::new(
+            Tracked(server_lbs_cpy),
+            Tracked(server_tokens_lb),
+            Tracked(request_proof),
+            write_pred,
+        );
+        let ghost qsize = self.spec_quorum_size();
+        let cancellation = req.cancellation();
+        #[allow(unused_parens)]
+        let quorum_res = bpool.broadcast_thrifty(
+            req,
+            write_pred,
+            accum,
+            self.thrifty.as_ref(),
+            self.quorum_size(),
+        ).wait_for(
+            (|s| -> (r: bool)
+                ensures
+                    r ==> s.spec_len() >= qsize,
+                { s.len() >= self.quorum_size() }),
+        );
+
+        let write_replies = match quorum_res {
+            Ok(q) => {
+                BroadcastPool::new(&self.pool).cancel(&cancellation);
+                q.into_accumulator()
+            },
+            Err(e) => {
+                return Err(
+                    error::WriteError::FailedSecondQuorum {
+                        obtained: e.into_accumulator().n_replies(),
+                        required: self.quorum_size(),
+                        timestamp: exec_ts,
+                        token: Tracked(token),
+                        commitment: Tracked(commitment),
+                    },
+                );
+            },
+        };
+
+        vlib::veprintln!("\n[client|{:>3}]: got write quorum quorum_size: {} quorum: {:?}\n", self.id, self.quorum_size() , write_replies.write_replies());
+
+        let exec_comp;
+        write_replies.lemma_quorum();
+        write_replies.lemma_timestamp();
+        let Tracked(replies_servers) = write_replies.servers_lb();  // needed to have an owned instance
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let tracked comp;
+            proof {
+                let ghost old_known = state.linearization_queue.known_timestamps();
+                let ghost old_watermark = state.linearization_queue.watermark();
+
+                state.servers.lemma_locs();
+                replies_servers.lemma_locs();
+
+                // NOTE: this is an annoying thing from the way that equality works for
+                // ServerUniverse. Even though replies_server does not change, it is not `==`
+                let ghost old_replies_servers = replies_servers;
+                let ghost quorum = write_replies.quorum();
+                replies_servers.lemma_lb(&state.servers);
+                old_replies_servers.lemma_eq(replies_servers);
+                assert(old_replies_servers.valid_quorum(quorum));
+
+                replies_servers.lemma_leq_retains_unanimity(state.servers, quorum, exec_ts);
+                state.linearization_queue.lemma_write_token(&token);
+                state.commitments.agree_commitment(&commitment);
+
+                let tracked (mut register, _view) = GhostVarAuth::<Option<u64>>::new(None);
+                let tracked resource = state.linearization_queue.apply_linearizers_up_to(&mut state.register, exec_ts);
+
+                if exec_ts > old_watermark {
+                    state.servers.lemma_quorum_lb(quorum, exec_ts);
+                    assert(state.linearization_queue.watermark() == exec_ts);
+                } else {
+                    assert(old_watermark == state.linearization_queue.watermark());
                 }
-                // XXX: debug assert
-                assert(state.inv());
-            });
 
-            let req = Request::new(
-                self.id,
-                request_id,
-                req_inner,
-                Tracked(request_proof.duplicate()),
-            );
+                comp = state.linearization_queue.extract_write_completion(token, resource);
 
-            let bpool = BroadcastPool::new(&self.pool);
-            let write_pred = Ghost(
-                WritePred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
-            );
-            let tracked server_lbs_cpy;
+                // XXX: load bearing
+                assert(state.linearization_queue.known_timestamps() == old_known);
+                state.compact_history();
+            }
+
+            exec_comp = Tracked(comp);
+
+            // XXX: debug assert
+            assert(state.inv());
+        });
+
+        Ok(exec_comp)
+    }
+
+    /// Read the current quorum timestamp, without the value
+    ///
+    /// This is a single round: it is the first phase of a write, without allocating a timestamp.
+    ///
+    /// The returned lower bound was taken on the watermark when the call started.
+    /// Since every completed write is at or below the watermark (see
+    /// `LinearizationQueue::lemma_completed_writes_le_watermark`), the returned timestamp is at or
+    /// above every write that completed before the call.
+    pub fn read_timestamp(&mut self) -> (r: Result<
+        (Timestamp, Tracked<MonotonicTimestampResource>),
+        error::ReadTimestampError,
+    >)
+        requires
+            old(self).inv(),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            final(self).watermark_loc() == old(self).watermark_loc(),
+            r is Ok ==> ({
+                let (ts, watermark_lb) = r->Ok_0;
+                &&& watermark_lb@@ is LowerBound
+                &&& watermark_lb@.loc() == final(self).watermark_loc()
+                &&& watermark_lb@@.timestamp() <= ts
+            }),
+    {
+        if self.next_request_id == u64::MAX {
+            return Err(error::ReadTimestampError::CounterExhausted);
+        }
+        let tracked server_lbs;
+        let tracked server_tokens_lb;
+        let tracked watermark_lb;
+        let ghost state_constant = self.state_inv@.constant();
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
             proof {
-                server_lbs_cpy = server_lbs.extract_lbs();
-                ServerUniverse::lemma_eq_timestamp_trans(
-                    request_proof.value()->Write_0.servers(),
-                    server_lbs,
-                    server_lbs_cpy,
+                server_lbs = state.servers.extract_lbs();
+                server_tokens_lb = state.server_tokens.lower_bound();
+                assert(server_tokens_lb@ == state.server_tokens@);
+                watermark_lb = state.linearization_queue.extract_watermark_lb();
+                state.servers.lemma_leq_quorums(server_lbs, state.linearization_queue.watermark());
+            }
+            // XXX: debug assert
+            assert(state.inv());
+        });
+
+        let req_inner = RequestInner::new_get_timestamp(Tracked(server_lbs.extract_lbs()));
+        let tracked request_proof;
+        let request_id;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let ghost old_dom = state.request_map.request_ctr_map().dom();
+            let tracked mut perm;
+            proof {
+                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
+            }
+            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
+            proof {
+                request_proof = state.request_map.issue_request_proof(
+                    self.request_ctr_token.borrow_mut(),
+                    request_id,
+                    req_inner,
+                    perm
                 );
+                request_proof.value()->GetTimestamp_0.servers().lemma_eq(server_lbs);
+                assert(state.request_map.request_ctr_map().dom() == old_dom);
             }
-            let accum = WriteAccumulator::new(
-                Tracked(server_lbs_cpy),
-                Tracked(server_tokens_lb),
-                Tracked(request_proof),
-                write_pred,
-            );
-            let ghost qsize = self.spec_quorum_size();
-            #[allow(unused_parens)]
-            let quorum_res = bpool.broadcast(req, write_pred, accum).wait_for(
-                (|s| -> (r: bool)
-                    ensures
-                        r ==> s.spec_len() >= qsize,
-                    { s.len() >= self.quorum_size() }),
-            );
+            // XXX: debug assert
+            assert(state.inv());
+        });
+        self.next_request_id = request_id + 1;
 
-            let write_replies = match quorum_res {
-                Ok(q) => q.into_accumulator(),
-                Err(e) => {
-                    return Err(
-                        error::WriteError::FailedSecondQuorum {
-                            obtained: e.into_accumulator().n_replies(),
-                            required: self.quorum_size(),
-                            timestamp: exec_ts,
-                            token: Tracked(token),
-                            commitment: Tracked(commitment),
-                        },
-                    );
-                },
-            };
+        let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
 
-            vlib::veprintln!("\n[client|{:>3}]: got write quorum quorum_size: {} quorum: {:?}\n", self.id, self.quorum_size() , write_replies.write_replies());
+        let bpool = BroadcastPool::new(&self.pool);
+        let get_ts_pred = Ghost(
+            GetTimestampPred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
+        );
+        let ghost qsize = self.spec_quorum_size();
+        let accum = GetTimestampAccumulator::new(
+            Tracked(server_lbs),
+            Tracked(server_tokens_lb),
+            Tracked(request_proof),
+            get_ts_pred,
+        );
+        let cancellation = req.cancellation();
+        #[allow(unused_parens)]
+        let quorum_res = bpool.broadcast_thrifty(
+            req,
+            get_ts_pred,
+            accum,
+            self.thrifty.as_ref(),
+            self.quorum_size(),
+        ).wait_for(
+            (|s| -> (r: bool)
+                ensures
+                    r ==> s.spec_len() >= qsize,
+                { s.len() >= self.quorum_size() }),
+        );
 
-            let exec_comp;
-            write_replies.lemma_quorum();
-            write_replies.lemma_timestamp();
-            let Tracked(replies_servers) = write_replies.servers_lb();  // needed to have an owned instance
-            vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
-                let tracked comp;
-                proof {
-                    let ghost old_known = state.linearization_queue.known_timestamps();
-                    let ghost old_watermark = state.linearization_queue.watermark();
+        let get_ts_replies = match quorum_res {
+            Ok(q) => {
+                BroadcastPool::new(&self.pool).cancel(&cancellation);
+                q.into_accumulator()
+            },
+            Err(e) => {
+                return Err(
+                    error::ReadTimestampError::FailedQuorum {
+                        obtained: e.into_accumulator().n_replies(),
+                        required: self.quorum_size(),
+                    },
+                );
+            },
+        };
 
-                    state.servers.lemma_locs();
-                    replies_servers.lemma_locs();
+        vlib::veprintln!("\n[client|{:>3}]: got get timestamp round quorum_size: {} quorum: {:?} max_timestamp: {:?}\n", self.id, self.quorum_size()
+            , get_ts_replies.get_ts_replies(), get_ts_replies.max_resp().timestamp() );
 
-                    // NOTE: this is an annoying thing from the way that equality works for
-                    // ServerUniverse. Even though replies_server does not change, it is not `==`
-                    let ghost old_replies_servers = replies_servers;
-                    let ghost quorum = write_replies.quorum();
-                    replies_servers.lemma_lb(&state.servers);
-                    old_replies_servers.lemma_eq(replies_servers);
-                    assert(old_replies_servers.valid_quorum(quorum));
+        assert(get_ts_replies.constant() == get_ts_pred@);
+        let max_ts = get_ts_replies.max_resp().timestamp();
+        get_ts_replies.lemma_quorum();
+        get_ts_replies.lemma_max_timestamp();
 
-                    replies_servers.lemma_leq_retains_unanimity(state.servers, quorum, exec_ts);
-                    state.linearization_queue.lemma_write_token(&token);
-                    state.commitments.agree_commitment(&commitment);
+        let Tracked(replies_servers) = get_ts_replies.servers_lb();  // needed to have an owned instance
+        let ghost replies_orig_servers = get_ts_replies.orig_servers();
+        proof {
+            // NOTE: this is an annoying thing from the way that equality works for
+            // ServerUniverse. Even though replies_server does not change, it is not `==`
+            let ghost old_replies_servers = replies_servers;
+            let ghost quorum = get_ts_replies.quorum();
+            old_replies_servers.lemma_eq(replies_servers);
+            assert(old_replies_servers.valid_quorum(quorum));
+
+            ServerUniverse::lemma_leq_trans(replies_orig_servers, old_replies_servers, replies_servers);
+            replies_orig_servers.lemma_leq_implies_validity(replies_servers, quorum);
+
+            // watermark <= orig_servers.quorum_timestamp(quorum) <= servers.quorum_timestamp(quorum) == max_ts
+            assert(watermark_lb@.timestamp() <= replies_orig_servers.quorum_timestamp(quorum));
+            replies_orig_servers.lemma_leq_quorum_timestamp(replies_servers, quorum);
+            assert(replies_servers.quorum_timestamp(quorum) == max_ts);
+        }
 
-                    let tracked (mut register, _view) = GhostVarAuth::<Option<u64>>::new(None);
-                    let tracked resource = state.linearization_queue.apply_linearizers_up_to(&mut state.register, exec_ts);
+        Ok((max_ts, Tracked(watermark_lb)))
+    }
 
-                    if exec_ts > old_watermark {
-                        state.servers.lemma_quorum_lb(quorum, exec_ts);
-                        assert(state.linearization_queue.watermark() == exec_ts);
-                    } else {
-                        assert(old_watermark == state.linearization_queue.watermark());
-                    }
+    /// Wait until a quorum of servers is past `since`
+    ///
+    /// Subscribes with every server; each one notifies once it accepts a write with a timestamp
+    /// newer than `since`. Resolves with the smallest notified timestamp, which is only known to
+    /// be newer than `since`: it does not say that a read started afterwards sees a newer value.
+    ///
+    /// The servers which did not notify drop the subscription once this client sends its next
+    /// request (or disconnects).
+    pub fn watch(&mut self, since: Timestamp) -> (r: Result<Timestamp, error::WatchError>)
+        requires
+            old(self).inv(),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> since < r->Ok_0,
+    {
+        if self.next_request_id == u64::MAX {
+            return Err(error::WatchError::CounterExhausted);
+        }
+        let ghost state_constant = self.state_inv@.constant();
+        let req_inner = RequestInner::new_subscribe(since);
+        let tracked request_proof;
+        let request_id;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let ghost old_dom = state.request_map.request_ctr_map().dom();
+            let tracked mut perm;
+            proof {
+                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
+            }
+            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
+            proof {
+                request_proof = state.request_map.issue_request_proof(
+                    self.request_ctr_token.borrow_mut(),
+                    request_id,
+                    req_inner,
+                    perm
+                );
+                assert(state.request_map.request_ctr_map().dom() == old_dom);
+            }
+            // XXX: debug assert
+            assert(state.inv());
+        });
+        self.next_request_id = request_id + 1;
 
-                    comp = state.linearization_queue.extract_write_completion(token, resource);
+        let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
 
-                    // XXX: load bearing
-                    assert(state.linearization_queue.known_timestamps() == old_known);
-                }
+        let bpool = BroadcastPool::new(&self.pool);
+        let sub_pred = Ghost(
+            SubscribePred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
+        );
+        let ghost qsize = self.spec_quorum_size();
+        let accum = SubscribeAccumulator::new(Tracked(request_proof), sub_pred);
+        let cancellation = req.cancellation();
+        #[allow(unused_parens)]
+        let quorum_res = bpool.broadcast(&req, sub_pred, accum).wait_for(
+            (|s| -> (r: bool)
+                ensures
+                    r ==> s.spec_len() >= qsize,
+                { s.len() >= self.quorum_size() }),
+        );
 
-                exec_comp = Tracked(comp);
+        let sub_replies = match quorum_res {
+            Ok(q) => {
+                BroadcastPool::new(&self.pool).cancel(&cancellation);
+                q.into_accumulator()
+            },
+            Err(e) => {
+                return Err(
+                    error::WatchError::FailedQuorum {
+                        obtained: e.into_accumulator().n_replies(),
+                        required: self.quorum_size(),
+                    },
+                );
+            },
+        };
 
-                // XXX: debug assert
-                assert(state.inv());
-            });
+        proof {
+            self.lemma_quorum_nonzero();
+        }
+        assert(sub_replies.constant() == sub_pred@);
+        Ok(sub_replies.min_timestamp())
+    }
+
+    /// Read the value that was written with a specific timestamp
+    ///
+    /// Servers keep only a bounded history of superseded values, so this fails if none of the
+    /// servers which replied remembers the timestamp. The returned commitment ties the value to the timestamp: since
+    /// commitments are persistent, this is the value that was written at that timestamp.
+    pub fn read_at(&mut self, timestamp: Timestamp) -> (r: Result<
+        (Option<u64>, Tracked<WriteCommitment>),
+        error::ReadAtError,
+    >)
+        requires
+            old(self).inv(),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            final(self).commitment_id() == old(self).commitment_id(),
+            r is Ok ==> ({
+                let (value, commitment) = r->Ok_0;
+                &&& commitment@.id() == final(self).commitment_id()
+                &&& commitment@.key() == timestamp
+                &&& commitment@.value() == value
+            }),
+    {
+        if self.next_request_id == u64::MAX {
+            return Err(error::ReadAtError::CounterExhausted);
+        }
+        let ghost state_constant = self.state_inv@.constant();
+        let req_inner = RequestInner::new_get_at(timestamp);
+        let tracked request_proof;
+        let request_id;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let ghost old_dom = state.request_map.request_ctr_map().dom();
+            let tracked mut perm;
+            proof {
+                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
+            }
+            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
+            proof {
+                request_proof = state.request_map.issue_request_proof(
+                    self.request_ctr_token.borrow_mut(),
+                    request_id,
+                    req_inner,
+                    perm
+                );
+                assert(state.request_map.request_ctr_map().dom() == old_dom);
+            }
+            // XXX: debug assert
+            assert(state.inv());
+        });
+        self.next_request_id = request_id + 1;
+
+        let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
+
+        let bpool = BroadcastPool::new(&self.pool);
+        let get_at_pred = Ghost(
+            GetAtPred::new(state_constant, bpool.spec_channels(), self.id, request_proof),
+        );
+        let accum = GetAtAccumulator::new(Tracked(request_proof), get_at_pred);
+        let cancellation = req.cancellation();
+        #[allow(unused_parens)]
+        let found_res = bpool.broadcast(&req, get_at_pred, accum).wait_for(
+            (|s| -> (r: bool)
+                ensures
+                    r ==> s.spec_accumulator().spec_found() is Some,
+                { s.accumulator().is_found() }),
+        );
 
-            Ok(exec_comp)
+        let get_at_replies = match found_res {
+            Ok(q) => {
+                BroadcastPool::new(&self.pool).cancel(&cancellation);
+                q.into_accumulator()
+            },
+            Err(e) => {
+                let accum = e.into_accumulator();
+                if !accum.is_found() {
+                    return Err(
+                        error::ReadAtError::NotFound {
+                            timestamp,
+                            replies: accum.get_at_replies().len(),
+                            servers: self.pool.len(),
+                        },
+                    );
+                }
+                accum
+            },
+        };
+
+        assert(get_at_replies.constant() == get_at_pred@);
+        match get_at_replies.into_found() {
+            Some((value, commitment)) => Ok((value, commitment)),
+            None => {
+                assert(false);
+                unreached()
+            },
         }
     }
 }
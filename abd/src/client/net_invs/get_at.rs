@@ -0,0 +1,330 @@
+use std::collections::BTreeSet;
+
+use crate::channel::ChannelInv;
+use crate::invariants::committed_to::WriteCommitment;
+use crate::invariants::requests::RequestProof;
+#[cfg(verus_only)]
+use crate::invariants::StatePredicate;
+use crate::proto::GetAtResponse;
+use crate::proto::Response;
+use crate::timestamp::Timestamp;
+
+use verdist::network::channel::Channel;
+#[cfg(verus_only)]
+use verdist::network::channel::ChannelInvariant;
+#[cfg(verus_only)]
+use verdist::rpc::proto::TaggedMessage;
+use verdist::rpc::replies::ReplyAccumulator;
+
+use vstd::invariant::InvariantPredicate;
+use vstd::pervasive::unreached;
+use vstd::prelude::*;
+use vstd::resource::Loc;
+
+verus! {
+
+#[allow(unused_variables, dead_code)]
+pub ghost struct GetAtPred<C: Channel<K = ChannelInv>> {
+    pub commitment_id: Loc,
+    pub request_map_id: Loc,
+    pub server_tokens_id: Loc,
+    pub timestamp: Timestamp,
+    pub channels: Map<C::Id, C>,
+    pub client_id: u64,
+    pub request_id: u64,
+}
+
+impl<C: Channel<K = ChannelInv>> GetAtPred<C> {
+    pub open spec fn new(
+        state: StatePredicate,
+        channels: Map<C::Id, C>,
+        client_id: u64,
+        request: RequestProof,
+    ) -> GetAtPred<C> {
+        GetAtPred {
+            commitment_id: state.commitments_ids.commitment_id,
+            request_map_id: state.request_map_ids.request_auth_id,
+            server_tokens_id: state.server_tokens_id,
+            timestamp: request.value()->GetAt_0.spec_timestamp(),
+            channels,
+            client_id,
+            request_id: request.key().1,
+        }
+    }
+}
+
+/// Collects replies to a read-at-timestamp request
+///
+/// Servers only keep a bounded history, so not every server may still know the value: it
+/// suffices that one of them does, since the commitment pins the value for that timestamp
+#[allow(dead_code)]
+pub struct GetAtAccumulator<C: Channel<K = ChannelInv, Id = (u64, u64)>> {
+    // EXEC state
+    /// The first response which carried a value
+    found: Option<GetAtResponse>,
+    /// Received replies
+    replies: BTreeSet<C::Id>,
+    // Spec state
+    commitment_id: Ghost<Loc>,
+    server_tokens_id: Ghost<Loc>,
+    /// channels of the pool this accumulator is working with
+    channels: Ghost<Map<C::Id, C>>,
+    /// get at request proof
+    request: Tracked<RequestProof>,
+}
+
+impl<C> InvariantPredicate<GetAtPred<C>, GetAtAccumulator<C>> for GetAtPred<C> where
+    C: Channel<K = ChannelInv, Id = (u64, u64)>,
+ {
+    open spec fn inv(pred: GetAtPred<C>, v: GetAtAccumulator<C>) -> bool {
+        pred == v.constant()
+    }
+}
+
+pub open spec fn get_at_request_inv(
+    request: RequestProof,
+    request_map_id: Loc,
+    client_id: u64,
+    request_id: u64,
+) -> bool {
+    &&& request.id() == request_map_id
+    &&& request.key().0 == client_id
+    &&& request.key().1 == request_id
+    &&& request.value().req_type() is GetAt
+}
+
+pub open spec fn get_at_channel_inv<C: Channel<K = ChannelInv, Id = (u64, u64)>>(
+    c_inv: ChannelInv,
+    pred: GetAtPred<C>,
+) -> bool {
+    &&& c_inv.commitment_id == pred.commitment_id
+    &&& c_inv.request_map_id == pred.request_map_id
+    &&& c_inv.server_tokens_id == pred.server_tokens_id
+}
+
+impl<C: Channel<K = ChannelInv, Id = (u64, u64)>> GetAtAccumulator<C> {
+    pub fn new(request: Tracked<RequestProof>, pred: Ghost<GetAtPred<C>>) -> (r: Self)
+        requires
+            get_at_request_inv(request@, pred@.request_map_id, pred@.client_id, pred@.request_id),
+            request@.value()->GetAt_0.spec_timestamp() == pred@.timestamp,
+            forall|c_id| #[trigger]
+                pred@.channels.contains_key(c_id) ==> {
+                    let c = pred@.channels[c_id];
+                    &&& c_id.0 == request@.key().0
+                    &&& get_at_channel_inv(c.constant(), pred@)
+                },
+        ensures
+            r.constant() == pred@,
+            r.replies().is_empty(),
+            r.spec_found() is None,
+    {
+        GetAtAccumulator {
+            found: None,
+            replies: BTreeSet::new(),
+            commitment_id: Ghost(pred@.commitment_id),
+            server_tokens_id: Ghost(pred@.server_tokens_id),
+            channels: Ghost(pred@.channels),
+            request,
+        }
+    }
+
+    closed spec fn channel_inv(channels: Map<C::Id, C>, k: GetAtPred<C>) -> bool {
+        forall|c_id| #[trigger]
+            channels.contains_key(c_id) ==> {
+                let c = channels[c_id];
+                &&& c_id.0 == k.client_id
+                &&& get_at_channel_inv(c.constant(), k)
+            }
+    }
+
+    closed spec fn found_inv(
+        found: GetAtResponse,
+        timestamp: Timestamp,
+        commitment_id: Loc,
+    ) -> bool {
+        &&& found.spec_value() is Some
+        &&& found.spec_commitment() is Some
+        &&& found.spec_commitment()->Some_0.key() == timestamp
+        &&& found.spec_commitment()->Some_0.value() == found.spec_value()->Some_0
+        &&& found.commitment_id() == commitment_id
+        &&& found.spec_timestamp() == timestamp
+    }
+
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& Self::channel_inv(self.channels@, self.constant())
+        &&& self.replies@.finite()
+        &&& self.request@.value().req_type() is GetAt
+        &&& self.found is Some ==> Self::found_inv(
+            self.found->Some_0,
+            self.spec_timestamp(),
+            self.commitment_id@,
+        )
+    }
+
+    // SPEC
+    pub open spec fn constant(self) -> GetAtPred<C> {
+        GetAtPred {
+            commitment_id: self.spec_commitment_id(),
+            request_map_id: self.request_map_id(),
+            server_tokens_id: self.server_tokens_id(),
+            timestamp: self.spec_timestamp(),
+            channels: self.spec_channels(),
+            client_id: self.client_id(),
+            request_id: self.request_id(),
+        }
+    }
+
+    pub closed spec fn client_id(self) -> u64 {
+        self.request@.key().0
+    }
+
+    pub closed spec fn request_id(self) -> u64 {
+        self.request@.key().1
+    }
+
+    pub closed spec fn spec_timestamp(self) -> Timestamp {
+        self.request@.value()->GetAt_0.spec_timestamp()
+    }
+
+    pub closed spec fn spec_commitment_id(self) -> Loc {
+        self.commitment_id@
+    }
+
+    pub closed spec fn request_map_id(self) -> Loc {
+        self.request@.id()
+    }
+
+    pub closed spec fn server_tokens_id(self) -> Loc {
+        self.server_tokens_id@
+    }
+
+    pub closed spec fn spec_channels(self) -> Map<C::Id, C> {
+        self.channels@
+    }
+
+    pub closed spec fn spec_found(self) -> Option<GetAtResponse> {
+        self.found
+    }
+
+    pub closed spec fn replies(self) -> Set<C::Id> {
+        self.replies@
+    }
+
+    // EXEC
+    pub fn is_found(&self) -> (r: bool)
+        ensures
+            r == self.spec_found() is Some,
+    {
+        self.found.is_some()
+    }
+
+    pub fn get_at_replies(&self) -> (r: BTreeSet<C::Id>)
+        ensures
+            r@ == self.replies(),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        self.replies.clone()
+    }
+
+    /// Returns the value found for the timestamp, along with its commitment
+    pub fn into_found(self) -> (r: Option<(Option<u64>, Tracked<WriteCommitment>)>)
+        ensures
+            r is Some <==> self.spec_found() is Some,
+            r is Some ==> {
+                let (value, commitment) = r->Some_0;
+                &&& commitment@.id() == self.spec_commitment_id()
+                &&& commitment@.key() == self.spec_timestamp()
+                &&& commitment@.value() == value
+            },
+    {
+        proof {
+            use_type_invariant(&self);
+        }
+        match self.found {
+            Some(resp) => {
+                let commitment = resp.commitment();
+                match resp.value() {
+                    Some(value) => Some((*value, commitment)),
+                    None => {
+                        assert(false);
+                        unreached()
+                    },
+                }
+            },
+            None => None,
+        }
+    }
+
+    fn insert_get_at(&mut self, id: (u64, u64), resp: Response)
+        requires
+            GetAtPred::inv(old(self).constant(), *old(self)),
+            old(self).client_id() == id.0,
+            resp.req_type() is GetAt,
+            resp.request() == old(self).request@.value(),
+            resp.get_at().spec_commitment() is Some ==> resp.get_at().commitment_id()
+                == old(self).spec_commitment_id(),
+        ensures
+            GetAtPred::inv(final(self).constant(), *final(self)),
+            final(self).constant() == old(self).constant(),
+            final(self).replies() == old(self).replies().insert(id),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(&*self);
+        }
+
+        if self.replies.contains(&id) {
+            return ;
+        }
+        self.replies.insert(id);
+
+        let r = resp.destruct_get_at();
+        r.lemma_get_at_response();
+        if self.found.is_none() && r.value().is_some() {
+            self.found = Some(r);
+        }
+    }
+}
+
+impl<C> ReplyAccumulator<C, GetAtPred<C>> for GetAtAccumulator<C> where
+    C: Channel<Id = (u64, u64), R = Response, K = ChannelInv>,
+ {
+    #[allow(unused_variables)]
+    #[verifier::exec_allows_no_decreases_clause]
+    fn insert(&mut self, pred: Ghost<GetAtPred<C>>, id: (u64, u64), reply: Response)
+        ensures
+            final(self).channels() == old(self).channels(),
+    {
+        proof {
+            use_type_invariant(&*self);
+
+            assume(C::K::recv_inv(self.channels()[id].constant(), id, reply));  // TODO(verus): this is a verus problem
+        }
+
+        reply.agree_request(&mut self.request);
+        reply.lemma_inv();
+
+        self.insert_get_at(id, reply);
+    }
+
+    open spec fn request_tag(self) -> u64 {
+        self.request_id()
+    }
+
+    open spec fn spec_handled_replies(self) -> Set<C::Id> {
+        self.replies()
+    }
+
+    fn handled_replies(&self) -> BTreeSet<C::Id> {
+        self.get_at_replies()
+    }
+
+    open spec fn channels(self) -> Map<C::Id, C> {
+        self.spec_channels()
+    }
+}
+
+} // verus!
@@ -1,7 +1,11 @@
+mod get_at;
 mod read;
+mod subscribe;
 mod write_read_phase;
 mod write_write_phase;
 
+pub use get_at::*;
 pub use read::*;
+pub use subscribe::*;
 pub use write_read_phase::*;
 pub use write_write_phase::*;
@@ -0,0 +1,294 @@
+use std::collections::BTreeSet;
+
+use crate::channel::ChannelInv;
+use crate::invariants::requests::RequestProof;
+#[cfg(verus_only)]
+use crate::invariants::StatePredicate;
+use crate::proto::Response;
+use crate::timestamp::Timestamp;
+
+use verdist::network::channel::Channel;
+#[cfg(verus_only)]
+use verdist::network::channel::ChannelInvariant;
+#[cfg(verus_only)]
+use verdist::rpc::proto::TaggedMessage;
+use verdist::rpc::replies::ReplyAccumulator;
+
+use vstd::invariant::InvariantPredicate;
+use vstd::prelude::*;
+use vstd::resource::Loc;
+
+verus! {
+
+#[allow(unused_variables, dead_code)]
+pub ghost struct SubscribePred<C: Channel<K = ChannelInv>> {
+    pub request_map_id: Loc,
+    pub server_tokens_id: Loc,
+    pub since: Timestamp,
+    pub channels: Map<C::Id, C>,
+    pub client_id: u64,
+    pub request_id: u64,
+}
+
+impl<C: Channel<K = ChannelInv>> SubscribePred<C> {
+    pub open spec fn new(
+        state: StatePredicate,
+        channels: Map<C::Id, C>,
+        client_id: u64,
+        request: RequestProof,
+    ) -> SubscribePred<C> {
+        SubscribePred {
+            request_map_id: state.request_map_ids.request_auth_id,
+            server_tokens_id: state.server_tokens_id,
+            since: request.value()->Subscribe_0.spec_since(),
+            channels,
+            client_id,
+            request_id: request.key().1,
+        }
+    }
+}
+
+/// Collects notifications for a subscription
+///
+/// Every notification carries a timestamp newer than the subscription's; the accumulator keeps
+/// the smallest of them, which every notifying server is known to be at or above
+#[allow(dead_code)]
+pub struct SubscribeAccumulator<C: Channel<K = ChannelInv, Id = (u64, u64)>> {
+    // EXEC state
+    /// The smallest notified timestamp
+    min_timestamp: Option<Timestamp>,
+    /// Received replies
+    replies: BTreeSet<C::Id>,
+    // Spec state
+    server_tokens_id: Ghost<Loc>,
+    /// channels of the pool this accumulator is working with
+    channels: Ghost<Map<C::Id, C>>,
+    /// subscribe request proof
+    request: Tracked<RequestProof>,
+}
+
+impl<C> InvariantPredicate<SubscribePred<C>, SubscribeAccumulator<C>> for SubscribePred<C> where
+    C: Channel<K = ChannelInv, Id = (u64, u64)>,
+ {
+    open spec fn inv(pred: SubscribePred<C>, v: SubscribeAccumulator<C>) -> bool {
+        pred == v.constant()
+    }
+}
+
+pub open spec fn subscribe_request_inv(
+    request: RequestProof,
+    request_map_id: Loc,
+    client_id: u64,
+    request_id: u64,
+) -> bool {
+    &&& request.id() == request_map_id
+    &&& request.key().0 == client_id
+    &&& request.key().1 == request_id
+    &&& request.value().req_type() is Subscribe
+}
+
+pub open spec fn subscribe_channel_inv<C: Channel<K = ChannelInv, Id = (u64, u64)>>(
+    c_inv: ChannelInv,
+    pred: SubscribePred<C>,
+) -> bool {
+    &&& c_inv.request_map_id == pred.request_map_id
+    &&& c_inv.server_tokens_id == pred.server_tokens_id
+}
+
+impl<C: Channel<K = ChannelInv, Id = (u64, u64)>> SubscribeAccumulator<C> {
+    pub fn new(request: Tracked<RequestProof>, pred: Ghost<SubscribePred<C>>) -> (r: Self)
+        requires
+            subscribe_request_inv(
+                request@,
+                pred@.request_map_id,
+                pred@.client_id,
+                pred@.request_id,
+            ),
+            request@.value()->Subscribe_0.spec_since() == pred@.since,
+            forall|c_id| #[trigger]
+                pred@.channels.contains_key(c_id) ==> {
+                    let c = pred@.channels[c_id];
+                    &&& c_id.0 == request@.key().0
+                    &&& subscribe_channel_inv(c.constant(), pred@)
+                },
+        ensures
+            r.constant() == pred@,
+            r.replies().is_empty(),
+    {
+        SubscribeAccumulator {
+            min_timestamp: None,
+            replies: BTreeSet::new(),
+            server_tokens_id: Ghost(pred@.server_tokens_id),
+            channels: Ghost(pred@.channels),
+            request,
+        }
+    }
+
+    closed spec fn channel_inv(channels: Map<C::Id, C>, k: SubscribePred<C>) -> bool {
+        forall|c_id| #[trigger]
+            channels.contains_key(c_id) ==> {
+                let c = channels[c_id];
+                &&& c_id.0 == k.client_id
+                &&& subscribe_channel_inv(c.constant(), k)
+            }
+    }
+
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& Self::channel_inv(self.channels@, self.constant())
+        &&& self.replies@.finite()
+        &&& self.request@.value().req_type() is Subscribe
+        &&& self.min_timestamp is None <==> self.replies@.is_empty()
+        &&& self.min_timestamp is Some ==> self.spec_since() < self.min_timestamp->Some_0
+    }
+
+    // SPEC
+    pub open spec fn constant(self) -> SubscribePred<C> {
+        SubscribePred {
+            request_map_id: self.request_map_id(),
+            server_tokens_id: self.server_tokens_id(),
+            since: self.spec_since(),
+            channels: self.spec_channels(),
+            client_id: self.client_id(),
+            request_id: self.request_id(),
+        }
+    }
+
+    pub closed spec fn client_id(self) -> u64 {
+        self.request@.key().0
+    }
+
+    pub closed spec fn request_id(self) -> u64 {
+        self.request@.key().1
+    }
+
+    pub closed spec fn spec_since(self) -> Timestamp {
+        self.request@.value()->Subscribe_0.spec_since()
+    }
+
+    pub closed spec fn request_map_id(self) -> Loc {
+        self.request@.id()
+    }
+
+    pub closed spec fn server_tokens_id(self) -> Loc {
+        self.server_tokens_id@
+    }
+
+    pub closed spec fn spec_channels(self) -> Map<C::Id, C> {
+        self.channels@
+    }
+
+    pub closed spec fn spec_min_timestamp(self) -> Timestamp
+        recommends
+            !self.replies().is_empty(),
+    {
+        self.min_timestamp->Some_0
+    }
+
+    pub closed spec fn replies(self) -> Set<C::Id> {
+        self.replies@
+    }
+
+    // EXEC
+    pub fn n_replies(&self) -> (r: usize)
+        ensures
+            r@ == self.replies().len(),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        self.replies.len()
+    }
+
+    pub fn subscribe_replies(&self) -> (r: BTreeSet<C::Id>)
+        ensures
+            r@ == self.replies(),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        self.replies.clone()
+    }
+
+    pub fn min_timestamp(&self) -> (r: Timestamp)
+        requires
+            !self.replies().is_empty(),
+        ensures
+            r == self.spec_min_timestamp(),
+            self.spec_since() < r,
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        self.min_timestamp.unwrap()
+    }
+
+    fn insert_subscribe(&mut self, id: (u64, u64), resp: Response)
+        requires
+            SubscribePred::inv(old(self).constant(), *old(self)),
+            old(self).client_id() == id.0,
+            resp.req_type() is Subscribe,
+            resp.request() == old(self).request@.value(),
+        ensures
+            SubscribePred::inv(final(self).constant(), *final(self)),
+            final(self).constant() == old(self).constant(),
+            final(self).replies() == old(self).replies().insert(id),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(&*self);
+        }
+
+        if self.replies.contains(&id) {
+            return ;
+        }
+
+        let r = resp.destruct_subscribe();
+        let timestamp = r.timestamp();
+        self.min_timestamp = match self.min_timestamp {
+            Some(min) if min <= timestamp => Some(min),
+            _ => Some(timestamp),
+        };
+        self.replies.insert(id);
+    }
+}
+
+impl<C> ReplyAccumulator<C, SubscribePred<C>> for SubscribeAccumulator<C> where
+    C: Channel<Id = (u64, u64), R = Response, K = ChannelInv>,
+ {
+    #[allow(unused_variables)]
+    #[verifier::exec_allows_no_decreases_clause]
+    fn insert(&mut self, pred: Ghost<SubscribePred<C>>, id: (u64, u64), reply: Response)
+        ensures
+            final(self).channels() == old(self).channels(),
+    {
+        proof {
+            use_type_invariant(&*self);
+
+            assume(C::K::recv_inv(self.channels()[id].constant(), id, reply));  // TODO(verus): this is a verus problem
+        }
+
+        reply.agree_request(&mut self.request);
+        reply.lemma_inv();
+
+        self.insert_subscribe(id, reply);
+    }
+
+    open spec fn request_tag(self) -> u64 {
+        self.request_id()
+    }
+
+    open spec fn spec_handled_replies(self) -> Set<C::Id> {
+        self.replies()
+    }
+
+    fn handled_replies(&self) -> BTreeSet<C::Id> {
+        self.subscribe_replies()
+    }
+
+    open spec fn channels(self) -> Map<C::Id, C> {
+        self.spec_channels()
+    }
+}
+
+} // verus!
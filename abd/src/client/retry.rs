@@ -0,0 +1,326 @@
+//! Retrying failed quorum phases
+//!
+//! A failed read hands the linearizer back (possibly already applied, see
+//! [`MaybeReadLinearized`]). Linearizers that can be rebuilt from it ([`RecoverRead`]) can be
+//! retried without the caller's intervention.
+//!
+//! Writes are not rebuilt: a write whose linearizer was already applied would be written twice.
+//! Instead, the first phase of the write is retried in place, keeping its linearizer in the queue,
+//! and a write that failed in its second phase (which is physically in flight) is completed
+//! ([`AbdPool::complete_write`]).
+use super::error;
+use super::AbdPool;
+use crate::channel::ChannelInv;
+use crate::invariants::lin_queue::MaybeReadLinearized;
+#[cfg(verus_only)]
+use crate::invariants::lin_queue::WriteTokenVal;
+use crate::proto::Request;
+use crate::proto::Response;
+use crate::timestamp::Timestamp;
+
+use specs::abd::AbdError;
+use specs::abd::AbdRegisterClient;
+use specs::abd::OwnedReadPerm;
+use specs::abd::RegisterRead;
+use specs::abd::RegisterWrite;
+
+use verdist::network::channel::Channel;
+use verdist::pool::ConnectionPool;
+
+use vstd::logatom::MutLinearizer;
+use vstd::logatom::ReadLinearizer;
+use vstd::pervasive::unreached;
+use vstd::prelude::*;
+
+use std::hash::Hash;
+use std::time::Duration;
+
+/// Sleep before retrying: the delay doubles with every attempt, up to the maximum. With jitter,
+/// a random delay up to that is taken instead, so that clients that failed together spread out.
+pub(crate) fn backoff(policy: &RetryPolicy, attempt: u32) {
+    let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+    let delay = policy.base_delay_ms.saturating_mul(factor).min(policy.max_delay_ms);
+    let delay = if policy.jitter {
+        rand::random_range(0..=delay)
+    } else {
+        delay
+    };
+    std::thread::sleep(Duration::from_millis(delay));
+}
+
+verus! {
+
+pub assume_specification[ backoff ](policy: &RetryPolicy, attempt: u32)
+;
+
+/// How many times, and how far apart, to attempt an operation
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry; it doubles with every retry after that
+    pub base_delay_ms: u64,
+    /// Upper bound on the delay between attempts
+    pub max_delay_ms: u64,
+    /// Wait for a random fraction of the delay
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff from 10ms up to 1s, with jitter
+    pub fn new(max_attempts: u32) -> (r: Self)
+        ensures
+            r.max_attempts == max_attempts,
+    {
+        RetryPolicy { max_attempts, base_delay_ms: 10, max_delay_ms: 1000, jitter: true }
+    }
+}
+
+/// Read linearizers that can be rebuilt from the outcome of a failed attempt
+///
+/// The rebuilt linearizer has the same postcondition, so a retry gives the caller the same
+/// guarantees the first attempt would have.
+pub trait RecoverRead: ReadLinearizer<RegisterRead> + Sized {
+    proof fn recover(tracked lincomp: MaybeReadLinearized<Self, Self::Completion>) -> (tracked r:
+        Self)
+        requires
+            lincomp.inv(),
+        ensures
+            r.pre(lincomp.op()),
+            r.namespaces() == lincomp.lin().namespaces(),
+            forall|op: RegisterRead, value: Option<u64>, c: Self::Completion| #[trigger]
+                r.post(op, value, c) == lincomp.lin().post(op, value, c),
+    ;
+}
+
+impl RecoverRead for OwnedReadPerm {
+    proof fn recover(tracked lincomp: MaybeReadLinearized<Self, Self::Completion>) -> (tracked r:
+        Self) {
+        match lincomp {
+            MaybeReadLinearized::Linearizer { lin, .. } => lin,
+            MaybeReadLinearized::Completion { completion, .. } => OwnedReadPerm {
+                register: completion,
+            },
+        }
+    }
+}
+
+impl<Pool, C, ML, RL> AbdPool<Pool, ML, RL> where
+    Pool: ConnectionPool<C = C>,
+    C: Channel<R = Response, S = Request, Id = (u64, u64), K = ChannelInv>,
+    C::Id: Eq + Hash,
+    ML: MutLinearizer<RegisterWrite>,
+    RL: RecoverRead,
+ {
+    /// Read, retrying when a quorum cannot be reached
+    ///
+    /// On error, the linearizer in the error is equivalent to `lin`
+    pub fn read_with_retry(&mut self, policy: &RetryPolicy, Tracked(lin): Tracked<RL>) -> (r:
+        Result<(Option<u64>, Timestamp, Tracked<RL::Completion>), error::ReadError<RL, RL::Completion>>)
+        requires
+            old(self).inv(),
+            lin.pre(RegisterRead { id: Ghost(old(self).register_loc()) }),
+            Self::read_lin_requires(lin),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> ({
+                let (val, ts, compl) = r->Ok_0;
+                lin.post(RegisterRead { id: Ghost(final(self).register_loc()) }, val, compl@)
+            }),
+            r is Err ==> exists|l: RL|
+                {
+                    &&& #[trigger] r->Err_0.err_ensures(
+                        RegisterRead { id: Ghost(final(self).register_loc()) },
+                        l,
+                    )
+                    &&& forall|op: RegisterRead, value: Option<u64>, c: RL::Completion| #[trigger]
+                        l.post(op, value, c) == lin.post(op, value, c)
+                },
+    {
+        let ghost orig_lin = lin;
+        let ghost op = RegisterRead { id: Ghost(self.register_loc()) };
+        let tracked mut lin = lin;
+        let mut attempt: u32 = 1;
+        loop
+            invariant
+                self.inv(),
+                self.register_loc() == old(self).register_loc(),
+                op == RegisterRead { id: Ghost(self.register_loc()) },
+                lin.pre(op),
+                Self::read_lin_requires(lin),
+                forall|op: RegisterRead, value: Option<u64>, c: RL::Completion| #[trigger]
+                    lin.post(op, value, c) == orig_lin.post(op, value, c),
+                1 <= attempt,
+            decreases u32::MAX - attempt,
+        {
+            let ghost cur_lin = lin;
+            let err = match self.read(Tracked(lin)) {
+                Ok(res) => return Ok(res),
+                Err(err) => err,
+            };
+            if attempt >= policy.max_attempts || attempt == u32::MAX {
+                assert(err.err_ensures(op, cur_lin));
+                return Err(err);
+            }
+            match err {
+                error::ReadError::FailedFirstQuorum { lincomp: Tracked(lincomp), .. } => {
+                    proof {
+                        lin = RL::recover(lincomp);
+                    }
+                },
+                error::ReadError::FailedSecondQuorum { lincomp: Tracked(lincomp), .. } => {
+                    proof {
+                        lin = RL::recover(lincomp);
+                    }
+                },
+                err => {
+                    assert(err.err_ensures(op, cur_lin));
+                    return Err(err);
+                },
+            }
+            vlib::veprintln!("[client|{:>3}]: retrying read (attempt {})", self.id, attempt + 1);
+            backoff(policy, attempt);
+            attempt = attempt + 1;
+        }
+    }
+
+    /// Write, retrying when the write cannot get past its first phase
+    ///
+    /// The first phase is retried in place, with the linearizer left in the queue: if a concurrent
+    /// write linearizes it in the meantime, the retry finishes that same write rather than writing
+    /// the value again. If the write fails in its second phase it is already physically in flight,
+    /// so it is completed instead (see [`AbdPool::complete_write`]). If that fails too, the error
+    /// is still in flight, and can be completed later.
+    ///
+    /// On error, the linearizer in the error is equivalent to `lin`
+    pub fn write_with_retry(
+        &mut self,
+        policy: &RetryPolicy,
+        value: Option<u64>,
+        Tracked(lin): Tracked<ML>,
+    ) -> (r: Result<Tracked<ML::Completion>, error::WriteError<ML, ML::Completion>>)
+        requires
+            old(self).inv(),
+            lin.pre(RegisterWrite { id: Ghost(old(self).register_loc()), new_value: value }),
+            Self::write_lin_requires(lin),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> ({
+                let comp = r->Ok_0;
+                lin.post(
+                    RegisterWrite { id: Ghost(final(self).register_loc()), new_value: value },
+                    (),
+                    comp@,
+                )
+            }),
+            r is Err ==> exists|l: ML|
+                {
+                    &&& #[trigger] r->Err_0.err_ensures(
+                        RegisterWrite { id: Ghost(final(self).register_loc()), new_value: value },
+                        l,
+                    )
+                    &&& forall|op: RegisterWrite, c: ML::Completion| #[trigger]
+                        l.post(op, (), c) == lin.post(op, (), c)
+                },
+            r is Err ==> final(self).in_flight(r->Err_0),
+    {
+        let ghost op = RegisterWrite { id: Ghost(self.register_loc()), new_value: value };
+        let err = match self.write_impl(value, Tracked(lin), None, Some(policy)) {
+            Ok((comp, _observed)) => return Ok(comp),
+            Err(error::ConditionalWriteError::Write(err)) => err,
+            Err(error::ConditionalWriteError::Conflict { .. }) => {
+                assert(false);
+                unreached()
+            },
+        };
+        if err.is_in_flight() {
+            let r = self.complete_write(policy, value, err);
+            // XXX: debug assert
+            assert(r is Err ==> r->Err_0.err_ensures(op, lin));
+            return r;
+        }
+        Err(err)
+    }
+
+    /// Complete a write that failed in its second phase
+    ///
+    /// The write is physically in flight, so the only way out is through: the `Write` phase is
+    /// re-broadcast, backing off between rounds, until a quorum acknowledges it. After
+    /// `policy.max_attempts` rounds (or if the client runs out of request ids), the write is handed
+    /// back still in flight, so that it can be completed later.
+    pub fn complete_write(
+        &mut self,
+        policy: &RetryPolicy,
+        value: Option<u64>,
+        err: error::WriteError<ML, ML::Completion>,
+    ) -> (r: Result<Tracked<ML::Completion>, error::WriteError<ML, ML::Completion>>)
+        requires
+            old(self).inv(),
+            old(self).in_flight(err),
+            err is FailedSecondQuorum,
+            err.inv(),
+            err->FailedSecondQuorum_token@.value().op.new_value == value,
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> ({
+                let WriteTokenVal { lin, op, .. } = err->FailedSecondQuorum_token@.value();
+                lin.post(op, (), r->Ok_0@)
+            }),
+            r is Err ==> ({
+                let e = r->Err_0;
+                &&& e is FailedSecondQuorum
+                &&& e.inv()
+                &&& final(self).in_flight(e)
+                &&& e->FailedSecondQuorum_token@.value().lin
+                    == err->FailedSecondQuorum_token@.value().lin
+                &&& e->FailedSecondQuorum_token@.value().op
+                    == err->FailedSecondQuorum_token@.value().op
+            }),
+    {
+        let ghost token_val = err->FailedSecondQuorum_token@.value();
+        let mut err = err;
+        let mut attempt: u32 = 1;
+        loop
+            invariant
+                self.inv(),
+                self.register_loc() == old(self).register_loc(),
+                self.in_flight(err),
+                err is FailedSecondQuorum,
+                err.inv(),
+                err->FailedSecondQuorum_token@.value().lin == token_val.lin,
+                err->FailedSecondQuorum_token@.value().op == token_val.op,
+                token_val.op.new_value == value,
+                1 <= attempt,
+            decreases u32::MAX - attempt,
+        {
+            if self.next_request_id == u64::MAX {
+                return Err(err);
+            }
+            let (timestamp, token, commitment) = match err {
+                error::WriteError::FailedSecondQuorum { timestamp, token, commitment, .. } => (
+                    timestamp,
+                    token,
+                    commitment,
+                ),
+                _ => {
+                    assert(false);
+                    unreached()
+                },
+            };
+            backoff(policy, attempt);
+            vlib::veprintln!("[client|{:>3}]: re-broadcasting write at {:?} (attempt {})", self.id, timestamp, attempt);
+            match self.write_phase(value, timestamp, token, commitment) {
+                Ok(comp) => return Ok(comp),
+                Err(e) => err = e,
+            }
+            if attempt >= policy.max_attempts || attempt == u32::MAX {
+                return Err(err);
+            }
+            attempt = attempt + 1;
+        }
+    }
+}
+
+} // verus!
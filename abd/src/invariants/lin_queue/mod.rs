@@ -117,6 +117,8 @@ pub struct LinearizationQueue<ML, RL> where
     next_read_op: nat,
     // everything up to the watermark is guaranteed to be applied
     watermark: MonotonicTimestampResource,
+    // committed values below this timestamp have been retired (see `compact`)
+    ghost retired_below: Timestamp,
     // This is the register this lin queue refers to
     ghost register_id: Loc,
 }
@@ -161,8 +163,13 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         &&& self.pending_writes.dom().finite()
         &&& self.pending_reads.dom().finite()
         &&& self.committed_to@.contains_key(self.watermark@.timestamp())
+        &&& self.retired_below <= self.watermark@.timestamp()
         &&& forall|ts: Timestamp| #[trigger]
             self.committed_to@.contains_key(ts) ==> ts <= self.watermark@.timestamp()
+        &&& forall|ts: Timestamp| #[trigger]
+            self.committed_to@.contains_key(ts) ==> self.retired_below <= ts
+        &&& forall|ts: Timestamp| #[trigger]
+            self.write_token_map@.contains_key(ts) ==> self.retired_below <= ts
         &&& forall|ts: Timestamp| #[trigger]
             self.write_token_map@.contains_key(ts) && ts <= self.watermark@.timestamp()
                 ==> self.committed_to@.contains_key(ts)
@@ -219,6 +226,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
                 &&& tok.min_ts.loc() == self.watermark.loc()
                 &&& tok.min_ts@ is LowerBound
                 &&& tok.min_ts@.timestamp() <= self.watermark@.timestamp()
+                &&& self.retired_below <= tok.min_ts@.timestamp()
             }
     }
 
@@ -322,6 +330,11 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         self.watermark@.timestamp()
     }
 
+    /// Committed values below this timestamp have been retired
+    pub closed spec fn retired_below(self) -> Timestamp {
+        self.retired_below
+    }
+
     pub closed spec fn current_value(self) -> Option<u64>
         recommends
             self.basic_inv(),
@@ -399,6 +412,30 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         lb.lemma_lower_bound(&self.watermark);
     }
 
+    /// Get a lower bound on the current watermark
+    pub proof fn extract_watermark_lb(tracked &self) -> (tracked r: MonotonicTimestampResource)
+        requires
+            self.inv(),
+        ensures
+            r@ is LowerBound,
+            r.loc() == self.watermark_id(),
+            r@.timestamp() == self.watermark(),
+    {
+        self.watermark.extract_lower_bound()
+    }
+
+    /// Every write that has completed (or whose value was committed) is at or below the watermark
+    pub proof fn lemma_completed_writes_le_watermark(self)
+        requires
+            self.inv(),
+        ensures
+            forall|ts: Timestamp| #[trigger]
+                self.completed_writes().contains_key(ts) ==> ts <= self.watermark(),
+            forall|ts: Timestamp| #[trigger]
+                self.committed_values().contains_key(ts) ==> ts <= self.watermark(),
+    {
+    }
+
     /// Show that if we have a write token for a key, then it exists
     pub proof fn lemma_write_token(tracked &self, tracked token: &LinWriteToken<ML>)
         requires
@@ -406,6 +443,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
             token.id() == self.write_token_id(),
         ensures
             self.outstanding_writes().contains_pair(token.key(), token.value()),
+            self.retired_below() <= token.key(),
             ({
                 ||| self.pending_writes().contains_key(token.key())
                 ||| self.completed_writes().contains_key(token.key())
@@ -421,6 +459,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
             token.id() == self.read_token_id(),
         ensures
             self.outstanding_reads().contains_pair(token.key(), token.value()),
+            self.retired_below() <= token.value().min_ts@.timestamp(),
             ({
                 ||| self.pending_reads().contains_key(token.key())
                 ||| self.completed_reads().contains_key(token.key())
@@ -451,6 +490,9 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
             self.outstanding_writes().dom() <= self.known_timestamps(),
             self.pending_writes().dom() <= self.known_timestamps(),
             self.completed_writes().dom() <= self.known_timestamps(),
+            self.retired_below() <= self.watermark(),
+            forall|ts: Timestamp| #[trigger]
+                self.known_timestamps().contains(ts) ==> self.retired_below() <= ts,
     {
     }
 }
@@ -469,6 +511,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
             result.register_id() == register_id,
             result.committed_to_id() == zero_commitment.id(),
             result.watermark() == Timestamp::spec_default(),
+            result.retired_below() == Timestamp::spec_default(),
             result.committed_values() == map![Timestamp::spec_default() => None::<u64>],
             result.current_value() == None::<u64>,
             result.outstanding_reads().is_empty(),
@@ -497,6 +540,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
             read_token_map,
             next_read_op: 0,
             watermark,
+            retired_below: Timestamp::spec_default(),
             register_id,
         }
     }
@@ -526,6 +570,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).current_value() == old(self).current_value(),
             final(self).watermark() == old(self).watermark(),
             final(self).committed_values() == old(self).committed_values(),
@@ -602,6 +647,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).current_value() == old(self).current_value(),
             final(self).watermark() == old(self).watermark(),
             final(self).committed_values() == old(self).committed_values(),
@@ -664,6 +710,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).current_value() == old(self).current_value(),
             final(self).watermark() == old(self).watermark(),
             final(self).committed_values() == old(self).committed_values(),
@@ -764,6 +811,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).current_value() == final(register)@,
             final(register).id() == old(register).id(),
             final(self).outstanding_writes() == old(self).outstanding_writes(),
@@ -861,6 +909,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).watermark() == old(self).watermark(),
             final(self).current_value() == old(self).current_value(),
             final(self).outstanding_writes() == old(self).outstanding_writes(),
@@ -946,6 +995,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).watermark() == old(self).watermark(),
             final(self).current_value() == old(self).current_value(),
             final(self).committed_values() == old(self).committed_values(),
@@ -993,6 +1043,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).watermark() == old(self).watermark(),
             final(self).current_value() == old(self).current_value(),
             final(self).committed_values() == old(self).committed_values(),
@@ -1030,6 +1081,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).watermark() == old(self).watermark(),
             final(self).current_value() == old(self).current_value(),
             final(self).committed_values() == old(self).committed_values(),
@@ -1085,6 +1137,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
         ensures
             final(self).inv(),
             final(self).ids() == old(self).ids(),
+            final(self).retired_below() == old(self).retired_below(),
             final(self).watermark() == old(self).watermark(),
             final(self).current_value() == old(self).current_value(),
             final(self).committed_values() == old(self).committed_values(),
@@ -1094,6 +1147,7 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
             final(self).completed_writes() == old(self).completed_writes(),
             token.value().lin == r.lin(),
             token.value().op == r.op(),
+            r.inv(),
     {
         token.agree(&self.read_token_map);
 
@@ -1119,6 +1173,145 @@ impl<ML, RL> LinearizationQueue<ML, RL> where
 
         lincomp
     }
+
+    /// Retire the committed values below `floor`
+    ///
+    /// Writes are only ever looked up by their own timestamp and reads only linearize at or above
+    /// their `min_ts`: once no outstanding operation is below `floor`, nothing below it is looked
+    /// up again. Retiring those values keeps the known timestamps to the live part of the history.
+    pub proof fn compact(tracked &mut self, floor: Timestamp)
+        requires
+            old(self).inv(),
+            old(self).retired_below() <= floor,
+            floor <= old(self).watermark(),
+            forall|ts: Timestamp| #[trigger]
+                old(self).outstanding_writes().contains_key(ts) ==> floor <= ts,
+            forall|key: (Option<u64>, nat)| #[trigger]
+                old(self).outstanding_reads().contains_key(key) ==> floor <= old(
+                    self,
+                ).outstanding_reads()[key].min_ts@.timestamp(),
+        ensures
+            final(self).inv(),
+            final(self).ids() == old(self).ids(),
+            final(self).retired_below() == floor,
+            final(self).watermark() == old(self).watermark(),
+            final(self).current_value() == old(self).current_value(),
+            final(self).committed_values() == old(self).committed_values().restrict(
+                old(self).committed_values().dom().filter(|ts: Timestamp| floor <= ts),
+            ),
+            final(self).known_timestamps() == old(self).known_timestamps().filter(
+                |ts: Timestamp| floor <= ts,
+            ),
+            final(self).outstanding_writes() == old(self).outstanding_writes(),
+            final(self).outstanding_reads() == old(self).outstanding_reads(),
+            final(self).pending_writes() == old(self).pending_writes(),
+            final(self).completed_writes() == old(self).completed_writes(),
+            final(self).pending_reads() == old(self).pending_reads(),
+            final(self).completed_reads() == old(self).completed_reads(),
+            forall|ts: Timestamp| #[trigger]
+                final(self).completed_writes().contains_key(ts) ==> floor <= ts,
+            forall|ts: Timestamp| #[trigger]
+                final(self).pending_writes().contains_key(ts) ==> floor <= ts,
+            forall|key: (Option<u64>, nat)| #[trigger]
+                final(self).completed_reads().contains_key(key) ==> floor <= final(
+                    self,
+                ).completed_reads()[key].timestamp(),
+    {
+        let ghost retired = self.committed_to@.dom().filter(|ts: Timestamp| ts < floor);
+        let tracked _retired = self.committed_to.split(retired);
+        self.retired_below = floor;
+
+        assert(self.committed_to@ =~= old(self).committed_to@.restrict(
+            old(self).committed_to@.dom().filter(|ts: Timestamp| floor <= ts),
+        ));
+        assert(self.known_timestamps() =~= old(self).known_timestamps().filter(
+            |ts: Timestamp| floor <= ts,
+        ));
+
+        // completed reads linearized at or above their min_ts, so their timestamps were kept
+        assert forall|key: (Option<u64>, nat)| #[trigger]
+            self.completed_reads.contains_key(key) implies {
+            &&& self.committed_to@.contains_key(self.completed_reads[key].timestamp())
+            &&& floor <= self.completed_reads[key].timestamp()
+        } by {
+            assert(self.read_token_map@.contains_key(key));
+        }
+        assert forall|ts: Timestamp| #[trigger]
+            self.completed_writes.contains_key(ts) implies floor <= ts by {
+            assert(self.write_token_map@.contains_key(ts));
+        }
+    }
+
+    /// The highest floor the queue can be compacted to (see [`LinearizationQueue::compact`])
+    ///
+    /// This is the lowest of the watermark, the timestamps of the outstanding writes and the
+    /// lower bounds of the outstanding reads.
+    pub proof fn compaction_floor(tracked &self) -> (floor: Timestamp)
+        requires
+            self.inv(),
+        ensures
+            self.retired_below() <= floor,
+            floor <= self.watermark(),
+            forall|ts: Timestamp| #[trigger]
+                self.outstanding_writes().contains_key(ts) ==> floor <= ts,
+            forall|key: (Option<u64>, nat)| #[trigger]
+                self.outstanding_reads().contains_key(key) ==> floor
+                    <= self.outstanding_reads()[key].min_ts@.timestamp(),
+    {
+        let ghost read_floors = self.read_token_map@.dom().map(
+            |key: (Option<u64>, nat)| self.read_token_map@[key].min_ts@.timestamp(),
+        );
+        let ghost candidates = self.write_token_map@.dom().union(read_floors).insert(
+            self.watermark@.timestamp(),
+        );
+        self.read_token_map@.dom().lemma_map_finite(
+            |key: (Option<u64>, nat)| self.read_token_map@[key].min_ts@.timestamp(),
+        );
+        let floor = lemma_least_timestamp(candidates);
+
+        assert forall|key: (Option<u64>, nat)| #[trigger]
+            self.read_token_map@.contains_key(key) implies floor
+            <= self.read_token_map@[key].min_ts@.timestamp() by {
+            assert(read_floors.contains(self.read_token_map@[key].min_ts@.timestamp()));
+        }
+        floor
+    }
+}
+
+/// A finite non-empty set of timestamps has a least element
+proof fn lemma_least_timestamp(s: Set<Timestamp>) -> (least: Timestamp)
+    requires
+        s.finite(),
+        s.len() > 0,
+    ensures
+        s.contains(least),
+        forall|ts: Timestamp| #[trigger] s.contains(ts) ==> least <= ts,
+    decreases s.len(),
+{
+    let ts = s.choose();
+    let rest = s.remove(ts);
+    if rest.len() == 0 {
+        rest.lemma_len0_is_empty();
+        assert forall|other: Timestamp| #[trigger] s.contains(other) implies ts <= other by {
+            if other != ts {
+                assert(rest.contains(other));
+            }
+        }
+        ts
+    } else {
+        let rest_least = lemma_least_timestamp(rest);
+        let least = if ts < rest_least {
+            ts
+        } else {
+            rest_least
+        };
+        assert forall|other: Timestamp| #[trigger] s.contains(other) implies least <= other by {
+            if other != ts {
+                assert(rest.contains(other));
+            }
+        }
+        least
+    }
 }
 
 } // verus!
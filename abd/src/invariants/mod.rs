@@ -1,5 +1,10 @@
+use vlib::client_ids::ClientIds;
+use vlib::client_ids::ClientIdsIds;
+#[cfg(verus_only)]
+use vlib::client_ids::ClientIdToken;
 use vlib::monotonic::map::GhostMonotonicMap;
 
+use vstd::atomic::PAtomicU64;
 #[cfg(verus_only)]
 use vstd::atomic::PermissionU64;
 use vstd::invariant::AtomicInvariant;
@@ -18,6 +23,7 @@ use vstd::resource::Loc;
 use specs::abd::RegisterRead;
 use specs::abd::RegisterWrite;
 
+use crate::client_ids::ClientIdService;
 #[allow(unused_imports)]
 use crate::timestamp::Timestamp;
 
@@ -61,6 +67,7 @@ pub struct StatePredicate {
     pub commitments_ids: CommitmentIds,
     pub request_map_ids: RequestMapIds,
     pub server_tokens_id: Loc,
+    pub client_ids_ids: ClientIdsIds,
 }
 
 pub struct State<ML, RL> where ML: MutLinearizer<RegisterWrite>, RL: ReadLinearizer<RegisterRead> {
@@ -70,6 +77,7 @@ pub struct State<ML, RL> where ML: MutLinearizer<RegisterWrite>, RL: ReadLineari
     pub tracked server_tokens: GhostMonotonicMap<u64, Loc>,
     pub tracked commitments: Commitments,
     pub tracked request_map: RequestMap,
+    pub tracked client_ids: ClientIds,
 }
 
 impl<ML, RL> State<ML, RL> where
@@ -87,10 +95,17 @@ impl<ML, RL> State<ML, RL> where
         &&& self.servers.is_auth()
         &&& self.commitments.is_full()
         &&& self.request_map.is_full()
+        &&& self.client_ids.is_full()
         // client ids
         &&& self.commitments.client_map().dom() == self.request_map.request_ctr_map().dom().insert(
             0,
         )
+        &&& forall|id: u64| #[trigger]
+            self.request_map.request_ctr_map().contains_key(id) ==> id
+                < self.client_ids.next_id()
+        &&& forall|id: u64| #[trigger]
+            self.client_ids.fresh().contains(id)
+                ==> !self.request_map.request_ctr_map().contains_key(id)
         // server claims
         &&& self.unclaimed_servers().finite()
         &&& self.server_tokens@.dom().finite()
@@ -107,12 +122,128 @@ impl<ML, RL> State<ML, RL> where
             == self.commitments.commitment_id()
         // matching state
         &&& self.linearization_queue.current_value() == self.register@
-        &&& self.linearization_queue.known_timestamps() == self.commitments.allocated().dom()
+        &&& self.linearization_queue.known_timestamps() == self.commitments.allocated().dom().filter(
+            |ts: Timestamp| self.linearization_queue.retired_below() <= ts,
+        )
         &&& forall|q: Quorum| #[trigger]
             self.servers.valid_quorum(q) ==> {
                 self.linearization_queue.watermark() <= self.servers.quorum_timestamp(q)
             }
     }
+
+    /// Logs in a client, trading in the token of its freshly allocated id
+    pub proof fn login(
+        tracked &mut self,
+        tracked client_id_token: ClientIdToken,
+        tracked client_perm: PermissionU64,
+        tracked request_perm: PermissionU64,
+    ) -> (tracked r: (ClientCtrToken, RequestCtrToken))
+        requires
+            old(self).inv(),
+            client_id_token.id() == old(self).client_ids.fresh_id(),
+            client_perm.value() == 0,
+            request_perm.value() == 0,
+        ensures
+            final(self).inv(),
+            final(self).register == old(self).register,
+            final(self).linearization_queue == old(self).linearization_queue,
+            final(self).servers == old(self).servers,
+            final(self).server_tokens == old(self).server_tokens,
+            final(self).commitments.ids() == old(self).commitments.ids(),
+            final(self).request_map.ids() == old(self).request_map.ids(),
+            final(self).client_ids.ids() == old(self).client_ids.ids(),
+            r.0.id() == final(self).commitments.client_map_id(),
+            r.0.key() == client_id_token.key(),
+            r.0.value().0 == 0,
+            r.0.value().1 == client_perm.id(),
+            r.1.id() == final(self).request_map.request_ctr_map_id(),
+            r.1.key() == client_id_token.key(),
+            r.1.value().0 == 0,
+            r.1.value().1 == request_perm.id(),
+    {
+        let client_id = client_id_token.key();
+        self.client_ids.claim(client_id_token);
+        assert(!self.request_map.request_ctr_map().contains_key(client_id));
+        assert(!self.commitments.client_map().contains_key(client_id));
+
+        let tracked client_ctr_token = self.commitments.login(client_id, client_perm);
+        self.commitments.agree_client_token(&client_ctr_token);
+        let tracked request_ctr_token = self.request_map.login(client_id, request_perm);
+        self.request_map.agree_client_token(&request_ctr_token);
+
+        assert(self.commitments.client_map().dom() == self.request_map.request_ctr_map().dom().insert(0));
+        (client_ctr_token, request_ctr_token)
+    }
+
+    /// Retires the committed values below `floor` (see [`LinearizationQueue::compact`])
+    ///
+    /// Past the compaction, the completed and pending operations are all at or above `floor`.
+    ///
+    /// Only the queue is compacted: [`Commitments`] still holds one allocation per write ever
+    /// made. Removing a key from its authoritative map takes the owned points-to, and the
+    /// [`WriteCommitment`]s handed out for these timestamps are persistent copies of it. The
+    /// retired allocations are instead dropped from the invariant relating the map to the queue
+    /// (new writes are always allocated above the watermark, so they are never looked up again).
+    pub proof fn compact(tracked &mut self, floor: Timestamp)
+        requires
+            old(self).inv(),
+            old(self).linearization_queue.retired_below() <= floor,
+            floor <= old(self).linearization_queue.watermark(),
+            forall|ts: Timestamp| #[trigger]
+                old(self).linearization_queue.outstanding_writes().contains_key(ts) ==> floor <= ts,
+            forall|key: (Option<u64>, nat)| #[trigger]
+                old(self).linearization_queue.outstanding_reads().contains_key(key) ==> floor <= old(
+                    self,
+                ).linearization_queue.outstanding_reads()[key].min_ts@.timestamp(),
+        ensures
+            final(self).inv(),
+            final(self).register == old(self).register,
+            final(self).servers == old(self).servers,
+            final(self).server_tokens == old(self).server_tokens,
+            final(self).commitments == old(self).commitments,
+            final(self).request_map == old(self).request_map,
+            final(self).client_ids == old(self).client_ids,
+            final(self).linearization_queue.ids() == old(self).linearization_queue.ids(),
+            final(self).linearization_queue.retired_below() == floor,
+            final(self).linearization_queue.watermark() == old(self).linearization_queue.watermark(),
+            final(self).linearization_queue.known_timestamps() == old(
+                self,
+            ).linearization_queue.known_timestamps().filter(|ts: Timestamp| floor <= ts),
+            forall|ts: Timestamp| #[trigger]
+                final(self).linearization_queue.completed_writes().contains_key(ts) ==> floor <= ts,
+            forall|ts: Timestamp| #[trigger]
+                final(self).linearization_queue.pending_writes().contains_key(ts) ==> floor <= ts,
+            forall|key: (Option<u64>, nat)| #[trigger]
+                final(self).linearization_queue.completed_reads().contains_key(key) ==> floor
+                    <= final(self).linearization_queue.completed_reads()[key].timestamp(),
+    {
+        self.linearization_queue.compact(floor);
+        assert(self.linearization_queue.known_timestamps() =~= self.commitments.allocated().dom().filter(
+            |ts: Timestamp| floor <= ts,
+        ));
+    }
+
+    /// Retires as much of the history as the outstanding operations allow
+    ///
+    /// Called whenever the watermark advances, once the operation which advanced it has taken
+    /// out its completion.
+    pub proof fn compact_history(tracked &mut self)
+        requires
+            old(self).inv(),
+        ensures
+            final(self).inv(),
+            final(self).register == old(self).register,
+            final(self).servers == old(self).servers,
+            final(self).server_tokens == old(self).server_tokens,
+            final(self).commitments == old(self).commitments,
+            final(self).request_map == old(self).request_map,
+            final(self).client_ids == old(self).client_ids,
+            final(self).linearization_queue.ids() == old(self).linearization_queue.ids(),
+            final(self).linearization_queue.watermark() == old(self).linearization_queue.watermark(),
+    {
+        let floor = self.linearization_queue.compaction_floor();
+        self.compact(floor);
+    }
 }
 
 impl<ML, RL> InvariantPredicate<StatePredicate, State<ML, RL>> for StatePredicate where
@@ -126,6 +257,7 @@ impl<ML, RL> InvariantPredicate<StatePredicate, State<ML, RL>> for StatePredicat
         &&& p.commitments_ids == state.commitments.ids()
         &&& p.request_map_ids == state.request_map.ids()
         &&& p.server_tokens_id == state.server_tokens.id()
+        &&& p.client_ids_ids == state.client_ids.ids()
         &&& state.inv()
     }
 }
@@ -134,20 +266,50 @@ pub type StateInvariant<ML, RL> = AtomicInvariant<StatePredicate, State<ML, RL>,
 
 pub type RegisterView = GhostVar<Option<u64>>;
 
-pub proof fn initialize_system_state<ML, RL>(tracked zero_perm: PermissionU64) -> (tracked r: (
-    Arc<StateInvariant<ML, RL>>,
-    RegisterView,
-)) where ML: MutLinearizer<RegisterWrite>, RL: ReadLinearizer<RegisterRead>
+/// Server ids of a system with `n_servers` servers: `0..n_servers`
+pub open spec fn server_ids(n_servers: u64) -> Set<u64> {
+    Set::new(|id: u64| id < n_servers)
+}
+
+pub proof fn lemma_server_ids(n_servers: u64)
+    ensures
+        server_ids(n_servers).finite(),
+        server_ids(n_servers).len() == n_servers,
+    decreases n_servers,
+{
+    if n_servers == 0 {
+        assert(server_ids(n_servers) =~= Set::empty());
+    } else {
+        let prev = (n_servers - 1) as u64;
+        lemma_server_ids(prev);
+        assert(server_ids(n_servers) =~= server_ids(prev).insert(prev));
+    }
+}
+
+pub proof fn initialize_system_state<ML, RL>(
+    server_ids: Set<u64>,
+    tracked zero_perm: PermissionU64,
+    tracked next_client_id_perm: PermissionU64,
+) -> (tracked r: (Arc<StateInvariant<ML, RL>>, RegisterView)) where
+    ML: MutLinearizer<RegisterWrite>,
+    RL: ReadLinearizer<RegisterRead>,
+
     requires
+        server_ids.finite(),
         zero_perm.value() == 1,
+        next_client_id_perm.value() == 1,
     ensures
         r.0.namespace() == state_inv_id(),
         r.0.constant().register_id == r.1.id(),
+        r.0.constant().server_locs.dom() == server_ids,
+        r.0.constant().client_ids_ids.next_perm_id == next_client_id_perm.id(),
 {
     let tracked (register, view) = GhostVarAuth::<Option<u64>>::new(None);
-    let tracked servers = ServerUniverse::dummy();
+    let tracked servers = ServerUniverse::new(server_ids);
+    servers.lemma_locs();
     let tracked commitments = Commitments::new(zero_perm);
     let tracked request_map = RequestMap::new();
+    let tracked client_ids = ClientIds::new(next_client_id_perm);
     let tracked zero_commitment = commitments.zero_commitment();
     let tracked mut linearization_queue = LinearizationQueue::new(register.id(), zero_commitment);
     let tracked server_tokens = GhostMonotonicMap::empty();
@@ -163,6 +325,7 @@ pub proof fn initialize_system_state<ML, RL>(tracked zero_perm: PermissionU64) -
         commitments_ids: commitments.ids(),
         request_map_ids: request_map.ids(),
         server_tokens_id: server_tokens.id(),
+        client_ids_ids: client_ids.ids(),
     };
 
     let tracked state = State {
@@ -172,6 +335,7 @@ pub proof fn initialize_system_state<ML, RL>(tracked zero_perm: PermissionU64) -
         commitments,
         request_map,
         server_tokens,
+        client_ids,
     };
     assert forall|id| #[trigger]
         state.unclaimed_servers().contains(
@@ -186,14 +350,43 @@ pub proof fn initialize_system_state<ML, RL>(tracked zero_perm: PermissionU64) -
     (Arc::new(state_inv), view)
 }
 
-pub axiom fn get_system_state<ML, RL>(server_ids: Set<u64>) -> (tracked r: (
-    Arc<StateInvariant<ML, RL>>,
-    RegisterView,
+/// Bootstraps the shared state of a system with servers `0..n_servers`
+///
+/// This should run once: every server and client is then handed a clone of the same invariant,
+/// and clients get their ids from the returned service
+pub fn initialize_system<ML, RL>(n_servers: u64) -> (r: (
+    Tracked<Arc<StateInvariant<ML, RL>>>,
+    Tracked<RegisterView>,
+    ClientIdService<ML, RL>,
 )) where ML: MutLinearizer<RegisterWrite>, RL: ReadLinearizer<RegisterRead>
     ensures
-        r.0.namespace() == state_inv_id(),
-        r.0.constant().register_id == r.1.id(),
-        r.0.constant().server_locs.dom() == server_ids,
-;
+        r.0@.namespace() == state_inv_id(),
+        r.0@.constant().register_id == r.1@.id(),
+        r.0@.constant().server_locs.dom() == server_ids(n_servers),
+        r.0@.constant().server_locs.len() == n_servers,
+        r.2.constant() == r.0@.constant(),
+{
+    // client 0 is reserved: its counter starts past the default timestamp
+    let (_zero_ctr, zero_perm) = PAtomicU64::new(1);
+    let (next_client_id, next_client_id_perm) = PAtomicU64::new(1);
+    let tracked state_inv;
+    let tracked service_inv;
+    let tracked view;
+    proof {
+        lemma_server_ids(n_servers);
+        let tracked Tracked(zero_perm) = zero_perm;
+        let tracked Tracked(next_client_id_perm) = next_client_id_perm;
+        let tracked (s, v) = initialize_system_state::<ML, RL>(
+            server_ids(n_servers),
+            zero_perm,
+            next_client_id_perm,
+        );
+        service_inv = s.clone();
+        state_inv = s;
+        view = v;
+    }
+    let service = ClientIdService::new(next_client_id, Tracked(service_inv));
+    (Tracked(state_inv), Tracked(view), service)
+}
 
 } // verus!
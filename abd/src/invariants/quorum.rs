@@ -24,15 +24,36 @@ pub struct Quorum {
 }
 
 impl ServerUniverse {
-    pub proof fn dummy() -> (tracked r: Self)
+    /// Allocates a universe where every server is unclaimed, at the default timestamp
+    pub proof fn new(server_ids: Set<u64>) -> (tracked r: Self)
+        requires
+            server_ids.finite(),
         ensures
             r.inv(),
             r.is_auth(),
+            r.dom() == server_ids,
             forall|q: Quorum| #[trigger]
                 r.valid_quorum(q) ==> r.quorum_timestamp(q) >= Timestamp::spec_default(),
             forall|id| #[trigger] r.contains_key(id) ==> r[id]@@ is FullRightToAdvance,
+        decreases server_ids.len(),
     {
-        ServerUniverse { map: Map::tracked_empty() }
+        if server_ids.len() == 0 {
+            server_ids.lemma_len0_is_empty();
+            let tracked r = ServerUniverse { map: Map::tracked_empty() };
+            assert(r.dom() =~= server_ids);
+            r
+        } else {
+            let server_id = server_ids.choose();
+            assert(server_ids.contains(server_id)) by {
+                if !server_ids.contains(server_id) {
+                    assert(server_ids =~= Set::<u64>::empty());
+                }
+            }
+            let tracked mut r = Self::new(server_ids.remove(server_id));
+            r.map.tracked_insert(server_id, Tracked(MonotonicTimestampResource::alloc()));
+            assert(r.dom() =~= server_ids);
+            r
+        }
     }
 
     pub open spec fn inv(self) -> bool {
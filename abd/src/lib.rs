@@ -1,5 +1,6 @@
 pub mod channel;
 pub mod client;
+pub mod client_ids;
 pub mod invariants;
 pub mod proto;
 pub mod resource;
@@ -4,6 +4,11 @@ use crate::invariants::ServerToken;
 use crate::resource::monotonic_timestamp::MonotonicTimestampResource;
 use crate::timestamp::Timestamp;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
 use vstd::prelude::*;
 use vstd::resource::map::GhostPersistentSubmap;
 use vstd::resource::Loc;
@@ -385,6 +390,45 @@ impl Clone for GetResponse {
     }
 }
 
+impl Codec for GetRequest {
+    #[allow(unused_variables)]
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(GetRequest { servers: dec.tracked()? })
+    }
+}
+
+impl Codec for GetResponse {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.value.encode(enc);
+        self.timestamp.encode(enc);
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let value = Option::<u64>::decode(dec)?;
+        let timestamp = Timestamp::decode(dec)?;
+        Ok(
+            GetResponse {
+                value,
+                timestamp,
+                lb: dec.tracked()?,
+                commitment: dec.tracked()?,
+                server_token: dec.tracked()?,
+            },
+        )
+    }
+}
+
 } // verus!
 impl std::fmt::Debug for GetRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
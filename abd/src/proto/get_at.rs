@@ -0,0 +1,338 @@
+use crate::invariants::committed_to::WriteCommitment;
+use crate::invariants::ServerToken;
+use crate::timestamp::Timestamp;
+
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
+use vstd::prelude::*;
+use vstd::resource::Loc;
+
+verus! {
+
+pub struct GetAtRequest {
+    timestamp: Timestamp,
+}
+
+pub struct GetAtResponse {
+    timestamp: Timestamp,
+    value: Option<Option<u64>>,
+    #[allow(unused)]
+    commitment: Tracked<Option<WriteCommitment>>,
+    #[allow(unused)]
+    server_token: Tracked<ServerToken>,
+}
+
+#[allow(unused)]
+impl GetAtRequest {
+    pub fn new(timestamp: Timestamp) -> (r: Self)
+        ensures
+            r.spec_timestamp() == timestamp,
+    {
+        GetAtRequest { timestamp }
+    }
+
+    pub closed spec fn spec_timestamp(self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub fn timestamp(&self) -> (ts: Timestamp)
+        ensures
+            ts == self.spec_timestamp(),
+        no_unwind
+    {
+        self.timestamp
+    }
+
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        self.timestamp == other.timestamp
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_timestamp() == b.spec_timestamp(),
+    {
+    }
+}
+
+#[allow(unused)]
+impl GetAtResponse {
+    /// A server only answers with a value if it still holds the commitment for that timestamp
+    #[verifier::type_invariant]
+    pub closed spec fn inv(self) -> bool {
+        &&& self.value is Some <==> self.commitment@ is Some
+        &&& self.value is Some ==> {
+            let commitment = self.commitment@->Some_0;
+            &&& commitment.key() == self.timestamp
+            &&& commitment.value() == self.value->Some_0
+        }
+    }
+
+    pub closed spec fn spec_timestamp(self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub closed spec fn spec_value(self) -> Option<Option<u64>> {
+        self.value
+    }
+
+    pub closed spec fn spec_commitment(self) -> Option<WriteCommitment> {
+        self.commitment@
+    }
+
+    pub closed spec fn spec_server_token(self) -> ServerToken {
+        self.server_token@
+    }
+
+    pub closed spec fn server_token_id(self) -> Loc {
+        self.server_token@.id()
+    }
+
+    pub closed spec fn server_id(self) -> u64 {
+        self.server_token@.key()
+    }
+
+    pub open spec fn commitment_id(self) -> Loc
+        recommends
+            self.spec_commitment() is Some,
+    {
+        self.spec_commitment()->Some_0.id()
+    }
+
+    pub fn new(
+        timestamp: Timestamp,
+        value: Option<Option<u64>>,
+        commitment: Tracked<Option<WriteCommitment>>,
+        server_token: Tracked<ServerToken>,
+    ) -> (r: Self)
+        requires
+            value is Some <==> commitment@ is Some,
+            value is Some ==> {
+                &&& commitment@->Some_0.key() == timestamp
+                &&& commitment@->Some_0.value() == value->Some_0
+            },
+        ensures
+            r.spec_timestamp() == timestamp,
+            r.spec_value() == value,
+            r.spec_commitment() == commitment@,
+            r.spec_server_token() == server_token@,
+            r.server_id() == server_token@.key(),
+            r.server_token_id() == server_token@.id(),
+    {
+        GetAtResponse { timestamp, value, commitment, server_token }
+    }
+
+    pub fn timestamp(&self) -> (ts: Timestamp)
+        ensures
+            ts == self.spec_timestamp(),
+        no_unwind
+    {
+        self.timestamp
+    }
+
+    pub fn value(&self) -> (value: &Option<Option<u64>>)
+        ensures
+            *value == self.spec_value(),
+        no_unwind
+    {
+        &self.value
+    }
+
+    pub fn commitment(&self) -> (r: Tracked<WriteCommitment>)
+        requires
+            self.spec_value() is Some,
+        ensures
+            r@.id() == self.commitment_id(),
+            r@.key() == self.spec_timestamp(),
+            r@.value() == self.spec_value()->Some_0,
+        no_unwind
+    {
+        let tracked commitment;
+        proof {
+            use_type_invariant(self);
+            commitment = self.commitment.borrow().tracked_borrow().duplicate();
+        }
+        Tracked(commitment)
+    }
+
+    pub fn lemma_get_at_response(&self)
+        ensures
+            self.spec_value() is Some <==> self.spec_commitment() is Some,
+            self.spec_value() is Some ==> {
+                &&& self.spec_commitment()->Some_0.key() == self.spec_timestamp()
+                &&& self.spec_commitment()->Some_0.value() == self.spec_value()->Some_0
+            },
+        no_unwind
+    {
+        proof {
+            use_type_invariant(self);
+        }
+    }
+
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        &&& self.timestamp == other.timestamp
+        &&& self.value == other.value
+        &&& self.commitment@ is Some <==> other.commitment@ is Some
+        &&& self.commitment@ is Some ==> {
+            &&& self.commitment@->Some_0.id() == other.commitment@->Some_0.id()
+            &&& self.commitment@->Some_0@ == other.commitment@->Some_0@
+        }
+        &&& self.server_token@.id() == other.server_token@.id()
+        &&& self.server_token@@ == other.server_token@@
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_timestamp() == b.spec_timestamp(),
+            a.spec_value() == b.spec_value(),
+            a.spec_commitment() is Some <==> b.spec_commitment() is Some,
+            a.spec_commitment() is Some ==> a.commitment_id() == b.commitment_id(),
+            a.spec_server_token().id() == b.spec_server_token().id(),
+            a.spec_server_token()@ == b.spec_server_token()@,
+            a.server_token_id() == b.server_token_id(),
+            a.server_id() == b.server_id(),
+    {
+    }
+}
+
+impl Clone for GetAtRequest {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        GetAtRequest { timestamp: self.timestamp.clone() }
+    }
+}
+
+impl Clone for GetAtResponse {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+    {
+        let tracked commitment;
+        let tracked server_token;
+        proof {
+            use_type_invariant(self);
+            commitment = if self.commitment@ is Some {
+                Some(self.commitment.borrow().tracked_borrow().duplicate())
+            } else {
+                None
+            };
+            server_token = self.server_token.borrow().duplicate();
+        }
+        GetAtResponse::new(
+            self.timestamp.clone(),
+            self.value.clone(),
+            Tracked(commitment),
+            Tracked(server_token),
+        )
+    }
+}
+
+impl Codec for GetAtRequest {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.timestamp.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let timestamp = Timestamp::decode(dec)?;
+        Ok(GetAtRequest { timestamp })
+    }
+}
+
+impl Codec for GetAtResponse {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.timestamp.encode(enc);
+        self.value.encode(enc);
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let timestamp = Timestamp::decode(dec)?;
+        let value = Option::<Option<u64>>::decode(dec)?;
+        Ok(
+            GetAtResponse {
+                timestamp,
+                value,
+                commitment: dec.tracked()?,
+                server_token: dec.tracked()?,
+            },
+        )
+    }
+}
+
+} // verus!
+impl std::fmt::Debug for GetAtRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetAtRequest")
+            .field("timestamp", &self.timestamp)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for GetAtResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GetAtResponse")
+            .field("timestamp", &self.timestamp)
+            .field("value", &self.value)
+            .finish()
+    }
+}
@@ -3,6 +3,11 @@ use crate::invariants::ServerToken;
 use crate::resource::monotonic_timestamp::MonotonicTimestampResource;
 use crate::timestamp::Timestamp;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
 use vstd::prelude::*;
 use vstd::resource::map::GhostPersistentSubmap;
 use vstd::resource::Loc;
@@ -314,6 +319,41 @@ impl Clone for GetTimestampResponse {
     }
 }
 
+impl Codec for GetTimestampRequest {
+    #[allow(unused_variables)]
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(GetTimestampRequest { servers: dec.tracked()? })
+    }
+}
+
+impl Codec for GetTimestampResponse {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.timestamp.encode(enc);
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let timestamp = Timestamp::decode(dec)?;
+        Ok(
+            GetTimestampResponse {
+                timestamp,
+                lb: dec.tracked()?,
+                server_token: dec.tracked()?,
+            },
+        )
+    }
+}
+
 } // verus!
 impl std::fmt::Debug for GetTimestampRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -1,22 +1,28 @@
 use vstd::prelude::*;
 
 mod get;
+mod get_at;
 mod get_timestamp;
 mod request;
 mod response;
+mod subscribe;
 mod write;
 
 pub use get::*;
+pub use get_at::*;
 pub use get_timestamp::*;
 pub use request::*;
 pub use response::*;
+pub use subscribe::*;
 pub use write::*;
 
 verus! {
 
 pub enum ReqType {
     Get,
+    GetAt,
     GetTimestamp,
+    Subscribe,
     Write,
 }
 
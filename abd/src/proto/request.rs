@@ -1,13 +1,24 @@
+#[cfg(verus_only)]
+use crate::channel::chan_request_inv;
+#[cfg(verus_only)]
+use crate::channel::ChannelInv;
 use crate::invariants::committed_to::WriteCommitment;
 use crate::invariants::quorum::ServerUniverse;
 use crate::invariants::requests::RequestProof;
 use crate::proto::get::GetRequest;
+use crate::proto::get_at::GetAtRequest;
 use crate::proto::get_timestamp::GetTimestampRequest;
+use crate::proto::subscribe::SubscribeRequest;
 use crate::proto::write::WriteRequest;
 #[cfg(verus_only)]
 use crate::proto::ReqType;
 use crate::timestamp::Timestamp;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+use verdist::rpc::proto::Cancellable;
 use verdist::rpc::proto::TaggedMessage;
 
 use vstd::prelude::*;
@@ -20,11 +31,15 @@ pub struct Request {
     request_id: u64,
     inner: RequestInner,
     request: Tracked<RequestProof>,
+    /// Whether this is the cancellation of the request (see [`Cancellable`])
+    cancel: bool,
 }
 
 pub enum RequestInner {
     Get(GetRequest),
+    GetAt(GetAtRequest),
     GetTimestamp(GetTimestampRequest),
+    Subscribe(SubscribeRequest),
     Write(WriteRequest),
 }
 
@@ -42,7 +57,9 @@ impl RequestInner {
     pub open spec fn req_type(self) -> ReqType {
         match self {
             RequestInner::Get(_) => ReqType::Get,
+            RequestInner::GetAt(_) => ReqType::GetAt,
             RequestInner::GetTimestamp(_) => ReqType::GetTimestamp,
+            RequestInner::Subscribe(_) => ReqType::Subscribe,
             RequestInner::Write(_) => ReqType::Write,
         }
     }
@@ -50,7 +67,9 @@ impl RequestInner {
     pub open spec fn spec_eq(self, other: Self) -> bool {
         match (self, other) {
             (RequestInner::Get(a), RequestInner::Get(b)) => a.spec_eq(b),
+            (RequestInner::GetAt(a), RequestInner::GetAt(b)) => a.spec_eq(b),
             (RequestInner::GetTimestamp(a), RequestInner::GetTimestamp(b)) => a.spec_eq(b),
+            (RequestInner::Subscribe(a), RequestInner::Subscribe(b)) => a.spec_eq(b),
             (RequestInner::Write(a), RequestInner::Write(b)) => a.spec_eq(b),
             (_, _) => false,
         }
@@ -62,7 +81,9 @@ impl RequestInner {
     {
         match a {
             RequestInner::Get(a) => { GetRequest::spec_eq_refl(a) },
+            RequestInner::GetAt(a) => { GetAtRequest::spec_eq_refl(a) },
             RequestInner::GetTimestamp(a) => { GetTimestampRequest::spec_eq_refl(a) },
+            RequestInner::Subscribe(a) => { SubscribeRequest::spec_eq_refl(a) },
             RequestInner::Write(a) => WriteRequest::spec_eq_refl(a),
         }
     }
@@ -75,10 +96,15 @@ impl RequestInner {
     {
         match (a, b) {
             (RequestInner::Get(a), RequestInner::Get(b)) => GetRequest::spec_eq_symm(a, b),
+            (RequestInner::GetAt(a), RequestInner::GetAt(b)) => GetAtRequest::spec_eq_symm(a, b),
             (
                 RequestInner::GetTimestamp(a),
                 RequestInner::GetTimestamp(b),
             ) => GetTimestampRequest::spec_eq_symm(a, b),
+            (
+                RequestInner::Subscribe(a),
+                RequestInner::Subscribe(b),
+            ) => SubscribeRequest::spec_eq_symm(a, b),
             (RequestInner::Write(a), RequestInner::Write(b)) => WriteRequest::spec_eq_symm(a, b),
             (_, _) => {},
         }
@@ -97,11 +123,21 @@ impl RequestInner {
                 RequestInner::Get(b),
                 RequestInner::Get(c),
             ) => GetRequest::spec_eq_trans(a, b, c),
+            (
+                RequestInner::GetAt(a),
+                RequestInner::GetAt(b),
+                RequestInner::GetAt(c),
+            ) => GetAtRequest::spec_eq_trans(a, b, c),
             (
                 RequestInner::GetTimestamp(a),
                 RequestInner::GetTimestamp(b),
                 RequestInner::GetTimestamp(c),
             ) => GetTimestampRequest::spec_eq_trans(a, b, c),
+            (
+                RequestInner::Subscribe(a),
+                RequestInner::Subscribe(b),
+                RequestInner::Subscribe(c),
+            ) => SubscribeRequest::spec_eq_trans(a, b, c),
             (
                 RequestInner::Write(a),
                 RequestInner::Write(b),
@@ -125,6 +161,17 @@ impl RequestInner {
         RequestInner::Get(GetRequest::new(servers))
     }
 
+    pub fn new_get_at(timestamp: Timestamp) -> (r: Self)
+        ensures
+            r.req_type() is GetAt,
+            ({
+                let req = r->GetAt_0;
+                req.spec_timestamp() == timestamp
+            }),
+    {
+        RequestInner::GetAt(GetAtRequest::new(timestamp))
+    }
+
     pub fn new_get_timestamp(servers: Tracked<ServerUniverse>) -> (r: Self)
         requires
             servers@.inv(),
@@ -139,6 +186,17 @@ impl RequestInner {
         RequestInner::GetTimestamp(GetTimestampRequest::new(servers))
     }
 
+    pub fn new_subscribe(since: Timestamp) -> (r: Self)
+        ensures
+            r.req_type() is Subscribe,
+            ({
+                let req = r->Subscribe_0;
+                req.spec_since() == since
+            }),
+    {
+        RequestInner::Subscribe(SubscribeRequest::new(since))
+    }
+
     pub fn new_write(
         value: Option<u64>,
         timestamp: Timestamp,
@@ -188,6 +246,13 @@ impl Request {
         self.inner->Get_0
     }
 
+    pub closed spec fn get_at(self) -> GetAtRequest
+        recommends
+            self.req_type() is GetAt,
+    {
+        self.inner->GetAt_0
+    }
+
     pub closed spec fn get_timestamp(self) -> GetTimestampRequest
         recommends
             self.req_type() is GetTimestamp,
@@ -195,6 +260,13 @@ impl Request {
         self.inner->GetTimestamp_0
     }
 
+    pub closed spec fn subscribe(self) -> SubscribeRequest
+        recommends
+            self.req_type() is Subscribe,
+    {
+        self.inner->Subscribe_0
+    }
+
     pub closed spec fn write(self) -> WriteRequest
         recommends
             self.req_type() is Write,
@@ -229,10 +301,23 @@ impl Request {
             r.client_id() == client_id,
             r.spec_tag() == request_id,
             r.req_type() is Get ==> r.get() == request_inner->Get_0,
+            r.req_type() is GetAt ==> r.get_at() == request_inner->GetAt_0,
             r.req_type() is GetTimestamp ==> r.get_timestamp() == request_inner->GetTimestamp_0,
+            r.req_type() is Subscribe ==> r.subscribe() == request_inner->Subscribe_0,
             r.req_type() is Write ==> r.write() == request_inner->Write_0,
     {
-        Request { request_id, inner: request_inner, request: request_proof }
+        Request { request_id, inner: request_inner, request: request_proof, cancel: false }
+    }
+
+    /// Whether this is a write request
+    pub fn is_write(&self) -> (r: bool)
+        ensures
+            r == self.req_type() is Write,
+    {
+        match self.inner {
+            RequestInner::Write(_) => true,
+            _ => false,
+        }
     }
 
     pub fn destruct(self) -> (r: (u64, RequestInner, Tracked<RequestProof>))
@@ -246,10 +331,14 @@ impl Request {
             r.2@.key() == (self.client_id(), self.spec_tag()),
             r.2@.value().spec_eq(r.1),
             r.1 is Get <==> self.req_type() is Get,
+            r.1 is GetAt <==> self.req_type() is GetAt,
             r.1 is GetTimestamp <==> self.req_type() is GetTimestamp,
+            r.1 is Subscribe <==> self.req_type() is Subscribe,
             r.1 is Write <==> self.req_type() is Write,
             self.req_type() is Get ==> r.1->Get_0 == self.get(),
+            self.req_type() is GetAt ==> r.1->GetAt_0 == self.get_at(),
             self.req_type() is GetTimestamp ==> r.1->GetTimestamp_0 == self.get_timestamp(),
+            self.req_type() is Subscribe ==> r.1->Subscribe_0 == self.subscribe(),
             self.req_type() is Write ==> r.1->Write_0 == self.write(),
         no_unwind
     {
@@ -311,7 +400,45 @@ impl Clone for Request {
         assert(self.request@.value().spec_eq(self.inner));
         assert(self.request@.value().spec_eq(inner));
         let request = Tracked(self.request.borrow().duplicate());
-        Request { request_id: self.request_id, inner, request }
+        Request { request_id: self.request_id, inner, request, cancel: self.cancel }
+    }
+}
+
+impl Cancellable for Request {
+    closed spec fn spec_is_cancel(self) -> bool {
+        self.cancel
+    }
+
+    fn is_cancel(&self) -> bool {
+        self.cancel
+    }
+
+    /// The cancellation carries the same request, so it can be sent wherever the request was
+    #[allow(unused_variables)]
+    fn cancellation(&self) -> (r: Self)
+        ensures
+            r.request_key() == self.request_key(),
+            forall|k: ChannelInv, client_id: u64, server_id: u64|
+                chan_request_inv(k, client_id, server_id, *self) ==> #[trigger] chan_request_inv(
+                    k,
+                    client_id,
+                    server_id,
+                    r,
+                ),
+    {
+        broadcast use GetRequest::lemma_spec_eq;
+        broadcast use GetTimestampRequest::lemma_spec_eq;
+        broadcast use WriteRequest::lemma_spec_eq;
+        broadcast use RequestInner::spec_eq_trans;
+        broadcast use RequestInner::spec_eq_symm;
+
+        proof {
+            use_type_invariant(self);
+        }
+        let inner = self.inner.clone();
+        assert(self.request@.value().spec_eq(inner));
+        let request = Tracked(self.request.borrow().duplicate());
+        Request { request_id: self.request_id, inner, request, cancel: true }
     }
 }
 
@@ -324,20 +451,83 @@ impl Clone for RequestInner {
     {
         match self {
             RequestInner::Get(get) => { RequestInner::Get(get.clone()) },
+            RequestInner::GetAt(get_at) => { RequestInner::GetAt(get_at.clone()) },
             RequestInner::GetTimestamp(get_ts) => { RequestInner::GetTimestamp(get_ts.clone()) },
+            RequestInner::Subscribe(sub) => { RequestInner::Subscribe(sub.clone()) },
             RequestInner::Write(write) => { RequestInner::Write(write.clone()) },
         }
     }
 }
 
+impl Codec for RequestInner {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            RequestInner::Get(m) => {
+                enc.put_u8(0);
+                m.encode(enc);
+            },
+            RequestInner::GetAt(m) => {
+                enc.put_u8(1);
+                m.encode(enc);
+            },
+            RequestInner::GetTimestamp(m) => {
+                enc.put_u8(2);
+                m.encode(enc);
+            },
+            RequestInner::Subscribe(m) => {
+                enc.put_u8(3);
+                m.encode(enc);
+            },
+            RequestInner::Write(m) => {
+                enc.put_u8(4);
+                m.encode(enc);
+            },
+        }
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        match dec.get_u8()? {
+            0 => Ok(RequestInner::Get(GetRequest::decode(dec)?)),
+            1 => Ok(RequestInner::GetAt(GetAtRequest::decode(dec)?)),
+            2 => Ok(RequestInner::GetTimestamp(GetTimestampRequest::decode(dec)?)),
+            3 => Ok(RequestInner::Subscribe(SubscribeRequest::decode(dec)?)),
+            4 => Ok(RequestInner::Write(WriteRequest::decode(dec)?)),
+            _ => Err(DecodeError),
+        }
+    }
+}
+
+impl Codec for Request {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.request_id.encode(enc);
+        self.inner.encode(enc);
+        self.cancel.encode(enc);
+    }
+
+    /// The request proof does not cross the wire: it is assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let request_id = u64::decode(dec)?;
+        let inner = RequestInner::decode(dec)?;
+        let cancel = bool::decode(dec)?;
+        Ok(Request { request_id, inner, request: dec.tracked()?, cancel })
+    }
+}
+
 } // verus!
 impl std::fmt::Debug for RequestInner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             RequestInner::Get(get) => f.debug_tuple("Get").field(&get).finish(),
+            RequestInner::GetAt(get_at) => f.debug_tuple("GetAt").field(&get_at).finish(),
             RequestInner::GetTimestamp(get_ts) => {
                 f.debug_tuple("GetTimestamp").field(&get_ts).finish()
             }
+            RequestInner::Subscribe(sub) => f.debug_tuple("Subscribe").field(&sub).finish(),
             RequestInner::Write(write) => f.debug_tuple("Write").field(&write).finish(),
         }
     }
@@ -348,6 +538,7 @@ impl std::fmt::Debug for Request {
         f.debug_struct("Request")
             .field("request_id", &self.request_id)
             .field("request", &self.inner)
+            .field("cancel", &self.cancel)
             .finish()
     }
 }
@@ -1,12 +1,18 @@
 use crate::invariants::requests::RequestProof;
 use crate::proto::get::GetResponse;
+use crate::proto::get_at::GetAtResponse;
 use crate::proto::get_timestamp::GetTimestampResponse;
+use crate::proto::subscribe::SubscribeResponse;
 #[cfg(verus_only)]
 use crate::proto::request::RequestInner;
 use crate::proto::write::WriteResponse;
 #[cfg(verus_only)]
 use crate::proto::ReqType;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
 use verdist::rpc::proto::TaggedMessage;
 
 use vstd::pervasive::unreached;
@@ -25,7 +31,9 @@ pub struct Response {
 
 pub enum ResponseInner {
     Get(GetResponse),
+    GetAt(GetAtResponse),
     GetTimestamp(GetTimestampResponse),
+    Subscribe(SubscribeResponse),
     Write(WriteResponse),
 }
 
@@ -44,7 +52,9 @@ impl Response {
         requires
             request@.key().1 == request_id,
             request@.value().req_type() is Get <==> inner is Get,
+            request@.value().req_type() is GetAt <==> inner is GetAt,
             request@.value().req_type() is GetTimestamp <==> inner is GetTimestamp,
+            request@.value().req_type() is Subscribe <==> inner is Subscribe,
             request@.value().req_type() is Write <==> inner is Write,
             request@.value().req_type() is Get ==> {
                 let get_req = request@.value()->Get_0;
@@ -53,6 +63,11 @@ impl Response {
                 &&& get_req.servers()[get_resp.server_id()]@@.timestamp()
                     <= get_resp.spec_timestamp()
             },
+            request@.value().req_type() is GetAt ==> {
+                let get_at_req = request@.value()->GetAt_0;
+                let get_at_resp = inner->GetAt_0;
+                get_at_req.spec_timestamp() == get_at_resp.spec_timestamp()
+            },
             request@.value().req_type() is GetTimestamp ==> {
                 let get_ts_req = request@.value()->GetTimestamp_0;
                 let get_ts_resp = inner->GetTimestamp_0;
@@ -60,6 +75,11 @@ impl Response {
                 &&& get_ts_req.servers()[get_ts_resp.server_id()]@@.timestamp()
                     <= get_ts_resp.spec_timestamp()
             },
+            request@.value().req_type() is Subscribe ==> {
+                let sub_req = request@.value()->Subscribe_0;
+                let sub_resp = inner->Subscribe_0;
+                sub_req.spec_since() < sub_resp.spec_timestamp()
+            },
             request@.value().req_type() is Write ==> {
                 let write_req = request@.value()->Write_0;
                 let write_resp = inner->Write_0;
@@ -77,10 +97,18 @@ impl Response {
                 &&& r.req_type() is Get
                 &&& inner->Get_0 == r.get()
             },
+            inner is GetAt ==> {
+                &&& r.req_type() is GetAt
+                &&& inner->GetAt_0 == r.get_at()
+            },
             inner is GetTimestamp ==> {
                 &&& r.req_type() is GetTimestamp
                 &&& inner->GetTimestamp_0 == r.get_timestamp()
             },
+            inner is Subscribe ==> {
+                &&& r.req_type() is Subscribe
+                &&& inner->Subscribe_0 == r.subscribe()
+            },
             inner is Write ==> {
                 &&& r.req_type() is Write
                 &&& inner->Write_0 == r.write()
@@ -99,6 +127,11 @@ impl Response {
             &&& get_req.servers().contains_key(get_resp.server_id())
             &&& get_req.servers()[get_resp.server_id()]@@.timestamp() <= get_resp.spec_timestamp()
         }
+        &&& self.req_type() is GetAt ==> {
+            let get_at_req = self.request()->GetAt_0;
+            let get_at_resp = self.get_at();
+            get_at_req.spec_timestamp() == get_at_resp.spec_timestamp()
+        }
         &&& self.req_type() is GetTimestamp ==> {
             let get_ts_req = self.request()->GetTimestamp_0;
             let get_ts_resp = self.get_timestamp();
@@ -106,6 +139,11 @@ impl Response {
             &&& get_ts_req.servers()[get_ts_resp.server_id()]@@.timestamp()
                 <= get_ts_resp.spec_timestamp()
         }
+        &&& self.req_type() is Subscribe ==> {
+            let sub_req = self.request()->Subscribe_0;
+            let sub_resp = self.subscribe();
+            sub_req.spec_since() < sub_resp.spec_timestamp()
+        }
         &&& self.req_type() is Write ==> {
             let write_req = self.request()->Write_0;
             let write_resp = self.write();
@@ -119,7 +157,9 @@ impl Response {
     pub open spec fn server_id(self) -> u64 {
         match self.req_type() {
             ReqType::Get => self.get().server_id(),
+            ReqType::GetAt => self.get_at().server_id(),
             ReqType::GetTimestamp => self.get_timestamp().server_id(),
+            ReqType::Subscribe => self.subscribe().server_id(),
             ReqType::Write => self.write().server_id(),
         }
     }
@@ -139,7 +179,9 @@ impl Response {
     pub closed spec fn req_type(self) -> ReqType {
         match self.inner {
             ResponseInner::Get(_) => ReqType::Get,
+            ResponseInner::GetAt(_) => ReqType::GetAt,
             ResponseInner::GetTimestamp(_) => ReqType::GetTimestamp,
+            ResponseInner::Subscribe(_) => ReqType::Subscribe,
             ResponseInner::Write(_) => ReqType::Write,
         }
     }
@@ -151,6 +193,13 @@ impl Response {
         self.inner->Get_0
     }
 
+    pub closed spec fn get_at(self) -> GetAtResponse
+        recommends
+            self.req_type() is GetAt,
+    {
+        self.inner->GetAt_0
+    }
+
     pub closed spec fn get_timestamp(self) -> GetTimestampResponse
         recommends
             self.req_type() is GetTimestamp,
@@ -158,6 +207,13 @@ impl Response {
         self.inner->GetTimestamp_0
     }
 
+    pub closed spec fn subscribe(self) -> SubscribeResponse
+        recommends
+            self.req_type() is Subscribe,
+    {
+        self.inner->Subscribe_0
+    }
+
     pub closed spec fn write(self) -> WriteResponse
         recommends
             self.req_type() is Write,
@@ -191,6 +247,26 @@ impl Response {
         }
     }
 
+    pub fn destruct_get_at(self) -> (r: GetAtResponse)
+        requires
+            self.req_type() is GetAt,
+        ensures
+            r == self.get_at(),
+            self.request()->GetAt_0.spec_timestamp() == r.spec_timestamp(),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(&self);
+        }
+        match self.inner {
+            ResponseInner::GetAt(g) => g,
+            _ => {
+                assert(false);
+                unreached()
+            },
+        }
+    }
+
     pub fn destruct_get_timestamp(self) -> (r: GetTimestampResponse)
         requires
             self.req_type() is GetTimestamp,
@@ -217,6 +293,26 @@ impl Response {
         }
     }
 
+    pub fn destruct_subscribe(self) -> (r: SubscribeResponse)
+        requires
+            self.req_type() is Subscribe,
+        ensures
+            r == self.subscribe(),
+            self.request()->Subscribe_0.spec_since() < r.spec_timestamp(),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(&self);
+        }
+        match self.inner {
+            ResponseInner::Subscribe(g) => g,
+            _ => {
+                assert(false);
+                unreached()
+            },
+        }
+    }
+
     pub fn destruct_write(self) -> (r: WriteResponse)
         requires
             self.req_type() is Write,
@@ -285,10 +381,12 @@ impl Response {
             a.request() == b.request(),
             a.req_type() == b.req_type(),
             a.req_type() is Get ==> GetResponse::spec_eq(a.get(), b.get()),
+            a.req_type() is GetAt ==> GetAtResponse::spec_eq(a.get_at(), b.get_at()),
             a.req_type() is GetTimestamp ==> GetTimestampResponse::spec_eq(
                 a.get_timestamp(),
                 b.get_timestamp(),
             ),
+            a.req_type() is Subscribe ==> SubscribeResponse::spec_eq(a.subscribe(), b.subscribe()),
             a.req_type() is Write ==> WriteResponse::spec_eq(a.write(), b.write()),
     {
     }
@@ -349,7 +447,9 @@ impl ResponseInner {
     pub open spec fn spec_eq(self, other: Self) -> bool {
         match (self, other) {
             (ResponseInner::Get(a), ResponseInner::Get(b)) => a.spec_eq(b),
+            (ResponseInner::GetAt(a), ResponseInner::GetAt(b)) => a.spec_eq(b),
             (ResponseInner::GetTimestamp(a), ResponseInner::GetTimestamp(b)) => a.spec_eq(b),
+            (ResponseInner::Subscribe(a), ResponseInner::Subscribe(b)) => a.spec_eq(b),
             (ResponseInner::Write(a), ResponseInner::Write(b)) => a.spec_eq(b),
             (_, _) => false,
         }
@@ -361,7 +461,9 @@ impl ResponseInner {
     {
         match a {
             ResponseInner::Get(a) => GetResponse::spec_eq_refl(a),
+            ResponseInner::GetAt(a) => GetAtResponse::spec_eq_refl(a),
             ResponseInner::GetTimestamp(a) => GetTimestampResponse::spec_eq_refl(a),
+            ResponseInner::Subscribe(a) => SubscribeResponse::spec_eq_refl(a),
             ResponseInner::Write(a) => WriteResponse::spec_eq_refl(a),
         }
     }
@@ -374,10 +476,15 @@ impl ResponseInner {
     {
         match (a, b) {
             (ResponseInner::Get(a), ResponseInner::Get(b)) => GetResponse::spec_eq_symm(a, b),
+            (ResponseInner::GetAt(a), ResponseInner::GetAt(b)) => GetAtResponse::spec_eq_symm(a, b),
             (
                 ResponseInner::GetTimestamp(a),
                 ResponseInner::GetTimestamp(b),
             ) => GetTimestampResponse::spec_eq_symm(a, b),
+            (
+                ResponseInner::Subscribe(a),
+                ResponseInner::Subscribe(b),
+            ) => SubscribeResponse::spec_eq_symm(a, b),
             (ResponseInner::Write(a), ResponseInner::Write(b)) => WriteResponse::spec_eq_symm(a, b),
             (_, _) => {},
         }
@@ -396,11 +503,21 @@ impl ResponseInner {
                 ResponseInner::Get(b),
                 ResponseInner::Get(c),
             ) => GetResponse::spec_eq_trans(a, b, c),
+            (
+                ResponseInner::GetAt(a),
+                ResponseInner::GetAt(b),
+                ResponseInner::GetAt(c),
+            ) => GetAtResponse::spec_eq_trans(a, b, c),
             (
                 ResponseInner::GetTimestamp(a),
                 ResponseInner::GetTimestamp(b),
                 ResponseInner::GetTimestamp(c),
             ) => GetTimestampResponse::spec_eq_trans(a, b, c),
+            (
+                ResponseInner::Subscribe(a),
+                ResponseInner::Subscribe(b),
+                ResponseInner::Subscribe(c),
+            ) => SubscribeResponse::spec_eq_trans(a, b, c),
             (
                 ResponseInner::Write(a),
                 ResponseInner::Write(b),
@@ -426,12 +543,18 @@ impl Clone for Response {
             if inner is Get {
                 GetResponse::lemma_spec_eq(self.inner->Get_0, inner->Get_0);
             }
+            if inner is GetAt {
+                GetAtResponse::lemma_spec_eq(self.inner->GetAt_0, inner->GetAt_0);
+            }
             if inner is GetTimestamp {
                 GetTimestampResponse::lemma_spec_eq(
                     self.inner->GetTimestamp_0,
                     inner->GetTimestamp_0,
                 );
             }
+            if inner is Subscribe {
+                SubscribeResponse::lemma_spec_eq(self.inner->Subscribe_0, inner->Subscribe_0);
+            }
             if inner is Write {
                 WriteResponse::lemma_spec_eq(self.inner->Write_0, inner->Write_0);
             }
@@ -448,20 +571,81 @@ impl Clone for ResponseInner {
     {
         match self {
             ResponseInner::Get(get) => { ResponseInner::Get(get.clone()) },
+            ResponseInner::GetAt(get_at) => { ResponseInner::GetAt(get_at.clone()) },
             ResponseInner::GetTimestamp(get_ts) => { ResponseInner::GetTimestamp(get_ts.clone()) },
+            ResponseInner::Subscribe(sub) => { ResponseInner::Subscribe(sub.clone()) },
             ResponseInner::Write(write) => { ResponseInner::Write(write.clone()) },
         }
     }
 }
 
+impl Codec for ResponseInner {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            ResponseInner::Get(m) => {
+                enc.put_u8(0);
+                m.encode(enc);
+            },
+            ResponseInner::GetAt(m) => {
+                enc.put_u8(1);
+                m.encode(enc);
+            },
+            ResponseInner::GetTimestamp(m) => {
+                enc.put_u8(2);
+                m.encode(enc);
+            },
+            ResponseInner::Subscribe(m) => {
+                enc.put_u8(3);
+                m.encode(enc);
+            },
+            ResponseInner::Write(m) => {
+                enc.put_u8(4);
+                m.encode(enc);
+            },
+        }
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        match dec.get_u8()? {
+            0 => Ok(ResponseInner::Get(GetResponse::decode(dec)?)),
+            1 => Ok(ResponseInner::GetAt(GetAtResponse::decode(dec)?)),
+            2 => Ok(ResponseInner::GetTimestamp(GetTimestampResponse::decode(dec)?)),
+            3 => Ok(ResponseInner::Subscribe(SubscribeResponse::decode(dec)?)),
+            4 => Ok(ResponseInner::Write(WriteResponse::decode(dec)?)),
+            _ => Err(DecodeError),
+        }
+    }
+}
+
+impl Codec for Response {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.request_id.encode(enc);
+        self.inner.encode(enc);
+    }
+
+    /// The request proof does not cross the wire: it is assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let request_id = u64::decode(dec)?;
+        let inner = ResponseInner::decode(dec)?;
+        Ok(Response { request_id, inner, request: dec.tracked()? })
+    }
+}
+
 } // verus!
 impl std::fmt::Debug for ResponseInner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ResponseInner::Get(get) => f.debug_tuple("Get").field(&get).finish(),
+            ResponseInner::GetAt(get_at) => f.debug_tuple("GetAt").field(&get_at).finish(),
             ResponseInner::GetTimestamp(get_ts) => {
                 f.debug_tuple("GetTimestamp").field(&get_ts).finish()
             }
+            ResponseInner::Subscribe(sub) => f.debug_tuple("Subscribe").field(&sub).finish(),
             ResponseInner::Write(write) => f.debug_tuple("Write").field(&write).finish(),
         }
     }
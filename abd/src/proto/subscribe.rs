@@ -0,0 +1,314 @@
+use crate::invariants::ServerToken;
+use crate::resource::monotonic_timestamp::MonotonicTimestampResource;
+use crate::timestamp::Timestamp;
+
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
+use vstd::prelude::*;
+use vstd::resource::map::GhostPersistentSubmap;
+use vstd::resource::Loc;
+
+verus! {
+
+/// Asks to be notified once the server holds a timestamp newer than `since`
+///
+/// A subscription fires (at most) once: the server either replies right away, if it is already
+/// past `since`, or holds on to the request until a write moves it past `since`.
+pub struct SubscribeRequest {
+    since: Timestamp,
+}
+
+pub struct SubscribeResponse {
+    timestamp: Timestamp,
+    #[allow(unused)]
+    lb: Tracked<MonotonicTimestampResource>,
+    #[allow(unused)]
+    server_token: Tracked<ServerToken>,
+}
+
+#[allow(unused)]
+impl SubscribeRequest {
+    pub fn new(since: Timestamp) -> (r: Self)
+        ensures
+            r.spec_since() == since,
+    {
+        SubscribeRequest { since }
+    }
+
+    pub closed spec fn spec_since(self) -> Timestamp {
+        self.since
+    }
+
+    pub fn since(&self) -> (ts: Timestamp)
+        ensures
+            ts == self.spec_since(),
+        no_unwind
+    {
+        self.since
+    }
+
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        self.since == other.since
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_since() == b.spec_since(),
+    {
+    }
+}
+
+#[allow(unused)]
+impl SubscribeResponse {
+    #[verifier::type_invariant]
+    pub closed spec fn inv(self) -> bool {
+        &&& self.lb@@ is LowerBound
+        &&& self.lb@.loc() == self.server_token@.value()
+        &&& self.lb@@.timestamp() == self.timestamp
+    }
+
+    pub closed spec fn lb(self) -> MonotonicTimestampResource {
+        self.lb@
+    }
+
+    pub closed spec fn spec_timestamp(self) -> Timestamp {
+        self.timestamp
+    }
+
+    pub closed spec fn spec_server_token(self) -> ServerToken {
+        self.server_token@
+    }
+
+    pub closed spec fn server_token_id(self) -> Loc {
+        self.server_token@.id()
+    }
+
+    pub closed spec fn server_id(self) -> u64 {
+        self.server_token@.key()
+    }
+
+    pub open spec fn loc(self) -> Loc {
+        self.lb().loc()
+    }
+
+    pub fn new(
+        timestamp: Timestamp,
+        lb: Tracked<MonotonicTimestampResource>,
+        server_token: Tracked<ServerToken>,
+    ) -> (r: Self)
+        requires
+            lb@@ is LowerBound,
+            lb@@.timestamp() == timestamp,
+            lb@.loc() == server_token@.value(),
+        ensures
+            r.lb() == lb@,
+            r.spec_timestamp() == timestamp,
+            r.spec_server_token() == server_token@,
+            r.server_id() == server_token@.key(),
+            r.server_token_id() == server_token@.id(),
+            r.loc() == server_token@.value(),
+    {
+        SubscribeResponse { timestamp, lb, server_token }
+    }
+
+    pub fn timestamp(&self) -> (ts: Timestamp)
+        ensures
+            ts == self.spec_timestamp(),
+        no_unwind
+    {
+        self.timestamp
+    }
+
+    pub fn duplicate_lb(&self) -> (r: Tracked<MonotonicTimestampResource>)
+        ensures
+            r@.loc() == self.lb().loc(),
+            r@@.timestamp() == self.lb()@.timestamp(),
+            r@@ is LowerBound,
+        no_unwind
+    {
+        let tracked lb;
+        proof {
+            use_type_invariant(self);
+            lb = self.lb.borrow().extract_lower_bound();
+        }
+        Tracked(lb)
+    }
+
+    pub fn lemma_subscribe_response(&self)
+        ensures
+            self.lb()@ is LowerBound,
+            self.spec_timestamp() == self.lb()@.timestamp(),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(self);
+        }
+    }
+
+    pub fn lemma_token_agree(&self, server_tokens: &mut Tracked<GhostPersistentSubmap<u64, Loc>>)
+        requires
+            self.server_token_id() == old(server_tokens)@.id(),
+        ensures
+            final(server_tokens)@.id() == old(server_tokens)@.id(),
+            final(server_tokens)@@ == old(server_tokens)@@,
+            final(server_tokens)@@.contains_key(self.server_id())
+                ==> final(server_tokens)@@[self.server_id()] == self.loc(),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(self);
+            server_tokens.borrow_mut().intersection_agrees_points_to(self.server_token.borrow());
+        }
+    }
+
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        &&& self.timestamp == other.timestamp
+        &&& self.lb@.loc() == other.lb@.loc()
+        &&& self.lb@@.timestamp() == other.lb@@.timestamp()
+        &&& self.server_token@.id() == other.server_token@.id()
+        &&& self.server_token@@ == other.server_token@@
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.lb().loc() == b.lb().loc(),
+            a.lb()@.timestamp() == b.lb()@.timestamp(),
+            a.spec_timestamp() == b.spec_timestamp(),
+            a.spec_server_token().id() == b.spec_server_token().id(),
+            a.spec_server_token()@ == b.spec_server_token()@,
+            a.server_token_id() == b.server_token_id(),
+            a.server_id() == b.server_id(),
+    {
+    }
+}
+
+impl Clone for SubscribeRequest {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        SubscribeRequest { since: self.since }
+    }
+}
+
+impl Clone for SubscribeResponse {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        let tracked new_lb;
+        let tracked server_token;
+        proof {
+            use_type_invariant(self);
+            new_lb = self.lb.borrow().extract_lower_bound();
+            server_token = self.server_token.borrow().duplicate();
+        }
+        SubscribeResponse::new(self.timestamp.clone(), Tracked(new_lb), Tracked(server_token))
+    }
+}
+
+impl Codec for SubscribeRequest {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.since.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let since = Timestamp::decode(dec)?;
+        Ok(SubscribeRequest { since })
+    }
+}
+
+impl Codec for SubscribeResponse {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.timestamp.encode(enc);
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let timestamp = Timestamp::decode(dec)?;
+        Ok(
+            SubscribeResponse {
+                timestamp,
+                lb: dec.tracked()?,
+                server_token: dec.tracked()?,
+            },
+        )
+    }
+}
+
+} // verus!
+impl std::fmt::Debug for SubscribeRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubscribeRequest")
+            .field("since", &self.since)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for SubscribeResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubscribeResponse")
+            .field("timestamp", &self.timestamp)
+            .finish()
+    }
+}
@@ -4,6 +4,11 @@ use crate::invariants::ServerToken;
 use crate::resource::monotonic_timestamp::MonotonicTimestampResource;
 use crate::timestamp::Timestamp;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
 use vstd::prelude::*;
 use vstd::resource::map::GhostPersistentSubmap;
 use vstd::resource::Loc;
@@ -373,6 +378,44 @@ impl Clone for WriteRequest {
     }
 }
 
+impl Codec for WriteRequest {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.value.encode(enc);
+        self.timestamp.encode(enc);
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let value = Option::<u64>::decode(dec)?;
+        let timestamp = Timestamp::decode(dec)?;
+        Ok(
+            WriteRequest {
+                value,
+                timestamp,
+                commitment: dec.tracked()?,
+                servers: dec.tracked()?,
+            },
+        )
+    }
+}
+
+impl Codec for WriteResponse {
+    #[allow(unused_variables)]
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+    }
+
+    /// The tracked resources do not cross the wire: they are assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(WriteResponse { lb: dec.tracked()?, server_token: dec.tracked()? })
+    }
+}
+
 } // verus!
 impl std::fmt::Debug for WriteRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -1,17 +1,22 @@
 use crate::channel::ChannelInv;
 #[cfg(verus_only)]
+use crate::channel::chan_response_inv;
+#[cfg(verus_only)]
 use crate::invariants;
 #[cfg(verus_only)]
 use crate::invariants::committed_to::WriteCommitment;
 #[cfg(verus_only)]
 use crate::invariants::quorum::ServerUniverse;
+use crate::invariants::requests::RequestProof;
 use crate::invariants::StateInvariant;
+use crate::proto::GetAtRequest;
 use crate::proto::GetRequest;
 use crate::proto::GetTimestampRequest;
 use crate::proto::Request;
 use crate::proto::RequestInner;
 use crate::proto::Response;
 use crate::proto::ResponseInner;
+use crate::proto::SubscribeRequest;
 use crate::proto::WriteRequest;
 #[cfg(verus_only)]
 use crate::proto::WriteResponse;
@@ -22,8 +27,6 @@ use crate::server::register::MonotonicRegisterInner;
 #[cfg(verus_only)]
 use crate::timestamp::Timestamp;
 
-use specs::abd::OwnedReadPerm;
-use specs::abd::OwnedWritePerm;
 use specs::abd::RegisterRead;
 use specs::abd::RegisterWrite;
 
@@ -34,10 +37,15 @@ use verdist::network::channel::Listener;
 use verdist::network::modelled::ModelledConnector;
 #[cfg(verus_only)]
 use verdist::network::modelled::ModelledListener;
-#[cfg(verus_only)]
+use verdist::network::uds::UdsConnector;
+use verdist::rpc::cancel::CancellingChannel;
 use verdist::rpc::proto::TaggedMessage;
+use verdist::server::reply_cache::Lookup;
+use verdist::server::reply_cache::ReplyCache;
+use verdist::server::reply_cache::ReplyCacheInv;
 
 use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
 
 #[cfg(verus_only)]
@@ -71,6 +79,44 @@ pub struct ServerInv {
     pub server_id: u64,
 }
 
+/// The replies cached by the server can be sent back to their clients
+pub open spec fn cached_reply_inv(
+    channel_inv: ChannelInv,
+    server_id: u64,
+) -> spec_fn(u64, Response) -> bool {
+    |client_id: u64, r: Response| chan_response_inv(channel_inv, client_id, server_id, r)
+}
+
+/// A subscription which has not fired yet
+///
+/// It is cancelled when its client sends another request (a client only waits on its latest
+/// request) or disconnects.
+pub struct Subscription {
+    pub client_id: u64,
+    pub request_id: u64,
+    pub request: SubscribeRequest,
+    pub request_proof: Tracked<RequestProof>,
+}
+
+impl Subscription {
+    pub open spec fn inv(self, request_map_id: Loc) -> bool {
+        &&& self.request_proof@.id() == request_map_id
+        &&& self.request_proof@.key() == (self.client_id, self.request_id)
+        &&& self.request_proof@.value() is Subscribe
+        &&& self.request_proof@.value()->Subscribe_0.spec_since() == self.request.spec_since()
+    }
+}
+
+pub struct SubscriptionsInv {
+    pub request_map_id: Loc,
+}
+
+impl vstd::rwlock::RwLockPredicate<Vec<Subscription>> for SubscriptionsInv {
+    open spec fn inv(self, v: Vec<Subscription>) -> bool {
+        forall|idx: int| 0 <= idx < v@.len() ==> #[trigger] v@[idx].inv(self.request_map_id)
+    }
+}
+
 impl<C> vstd::rwlock::RwLockPredicate<Vec<C>> for ServerInv where
     C: Channel<Id = (u64, u64), R = Request, S = Response, K = ChannelInv>,
  {
@@ -95,10 +141,14 @@ pub struct RegisterServer<L, C, ML, RL> where
     id: u64,
     /// Listener channel
     listener: L,
-    /// Connected clients
-    connected: RwLock<Vec<C>, ServerInv>,
+    /// Connected clients (dropping the requests they cancel)
+    connected: RwLock<Vec<CancellingChannel<C>>, ServerInv>,
     /// Register state
     register: MonotonicRegister<ML, RL>,
+    /// Pending subscriptions
+    subscriptions: RwLock<Vec<Subscription>, SubscriptionsInv>,
+    /// Last reply to each client, to answer duplicate requests
+    replies: RwLock<ReplyCache<Response>, ReplyCacheInv<Response>>,
 }
 
 impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
@@ -116,10 +166,18 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         let ghost channel_inv = ChannelInv::from_state_pred(state_inv@.constant());
         let ghost server_inv = ServerInv { channel_inv, server_id: id };
         assert(server_inv.inv(empty));
+        let ghost subscriptions_inv = SubscriptionsInv {
+            request_map_id: channel_inv.request_map_id,
+        };
+        let ghost replies_inv = ReplyCacheInv { reply_inv: cached_reply_inv(channel_inv, id) };
+        let replies = ReplyCache::new();
+        assert(replies_inv.inv(replies));
         RegisterServer {
             id,
             register: MonotonicRegister::new(id, state_inv),
             connected: RwLock::new(empty, Ghost(server_inv)),
+            subscriptions: RwLock::new(Vec::new(), Ghost(subscriptions_inv)),
+            replies: RwLock::new(replies, Ghost(replies_inv)),
             listener,
         }
     }
@@ -132,6 +190,15 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         &&& self.connected.pred().server_id == self.id
         &&& self.server_locs().contains_key(self.id)
         &&& self.server_locs()[self.id] == self.register.resource_loc()
+        &&& self.subscriptions.pred().request_map_id == self.request_map_id()
+        &&& self.replies.pred().reply_inv == cached_reply_inv(
+            self.connected.pred().channel_inv,
+            self.id,
+        )
+    }
+
+    closed spec fn request_map_id(self) -> Loc {
+        self.connected.pred().channel_inv.request_map_id
     }
 
     closed spec fn commitment_id(self) -> Loc {
@@ -155,7 +222,7 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         }
         let (mut guard, handle) = self.connected.acquire_write();
         assume(channel.spec_id().0 == self.id);  // TODO(connector)
-        guard.push(channel);
+        guard.push(CancellingChannel::new(channel));
         assert(ServerInv::inv(self.connected.pred(), guard));
         handle.release_write(guard);
     }
@@ -182,6 +249,24 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         ResponseInner::Get(self.register.read(req))
     }
 
+    fn handle_get_at(&self, req: GetAtRequest) -> (r: ResponseInner)
+        ensures
+            r is GetAt,
+            ({
+                let resp = r->GetAt_0;
+                &&& resp.server_id() == self.id
+                &&& resp.spec_commitment() is Some ==> resp.commitment_id() == self.commitment_id()
+                &&& resp.server_token_id() == self.server_token_id()
+                &&& self.server_locs().contains_key(resp.server_id())
+                &&& req.spec_timestamp() == resp.spec_timestamp()
+            }),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        ResponseInner::GetAt(self.register.read_at(req))
+    }
+
     fn handle_get_timestamp(&self, req: GetTimestampRequest) -> (r: ResponseInner)
         requires
             req.servers().locs() == self.server_locs(),
@@ -203,6 +288,188 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         ResponseInner::GetTimestamp(self.register.read_timestamp(req))
     }
 
+    /// Either answers the subscription right away or parks it until a newer write arrives
+    fn handle_subscribe(
+        &self,
+        req: SubscribeRequest,
+        client_id: u64,
+        request_id: u64,
+        request_proof: Tracked<RequestProof>,
+    ) -> (r: Option<ResponseInner>)
+        requires
+            request_proof@.id() == self.request_map_id(),
+            request_proof@.key() == (client_id, request_id),
+            request_proof@.value() is Subscribe,
+            request_proof@.value()->Subscribe_0.spec_since() == req.spec_since(),
+        ensures
+            r is Some ==> {
+                &&& r->Some_0 is Subscribe
+                &&& {
+                    let resp = r->Some_0->Subscribe_0;
+                    &&& resp.server_id() == self.id
+                    &&& resp.server_token_id() == self.server_token_id()
+                    &&& self.server_locs().contains_key(resp.server_id())
+                    &&& self.server_locs()[resp.server_id()] == resp.loc()
+                    &&& req.spec_since() < resp.spec_timestamp()
+                }
+            },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        // hold the lock while checking the register: a write which slips in between the check and
+        // parking the subscription is followed by a `notify_subscribers`, which then waits for the
+        // subscription to be parked
+        let (mut subscriptions, handle) = self.subscriptions.acquire_write();
+        match self.register.notify(&req) {
+            Some(resp) => {
+                handle.release_write(subscriptions);
+                Some(ResponseInner::Subscribe(resp))
+            },
+            None => {
+                let sub = Subscription { client_id, request_id, request: req, request_proof };
+                assert(sub.inv(self.subscriptions.pred().request_map_id));
+                subscriptions.push(sub);
+                assert forall|idx: int| 0 <= idx < subscriptions@.len() implies #[trigger]
+                    subscriptions@[idx].inv(self.subscriptions.pred().request_map_id) by {}
+                handle.release_write(subscriptions);
+                None
+            },
+        }
+    }
+
+    /// Drops the subscriptions of `client_id`
+    fn cancel_subscriptions(&self, client_id: u64) {
+        proof {
+            use_type_invariant(self);
+        }
+        let ghost request_map_id = self.subscriptions.pred().request_map_id;
+        let (mut subscriptions, handle) = self.subscriptions.acquire_write();
+        let mut idx = 0;
+        while idx < subscriptions.len()
+            invariant
+                idx <= subscriptions@.len(),
+                forall|i: int|
+                    0 <= i < subscriptions@.len() ==> #[trigger] subscriptions@[i].inv(
+                        request_map_id,
+                    ),
+                request_map_id == self.subscriptions.pred().request_map_id,
+            decreases subscriptions@.len() - idx,
+        {
+            if subscriptions[idx].client_id == client_id {
+                let ghost old_subscriptions = subscriptions@;
+                subscriptions.swap_remove(idx);
+                assert forall|i: int| 0 <= i < subscriptions@.len() implies #[trigger]
+                    subscriptions@[i].inv(request_map_id) by {
+                    if i == idx {
+                        assert(subscriptions@[i] == old_subscriptions[old_subscriptions.len() - 1]);
+                    } else {
+                        assert(subscriptions@[i] == old_subscriptions[i]);
+                    }
+                }
+            } else {
+                idx += 1;
+            }
+        }
+        handle.release_write(subscriptions);
+    }
+
+    /// Forgets the replies cached for `client_id` (see [`ReplyCache::evict`])
+    fn evict_replies(&self, client_id: u64) {
+        let ghost reply_inv = self.replies.pred().reply_inv;
+        let (mut replies, handle) = self.replies.acquire_write();
+        replies.evict(client_id, Ghost(reply_inv));
+        handle.release_write(replies);
+    }
+
+    /// Sends the notifications for the subscriptions which are now behind the register
+    ///
+    /// Only needed after a write: the register does not move otherwise
+    fn notify_subscribers(&self, connected: &Vec<CancellingChannel<C>>)
+        requires
+            forall|idx|
+                0 <= idx < connected@.len() ==> {
+                    let chan = #[trigger] connected@[idx];
+                    &&& self.connected.pred().channel_inv == chan.constant()
+                    &&& self.id == chan.spec_id().0
+                },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        let ghost request_map_id = self.subscriptions.pred().request_map_id;
+        let read_handle = self.subscriptions.acquire_read();
+        let idle = read_handle.borrow().len() == 0;
+        read_handle.release_read();
+        if idle {
+            return ;
+        }
+
+        let (mut subscriptions, handle) = self.subscriptions.acquire_write();
+        let mut pending = Vec::new();
+        while subscriptions.len() > 0
+            invariant
+                forall|idx: int|
+                    0 <= idx < subscriptions@.len() ==> #[trigger] subscriptions@[idx].inv(
+                        request_map_id,
+                    ),
+                forall|idx: int|
+                    0 <= idx < pending@.len() ==> #[trigger] pending@[idx].inv(request_map_id),
+                forall|idx|
+                    0 <= idx < connected@.len() ==> {
+                        let chan = #[trigger] connected@[idx];
+                        &&& self.connected.pred().channel_inv == chan.constant()
+                        &&& self.id == chan.spec_id().0
+                    },
+                request_map_id == self.request_map_id(),
+            decreases subscriptions@.len(),
+        {
+            let sub = subscriptions.pop().unwrap();
+            assert(sub.inv(request_map_id));
+
+            let resp = match self.register.notify(&sub.request) {
+                Some(resp) => resp,
+                None => {
+                    pending.push(sub);
+                    assert forall|idx: int| 0 <= idx < pending@.len() implies #[trigger]
+                        pending@[idx].inv(request_map_id) by {}
+                    continue ;
+                },
+            };
+
+            let response = Response::new(
+                sub.request_id,
+                ResponseInner::Subscribe(resp),
+                sub.request_proof,
+            );
+            for channel in it: connected.iter()
+                invariant
+                    forall|idx|
+                        0 <= idx < connected@.len() ==> {
+                            let chan = #[trigger] connected@[idx];
+                            &&& self.connected.pred().channel_inv == chan.constant()
+                            &&& self.id == chan.spec_id().0
+                        },
+                    connected@ == it.elements,
+                    response.request_key().0 == sub.client_id,
+                    response.request_id() == self.request_map_id(),
+                    response.server_id() == self.id,
+                    response.req_type() is Subscribe,
+                    response.subscribe().server_token_id() == self.server_token_id(),
+                    self.server_locs().contains_key(response.server_id()),
+                    self.server_locs()[response.server_id()] == response.subscribe().loc(),
+            {
+                if channel.id().1 == sub.client_id {
+                    assert(C::K::send_inv(channel.constant(), channel.spec_id(), response));
+                    // a failed send means the client is gone: the channel is dropped on the next poll
+                    let _ = channel.send(&response);
+                    break ;
+                }
+            }
+        }
+        handle.release_write(pending);
+    }
+
     fn handle_write(&self, req: WriteRequest) -> (r: ResponseInner)
         requires
             req.servers().locs() == self.server_locs(),
@@ -226,14 +493,71 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         ResponseInner::Write(self.register.write(req))
     }
 
+    closed spec fn handle_ensures(self, request: Request, r: Response) -> bool {
+        &&& r.spec_tag() == request.spec_tag()
+        &&& r.request_id() == request.request_id()
+        &&& r.request_key() == request.request_key()
+        &&& r.request().spec_eq(request.request())
+        &&& r.server_id() == self.id
+        &&& request.req_type() == r.req_type()
+        &&& r.req_type() is Get ==> {
+            let get_req = request.get();
+            let resp = r.get();
+            &&& resp.spec_commitment().id() == self.commitment_id()
+            &&& resp.server_token_id() == self.server_token_id()
+            &&& self.server_locs().contains_key(resp.server_id())
+            &&& self.server_locs()[resp.server_id()] == resp.loc()
+            &&& get_req.servers().contains_key(resp.server_id())
+            &&& get_req.servers()[resp.server_id()]@@.timestamp() <= resp.spec_timestamp()
+        }
+        &&& r.req_type() is GetAt ==> {
+            let get_at_req = request.get_at();
+            let resp = r.get_at();
+            &&& resp.spec_commitment() is Some ==> resp.commitment_id() == self.commitment_id()
+            &&& resp.server_token_id() == self.server_token_id()
+            &&& self.server_locs().contains_key(resp.server_id())
+            &&& get_at_req.spec_timestamp() == resp.spec_timestamp()
+        }
+        &&& r.req_type() is GetTimestamp ==> {
+            let get_ts_req = request.get_timestamp();
+            let resp = r.get_timestamp();
+            &&& resp.server_token_id() == self.server_token_id()
+            &&& self.server_locs().contains_key(resp.server_id())
+            &&& self.server_locs()[resp.server_id()] == resp.loc()
+            &&& get_ts_req.servers().contains_key(resp.server_id())
+            &&& get_ts_req.servers()[resp.server_id()]@@.timestamp() <= resp.spec_timestamp()
+        }
+        &&& r.req_type() is Subscribe ==> {
+            let sub_req = request.subscribe();
+            let resp = r.subscribe();
+            &&& resp.server_token_id() == self.server_token_id()
+            &&& self.server_locs().contains_key(resp.server_id())
+            &&& self.server_locs()[resp.server_id()] == resp.loc()
+            &&& sub_req.spec_since() < resp.spec_timestamp()
+        }
+        &&& r.req_type() is Write ==> {
+            let write_req = request.write();
+            let resp = r.write();
+            &&& resp.server_token_id() == self.server_token_id()
+            &&& self.server_locs().contains_key(resp.server_id())
+            &&& self.server_locs()[resp.server_id()] == resp.loc()
+            &&& write_req.servers().contains_key(resp.server_id())
+            &&& write_req.servers()[resp.server_id()]@@.timestamp() <= resp.spec_timestamp()
+        }
+    }
+
+    /// Handles a request
+    ///
+    /// Returns `None` if the request is a subscription that was parked (see `handle_subscribe`)
     fn handle(
         &self,
         request: Request,
         #[allow(unused_variables)]
         client_id: u64,
-    ) -> (r: Response)
+    ) -> (r: Option<Response>)
         requires
             request.request_key() == (client_id, request.spec_tag()),
+            request.request_id() == self.request_map_id(),
             request.req_type() is Get ==> {
                 let get_req = request.get();
                 &&& get_req.servers().locs() == self.server_locs()
@@ -248,46 +572,30 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
                 &&& write_req.commitment_id() == self.commitment_id()
             },
         ensures
-            r.spec_tag() == request.spec_tag(),
-            r.request_id() == request.request_id(),
-            r.request_key() == request.request_key(),
-            r.request().spec_eq(request.request()),
-            r.server_id() == self.id,
-            request.req_type() == r.req_type(),
-            r.req_type() is Get ==> ({
-                let get_req = request.get();
-                let resp = r.get();
-                &&& resp.spec_commitment().id() == self.commitment_id()
-                &&& resp.server_token_id() == self.server_token_id()
-                &&& self.server_locs().contains_key(resp.server_id())
-                &&& self.server_locs()[resp.server_id()] == resp.loc()
-                &&& get_req.servers().contains_key(resp.server_id())
-                &&& get_req.servers()[resp.server_id()]@@.timestamp() <= resp.spec_timestamp()
-            }),
-            r.req_type() is GetTimestamp ==> ({
-                let get_ts_req = request.get_timestamp();
-                let resp = r.get_timestamp();
-                &&& resp.server_token_id() == self.server_token_id()
-                &&& self.server_locs().contains_key(resp.server_id())
-                &&& self.server_locs()[resp.server_id()] == resp.loc()
-                &&& get_ts_req.servers().contains_key(resp.server_id())
-                &&& get_ts_req.servers()[resp.server_id()]@@.timestamp() <= resp.spec_timestamp()
-            }),
-            r.req_type() is Write ==> ({
-                let write_req = request.write();
-                let resp = r.write();
-                &&& resp.server_token_id() == self.server_token_id()
-                &&& self.server_locs().contains_key(resp.server_id())
-                &&& self.server_locs()[resp.server_id()] == resp.loc()
-                &&& write_req.servers().contains_key(resp.server_id())
-                &&& write_req.servers()[resp.server_id()]@@.timestamp() <= resp.spec_timestamp()
-            }),
+            r is None ==> request.req_type() is Subscribe,
+            r is Some ==> self.handle_ensures(request, r->Some_0),
     {
         vlib::veprintln!("[server|{:>3}]: received req: {:?}", self.id, request);
         let (request_id, request_inner, request_proof) = request.destruct();
         let resp_inner = match request_inner {
             RequestInner::Get(req) => self.handle_get(req),
+            RequestInner::GetAt(req) => self.handle_get_at(req),
             RequestInner::GetTimestamp(req) => self.handle_get_timestamp(req),
+            RequestInner::Subscribe(req) => {
+                let tracked sub_proof;
+                proof {
+                    let proof_sub_req = request_proof@.value()->Subscribe_0;
+                    SubscribeRequest::lemma_spec_eq(proof_sub_req, req);
+                    sub_proof = request_proof.borrow().duplicate();
+                }
+                match self.handle_subscribe(req, client_id, request_id, Tracked(sub_proof)) {
+                    Some(resp_inner) => resp_inner,
+                    None => {
+                        vlib::veprintln!("[server|{:>3}]: parked subscription", self.id);
+                        return None;
+                    },
+                }
+            },
             RequestInner::Write(req) => self.handle_write(req),
         };
 
@@ -301,6 +609,11 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
                 ServerUniverse::lemma_eq(proof_get_req.servers(), get_req.servers());
                 proof_get_req.servers().lemma_locs();
             }
+            if request_inner is GetAt {
+                let get_at_req = request_inner->GetAt_0;
+                let proof_get_at_req = request_proof@.value()->GetAt_0;
+                GetAtRequest::lemma_spec_eq(proof_get_at_req, get_at_req);
+            }
             if request_inner is GetTimestamp {
                 let get_ts_req = request_inner->GetTimestamp_0;
                 let proof_get_ts_req = request_proof@.value()->GetTimestamp_0;
@@ -310,6 +623,11 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
                 ServerUniverse::lemma_eq(proof_get_ts_req.servers(), get_ts_req.servers());
                 proof_get_ts_req.servers().lemma_locs();
             }
+            if request_inner is Subscribe {
+                let sub_req = request_inner->Subscribe_0;
+                let proof_sub_req = request_proof@.value()->Subscribe_0;
+                SubscribeRequest::lemma_spec_eq(proof_sub_req, sub_req);
+            }
             if request_inner is Write {
                 let write_req = request_inner->Write_0;
                 let proof_write_req = request_proof@.value()->Write_0;
@@ -326,7 +644,91 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
             RequestInner::spec_eq_refl(r.request());
         }
         vlib::veprintln!("[server|{:>3}]: sending resp: {:?}", self.id, r);
-        r
+        Some(r)
+    }
+
+    /// Handles a request, unless it was already handled
+    ///
+    /// Duplicates of the latest request of a client get the reply that was sent to it, and
+    /// requests which were already acknowledged get none (see [`ReplyCache`])
+    fn handle_once(&self, request: Request, client_id: u64) -> (r: Option<Response>)
+        requires
+            request.request_key() == (client_id, request.spec_tag()),
+            request.request_id() == self.request_map_id(),
+            request.req_type() is Get ==> {
+                let get_req = request.get();
+                &&& get_req.servers().locs() == self.server_locs()
+            },
+            request.req_type() is GetTimestamp ==> {
+                let get_ts_req = request.get_timestamp();
+                &&& get_ts_req.servers().locs() == self.server_locs()
+            },
+            request.req_type() is Write ==> {
+                let write_req = request.write();
+                &&& write_req.servers().locs() == self.server_locs()
+                &&& write_req.commitment_id() == self.commitment_id()
+            },
+        ensures
+            r is Some ==> {
+                let resp = r->Some_0;
+                &&& chan_response_inv(self.connected.pred().channel_inv, client_id, self.id, resp)
+                &&& resp.spec_tag() == request.spec_tag()
+            },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        let ghost channel_inv = self.connected.pred().channel_inv;
+        let ghost reply_inv = self.replies.pred().reply_inv;
+        let (mut replies, handle) = self.replies.acquire_write();
+        let tag = request.tag();
+        let res = match replies.lookup(client_id, tag, Ghost(reply_inv)) {
+            Lookup::New => {
+                // a client only waits on its latest request: this one acknowledges the others
+                replies.acknowledge(client_id, tag, Ghost(reply_inv));
+                replies.start(client_id, tag, Ghost(reply_inv));
+                self.cancel_subscriptions(client_id);
+                let res = self.handle(request, client_id);
+                if let Some(response) = &res {
+                    assert(chan_response_inv(channel_inv, client_id, self.id, *response));
+                    let cached = response.clone();
+                    proof {
+                        broadcast use crate::proto::Response::lemma_spec_eq;
+                        broadcast use crate::proto::GetResponse::lemma_spec_eq;
+                        broadcast use crate::proto::GetAtResponse::lemma_spec_eq;
+                        broadcast use crate::proto::GetTimestampResponse::lemma_spec_eq;
+                        broadcast use crate::proto::SubscribeResponse::lemma_spec_eq;
+                        broadcast use crate::proto::WriteResponse::lemma_spec_eq;
+
+                        assert(chan_response_inv(channel_inv, client_id, self.id, cached));
+                    }
+                    replies.record(client_id, cached, Ghost(reply_inv));
+                }
+                res
+            },
+            Lookup::Duplicate => {
+                let response = replies.reply(client_id, tag).unwrap().clone();
+                proof {
+                    broadcast use crate::proto::Response::lemma_spec_eq;
+                    broadcast use crate::proto::GetResponse::lemma_spec_eq;
+                    broadcast use crate::proto::GetAtResponse::lemma_spec_eq;
+                    broadcast use crate::proto::GetTimestampResponse::lemma_spec_eq;
+                    broadcast use crate::proto::SubscribeResponse::lemma_spec_eq;
+                    broadcast use crate::proto::WriteResponse::lemma_spec_eq;
+
+                    replies.lemma_reply(reply_inv, client_id, tag);
+                    assert(chan_response_inv(channel_inv, client_id, self.id, response));
+                }
+                vlib::veprintln!("[server|{:>3}]: resending resp: {:?}", self.id, response);
+                Some(response)
+            },
+            Lookup::Stale => {
+                vlib::veprintln!("[server|{:>3}]: dropped stale req: {:?}", self.id, request);
+                None
+            },
+        };
+        handle.release_write(replies);
+        res
     }
 
     fn poll(&self) -> bool {
@@ -358,18 +760,16 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
         }
 
         let mut drop = HashSet::new();
+        let mut gone = Vec::new();
+        let mut wrote = false;
         let (mut connected, handle) = self.connected.acquire_write();
 
         let ghost connected_pred = self.connected.pred();
         let iterator = connected.iter();
-        #[allow(unused_variables)]
-        let mut idx = 0usize;
-        #[allow(unused_assignments)]
         for channel in it: iterator
             invariant
                 self.connected.pred() == connected_pred,
                 connected_pred.server_id == self.id,
-                idx == it.pos,
                 connected@ == it.elements,
                 forall|idx|
                     0 <= idx < connected@.len() ==> {
@@ -381,23 +781,25 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
             match channel.try_recv() {
                 Ok(req) => {
                     assert(C::K::recv_inv(channel.constant(), channel.spec_id(), req));
-                    let response = self.handle(req, channel.id().1);
-                    assert(C::K::send_inv(channel.constant(), channel.spec_id(), response));
-                    if channel.send(&response).is_err() {
-                        drop.insert(channel.id());
+                    wrote = wrote || req.is_write();
+                    if let Some(response) = self.handle_once(req, channel.id().1) {
+                        assert(C::K::send_inv(channel.constant(), channel.spec_id(), response));
+                        if channel.send(&response).is_err() {
+                            drop.insert(channel.id());
+                            gone.push(channel.id().1);
+                        }
                     }
                 },
                 Err(verdist::network::error::TryRecvError::Empty) => {},
                 Err(verdist::network::error::TryRecvError::Disconnected) => {
                     drop.insert(channel.id());
+                    gone.push(channel.id().1);
                 },
             }
-            assume(idx < usize::MAX);  // XXX: overflow
-            idx += 1;
         }
 
         let ghost old_c = connected@;
-        let filter_fn = |c: &C| !drop.contains(&c.id());
+        let filter_fn = |c: &CancellingChannel<C>| !drop.contains(&c.id());
         connected.retain(filter_fn);
         proof {
             let ghost server_inv = self.connected.pred();
@@ -410,50 +812,97 @@ impl<L, C, ML, RL> RegisterServer<L, C, ML, RL> where
                 old_c.lemma_filter_contains_rev(|c| filter_fn.ensures((&c,), true), chan);
             }
         }
+        for gone_idx in 0..gone.len()
+            invariant
+                forall|idx|
+                    0 <= idx < connected@.len() ==> {
+                        let chan = #[trigger] connected@[idx];
+                        &&& self.connected.pred().channel_inv == chan.constant()
+                        &&& self.id == chan.spec_id().0
+                    },
+        {
+            self.cancel_subscriptions(gone[gone_idx]);
+            self.evict_replies(gone[gone_idx]);
+        }
+        if wrote {
+            self.notify_subscribers(&connected);
+        }
         handle.release_write(connected);
 
         true
     }
 }
 
-fn create_server<L, C, ML, RL>(server_id: u64, listener: L) -> RegisterServer<L, C, ML, RL> where
+fn create_server<L, C, ML, RL>(
+    server_id: u64,
+    listener: L,
+    state_inv: &Tracked<Arc<StateInvariant<ML, RL>>>,
+) -> RegisterServer<L, C, ML, RL> where
     L: Listener<C>,
     C: Channel<R = Request, S = Response, Id = (u64, u64), K = ChannelInv>,
     ML: MutLinearizer<RegisterWrite>,
     RL: ReadLinearizer<RegisterRead>,
- {
-    // XXX: this comes from the limitation on run_modelled_server
-    let ghost server_ids = arbitrary::<Set<u64>>().insert(server_id);
-    let tracked state_inv;
+
+    requires
+        state_inv@.namespace() == invariants::state_inv_id(),
+        state_inv@.constant().server_locs.contains_key(server_id),
+{
+    let tracked server_inv;
     proof {
-        let tracked (s, v) = invariants::get_system_state::<ML, RL>(server_ids);
-        state_inv = s;
+        server_inv = state_inv.borrow().clone();
     }
-    RegisterServer::new(listener, server_id, Tracked(state_inv))
+    RegisterServer::new(listener, server_id, Tracked(server_inv))
 }
 
 } // verus!
+/// Polls `server` in the background (see [`verdist::server::spawn_pollers`])
+// Why is this unverified:
+// - minor: no support for tracing
+// - major: verus does not support threads
+fn spawn_server<L, C, ML, RL>(server: RegisterServer<L, C, ML, RL>) where
+    L: Listener<C> + Send + Sync + 'static,
+    C: Channel<R = Request, S = Response, Id = (u64, u64), K = ChannelInv> + Send + Sync + 'static,
+    ML: MutLinearizer<RegisterWrite> + Send + Sync + 'static,
+    RL: ReadLinearizer<RegisterRead> + Send + Sync + 'static,
+{
+    vlib::veprintln!("[server|{:>3}]: starting", server.id);
+    verdist::server::spawn_pollers(server, |server| server.poll());
+}
+
 // Why is this unverified:
 // - minor: no support for tracing
 // - major: verus does not support threads
-pub fn run_modelled_server(server_id: u64) -> ModelledConnector<Response, Request>
+pub fn run_modelled_server<ML, RL>(
+    server_id: u64,
+    state_inv: &Tracked<Arc<StateInvariant<ML, RL>>>,
+) -> ModelledConnector<Response, Request>
+where
+    ML: MutLinearizer<RegisterWrite> + Send + Sync + 'static,
+    RL: ReadLinearizer<RegisterRead> + Send + Sync + 'static,
 // requires
-    // server_ids@.contains(server_id),
+    // state_inv@.constant().server_locs.contains_key(server_id),
 {
     let (listener, connector) = verdist::network::modelled::listen_channel(server_id);
-    std::thread::spawn(move || {
-        let server = Arc::new(create_server::<_, _, OwnedWritePerm, OwnedReadPerm>(
-            server_id, listener,
-        ));
-        vlib::veprintln!("[server|{:>3}]: starting", server.id);
-
-        std::thread::scope(|s| {
-            for _ in 0..5 {
-                let serv = server.clone();
-                s.spawn(move || while serv.poll() {});
-            }
-        });
-    });
-
+    spawn_server(create_server(server_id, listener, state_inv));
     connector
 }
+
+/// Runs a server listening on the Unix domain socket at `path` (see [`verdist::network::uds`])
+// Why is this unverified:
+// - minor: no support for tracing
+// - major: verus does not support threads
+pub fn run_uds_server<ML, RL>(
+    server_id: u64,
+    path: impl AsRef<Path>,
+    state_inv: &Tracked<Arc<StateInvariant<ML, RL>>>,
+) -> std::io::Result<UdsConnector<Response, Request>>
+where
+    ML: MutLinearizer<RegisterWrite> + Send + Sync + 'static,
+    RL: ReadLinearizer<RegisterRead> + Send + Sync + 'static,
+// requires
+    // state_inv@.constant().server_locs.contains_key(server_id),
+{
+    let (listener, connector) = verdist::network::uds::listen_uds(server_id, path)?;
+    spawn_server(create_server(server_id, listener, state_inv));
+    Ok(connector)
+}
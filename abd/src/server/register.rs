@@ -3,8 +3,12 @@ use crate::invariants;
 use crate::invariants::committed_to::WriteCommitment;
 use crate::invariants::ServerToken;
 use crate::invariants::StateInvariant;
+use crate::proto::GetAtRequest;
+use crate::proto::GetAtResponse;
 use crate::proto::GetRequest;
 use crate::proto::GetTimestampRequest;
+use crate::proto::SubscribeRequest;
+use crate::proto::SubscribeResponse;
 use crate::proto::WriteRequest;
 use crate::proto::WriteResponse;
 use crate::proto::{GetResponse, GetTimestampResponse};
@@ -24,6 +28,24 @@ use vstd::rwlock::RwLock;
 
 verus! {
 
+/// Number of superseded `(timestamp, value)` pairs each server keeps around
+pub const HISTORY_LEN: usize = 16;
+
+/// A value which was once the latest value of the register
+pub struct HistoryEntry {
+    pub timestamp: Timestamp,
+    pub value: Option<u64>,
+    pub commitment: Tracked<WriteCommitment>,
+}
+
+impl HistoryEntry {
+    pub open spec fn inv(self, commitment_id: Loc) -> bool {
+        &&& self.timestamp == self.commitment@.key()
+        &&& self.value == self.commitment@.value()
+        &&& self.commitment@.id() == commitment_id
+    }
+}
+
 #[allow(dead_code)]
 pub struct RegisterIds {
     pub resource_loc: Loc,
@@ -42,6 +64,8 @@ pub struct MonotonicRegisterInner<ML, RL> where
     pub value: Option<u64>,
     pub timestamp: Timestamp,
     pub commitment: Tracked<WriteCommitment>,
+    /// Bounded history of superseded values, oldest first
+    pub history: Vec<HistoryEntry>,
     pub resource: Tracked<MonotonicTimestampResource>,
     pub state_inv: Tracked<Arc<StateInvariant<ML, RL>>>,
     pub server_token: Tracked<ServerToken>,
@@ -109,6 +133,7 @@ impl<ML, RL> MonotonicRegisterInner<ML, RL> where
             timestamp: Timestamp::default(),
             resource: Tracked(resource),
             commitment: Tracked(zero_commitment),
+            history: Vec::new(),
             server_token: Tracked(server_token),
             state_inv,
         }
@@ -153,6 +178,11 @@ impl<ML, RL> MonotonicRegisterInner<ML, RL> where
         &&& self.server_token@.value() == self.resource@.loc()
         &&& self.server_token@.id() == self.state_inv@.constant().server_tokens_id
         &&& self.commitment_id() == self.state_inv@.constant().commitments_ids.commitment_id
+        &&& self.history@.len() <= HISTORY_LEN
+        &&& forall|idx: int|
+            0 <= idx < self.history@.len() ==> #[trigger] self.history@[idx].inv(
+                self.commitment_id(),
+            )
     }
 
     #[allow(unused_variables)]
@@ -243,6 +273,87 @@ impl<ML, RL> MonotonicRegisterInner<ML, RL> where
         GetTimestampResponse::new(self.timestamp.clone(), Tracked(new_lb), Tracked(server_token))
     }
 
+    /// Builds the notification for a subscription, if the register is already past `since`
+    pub fn notify(&self, req: &SubscribeRequest) -> (r: Option<SubscribeResponse>)
+        requires
+            self.resource@@ is HalfRightToAdvance,
+            self.inv(),
+        ensures
+            r is Some ==> {
+                let resp = r->Some_0;
+                &&& req.spec_since() < resp.spec_timestamp()
+                &&& resp.server_token_id() == self.server_token_id()
+                &&& resp.loc() == self.resource_loc()
+                &&& resp.server_id() == self.id()
+            },
+    {
+        if self.timestamp <= req.since() {
+            return None;
+        }
+
+        let tracked r = self.resource.borrow();
+        let tracked lb;
+        let tracked server_token;
+        proof {
+            lb = r.extract_lower_bound();
+            server_token = self.server_token.borrow().duplicate();
+        }
+        Some(SubscribeResponse::new(self.timestamp, Tracked(lb), Tracked(server_token)))
+    }
+
+    /// Looks up the value written at a specific timestamp
+    ///
+    /// Only the current value and the last `HISTORY_LEN` superseded values are kept, so older
+    /// timestamps (and timestamps never seen by this server) yield no value
+    pub fn read_at(&self, req: GetAtRequest) -> (r: GetAtResponse)
+        requires
+            self.inv(),
+        ensures
+            r.spec_timestamp() == req.spec_timestamp(),
+            r.spec_commitment() is Some ==> r.commitment_id() == self.commitment_id(),
+            r.server_token_id() == self.server_token_id(),
+            r.server_id() == self.id(),
+    {
+        let timestamp = req.timestamp();
+        let tracked server_token;
+        proof {
+            server_token = self.server_token.borrow().duplicate();
+        }
+
+        if timestamp == self.timestamp {
+            let tracked commitment = self.commitment.borrow().duplicate();
+            return GetAtResponse::new(
+                timestamp,
+                Some(self.value.clone()),
+                Tracked(Some(commitment)),
+                Tracked(server_token),
+            );
+        }
+
+        let mut idx = 0;
+        while idx < self.history.len()
+            invariant
+                self.inv(),
+                0 <= idx <= self.history@.len(),
+            decreases self.history@.len() - idx,
+        {
+            let entry = &self.history[idx];
+            assert(entry.inv(self.commitment_id()));  // TRIGGER
+            if entry.timestamp == timestamp {
+                let tracked commitment = entry.commitment.borrow().duplicate();
+                return GetAtResponse::new(
+                    timestamp,
+                    Some(entry.value.clone()),
+                    Tracked(Some(commitment)),
+                    Tracked(server_token),
+                );
+            }
+            idx += 1;
+        }
+
+        GetAtResponse::new(timestamp, None, Tracked(None), Tracked(server_token))
+    }
+
     pub fn write(self, req: WriteRequest) -> (r: Self)
         requires
             self.resource@@ is HalfRightToAdvance,
@@ -307,12 +418,39 @@ impl<ML, RL> MonotonicRegisterInner<ML, RL> where
                 assert(state.inv());
             });
 
+            let ghost commitment_id = self.commitment_id();
+            let mut history = self.history;
+            history.push(
+                HistoryEntry {
+                    timestamp: self.timestamp,
+                    value: self.value,
+                    commitment: self.commitment,
+                },
+            );
+            if history.len() > HISTORY_LEN {
+                history.remove(0);
+            }
+            assert forall|idx: int| 0 <= idx < history@.len() implies #[trigger] history@[idx].inv(
+                commitment_id,
+            ) by {
+                if history@.len() > self.history@.len() {
+                    if idx < self.history@.len() {
+                        assert(self.history@[idx].inv(commitment_id));  // TRIGGER
+                    }
+                } else {
+                    if idx + 1 < self.history@.len() {
+                        assert(self.history@[idx + 1].inv(commitment_id));  // TRIGGER
+                    }
+                }
+            }
+
             MonotonicRegisterInner {
                 id: self.id,
                 value,
                 timestamp,
                 resource: Tracked(r),
                 commitment,
+                history,
                 server_token: self.server_token,
                 state_inv: self.state_inv,
             }
@@ -431,6 +569,39 @@ impl<ML, RL> MonotonicRegister<ML, RL> where
         res
     }
 
+    pub fn notify(&self, req: &SubscribeRequest) -> (r: Option<SubscribeResponse>)
+        ensures
+            r is Some ==> {
+                let resp = r->Some_0;
+                &&& req.spec_since() < resp.spec_timestamp()
+                &&& resp.loc() == self.resource_loc()
+                &&& resp.server_id() == self.id()
+                &&& resp.server_token_id() == self.server_token_id()
+            },
+    {
+        let handle = self.inner.acquire_read();
+        let inner = handle.borrow();
+        let res = inner.notify(req);
+        handle.release_read();
+
+        res
+    }
+
+    pub fn read_at(&self, req: GetAtRequest) -> (r: GetAtResponse)
+        ensures
+            r.spec_timestamp() == req.spec_timestamp(),
+            r.spec_commitment() is Some ==> r.commitment_id() == self.commitment_id(),
+            r.server_token_id() == self.server_token_id(),
+            r.server_id() == self.id(),
+    {
+        let handle = self.inner.acquire_read();
+        let inner = handle.borrow();
+        let res = inner.read_at(req);
+        handle.release_read();
+
+        res
+    }
+
     pub fn write(&self, req: WriteRequest) -> (r: WriteResponse)
         requires
             req.servers().locs().contains_key(self.id()),
@@ -1,3 +1,8 @@
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
 use vstd::prelude::*;
 
 verus! {
@@ -88,6 +93,23 @@ impl Timestamp {
     }
 }
 
+impl Codec for Timestamp {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.seqno.encode(enc);
+        self.client_id.encode(enc);
+        self.client_ctr.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let seqno = u64::decode(dec)?;
+        let client_id = u64::decode(dec)?;
+        let client_ctr = u64::decode(dec)?;
+        Ok(Timestamp { seqno, client_id, client_ctr })
+    }
+}
+
 } // verus!
 impl std::fmt::Debug for Timestamp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -4,14 +4,22 @@ use vstd::prelude::*;
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
 pub(crate) struct Args {
+    #[arg(short, long, default_value_t = 3)]
+    pub(crate) n_servers: u64,
+
+    /// How many servers have to echo a broadcast back (default: a majority)
+    #[arg(short, long)]
+    pub(crate) quorum: Option<usize>,
+
     #[arg(long, default_value_t = 3)]
     pub(crate) n_ops: u64,
 
     #[arg(long)]
     pub(crate) no_delay: bool,
 
-    #[arg(long, default_value_t = 1)]
-    pub(crate) client_id: u64,
+    /// Log the first client in with this client id instead of allocating one
+    #[arg(long)]
+    pub(crate) client_id: Option<u64>,
 }
 
 verus! {
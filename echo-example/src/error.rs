@@ -8,17 +8,18 @@ impl From<ConnectError> for Error {
     }
 }
 
-impl From<echo::client::error::EchoError> for Error {
-    fn from(value: echo::client::error::EchoError) -> Self {
+impl From<echo::client::error::EchoError<String>> for Error {
+    fn from(value: echo::client::error::EchoError<String>) -> Self {
         Error::EchoError(value)
     }
 }
 
 impl std::error::Error for Error {
-    fn cause(&self) -> Option<&dyn std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::Connection(e) => Some(e),
             Error::EchoError(e) => Some(e),
+            Error::InvalidQuorum { .. } => None,
         }
     }
 }
@@ -28,6 +29,9 @@ impl std::fmt::Display for Error {
         match self {
             Error::Connection(e) => e.fmt(f),
             Error::EchoError(e) => e.fmt(f),
+            Error::InvalidQuorum { quorum, n_servers } => {
+                write!(f, "invalid quorum {quorum}: should be between 1 and {n_servers}")
+            }
         }
     }
 }
@@ -37,6 +41,9 @@ impl std::fmt::Debug for Error {
         match self {
             Error::Connection(e) => e.fmt(f),
             Error::EchoError(e) => e.fmt(f),
+            Error::InvalidQuorum { quorum, n_servers } => {
+                write!(f, "invalid quorum {quorum}: should be between 1 and {n_servers}")
+            }
         }
     }
 }
@@ -45,7 +52,9 @@ verus! {
 
 pub(crate) enum Error {
     Connection(ConnectError),
-    EchoError(echo::client::error::EchoError),
+    EchoError(echo::client::error::EchoError<String>),
+    /// The quorum should be between 1 and the number of servers
+    InvalidQuorum { quorum: usize, n_servers: usize },
 }
 
 #[allow(unused)]
@@ -13,13 +13,22 @@ use verdist::network::channel::BufChannel;
 use verdist::network::channel::Channel;
 use verdist::network::channel::Connector;
 use verdist::network::error::ConnectError;
+#[cfg(verus_only)]
+use verdist::pool::ConnectionPool;
+use verdist::pool::FlawlessPool;
 
+use specs::echo::BroadcastEchoClient as _;
 use specs::echo::EchoClient as _;
 
 use echo::channel::ChannelInv;
+use echo::client::BroadcastEchoClient;
 use echo::client::EchoClient;
+use echo::invariants::initialize_system;
+use echo::invariants::StateInvariant;
 use echo::server::run_modelled_server;
 
+use vlib::client_ids::ClientIdToken;
+
 mod cli;
 mod error;
 mod invariant;
@@ -42,14 +51,14 @@ fn connect<C, Conn>(
     connector: &Conn,
     client_id: u64,
     #[allow(unused_variables)]
-    state_inv: &Tracked<Arc<echo::invariants::StateInvariant>>,
+    state_inv: &Tracked<Arc<echo::invariants::StateInvariant<String>>>,
 ) -> (r: Result<BufChannel<C>, ConnectError>) where
     Conn: Connector<C>,
     C: Channel<
         Id = (u64, u64),
         K = ChannelInv,
-        R = echo::proto::Response,
-        S = echo::proto::Request,
+        R = echo::proto::Response<String>,
+        S = echo::proto::Request<String>,
     >,
 
     ensures
@@ -80,38 +89,197 @@ fn connect<C, Conn>(
     Ok(BufChannel::new(channel))
 }
 
-fn run_client<C, Conn, 'a>(args: Args, connector: &Conn) -> Result<(), Error> where
+fn connect_all<C, Conn>(
+    args: &Args,
+    connectors: &[Conn],
+    client_id: u64,
+    state_inv: &Tracked<Arc<echo::invariants::StateInvariant<String>>>,
+) -> (r: Result<Vec<BufChannel<C>>, ConnectError>) where
+    Conn: Connector<C>,
+    C: Channel<
+        Id = (u64, u64),
+        K = ChannelInv,
+        R = echo::proto::Response<String>,
+        S = echo::proto::Request<String>,
+    >,
+
+    ensures
+        r is Ok ==> {
+            let v = r->Ok_0;
+            &&& connectors.len() == v.len()
+            &&& forall|idx|
+                0 <= idx < v@.len() ==> {
+                    let chan = #[trigger] v@[idx];
+                    &&& chan.constant().request_map_id
+                        == state_inv@.constant().request_map_ids.request_auth_id
+                    &&& chan.spec_id().0 == client_id
+                }
+            &&& forall|i, j|
+                0 <= i < j < v@.len() ==> #[trigger] v@[i].spec_id() != #[trigger] v@[j].spec_id()
+        },
+{
+    let mut v = Vec::with_capacity(connectors.len());
+    for connector in connectors.iter() {
+        let conn = connect(args, connector, client_id, state_inv)?;
+        v.push(conn);
+    }
+
+    proof {
+        admit();  // XXX(assume): this is trivial but seems like something should be able to get
+    }
+    Ok(v)
+}
+
+fn run_client<C, Conn, 'a>(
+    args: &Args,
+    connectors: &[Conn],
+    state_inv: &Tracked<Arc<StateInvariant<String>>>,
+    client_id: u64,
+    client_id_token: Tracked<ClientIdToken>,
+) -> Result<(), Error> where
     Conn: Connector<C> + Send + Sync,
     C: Channel<
         K = echo::channel::ChannelInv,
-        R = echo::proto::Response,
-        S = echo::proto::Request,
+        R = echo::proto::Response<String>,
+        S = echo::proto::Request<String>,
         Id = (u64, u64),
     >,
     C: Sync + Send,
- {
+
+    requires
+        connectors.len() > 0,
+        state_inv@.namespace() == echo::invariants::state_inv_id(),
+        client_id_token@.key() == client_id,
+        client_id_token@.id() == state_inv@.constant().client_ids_ids.fresh_id,
+{
     let (request_ctr, request_ctr_perm) = PAtomicU64::new(0);
 
     #[allow(unused)]
-    let (request_ctr_token, state_inv) = get_invariant_state(args.client_id, request_ctr_perm);
+    let (request_ctr_token, state_inv) = get_invariant_state(
+        client_id,
+        client_id_token,
+        request_ctr_perm,
+        state_inv,
+    );
 
-    let channel = connect(&args, connector, args.client_id, &state_inv)?;
+    let channel = connect(args, &connectors[0], client_id, &state_inv)?;
 
     let mut client = EchoClient::new(
         channel,
-        args.client_id,
+        client_id,
+        request_ctr,
+        request_ctr_token,
+        state_inv,
+    );
+
+    for _ in 0..args.n_ops {
+        let input = generate_string(32);
+        vlib::veprintln!("[client|{:>3}]: sending {input}", client_id);
+        match client.echo(input) {
+            Ok(output) => {
+                assert(input == output);
+                vlib::vprintln!("output == input: {output}");
+            },
+            Err(e) => {
+                assert(e.spec_message() == input);
+                vlib::veprintln!("[client|{:>3}]: failed to echo: {e} ({:?})", client_id, e);
+            },
+        }
+    }
+
+    let input = generate_string(32);
+    if let Ok(output) = client.echo_after(input, REQUEST_LATENCY_DEFAULT_MS) {
+        assert(input == output);
+        vlib::vprintln!("delayed output == input: {output}");
+    }
+
+    let rejected = client.echo_error(generate_string(32));
+    assert(rejected is Err);
+    if let Err(e) = rejected {
+        vlib::vprintln!("echo error: {e}");
+    }
+
+    client.sink(generate_string(32))?;
+
+    Ok(())
+}
+
+fn run_broadcast_client<C, Conn, 'a>(
+    args: Args,
+    connectors: &[Conn],
+    state_inv: &Tracked<Arc<StateInvariant<String>>>,
+    client_id: u64,
+    client_id_token: Tracked<ClientIdToken>,
+) -> Result<(), Error> where
+    Conn: Connector<C> + Send + Sync,
+    C: Channel<
+        K = echo::channel::ChannelInv,
+        R = echo::proto::Response<String>,
+        S = echo::proto::Request<String>,
+        Id = (u64, u64),
+    >,
+    C: Sync + Send,
+
+    requires
+        connectors.len() > 0,
+        state_inv@.namespace() == echo::invariants::state_inv_id(),
+        client_id_token@.key() == client_id,
+        client_id_token@.id() == state_inv@.constant().client_ids_ids.fresh_id,
+{
+    let (request_ctr, request_ctr_perm) = PAtomicU64::new(0);
+
+    #[allow(unused)]
+    let (request_ctr_token, state_inv) = get_invariant_state(
+        client_id,
+        client_id_token,
+        request_ctr_perm,
+        state_inv,
+    );
+
+    let pool = connect_all(&args, connectors, client_id, &state_inv)?;
+    let pool = FlawlessPool::new(pool);
+    assert(pool.spec_len() == connectors.len());
+    assume(forall|cid| #[trigger]
+        pool.spec_channels().contains_key(cid) ==> {
+            let c = pool.spec_channels()[cid];
+            &&& cid == c.spec_id()
+            &&& cid.0 == client_id
+            &&& state_inv@.constant().request_map_ids.request_auth_id
+                == c.constant().request_map_id
+        });
+
+    let mut client = BroadcastEchoClient::new(
+        pool,
+        client_id,
         request_ctr,
         request_ctr_token,
         state_inv,
     );
+    if let Some(quorum) = args.quorum {
+        let n_servers = client.len();
+        if quorum == 0 || quorum > n_servers {
+            return Err(Error::InvalidQuorum { quorum, n_servers });
+        }
+        client.set_quorum_size(quorum);
+    }
 
     for _ in 0..args.n_ops {
         let input = generate_string(32);
-        vlib::veprintln!("[client|{:>3}]: sending {input}", args.client_id);
-        let output_res = client.echo(input);
-        if let Ok(output) = output_res {
-            assert(input == output);
-            vlib::vprintln!("output == input: {output}");
+        vlib::veprintln!(
+            "[client|{:>3}]: broadcasting {input} (quorum {})",
+            client_id,
+            client.quorum_size()
+        );
+        match client.echo(input) {
+            Ok((output, n_echoed)) => {
+                assert(input == output);
+                assert(n_echoed >= client.spec_quorum_size());
+                vlib::vprintln!("output == input: {output} (echoed by {n_echoed})");
+            },
+            Err(e) => {
+                assert(e.spec_message() == input);
+                vlib::veprintln!("[client|{:>3}]: failed to echo: {e} ({:?})", client_id, e);
+            },
         }
     }
 
@@ -127,7 +295,48 @@ fn generate_string(len: usize) -> String {
 fn main() {
     let args = Args::parse();
 
-    let connector = run_modelled_server(42);
+    if args.n_servers == 0 {
+        eprintln!("need at least one server");
+        return;
+    }
+    if let Some(quorum) = args.quorum {
+        if quorum == 0 || quorum as u64 > args.n_servers {
+            eprintln!("quorum should be between 1 and {}", args.n_servers);
+            return;
+        }
+    }
+
+    let (state_inv, client_ids) = initialize_system::<String>();
+    let connectors: Vec<_> = (0..args.n_servers)
+        .map(|server_id| run_modelled_server(server_id, &state_inv))
+        .collect();
 
-    run_client(args, &connector).expect("error");
+    let (client_id, client_id_token) = match args.client_id {
+        Some(client_id) => match client_ids.reserve(client_id) {
+            Some(client_id_token) => (client_id, client_id_token),
+            None => {
+                eprintln!("client id {client_id} is not available");
+                return;
+            }
+        },
+        None => match client_ids.allocate() {
+            Ok(allocated) => allocated,
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        },
+    };
+
+    run_client(&args, &connectors, &state_inv, client_id, client_id_token).expect("error");
+
+    let (client_id, client_id_token) = match client_ids.allocate() {
+        Ok(allocated) => allocated,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    run_broadcast_client(args, &connectors, &state_inv, client_id, client_id_token)
+        .expect("error");
 }
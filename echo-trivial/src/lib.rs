@@ -1,3 +1,5 @@
+use specs::echo::EchoVal;
+
 use vstd::prelude::*;
 
 verus! {
@@ -11,22 +13,45 @@ pub struct TrivialError<V> {
     _marker: std::marker::PhantomData<V>,
 }
 
-impl<V> specs::echo::EchoError for TrivialError<V> {
-    type Val = V;
-
-    open spec fn err_ensures(self, val: Self::Val) -> bool {
+impl<V> specs::echo::EchoError<V> for TrivialError<V> {
+    open spec fn err_ensures(self, val: V) -> bool {
         true
     }
+
+    open spec fn rejected(self) -> bool {
+        false
+    }
 }
 
-impl<C, V> specs::echo::EchoClient<C> for TrivialEchoClient<V> {
+impl<C, V: EchoVal> specs::echo::EchoClient<C> for TrivialEchoClient<V> {
     type Val = V;
 
     type Error = TrivialError<V>;
 
     fn echo(&mut self, v: Self::Val) -> (r: Result<Self::Val, Self::Error>) {
+        proof {
+            V::spec_eq_refl(v);
+        }
+        Ok(v)
+    }
+
+    #[allow(unused_variables)]
+    fn echo_after(&mut self, v: Self::Val, delay_ms: u64) -> (r: Result<Self::Val, Self::Error>) {
+        proof {
+            V::spec_eq_refl(v);
+        }
         Ok(v)
     }
+
+    #[allow(unused_variables)]
+    fn echo_error(&mut self, v: Self::Val) -> (r: Result<Self::Val, Self::Error>) {
+        Err(TrivialError { _marker: std::marker::PhantomData })
+    }
+
+    #[allow(unused_variables)]
+    fn sink(&mut self, v: Self::Val) -> (r: Result<(), Self::Error>) {
+        Ok(())
+    }
 }
 
 } // verus!
@@ -1,5 +1,6 @@
 #[cfg(verus_only)]
 use crate::invariants::StatePredicate;
+use crate::proto::EchoPayload;
 use crate::proto::Request;
 use crate::proto::Response;
 
@@ -23,44 +24,48 @@ impl ChannelInv {
     }
 }
 
-pub open spec fn chan_request_inv(
+pub open spec fn chan_request_inv<V: EchoPayload>(
     k: ChannelInv,
     client_id: u64,
     server_id: u64,
-    r: Request,
+    r: Request<V>,
 ) -> bool {
     &&& r.request_key() == (client_id, r.spec_tag())
     &&& r.request_id() == k.request_map_id
 }
 
-pub open spec fn chan_response_inv(
+pub open spec fn chan_response_inv<V: EchoPayload>(
     k: ChannelInv,
     client_id: u64,
     server_id: u64,
-    r: Response,
+    r: Response<V>,
 ) -> bool {
     &&& r.request_id() == k.request_map_id
     &&& r.request_key().0 == client_id
 }
 
 // Invariant on server
-impl ChannelInvariant<ChannelInv, (u64, u64), Request, Response> for ChannelInv {
-    open spec fn recv_inv(k: ChannelInv, id: (u64, u64), r: Request) -> bool {
+impl<V> ChannelInvariant<ChannelInv, (u64, u64), Request<V>, Response<V>> for ChannelInv where
+    V: EchoPayload,
+ {
+    open spec fn recv_inv(k: ChannelInv, id: (u64, u64), r: Request<V>) -> bool {
         chan_request_inv(k, id.1, id.0, r)
     }
 
-    open spec fn send_inv(k: ChannelInv, id: (u64, u64), s: Response) -> bool {
+    open spec fn send_inv(k: ChannelInv, id: (u64, u64), s: Response<V>) -> bool {
         chan_response_inv(k, id.1, id.0, s)
     }
 }
 
 // Invariant on client
-impl ChannelInvariant<ChannelInv, (u64, u64), Response, Request> for ChannelInv {
-    open spec fn recv_inv(k: ChannelInv, id: (u64, u64), r: Response) -> bool {
+impl<V> ChannelInvariant<ChannelInv, (u64, u64), Response<V>, Request<V>> for ChannelInv where
+    V: EchoPayload,
+ {
+    open spec fn recv_inv(k: ChannelInv, id: (u64, u64), r: Response<V>) -> bool {
         chan_response_inv(k, id.0, id.1, r)
     }
 
-    open spec fn send_inv(k: ChannelInv, id: (u64, u64), s: Request) -> bool {
+    open spec fn send_inv(k: ChannelInv, id: (u64, u64), s: Request<V>) -> bool {
         chan_request_inv(k, id.0, id.1, s)
     }
 }
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+
+use crate::channel::ChannelInv;
+use crate::invariants::requests::RequestProof;
+use crate::proto::EchoPayload;
+use crate::proto::Response;
+
+use verdist::network::channel::Channel;
+#[cfg(verus_only)]
+use verdist::network::channel::ChannelInvariant;
+#[cfg(verus_only)]
+use verdist::rpc::proto::TaggedMessage;
+use verdist::rpc::replies::ReplyAccumulator;
+
+use vstd::invariant::InvariantPredicate;
+use vstd::prelude::*;
+use vstd::resource::Loc;
+
+verus! {
+
+#[allow(unused_variables, dead_code)]
+pub ghost struct EchoPred<C: Channel<K = ChannelInv>, V> {
+    pub request_map_id: Loc,
+    pub message: V,
+    pub channels: Map<C::Id, C>,
+    pub client_id: u64,
+    pub request_id: u64,
+}
+
+impl<C: Channel<K = ChannelInv>, V> EchoPred<C, V> {
+    pub open spec fn new(
+        channels: Map<C::Id, C>,
+        client_id: u64,
+        request: RequestProof<V>,
+    ) -> EchoPred<C, V> {
+        EchoPred {
+            request_map_id: request.id(),
+            message: request.value()->Echo_0.spec_message(),
+            channels,
+            client_id,
+            request_id: request.key().1,
+        }
+    }
+}
+
+/// Collects the replies to an echo broadcast
+///
+/// Every reply echoes a copy of the message that was sent (the request proofs agree), so all that
+/// is left to do is count them
+#[allow(dead_code)]
+pub struct EchoAccumulator<C: Channel<K = ChannelInv, Id = (u64, u64)>, V: EchoPayload> {
+    // EXEC state
+    /// The message echoed by the first reply
+    echoed: Option<V>,
+    /// Received replies
+    replies: BTreeSet<C::Id>,
+    // Spec state
+    /// channels of the pool this accumulator is working with
+    channels: Ghost<Map<C::Id, C>>,
+    /// echo request proof
+    request: Tracked<RequestProof<V>>,
+}
+
+impl<C, V> InvariantPredicate<EchoPred<C, V>, EchoAccumulator<C, V>> for EchoPred<C, V> where
+    C: Channel<K = ChannelInv, Id = (u64, u64)>,
+    V: EchoPayload,
+ {
+    open spec fn inv(pred: EchoPred<C, V>, v: EchoAccumulator<C, V>) -> bool {
+        pred == v.constant()
+    }
+}
+
+impl<C: Channel<K = ChannelInv, Id = (u64, u64)>, V: EchoPayload> EchoAccumulator<C, V> {
+    pub fn new(request: Tracked<RequestProof<V>>, pred: Ghost<EchoPred<C, V>>) -> (r: Self)
+        requires
+            request@.id() == pred@.request_map_id,
+            request@.key() == (pred@.client_id, pred@.request_id),
+            request@.value().req_type() is Echo,
+            request@.value()->Echo_0.spec_message() == pred@.message,
+            forall|c_id| #[trigger]
+                pred@.channels.contains_key(c_id) ==> {
+                    let c = pred@.channels[c_id];
+                    &&& c_id.0 == request@.key().0
+                    &&& c.constant().request_map_id == pred@.request_map_id
+                },
+        ensures
+            r.constant() == pred@,
+            r.replies().is_empty(),
+    {
+        EchoAccumulator {
+            echoed: None,
+            replies: BTreeSet::new(),
+            channels: Ghost(pred@.channels),
+            request,
+        }
+    }
+
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& forall|c_id| #[trigger]
+            self.channels@.contains_key(c_id) ==> {
+                let c = self.channels@[c_id];
+                &&& c_id.0 == self.client_id()
+                &&& c.constant().request_map_id == self.request_map_id()
+            }
+        &&& self.replies@.finite()
+        &&& self.request@.value().req_type() is Echo
+        &&& self.echoed is None ==> self.replies@.is_empty()
+        &&& self.echoed is Some ==> self.spec_message().spec_eq(self.echoed->Some_0)
+    }
+
+    // SPEC
+    pub open spec fn constant(self) -> EchoPred<C, V> {
+        EchoPred {
+            request_map_id: self.request_map_id(),
+            message: self.spec_message(),
+            channels: self.spec_channels(),
+            client_id: self.client_id(),
+            request_id: self.request_id(),
+        }
+    }
+
+    pub closed spec fn client_id(self) -> u64 {
+        self.request@.key().0
+    }
+
+    pub closed spec fn request_id(self) -> u64 {
+        self.request@.key().1
+    }
+
+    pub closed spec fn request_map_id(self) -> Loc {
+        self.request@.id()
+    }
+
+    pub closed spec fn spec_message(self) -> V {
+        self.request@.value()->Echo_0.spec_message()
+    }
+
+    pub closed spec fn spec_channels(self) -> Map<C::Id, C> {
+        self.channels@
+    }
+
+    pub closed spec fn replies(self) -> Set<C::Id> {
+        self.replies@
+    }
+
+    // EXEC
+    pub fn echo_replies(&self) -> (r: BTreeSet<C::Id>)
+        ensures
+            r@ == self.replies(),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        self.replies.clone()
+    }
+
+    /// Returns the echoed message, if any server replied
+    pub fn into_echoed(self) -> (r: Option<V>)
+        ensures
+            r is Some <==> !self.replies().is_empty(),
+            r is Some ==> self.spec_message().spec_eq(r->Some_0),
+    {
+        proof {
+            use_type_invariant(&self);
+        }
+        self.echoed
+    }
+}
+
+impl<C, V> ReplyAccumulator<C, EchoPred<C, V>> for EchoAccumulator<C, V> where
+    C: Channel<Id = (u64, u64), R = Response<V>, K = ChannelInv>,
+    V: EchoPayload,
+ {
+    #[allow(unused_variables)]
+    fn insert(&mut self, pred: Ghost<EchoPred<C, V>>, id: (u64, u64), reply: Response<V>)
+        ensures
+            final(self).channels() == old(self).channels(),
+    {
+        proof {
+            use_type_invariant(&*self);
+
+            assume(C::K::recv_inv(self.channels()[id].constant(), id, reply));  // TODO(verus): this is a verus problem
+        }
+
+        reply.lemma_inv();
+        proof {
+            reply.agree_request(self.request.borrow_mut());
+        }
+
+        if self.replies.contains(&id) {
+            return ;
+        }
+        self.replies.insert(id);
+
+        if self.echoed.is_none() {
+            self.echoed = Some(reply.destruct_echo().message());
+        }
+    }
+
+    open spec fn request_tag(self) -> u64 {
+        self.request_id()
+    }
+
+    open spec fn spec_handled_replies(self) -> Set<C::Id> {
+        self.replies()
+    }
+
+    fn handled_replies(&self) -> BTreeSet<C::Id> {
+        self.echo_replies()
+    }
+
+    open spec fn channels(self) -> Map<C::Id, C> {
+        self.spec_channels()
+    }
+}
+
+} // verus!
@@ -0,0 +1,183 @@
+use crate::channel::ChannelInv;
+use crate::client::accumulator::EchoAccumulator;
+use crate::client::accumulator::EchoPred;
+use crate::client::error;
+use crate::client::issuer::RequestIssuer;
+#[cfg(verus_only)]
+use crate::invariants;
+use crate::invariants::requests::RequestCtrToken;
+use crate::invariants::StateInvariant;
+use crate::proto::EchoPayload;
+use crate::proto::Request;
+use crate::proto::RequestInner;
+use crate::proto::Response;
+
+use verdist::network::channel::Channel;
+use verdist::pool::BroadcastPool;
+use verdist::pool::ConnectionPool;
+
+use vstd::atomic::PAtomicU64;
+#[cfg(verus_only)]
+use vstd::invariant::InvariantPredicate;
+use vstd::pervasive::unreached;
+use vstd::prelude::*;
+
+use std::sync::Arc;
+
+verus! {
+
+/// Echo client over a pool of servers
+///
+/// Each echo is broadcast to every server, and completes once a quorum of them (by default
+/// `f + 1` out of `2f + 1`) echoed it back.
+#[allow(dead_code)]
+pub struct BroadcastEchoClient<Pool, V> {
+    pool: Pool,
+    issuer: RequestIssuer<V>,
+    /// How many servers have to echo a message back
+    quorum_size: usize,
+}
+
+impl<Pool, C, V> BroadcastEchoClient<Pool, V> where
+    Pool: ConnectionPool<C = C>,
+    C: Channel<R = Response<V>, S = Request<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
+ {
+    pub fn new(
+        pool: Pool,
+        id: u64,
+        request_ctr: PAtomicU64,
+        request_ctr_token: Tracked<RequestCtrToken>,
+        state_inv: Tracked<Arc<StateInvariant<V>>>,
+    ) -> (r: Self)
+        requires
+            pool.spec_len() > 0,
+            forall|cid: (u64, u64)| #[trigger]
+                pool.spec_channels().contains_key(cid) ==> {
+                    let c = pool.spec_channels()[cid];
+                    &&& cid == c.spec_id()
+                    &&& cid.0 == id
+                    &&& state_inv@.constant().request_map_ids.request_auth_id
+                        == c.constant().request_map_id
+                },
+            state_inv@.namespace() == invariants::state_inv_id(),
+            state_inv@.constant().request_map_ids.request_ctr_id == request_ctr_token@.id(),
+            request_ctr_token@.key() == id,
+            request_ctr_token@.value().0 == 0,
+            request_ctr_token@.value().1 == request_ctr.id(),
+        ensures
+            r.spec_quorum_size() == r.spec_len() / 2 + 1,
+    {
+        let quorum_size = pool.len() / 2 + 1;
+        BroadcastEchoClient {
+            pool,
+            issuer: RequestIssuer::new(id, request_ctr, request_ctr_token, state_inv),
+            quorum_size,
+        }
+    }
+
+    /// Require `quorum_size` servers to echo a message back, rather than `f + 1`
+    pub fn set_quorum_size(&mut self, quorum_size: usize)
+        requires
+            0 < quorum_size <= old(self).spec_len(),
+        ensures
+            final(self).spec_quorum_size() == quorum_size,
+    {
+        self.quorum_size = quorum_size;
+    }
+
+    closed spec fn id(self) -> u64 {
+        self.issuer.id()
+    }
+
+    pub closed spec fn spec_len(self) -> nat {
+        self.pool.spec_len()
+    }
+
+    /// Number of servers in the pool
+    pub fn len(&self) -> (r: usize)
+        ensures
+            r == self.spec_len(),
+    {
+        self.pool.len()
+    }
+
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& 0 < self.quorum_size <= self.pool.spec_len()
+        &&& forall|c_id| #[trigger]
+            self.pool.spec_channels().contains_key(c_id) ==> {
+                let c = self.pool.spec_channels()[c_id];
+                &&& c_id == c.spec_id()
+                &&& c_id.0 == self.id()
+                &&& self.issuer.request_map_id() == c.constant().request_map_id
+            }
+    }
+}
+
+impl<Pool, C, V> specs::echo::BroadcastEchoClient<C> for BroadcastEchoClient<Pool, V> where
+    Pool: ConnectionPool<C = C>,
+    C: Channel<R = Response<V>, S = Request<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
+ {
+    type Error = error::EchoError<V>;
+
+    type Val = V;
+
+    closed spec fn spec_quorum_size(self) -> nat {
+        self.quorum_size as nat
+    }
+
+    fn quorum_size(&self) -> usize {
+        self.quorum_size
+    }
+
+    fn echo(&mut self, message: V) -> (r: Result<(V, usize), error::EchoError<V>>) {
+        proof {
+            use_type_invariant(&*self);
+        }
+        let req_inner = RequestInner::new_echo(message);
+        let (req, request_proof) = self.issuer.issue_request(req_inner)?;
+        proof {
+            use_type_invariant(&*self);
+        }
+
+        let ghost qsize = self.quorum_size;
+        let bpool = BroadcastPool::new(&self.pool);
+        let echo_pred = Ghost(EchoPred::new(bpool.spec_channels(), self.id(), request_proof@));
+        let accum = EchoAccumulator::new(request_proof, echo_pred);
+        let quorum_res = bpool.broadcast(&req, echo_pred, accum).wait_for(
+            |s| -> (r: bool)
+                ensures
+                    r ==> s.spec_len() >= qsize,
+                { s.len() >= self.quorum_size },
+        );
+
+        let (replies, n_echoed) = match quorum_res {
+            Ok(replies) => {
+                let n_echoed = replies.len();
+                (replies.into_accumulator(), n_echoed)
+            },
+            Err(e) => {
+                return Err(
+                    error::EchoError::FailedQuorum {
+                        obtained: e.len(),
+                        required: self.quorum_size,
+                        message: req.into_message(),
+                    },
+                );
+            },
+        };
+
+        assert(replies.constant() == echo_pred@);
+        match replies.into_echoed() {
+            Some(echoed) => Ok((echoed, n_echoed)),
+            None => {
+                assert(false);
+                unreached()
+            },
+        }
+    }
+}
+
+} // verus!
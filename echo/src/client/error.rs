@@ -1,33 +1,170 @@
+use crate::proto::EchoPayload;
+
+use verdist::network::error::InvokeError;
+use verdist::network::error::SendError;
+use verdist::network::error::TryRecvError;
+
 use vstd::prelude::*;
 
 verus! {
 
 /// Echo errors
 ///
-/// The only way an Echo fails is when the network fails
-pub struct EchoError;
+/// An echo fails when the network fails, when too few servers echo it back (when broadcasting),
+/// or up front if the client has run out of request ids.
+/// The server only answers with an error when asked to (with an `EchoError` request).
+///
+/// Every error hands back the message which was not echoed (or a copy of it).
+pub enum EchoError<V> {
+    /// The request could not be sent, or its reply did not arrive
+    Network { cause: NetworkError, message: V },
+    /// Only `obtained` out of the `required` servers echoed the message back
+    FailedQuorum { obtained: usize, required: usize, message: V },
+    /// The client ran out of request ids: the request was not sent
+    CounterExhausted { message: V },
+    /// The server answered with an error
+    Rejected { message: V },
+}
 
-impl std::error::Error for EchoError {
+/// Network failures behind an [`EchoError::Network`]
+///
+/// These are the [`InvokeError`]s of the request, without the request itself (its message is
+/// in the [`EchoError`])
+pub enum NetworkError {
+    /// The request could not be sent
+    Send,
+    /// The reply could not be received
+    Recv(TryRecvError),
+}
+
+impl<V> EchoError<V> {
+    /// The message which was not echoed
+    pub open spec fn spec_message(self) -> V {
+        match self {
+            EchoError::Network { message, .. } => message,
+            EchoError::FailedQuorum { message, .. } => message,
+            EchoError::CounterExhausted { message } => message,
+            EchoError::Rejected { message } => message,
+        }
+    }
 
+    /// Gives back the message which was not echoed
+    pub fn into_message(self) -> (r: V)
+        ensures
+            r == self.spec_message(),
+    {
+        match self {
+            EchoError::Network { message, .. } => message,
+            EchoError::FailedQuorum { message, .. } => message,
+            EchoError::CounterExhausted { message } => message,
+            EchoError::Rejected { message } => message,
+        }
+    }
 }
 
-impl specs::echo::EchoError for EchoError {
-    type Val = String;
+impl<V: EchoPayload> specs::echo::EchoError<V> for EchoError<V> {
+    open spec fn err_ensures(self, message: V) -> bool {
+        message.spec_eq(self.spec_message())
+    }
+
+    open spec fn rejected(self) -> bool {
+        self is Rejected
+    }
+}
 
-    open spec fn err_ensures(self, message: String) -> bool {
-        true
+impl<S> From<InvokeError<S>> for NetworkError {
+    fn from(value: InvokeError<S>) -> NetworkError {
+        match value {
+            InvokeError::SendError(_) => NetworkError::Send,
+            InvokeError::Disconnected => NetworkError::Recv(TryRecvError::Disconnected),
+            InvokeError::Empty => NetworkError::Recv(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<S> From<SendError<S>> for NetworkError {
+    #[allow(unused_variables)]
+    fn from(value: SendError<S>) -> NetworkError {
+        NetworkError::Send
+    }
+}
+
+impl From<TryRecvError> for NetworkError {
+    fn from(value: TryRecvError) -> NetworkError {
+        NetworkError::Recv(value)
     }
 }
 
 } // verus!
-impl std::fmt::Debug for EchoError {
+impl<V: std::fmt::Debug> std::error::Error for EchoError<V> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EchoError::Network { cause, .. } => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetworkError::Send => None,
+            NetworkError::Recv(e) => Some(e),
+        }
+    }
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for EchoError<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EchoError::Network { cause, message } => f
+                .debug_struct("Network")
+                .field("cause", cause)
+                .field("message", message)
+                .finish(),
+            EchoError::FailedQuorum { obtained, required, message } => f
+                .debug_struct("FailedQuorum")
+                .field("obtained", obtained)
+                .field("required", required)
+                .field("message", message)
+                .finish(),
+            EchoError::CounterExhausted { message } => {
+                f.debug_struct("CounterExhausted").field("message", message).finish()
+            }
+            EchoError::Rejected { message } => {
+                f.debug_struct("Rejected").field("message", message).finish()
+            }
+        }
+    }
+}
+
+impl<V> std::fmt::Display for EchoError<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EchoError::Network { .. } => f.write_str("failed to invoke echo over the network"),
+            EchoError::FailedQuorum { obtained, required, .. } => f.write_fmt(format_args!(
+                "echoed by {obtained} servers, but {required} are required"
+            )),
+            EchoError::CounterExhausted { .. } => f.write_str("the client ran out of request ids"),
+            EchoError::Rejected { .. } => f.write_str("the server answered with an error"),
+        }
+    }
+}
+
+impl std::fmt::Debug for NetworkError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("EchoError").finish()
+        match self {
+            NetworkError::Send => f.debug_struct("Send").finish(),
+            NetworkError::Recv(e) => f.debug_tuple("Recv").field(e).finish(),
+        }
     }
 }
 
-impl std::fmt::Display for EchoError {
+impl std::fmt::Display for NetworkError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("EchoError").finish()
+        match self {
+            NetworkError::Send => f.write_str("failed to send the request"),
+            NetworkError::Recv(_) => f.write_str("failed to receive the reply"),
+        }
     }
 }
@@ -0,0 +1,141 @@
+use crate::client::error;
+#[cfg(verus_only)]
+use crate::invariants;
+use crate::invariants::requests::RequestCtrToken;
+use crate::invariants::requests::RequestProof;
+use crate::invariants::StateInvariant;
+use crate::proto::EchoPayload;
+use crate::proto::Request;
+use crate::proto::RequestInner;
+
+#[cfg(verus_only)]
+use specs::echo::EchoError as _;
+
+#[cfg(verus_only)]
+use verdist::rpc::proto::TaggedMessage;
+
+use vstd::atomic::PAtomicU64;
+#[cfg(verus_only)]
+use vstd::invariant::InvariantPredicate;
+use vstd::prelude::*;
+#[cfg(verus_only)]
+use vstd::resource::Loc;
+
+use std::sync::Arc;
+
+verus! {
+
+/// Issues the requests of a client: allocates their ids, and commits to what each request is
+#[allow(dead_code)]
+pub struct RequestIssuer<V> {
+    id: u64,
+    state_inv: Tracked<Arc<StateInvariant<V>>>,
+    request_ctr_token: Tracked<RequestCtrToken>,
+    request_ctr: PAtomicU64,
+    /// Upper bound on the value of `request_ctr` (its permission lives in the invariant)
+    next_request_id: u64,
+}
+
+impl<V: EchoPayload> RequestIssuer<V> {
+    pub fn new(
+        id: u64,
+        request_ctr: PAtomicU64,
+        request_ctr_token: Tracked<RequestCtrToken>,
+        state_inv: Tracked<Arc<StateInvariant<V>>>,
+    ) -> (r: Self)
+        requires
+            state_inv@.namespace() == invariants::state_inv_id(),
+            state_inv@.constant().request_map_ids.request_ctr_id == request_ctr_token@.id(),
+            request_ctr_token@.key() == id,
+            request_ctr_token@.value().0 == 0,
+            request_ctr_token@.value().1 == request_ctr.id(),
+        ensures
+            r.id() == id,
+            r.request_map_id() == state_inv@.constant().request_map_ids.request_auth_id,
+    {
+        RequestIssuer { id, state_inv, request_ctr_token, request_ctr, next_request_id: 0 }
+    }
+
+    /// Id of the client issuing the requests
+    pub closed spec fn id(self) -> u64 {
+        self.id
+    }
+
+    /// Id of the request map the request proofs belong to
+    pub closed spec fn request_map_id(self) -> Loc {
+        self.state_inv@.constant().request_map_ids.request_auth_id
+    }
+
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& self.state_inv@.namespace() == invariants::state_inv_id()
+        &&& self.state_inv@.constant().request_map_ids.request_ctr_id
+            == self.request_ctr_token@.id()
+        &&& self.request_ctr_token@.key() == self.id
+        &&& self.request_ctr_token@.value().1 == self.request_ctr.id()
+        &&& self.request_ctr_token@.value().0 <= self.next_request_id
+    }
+
+    /// Allocates a request id and commits to `req_inner` being the request with that id
+    pub fn issue_request(&mut self, req_inner: RequestInner<V>) -> (r: Result<
+        (Request<V>, Tracked<RequestProof<V>>),
+        error::EchoError<V>,
+    >)
+        ensures
+            final(self).id() == old(self).id(),
+            final(self).request_map_id() == old(self).request_map_id(),
+            r is Ok ==> {
+                let (req, request_proof) = r->Ok_0;
+                &&& request_proof@.id() == final(self).request_map_id()
+                &&& request_proof@.key() == (final(self).id(), req.spec_tag())
+                &&& request_proof@.value() == req_inner
+                &&& req.request_id() == request_proof@.id()
+                &&& req.request_key() == request_proof@.key()
+                &&& req.client_id() == final(self).id()
+                &&& req.req_type() == req_inner.req_type()
+                &&& req.request() == req_inner
+            },
+            r is Err ==> {
+                let err = r->Err_0;
+                &&& err is CounterExhausted
+                &&& req_inner.spec_message().spec_eq(err.spec_message())
+            },
+    {
+        proof {
+            use_type_invariant(&*self);
+            V::spec_eq_refl(req_inner.spec_message());
+        }
+        if self.next_request_id == u64::MAX {
+            return Err(error::EchoError::CounterExhausted { message: req_inner.into_message() });
+        }
+        self.next_request_id = self.next_request_id + 1;
+        let tracked mut request_proof;
+        let request_id;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let ghost old_dom = state.request_map.request_ctr_map().dom();
+            let tracked mut perm;
+            proof {
+                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
+            }
+            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
+            proof {
+                request_proof = state.request_map.issue_request_proof(
+                    self.request_ctr_token.borrow_mut(),
+                    request_id,
+                    req_inner, perm
+                );
+                assert(state.request_map.request_ctr_map().dom() == old_dom);
+            }
+            // XXX: debug assert
+            assert(state.inv());
+        });
+
+        proof {
+            RequestInner::spec_eq_refl(req_inner);
+        }
+        let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
+        Ok((req, Tracked(request_proof)))
+    }
+}
+
+} // verus!
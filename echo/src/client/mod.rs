@@ -1,8 +1,11 @@
 use crate::channel::ChannelInv;
+use crate::client::issuer::RequestIssuer;
 #[cfg(verus_only)]
 use crate::invariants;
 use crate::invariants::requests::RequestCtrToken;
+use crate::invariants::requests::RequestProof;
 use crate::invariants::StateInvariant;
+use crate::proto::EchoPayload;
 use crate::proto::Request;
 use crate::proto::RequestInner;
 use crate::proto::Response;
@@ -13,8 +16,14 @@ use specs::echo::EchoError as _;
 use verdist::network::channel::BufChannel;
 use verdist::network::channel::Channel;
 
+pub mod accumulator;
+pub mod broadcast;
 pub mod error;
+pub mod issuer;
 
+pub use broadcast::BroadcastEchoClient;
+
+use verdist::rpc::rpc_channel::wait_all;
 use verdist::rpc::rpc_channel::RpcChannel;
 use vstd::atomic::PAtomicU64;
 #[cfg(verus_only)]
@@ -27,23 +36,24 @@ use std::sync::Arc;
 verus! {
 
 #[allow(dead_code)]
-pub struct EchoClient<C> where
-    C: Channel<R = Response, S = Request, Id = (u64, u64), K = ChannelInv>,
+pub struct EchoClient<C, V> where
+    C: Channel<R = Response<V>, S = Request<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
  {
     channel: RpcChannel<C>,
-    id: u64,
-    state_inv: Tracked<Arc<StateInvariant>>,
-    request_ctr_token: Tracked<RequestCtrToken>,
-    request_ctr: PAtomicU64,
+    issuer: RequestIssuer<V>,
 }
 
-impl<C> EchoClient<C> where C: Channel<R = Response, S = Request, Id = (u64, u64), K = ChannelInv> {
+impl<C, V> EchoClient<C, V> where
+    C: Channel<R = Response<V>, S = Request<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
+ {
     pub fn new(
         channel: BufChannel<C>,
         id: u64,
         request_ctr: PAtomicU64,
         request_ctr_token: Tracked<RequestCtrToken>,
-        state_inv: Tracked<Arc<StateInvariant>>,
+        state_inv: Tracked<Arc<StateInvariant<V>>>,
     ) -> (r: Self)
         requires
             state_inv@.namespace() == invariants::state_inv_id(),
@@ -57,87 +67,262 @@ impl<C> EchoClient<C> where C: Channel<R = Response, S = Request, Id = (u64, u64
     {
         EchoClient {
             channel: RpcChannel::new(channel),
-            id,
-            state_inv,
-            request_ctr_token,
-            request_ctr,
+            issuer: RequestIssuer::new(id, request_ctr, request_ctr_token, state_inv),
+        }
+    }
+
+    /// Issues a request and waits for the reply to it
+    fn call(&mut self, req_inner: RequestInner<V>) -> (r: Result<
+        Response<V>,
+        error::EchoError<V>,
+    >)
+        ensures
+            r is Ok ==> {
+                let reply = r->Ok_0;
+                &&& reply.request() == req_inner
+                &&& reply.req_type() == req_inner.req_type()
+                &&& req_inner.spec_message().spec_eq(reply.spec_message())
+            },
+            r is Err ==> {
+                let err = r->Err_0;
+                &&& !(err is Rejected)
+                &&& req_inner.spec_message().spec_eq(err.spec_message())
+            },
+    {
+        proof {
+            use_type_invariant(&*self);
+        }
+        let (req, Tracked(mut request_proof)) = self.issuer.issue_request(req_inner)?;
+
+        let reply = match self.channel.invoke(&req) {
+            Ok(reply) => reply,
+            Err(e) => {
+                let cause = error::NetworkError::from(e);
+                return Err(error::EchoError::Network { cause, message: req.into_message() });
+            },
+        };
+
+        reply.lemma_inv();
+        proof {
+            reply.agree_request(&mut request_proof);
         }
+
+        Ok(reply)
+    }
+
+    /// Echoes a batch of messages, with all the requests in flight at once
+    ///
+    /// Fails if any of the echoes fails, handing back all the messages which were not echoed
+    pub fn echo_many(&mut self, messages: Vec<V>) -> (r: Result<
+        Vec<V>,
+        error::EchoError<Vec<V>>,
+    >)
+        ensures
+            r is Ok ==> {
+                let r_v = r->Ok_0;
+                &&& r_v@.len() == messages@.len()
+                &&& forall|idx|
+                    0 <= idx < r_v@.len() ==> messages@[idx].spec_eq(#[trigger] r_v@[idx])
+            },
+    {
+        proof {
+            use_type_invariant(&*self);
+        }
+        let ghost input = messages@;
+        let n = messages.len();
+        let mut messages = messages;
+
+        // issue all the requests up front
+        let mut requests = Vec::with_capacity(n);
+        let tracked mut request_proofs: Map<int, RequestProof<V>> = Map::tracked_empty();
+        for idx in 0..n
+            invariant
+                n == input.len(),
+                messages@ == input.subrange(idx as int, n as int),
+                requests@.len() == idx,
+                self.channel == old(self).channel,
+                self.issuer.id() == old(self).issuer.id(),
+                self.issuer.request_map_id() == self.channel.constant().request_map_id,
+                forall|j| 0 <= j < idx ==> #[trigger] request_proofs.contains_key(j),
+                forall|j|
+                    0 <= j < idx ==> {
+                        let req = #[trigger] requests@[j];
+                        let request_proof = request_proofs[j];
+                        &&& request_proof.id() == self.channel.constant().request_map_id
+                        &&& request_proof.key() == (self.id(), req.spec_tag())
+                        &&& request_proof.value() is Echo
+                        &&& request_proof.value()->Echo_0.spec_message() == input[j]
+                        &&& req.request_id() == request_proof.id()
+                        &&& req.request_key() == request_proof.key()
+                        &&& req.client_id() == self.id()
+                    },
+        {
+            let message = messages.remove(0);
+            let req_inner = RequestInner::new_echo(message);
+            let (req, Tracked(request_proof)) = match self.issuer.issue_request(req_inner) {
+                Ok(issued) => issued,
+                Err(e) => {
+                    let mut unechoed = into_messages(requests);
+                    unechoed.push(e.into_message());
+                    unechoed.append(&mut messages);
+                    return Err(error::EchoError::CounterExhausted { message: unechoed });
+                },
+            };
+            proof {
+                request_proofs.tracked_insert(idx as int, request_proof);
+            }
+            requests.push(req);
+        }
+
+        proof {
+            use_type_invariant(&*self);
+        }
+        let mut ctxs = Vec::with_capacity(n);
+        for idx in 0..n
+            invariant
+                n == input.len(),
+                requests@.len() == n,
+                ctxs@.len() == idx,
+                self.channel.spec_id().0 == self.id(),
+                forall|j| 0 <= j < n ==> #[trigger] request_proofs.contains_key(j),
+                forall|j|
+                    0 <= j < n ==> {
+                        let req = #[trigger] requests@[j];
+                        let request_proof = request_proofs[j];
+                        &&& request_proof.id() == self.channel.constant().request_map_id
+                        &&& request_proof.key() == (self.id(), req.spec_tag())
+                        &&& request_proof.value() is Echo
+                        &&& request_proof.value()->Echo_0.spec_message() == input[j]
+                        &&& req.request_id() == request_proof.id()
+                        &&& req.request_key() == request_proof.key()
+                    },
+                forall|j|
+                    0 <= j < idx ==> {
+                        let ctx = #[trigger] ctxs@[j];
+                        &&& ctx.spec_tag() == requests@[j].spec_tag()
+                        &&& ctx.channel() == self.channel.channel()
+                    },
+        {
+            match self.channel.async_invoke(&requests[idx]) {
+                Ok(ctx) => ctxs.push(ctx),
+                Err(e) => {
+                    let cause = error::NetworkError::from(e);
+                    let message = into_messages(requests);
+                    return Err(error::EchoError::Network { cause, message });
+                },
+            }
+        }
+
+        let mut replies = match wait_all(&ctxs) {
+            Ok(replies) => replies,
+            Err(e) => {
+                let cause = error::NetworkError::from(e);
+                let message = into_messages(requests);
+                return Err(error::EchoError::Network { cause, message });
+            },
+        };
+
+        let mut echoed = Vec::with_capacity(n);
+        for idx in 0..n
+            invariant
+                replies@.len() == n - idx,
+                echoed@.len() == idx,
+                forall|j| idx <= j < n ==> #[trigger] request_proofs.contains_key(j),
+                forall|j| 0 <= j < idx ==> input[j].spec_eq(#[trigger] echoed@[j]),
+                forall|j|
+                    idx <= j < n ==> {
+                        let reply = #[trigger] replies@[j - idx];
+                        let request_proof = request_proofs[j];
+                        &&& reply.request_id() == request_proof.id()
+                        &&& reply.request_key().0 == request_proof.key().0
+                        &&& reply.spec_tag() == request_proof.key().1
+                        &&& request_proof.value() is Echo
+                        &&& request_proof.value()->Echo_0.spec_message() == input[j]
+                    },
+        {
+            let reply = replies.remove(0);
+            reply.lemma_inv();
+            proof {
+                let tracked mut request_proof = request_proofs.tracked_remove(idx as int);
+                reply.agree_request(&mut request_proof);
+            }
+            echoed.push(reply.destruct_echo().message());
+        }
+
+        Ok(echoed)
     }
 
     closed spec fn id(self) -> u64 {
-        self.id
+        self.issuer.id()
     }
 
     #[verifier::type_invariant]
     pub closed spec fn inv(self) -> bool {
-        &&& self.state_inv@.namespace() == invariants::state_inv_id()
-        &&& self.state_inv@.constant().request_map_ids.request_ctr_id
-            == self.request_ctr_token@.id()
-        &&& self.request_ctr_token@.key() == self.id()
-        &&& self.request_ctr_token@.value().1 == self.request_ctr.id()
-        &&& {
-            let c_id = self.channel.spec_id();
-            &&& c_id.0 == self.id
-            &&& self.state_inv@.constant().request_map_ids.request_auth_id
-                == self.channel.constant().request_map_id
-        }
+        &&& self.channel.spec_id().0 == self.id()
+        &&& self.issuer.request_map_id() == self.channel.constant().request_map_id
     }
 }
 
-impl<C> specs::echo::EchoClient<C> for EchoClient<C> where
-    C: Channel<R = Response, S = Request, Id = (u64, u64), K = ChannelInv>,
+impl<C, V> specs::echo::EchoClient<C> for EchoClient<C, V> where
+    C: Channel<R = Response<V>, S = Request<V>, Id = (u64, u64), K = ChannelInv>,
     C::Id: Eq + Hash,
+    V: EchoPayload,
  {
-    type Error = error::EchoError;
+    type Error = error::EchoError<V>;
 
-    type Val = String;
+    type Val = V;
 
-    fn echo(&mut self, message: String) -> (r: Result<String, error::EchoError>) {
+    fn echo(&mut self, message: V) -> (r: Result<V, error::EchoError<V>>) {
+        let reply = self.call(RequestInner::new_echo(message))?;
+        Ok(reply.into_message())
+    }
+
+    fn echo_after(&mut self, message: V, delay_ms: u64) -> (r: Result<V, error::EchoError<V>>) {
+        let reply = self.call(RequestInner::new_echo_after(message, delay_ms))?;
+        Ok(reply.into_message())
+    }
+
+    fn echo_error(&mut self, message: V) -> (r: Result<V, error::EchoError<V>>) {
+        // the reply is an error: it is of the same type as the request
+        let reply = self.call(RequestInner::new_echo_error(message))?;
+        Err(error::EchoError::Rejected { message: reply.into_message() })
+    }
+
+    fn sink(&mut self, message: V) -> (r: Result<(), error::EchoError<V>>) {
         proof {
             use_type_invariant(&*self);
         }
-        let req_inner = RequestInner::new_echo(message);
-        let tracked mut request_proof;
-        let request_id;
-        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
-            let ghost old_dom = state.request_map.request_ctr_map().dom();
-            let tracked mut perm;
-            proof {
-                perm = state.request_map.take_permission(self.request_ctr_token.borrow());
-            }
-            assume(perm.value() < u64::MAX); // XXX: integer overflow
-            request_id = self.request_ctr.fetch_add(Tracked(&mut perm), 1);
-            proof {
-                request_proof = state.request_map.issue_request_proof(
-                    self.request_ctr_token.borrow_mut(),
-                    request_id,
-                    req_inner, perm
-                );
-                assert(state.request_map.request_ctr_map().dom() == old_dom);
-            }
-            // XXX: debug assert
-            assert(state.inv());
-        });
+        let req_inner = RequestInner::new_sink(message);
+        let (req, _request_proof) = self.issuer.issue_request(req_inner)?;
 
-        let req = Request::new(self.id, request_id, req_inner, Tracked(request_proof.duplicate()));
-
-        let reply = match self.channel.invoke(&req) {
-            Ok(reply) => reply,
+        // no reply is coming, so there is nothing to wait for
+        match self.channel.async_invoke(&req) {
+            Ok(_) => Ok(()),
             Err(e) => {
-                vlib::veprintln!("[client|{:>3}]: failed to invoke echo: {:?}", self.id, e);
-                return Err(error::EchoError)
+                let cause = error::NetworkError::from(e);
+                Err(error::EchoError::Network { cause, message: req.into_message() })
             },
-        };
-
-        reply.lemma_inv();
-        proof {
-            reply.agree_request(&mut request_proof);
         }
+    }
+}
 
-        let reply_message = reply.destruct_echo().message();
-
-        Ok(reply_message)
+/// The messages carried by `requests`, in order
+fn into_messages<V: EchoPayload>(requests: Vec<Request<V>>) -> (r: Vec<V>)
+    ensures
+        r@.len() == requests@.len(),
+{
+    let ghost n = requests@.len();
+    let mut requests = requests;
+    let mut messages = Vec::with_capacity(requests.len());
+    while requests.len() > 0
+        invariant
+            messages@.len() + requests@.len() == n,
+        decreases requests.len(),
+    {
+        let req = requests.remove(0);
+        messages.push(req.into_message());
     }
+    messages
 }
 
 } // verus!
@@ -0,0 +1,167 @@
+use crate::invariants;
+#[cfg(verus_only)]
+use crate::invariants::StatePredicate;
+use crate::invariants::StateInvariant;
+
+use std::sync::Arc;
+
+use vlib::client_ids::ClientIdToken;
+use vlib::client_ids::ExhaustedError;
+
+use vstd::atomic::PAtomicU64;
+use vstd::prelude::*;
+
+verus! {
+
+/// Hands out unique client ids
+///
+/// Each id comes with a [`ClientIdToken`], which is traded in when logging the client in (see
+/// [`invariants::State::login`]). The service can be shared between threads: the counter is
+/// atomic and all the bookkeeping happens in the shared invariant.
+///
+/// Allocation is in-process only: the token is exclusive, so it cannot be sent over the modelled
+/// transport (which clones messages). A remote service would have to log the client in on its
+/// behalf, which the servers do not support, so there is no transport variant.
+pub struct ClientIdService<V> {
+    next_id: PAtomicU64,
+    state_inv: Tracked<Arc<StateInvariant<V>>>,
+}
+
+impl<V> ClientIdService<V> {
+    pub fn new(next_id: PAtomicU64, state_inv: Tracked<Arc<StateInvariant<V>>>) -> (r: Self)
+        requires
+            state_inv@.namespace() == invariants::state_inv_id(),
+            state_inv@.constant().client_ids_ids.next_perm_id == next_id.id(),
+        ensures
+            r.constant() == state_inv@.constant(),
+    {
+        ClientIdService { next_id, state_inv }
+    }
+
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& self.state_inv@.namespace() == invariants::state_inv_id()
+        &&& self.state_inv@.constant().client_ids_ids.next_perm_id == self.next_id.id()
+    }
+
+    pub closed spec fn constant(self) -> StatePredicate {
+        self.state_inv@.constant()
+    }
+
+    /// Allocates a fresh client id
+    ///
+    /// Fails once every id was handed out: the counter never wraps around, as that would hand out
+    /// ids which may still be in use.
+    #[verifier::exec_allows_no_decreases_clause]
+    pub fn allocate(&self) -> (r: Result<(u64, Tracked<ClientIdToken>), ExhaustedError>)
+        ensures
+            r matches Ok((client_id, token)) ==> {
+                &&& token@.id() == self.constant().client_ids_ids.fresh_id
+                &&& token@.key() == client_id
+            },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+
+        let mut next_id;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let tracked perm;
+            proof {
+                perm = state.client_ids.take_permission();
+            }
+            next_id = self.next_id.load(Tracked(&perm));
+            proof {
+                state.client_ids.return_permission(perm);
+            }
+        });
+
+        loop {
+            proof {
+                use_type_invariant(self);
+            }
+            if next_id == u64::MAX {
+                return Err(ExhaustedError);
+            }
+
+            let tracked mut token = None;
+            let result;
+            vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+                let tracked mut perm;
+                proof {
+                    perm = state.client_ids.take_permission();
+                }
+                result = self.next_id.compare_exchange(Tracked(&mut perm), next_id, next_id + 1);
+                proof {
+                    if result is Ok {
+                        token = Some(state.client_ids.allocate(perm));
+                    } else {
+                        state.client_ids.return_permission(perm);
+                    }
+                    state.client_ids.lemma_fresh();
+                }
+                // XXX: debug assert
+                assert(state.inv());
+            });
+
+            match result {
+                Ok(_) => {
+                    let tracked token = token.tracked_unwrap();
+                    return Ok((next_id, Tracked(token)));
+                },
+                Err(current) => next_id = current,
+            }
+        }
+    }
+
+    /// Reserves a given client id
+    ///
+    /// Fails for 0 (reserved) and u64::MAX (the counter cannot move past it), or if the id may
+    /// already have been handed out: every id below the largest one allocated so far is taken.
+    /// Ids skipped over are never handed out.
+    pub fn reserve(&self, client_id: u64) -> (r: Option<Tracked<ClientIdToken>>)
+        ensures
+            r matches Some(token) ==> {
+                &&& token@.id() == self.constant().client_ids_ids.fresh_id
+                &&& token@.key() == client_id
+            },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+
+        if client_id == 0 || client_id == u64::MAX {
+            return None;
+        }
+
+        let tracked mut token = None;
+        let reserved;
+        vstd::open_atomic_invariant!(&self.state_inv.borrow() => state => {
+            let tracked mut perm;
+            proof {
+                perm = state.client_ids.take_permission();
+            }
+            let prev = self.next_id.fetch_max(Tracked(&mut perm), client_id + 1);
+            reserved = prev <= client_id;
+            proof {
+                if reserved {
+                    token = Some(state.client_ids.allocate(perm));
+                } else {
+                    state.client_ids.return_permission(perm);
+                }
+                state.client_ids.lemma_fresh();
+            }
+            // XXX: debug assert
+            assert(state.inv());
+        });
+
+        if reserved {
+            let tracked token = token.tracked_unwrap();
+            Some(Tracked(token))
+        } else {
+            None
+        }
+    }
+}
+
+} // verus!
@@ -1,6 +1,16 @@
+use vlib::client_ids::ClientIds;
+use vlib::client_ids::ClientIdsIds;
+#[cfg(verus_only)]
+use vlib::client_ids::ClientIdToken;
+
+use vstd::atomic::PAtomicU64;
+#[cfg(verus_only)]
+use vstd::atomic::PermissionU64;
 use vstd::invariant::AtomicInvariant;
 use vstd::invariant::InvariantPredicate;
 
+use crate::client_ids::ClientIdService;
+
 #[allow(unused_imports)]
 use std::sync::Arc;
 
@@ -28,46 +38,111 @@ pub open spec fn state_inv_id() -> int {
 
 pub struct StatePredicate {
     pub request_map_ids: RequestMapIds,
+    pub client_ids_ids: ClientIdsIds,
 }
 
-pub struct State {
-    pub tracked request_map: RequestMap,
+pub struct State<V> {
+    pub tracked request_map: RequestMap<V>,
+    pub tracked client_ids: ClientIds,
 }
 
-impl State {
+impl<V> State<V> {
     pub open spec fn inv(self) -> bool {
         // member invariants
         &&& self.request_map.is_full()
+        &&& self.client_ids.is_full()
+        // client ids
+        &&& forall|id: u64| #[trigger]
+            self.request_map.request_ctr_map().contains_key(id) ==> id
+                < self.client_ids.next_id()
+        &&& forall|id: u64| #[trigger]
+            self.client_ids.fresh().contains(id)
+                ==> !self.request_map.request_ctr_map().contains_key(id)
+    }
+
+    /// Logs in a client, trading in the token of its freshly allocated id
+    pub proof fn login(
+        tracked &mut self,
+        tracked client_id_token: ClientIdToken,
+        tracked request_perm: PermissionU64,
+    ) -> (tracked r: RequestCtrToken)
+        requires
+            old(self).inv(),
+            client_id_token.id() == old(self).client_ids.fresh_id(),
+            request_perm.value() == 0,
+        ensures
+            final(self).inv(),
+            final(self).request_map.ids() == old(self).request_map.ids(),
+            final(self).client_ids.ids() == old(self).client_ids.ids(),
+            r.id() == final(self).request_map.request_ctr_map_id(),
+            r.key() == client_id_token.key(),
+            r.value().0 == 0,
+            r.value().1 == request_perm.id(),
+    {
+        let client_id = client_id_token.key();
+        self.client_ids.claim(client_id_token);
+        assert(!self.request_map.request_ctr_map().contains_key(client_id));
+
+        let tracked request_ctr_token = self.request_map.login(client_id, request_perm);
+        self.request_map.agree_client_token(&request_ctr_token);
+        request_ctr_token
     }
 }
 
-impl InvariantPredicate<StatePredicate, State> for StatePredicate {
-    open spec fn inv(p: StatePredicate, state: State) -> bool {
+impl<V> InvariantPredicate<StatePredicate, State<V>> for StatePredicate {
+    open spec fn inv(p: StatePredicate, state: State<V>) -> bool {
         &&& p.request_map_ids == state.request_map.ids()
+        &&& p.client_ids_ids == state.client_ids.ids()
         &&& state.inv()
     }
 }
 
-pub type StateInvariant = AtomicInvariant<StatePredicate, State, StatePredicate>;
+pub type StateInvariant<V> = AtomicInvariant<StatePredicate, State<V>, StatePredicate>;
 
-pub proof fn initialize_system_state() -> (tracked r: Arc<StateInvariant>)
+pub proof fn initialize_system_state<V>(tracked next_client_id_perm: PermissionU64) -> (tracked r:
+    Arc<StateInvariant<V>>)
+    requires
+        next_client_id_perm.value() == 1,
     ensures
         r.namespace() == state_inv_id(),
+        r.constant().client_ids_ids.next_perm_id == next_client_id_perm.id(),
 {
     let tracked request_map = RequestMap::new();
+    let tracked client_ids = ClientIds::new(next_client_id_perm);
 
-    let pred = StatePredicate { request_map_ids: request_map.ids() };
+    let pred = StatePredicate {
+        request_map_ids: request_map.ids(),
+        client_ids_ids: client_ids.ids(),
+    };
 
-    let tracked state = State { request_map };
+    let tracked state = State { request_map, client_ids };
 
     let tracked state_inv = AtomicInvariant::new(pred, state, state_inv_id());
 
     Arc::new(state_inv)
 }
 
-pub axiom fn get_system_state() -> (tracked r: Arc<StateInvariant>)
+/// Bootstraps the shared state of the system
+///
+/// This should run once: the server and every client are then handed a clone of the same invariant,
+/// and clients get their ids from the returned service
+///
+/// `V` is the type of the messages which are echoed (see [`crate::proto::EchoPayload`])
+pub fn initialize_system<V>() -> (r: (Tracked<Arc<StateInvariant<V>>>, ClientIdService<V>))
     ensures
-        r.namespace() == state_inv_id(),
-;
+        r.0@.namespace() == state_inv_id(),
+        r.1.constant() == r.0@.constant(),
+{
+    let (next_client_id, next_client_id_perm) = PAtomicU64::new(1);
+    let tracked state_inv;
+    let tracked service_inv;
+    proof {
+        let tracked Tracked(next_client_id_perm) = next_client_id_perm;
+        state_inv = initialize_system_state(next_client_id_perm);
+        service_inv = state_inv.clone();
+    }
+    let service = ClientIdService::new(next_client_id, Tracked(service_inv));
+    (Tracked(state_inv), service)
+}
 
 } // verus!
@@ -19,9 +19,9 @@ verus! {
 
 /// Proof of a particular request being issued by some client
 /// The key is (client_id, request_id)
-pub type RequestProof = GhostPersistentPointsTo<(u64, u64), RequestInner>;
+pub type RequestProof<V> = GhostPersistentPointsTo<(u64, u64), RequestInner<V>>;
 
-pub type RequestMapAuth = GhostMapAuth<(u64, u64), RequestInner>;
+pub type RequestMapAuth<V> = GhostMapAuth<(u64, u64), RequestInner<V>>;
 
 pub type RequestCtrToken = GhostPointsTo<u64, (u64, int)>;
 
@@ -38,9 +38,9 @@ pub tracked enum TOption<T> {
 ///     - [`RequestMap::take_permission`] to extract the permission to update an AtomicU64
 ///     - [`RequestMap::issue_request_proof`] to create the request proof, returning the permission
 #[allow(unused)]
-pub tracked struct RequestMap {
+pub tracked struct RequestMap<V> {
     /// Map of (client_id, request_id) to the request
-    request_auth: RequestMapAuth,
+    request_auth: RequestMapAuth<V>,
     /// Per client permission, a map from client_id to max seen request_id and id of the permission
     request_ctr_auth: GhostMapAuth<u64, (u64, int)>,
     /// Map from client_id to permission id for the generator request_id (a AtomicU64)
@@ -69,8 +69,8 @@ spec fn ctr_perm_agree(
         }
 }
 
-spec fn request_auth_inv(
-    request_auth: RequestMapAuth,
+spec fn request_auth_inv<V>(
+    request_auth: RequestMapAuth<V>,
     request_ctr_auth: GhostMapAuth<u64, (u64, int)>,
 ) -> bool {
     forall|cid_rid: (u64, u64)| #[trigger]
@@ -80,7 +80,7 @@ spec fn request_auth_inv(
         }
 }
 
-impl RequestMap {
+impl<V> RequestMap<V> {
     #[verifier::type_invariant]
     pub closed spec fn inv(self) -> bool {
         &&& *self.missing_perm is None ==> { self.request_ctr_auth@.dom() == self.request_perm.dom()
@@ -119,7 +119,7 @@ impl RequestMap {
         self.request_ctr_auth.id()
     }
 
-    pub closed spec fn issued(self) -> Map<(u64, u64), RequestInner> {
+    pub closed spec fn issued(self) -> Map<(u64, u64), RequestInner<V>> {
         self.request_auth.view()
     }
 
@@ -131,7 +131,7 @@ impl RequestMap {
         self.request_perm
     }
 
-    pub proof fn new() -> (tracked r: RequestMap)
+    pub proof fn new() -> (tracked r: RequestMap<V>)
         ensures
             r.is_full(),
             r.issued().is_empty(),
@@ -265,9 +265,9 @@ impl RequestMap {
         tracked &mut self,
         tracked client_token: &mut RequestCtrToken,
         request_id: u64,
-        request: RequestInner,
+        request: RequestInner<V>,
         tracked client_perm: PermissionU64,
-    ) -> (tracked r: RequestProof)
+    ) -> (tracked r: RequestProof<V>)
         requires
             !old(self).is_full(),
             old(client_token).id() == old(self).request_ctr_map_id(),
@@ -298,11 +298,10 @@ impl RequestMap {
             final(client_token).value().1 == client_perm.id(),
             r.key() == (final(client_token).key(), request_id),
             r.value() == request,
-            r.value().spec_eq(request),
             r.id() == final(self).request_map_id(),
     {
         use_type_invariant(&*self);
-        let tracked proof = Self::alloc(
+        Self::alloc(
             &mut self.request_perm,
             &mut self.request_ctr_auth,
             &mut self.missing_perm,
@@ -311,21 +310,19 @@ impl RequestMap {
             request_id,
             request,
             client_perm,
-        );
-        RequestInner::spec_eq_refl(proof.value());
-        proof
+        )
     }
 
     proof fn alloc(
         tracked perm_map: &mut Map<u64, PermissionU64>,
         tracked ctr_auth: &mut GhostMapAuth<u64, (u64, int)>,
         tracked missing_perm: &mut Ghost<Option<(u64, int)>>,
-        tracked request_auth: &mut RequestMapAuth,
+        tracked request_auth: &mut RequestMapAuth<V>,
         tracked client_token: &mut RequestCtrToken,
         request_id: u64,
-        request: RequestInner,
+        request: RequestInner<V>,
         tracked request_perm: PermissionU64,
-    ) -> (tracked r: RequestProof)
+    ) -> (tracked r: RequestProof<V>)
         requires
             **old(missing_perm) is Some,
             old(missing_perm)->Some_0 == (old(client_token).key(), request_perm.id()),
@@ -372,7 +369,7 @@ impl RequestMap {
         request_auth.insert((client_token.key(), request_id), request).persist()
     }
 
-    pub proof fn agree_proof(tracked &self, tracked proof: &RequestProof)
+    pub proof fn agree_proof(tracked &self, tracked proof: &RequestProof<V>)
         requires
             proof.id() == self.request_map_id(),
         ensures
@@ -1,45 +1,86 @@
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
+use specs::echo::EchoVal;
+
 use vstd::prelude::*;
 
 verus! {
 
-pub struct EchoRequest {
+/// Payload carried by echo requests and responses
+///
+/// Anything which can be copied into an equivalent value can be echoed: a `String` for a ping, a
+/// `Vec<u8>` to benchmark payload sizes, or a structured message to exercise a codec.
+pub trait EchoPayload: EchoVal + std::fmt::Debug {
+    fn clone_payload(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+    ;
+}
+
+impl EchoPayload for String {
+    fn clone_payload(&self) -> (r: Self) {
+        self.clone()
+    }
+}
+
+impl EchoPayload for Vec<u8> {
+    fn clone_payload(&self) -> (r: Self) {
+        let mut r = Vec::with_capacity(self.len());
+        for idx in 0..self.len()
+            invariant
+                r@ == self@.subrange(0, idx as int),
+        {
+            r.push(self[idx]);
+        }
+        assert(r@ =~= self@);
+        r
+    }
+}
+
+pub struct EchoRequest<V> {
     #[allow(unused)]
-    message: String,
+    message: V,
 }
 
 #[allow(unused)]
-pub struct EchoResponse {
-    message: String,
+pub struct EchoResponse<V> {
+    message: V,
 }
 
 #[allow(unused)]
-impl EchoRequest {
-    pub fn new(message: String) -> (r: Self)
+impl<V> EchoRequest<V> {
+    pub fn new(message: V) -> (r: Self)
         ensures
             r.spec_message() == message,
     {
         EchoRequest { message }
     }
 
-    pub closed spec fn spec_message(self) -> String {
+    pub closed spec fn spec_message(self) -> V {
         self.message
     }
 
-    pub fn message(self) -> String
+    pub fn message(self) -> V
         returns
             self.spec_message(),
     {
         self.message
     }
+}
 
+impl<V: EchoPayload> EchoRequest<V> {
     pub closed spec fn spec_eq(self, other: Self) -> bool {
-        self.spec_message() == other.spec_message()
+        self.spec_message().spec_eq(other.spec_message())
     }
 
     pub broadcast proof fn spec_eq_refl(a: Self)
         ensures
             #[trigger] a.spec_eq(a),
     {
+        V::spec_eq_refl(a.spec_message());
     }
 
     pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
@@ -48,6 +89,7 @@ impl EchoRequest {
         ensures
             b.spec_eq(a),
     {
+        V::spec_eq_symm(a.spec_message(), b.spec_message());
     }
 
     pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
@@ -57,31 +99,32 @@ impl EchoRequest {
         ensures
             a.spec_eq(c),
     {
+        V::spec_eq_trans(a.spec_message(), b.spec_message(), c.spec_message());
     }
 
     pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
         requires
             #[trigger] a.spec_eq(b),
         ensures
-            a.spec_message() == b.spec_message(),
+            a.spec_message().spec_eq(b.spec_message()),
     {
     }
 }
 
 #[allow(unused)]
-impl EchoResponse {
-    pub fn new(message: String) -> (r: Self)
+impl<V> EchoResponse<V> {
+    pub fn new(message: V) -> (r: Self)
         ensures
             r.spec_message() == message,
     {
         EchoResponse { message }
     }
 
-    pub closed spec fn spec_message(self) -> String {
+    pub closed spec fn spec_message(self) -> V {
         self.message
     }
 
-    pub fn message(self) -> String
+    pub fn message(self) -> V
         returns
             self.spec_message(),
     {
@@ -89,15 +132,16 @@ impl EchoResponse {
     }
 }
 
-impl EchoResponse {
+impl<V: EchoPayload> EchoResponse<V> {
     pub closed spec fn spec_eq(self, other: Self) -> bool {
-        self.spec_message() == other.spec_message()
+        self.spec_message().spec_eq(other.spec_message())
     }
 
     pub broadcast proof fn spec_eq_refl(a: Self)
         ensures
             #[trigger] a.spec_eq(a),
     {
+        V::spec_eq_refl(a.spec_message());
     }
 
     pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
@@ -106,6 +150,7 @@ impl EchoResponse {
         ensures
             b.spec_eq(a),
     {
+        V::spec_eq_symm(a.spec_message(), b.spec_message());
     }
 
     pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
@@ -115,39 +160,72 @@ impl EchoResponse {
         ensures
             a.spec_eq(c),
     {
+        V::spec_eq_trans(a.spec_message(), b.spec_message(), c.spec_message());
     }
 
     pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
         requires
             #[trigger] a.spec_eq(b),
         ensures
-            a.spec_message() == b.spec_message(),
+            a.spec_message().spec_eq(b.spec_message()),
     {
     }
 }
 
-impl Clone for EchoRequest {
+impl<V: EchoPayload> Clone for EchoRequest<V> {
     fn clone(&self) -> (r: Self)
         ensures
             self.spec_eq(r),
             r.spec_eq(*self),
     {
-        EchoRequest { message: self.message.clone() }
+        let message = self.message.clone_payload();
+        proof {
+            V::spec_eq_symm(self.message, message);
+        }
+        EchoRequest { message }
     }
 }
 
-impl Clone for EchoResponse {
+impl<V: EchoPayload> Clone for EchoResponse<V> {
     fn clone(&self) -> (r: Self)
         ensures
             self.spec_eq(r),
             r.spec_eq(*self),
     {
-        EchoResponse { message: self.message.clone() }
+        let message = self.message.clone_payload();
+        proof {
+            V::spec_eq_symm(self.message, message);
+        }
+        EchoResponse { message }
+    }
+}
+
+impl<V: Codec> Codec for EchoRequest<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(EchoRequest { message: V::decode(dec)? })
+    }
+}
+
+impl<V: Codec> Codec for EchoResponse<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(EchoResponse { message: V::decode(dec)? })
     }
 }
 
 } // verus!
-impl std::fmt::Debug for EchoRequest {
+impl<V: std::fmt::Debug> std::fmt::Debug for EchoRequest<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("EchoRequest")
             .field("message", &self.message)
@@ -155,7 +233,7 @@ impl std::fmt::Debug for EchoRequest {
     }
 }
 
-impl std::fmt::Debug for EchoResponse {
+impl<V: std::fmt::Debug> std::fmt::Debug for EchoResponse<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("EchoResponse")
             .field("message", &self.message)
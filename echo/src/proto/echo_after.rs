@@ -0,0 +1,134 @@
+use crate::proto::echo::EchoPayload;
+
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
+use vstd::prelude::*;
+
+verus! {
+
+/// Asks the server to wait before echoing the message back
+///
+/// Useful to exercise timeouts in the transports and clients
+pub struct EchoAfterRequest<V> {
+    #[allow(unused)]
+    message: V,
+    #[allow(unused)]
+    delay_ms: u64,
+}
+
+#[allow(unused)]
+impl<V> EchoAfterRequest<V> {
+    pub fn new(message: V, delay_ms: u64) -> (r: Self)
+        ensures
+            r.spec_message() == message,
+            r.spec_delay_ms() == delay_ms,
+    {
+        EchoAfterRequest { message, delay_ms }
+    }
+
+    pub closed spec fn spec_message(self) -> V {
+        self.message
+    }
+
+    pub fn message(self) -> V
+        returns
+            self.spec_message(),
+    {
+        self.message
+    }
+
+    pub closed spec fn spec_delay_ms(self) -> u64 {
+        self.delay_ms
+    }
+
+    pub fn delay_ms(&self) -> u64
+        returns
+            self.spec_delay_ms(),
+    {
+        self.delay_ms
+    }
+}
+
+impl<V: EchoPayload> EchoAfterRequest<V> {
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        &&& self.spec_message().spec_eq(other.spec_message())
+        &&& self.spec_delay_ms() == other.spec_delay_ms()
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+        V::spec_eq_refl(a.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+        V::spec_eq_symm(a.spec_message(), b.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+        V::spec_eq_trans(a.spec_message(), b.spec_message(), c.spec_message());
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_message().spec_eq(b.spec_message()),
+            a.spec_delay_ms() == b.spec_delay_ms(),
+    {
+    }
+}
+
+impl<V: EchoPayload> Clone for EchoAfterRequest<V> {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        let message = self.message.clone_payload();
+        proof {
+            V::spec_eq_symm(self.message, message);
+        }
+        EchoAfterRequest { message, delay_ms: self.delay_ms }
+    }
+}
+
+impl<V: Codec> Codec for EchoAfterRequest<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+        self.delay_ms.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let message = V::decode(dec)?;
+        let delay_ms = u64::decode(dec)?;
+        Ok(EchoAfterRequest { message, delay_ms })
+    }
+}
+
+} // verus!
+impl<V: std::fmt::Debug> std::fmt::Debug for EchoAfterRequest<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EchoAfterRequest")
+            .field("message", &self.message)
+            .field("delay_ms", &self.delay_ms)
+            .finish()
+    }
+}
@@ -0,0 +1,217 @@
+use crate::proto::echo::EchoPayload;
+
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
+use vstd::prelude::*;
+
+verus! {
+
+/// Asks the server to answer with an error
+///
+/// Useful to exercise the error paths of the clients
+pub struct EchoErrorRequest<V> {
+    #[allow(unused)]
+    message: V,
+}
+
+/// Error the server answers an [`EchoErrorRequest`] with
+///
+/// It hands back the message it refused to echo
+#[allow(unused)]
+pub struct EchoErrorResponse<V> {
+    message: V,
+}
+
+#[allow(unused)]
+impl<V> EchoErrorRequest<V> {
+    pub fn new(message: V) -> (r: Self)
+        ensures
+            r.spec_message() == message,
+    {
+        EchoErrorRequest { message }
+    }
+
+    pub closed spec fn spec_message(self) -> V {
+        self.message
+    }
+
+    pub fn message(self) -> V
+        returns
+            self.spec_message(),
+    {
+        self.message
+    }
+}
+
+impl<V: EchoPayload> EchoErrorRequest<V> {
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        self.spec_message().spec_eq(other.spec_message())
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+        V::spec_eq_refl(a.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+        V::spec_eq_symm(a.spec_message(), b.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+        V::spec_eq_trans(a.spec_message(), b.spec_message(), c.spec_message());
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_message().spec_eq(b.spec_message()),
+    {
+    }
+}
+
+#[allow(unused)]
+impl<V> EchoErrorResponse<V> {
+    pub fn new(message: V) -> (r: Self)
+        ensures
+            r.spec_message() == message,
+    {
+        EchoErrorResponse { message }
+    }
+
+    pub closed spec fn spec_message(self) -> V {
+        self.message
+    }
+
+    pub fn message(self) -> V
+        returns
+            self.spec_message(),
+    {
+        self.message
+    }
+}
+
+impl<V: EchoPayload> EchoErrorResponse<V> {
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        self.spec_message().spec_eq(other.spec_message())
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+        V::spec_eq_refl(a.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+        V::spec_eq_symm(a.spec_message(), b.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+        V::spec_eq_trans(a.spec_message(), b.spec_message(), c.spec_message());
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_message().spec_eq(b.spec_message()),
+    {
+    }
+}
+
+impl<V: EchoPayload> Clone for EchoErrorRequest<V> {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        let message = self.message.clone_payload();
+        proof {
+            V::spec_eq_symm(self.message, message);
+        }
+        EchoErrorRequest { message }
+    }
+}
+
+impl<V: EchoPayload> Clone for EchoErrorResponse<V> {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        let message = self.message.clone_payload();
+        proof {
+            V::spec_eq_symm(self.message, message);
+        }
+        EchoErrorResponse { message }
+    }
+}
+
+impl<V: Codec> Codec for EchoErrorRequest<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(EchoErrorRequest { message: V::decode(dec)? })
+    }
+}
+
+impl<V: Codec> Codec for EchoErrorResponse<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(EchoErrorResponse { message: V::decode(dec)? })
+    }
+}
+
+} // verus!
+impl<V: std::fmt::Debug> std::fmt::Debug for EchoErrorRequest<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EchoErrorRequest")
+            .field("message", &self.message)
+            .finish()
+    }
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for EchoErrorResponse<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EchoErrorResponse")
+            .field("message", &self.message)
+            .finish()
+    }
+}
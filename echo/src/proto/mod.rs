@@ -1,17 +1,30 @@
 use vstd::prelude::*;
 
 mod echo;
+mod echo_after;
+mod echo_error;
 mod request;
 mod response;
+mod sink;
 
 pub use echo::*;
+pub use echo_after::*;
+pub use echo_error::*;
 pub use request::*;
 pub use response::*;
+pub use sink::*;
 
 verus! {
 
+/// Kinds of echo requests
+///
+/// There is no request to echo the message back reversed: an [`EchoPayload`] can be anything
+/// which can be cloned, and reversing it is not defined in general
 pub enum ReqType {
     Echo,
+    EchoAfter,
+    EchoError,
+    Sink,
 }
 
 } // verus!
@@ -1,8 +1,16 @@
 use crate::invariants::requests::RequestProof;
+use crate::proto::echo::EchoPayload;
 use crate::proto::echo::EchoRequest;
+use crate::proto::echo_after::EchoAfterRequest;
+use crate::proto::echo_error::EchoErrorRequest;
+use crate::proto::sink::SinkRequest;
 #[cfg(verus_only)]
 use crate::proto::ReqType;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
 use verdist::rpc::proto::TaggedMessage;
 
 use vstd::prelude::*;
@@ -11,17 +19,20 @@ use vstd::resource::Loc;
 
 verus! {
 
-pub struct Request {
+pub struct Request<V: EchoPayload> {
     request_id: u64,
-    inner: RequestInner,
-    request: Tracked<RequestProof>,
+    inner: RequestInner<V>,
+    request: Tracked<RequestProof<V>>,
 }
 
-pub enum RequestInner {
-    Echo(EchoRequest),
+pub enum RequestInner<V> {
+    Echo(EchoRequest<V>),
+    EchoAfter(EchoAfterRequest<V>),
+    EchoError(EchoErrorRequest<V>),
+    Sink(SinkRequest<V>),
 }
 
-impl TaggedMessage for Request {
+impl<V: EchoPayload> TaggedMessage for Request<V> {
     fn tag(&self) -> u64 {
         self.request_id
     }
@@ -31,14 +42,27 @@ impl TaggedMessage for Request {
     }
 }
 
-impl RequestInner {
+impl<V> RequestInner<V> {
     pub open spec fn req_type(self) -> ReqType {
         match self {
             RequestInner::Echo(_) => ReqType::Echo,
+            RequestInner::EchoAfter(_) => ReqType::EchoAfter,
+            RequestInner::EchoError(_) => ReqType::EchoError,
+            RequestInner::Sink(_) => ReqType::Sink,
         }
     }
 
-    pub fn new_echo(message: String) -> (r: Self)
+    /// The message carried by the request, whatever its type
+    pub open spec fn spec_message(self) -> V {
+        match self {
+            RequestInner::Echo(req) => req.spec_message(),
+            RequestInner::EchoAfter(req) => req.spec_message(),
+            RequestInner::EchoError(req) => req.spec_message(),
+            RequestInner::Sink(req) => req.spec_message(),
+        }
+    }
+
+    pub fn new_echo(message: V) -> (r: Self)
         ensures
             r.req_type() is Echo,
             ({
@@ -49,9 +73,63 @@ impl RequestInner {
         RequestInner::Echo(EchoRequest::new(message))
     }
 
+    pub fn new_echo_after(message: V, delay_ms: u64) -> (r: Self)
+        ensures
+            r.req_type() is EchoAfter,
+            ({
+                let req = r->EchoAfter_0;
+                &&& req.spec_message() == message
+                &&& req.spec_delay_ms() == delay_ms
+            }),
+    {
+        RequestInner::EchoAfter(EchoAfterRequest::new(message, delay_ms))
+    }
+
+    pub fn new_echo_error(message: V) -> (r: Self)
+        ensures
+            r.req_type() is EchoError,
+            ({
+                let req = r->EchoError_0;
+                req.spec_message() == message
+            }),
+    {
+        RequestInner::EchoError(EchoErrorRequest::new(message))
+    }
+
+    pub fn new_sink(message: V) -> (r: Self)
+        ensures
+            r.req_type() is Sink,
+            ({
+                let req = r->Sink_0;
+                req.spec_message() == message
+            }),
+    {
+        RequestInner::Sink(SinkRequest::new(message))
+    }
+
+    /// Gives back the message carried by the request
+    pub fn into_message(self) -> (r: V)
+        ensures
+            r == self.spec_message(),
+        no_unwind
+    {
+        match self {
+            RequestInner::Echo(req) => req.message(),
+            RequestInner::EchoAfter(req) => req.message(),
+            RequestInner::EchoError(req) => req.message(),
+            RequestInner::Sink(req) => req.message(),
+        }
+    }
+}
+
+impl<V: EchoPayload> RequestInner<V> {
     pub open spec fn spec_eq(self, other: Self) -> bool {
         match (self, other) {
             (RequestInner::Echo(a), RequestInner::Echo(b)) => a.spec_eq(b),
+            (RequestInner::EchoAfter(a), RequestInner::EchoAfter(b)) => a.spec_eq(b),
+            (RequestInner::EchoError(a), RequestInner::EchoError(b)) => a.spec_eq(b),
+            (RequestInner::Sink(a), RequestInner::Sink(b)) => a.spec_eq(b),
+            (_, _) => false,
         }
     }
 
@@ -61,6 +139,9 @@ impl RequestInner {
     {
         match a {
             RequestInner::Echo(a) => { EchoRequest::spec_eq_refl(a) },
+            RequestInner::EchoAfter(a) => { EchoAfterRequest::spec_eq_refl(a) },
+            RequestInner::EchoError(a) => { EchoErrorRequest::spec_eq_refl(a) },
+            RequestInner::Sink(a) => { SinkRequest::spec_eq_refl(a) },
         }
     }
 
@@ -72,6 +153,16 @@ impl RequestInner {
     {
         match (a, b) {
             (RequestInner::Echo(a), RequestInner::Echo(b)) => EchoRequest::spec_eq_symm(a, b),
+            (
+                RequestInner::EchoAfter(a),
+                RequestInner::EchoAfter(b),
+            ) => EchoAfterRequest::spec_eq_symm(a, b),
+            (
+                RequestInner::EchoError(a),
+                RequestInner::EchoError(b),
+            ) => EchoErrorRequest::spec_eq_symm(a, b),
+            (RequestInner::Sink(a), RequestInner::Sink(b)) => SinkRequest::spec_eq_symm(a, b),
+            (_, _) => {},
         }
     }
 
@@ -88,11 +179,51 @@ impl RequestInner {
                 RequestInner::Echo(b),
                 RequestInner::Echo(c),
             ) => EchoRequest::spec_eq_trans(a, b, c),
+            (
+                RequestInner::EchoAfter(a),
+                RequestInner::EchoAfter(b),
+                RequestInner::EchoAfter(c),
+            ) => EchoAfterRequest::spec_eq_trans(a, b, c),
+            (
+                RequestInner::EchoError(a),
+                RequestInner::EchoError(b),
+                RequestInner::EchoError(c),
+            ) => EchoErrorRequest::spec_eq_trans(a, b, c),
+            (
+                RequestInner::Sink(a),
+                RequestInner::Sink(b),
+                RequestInner::Sink(c),
+            ) => SinkRequest::spec_eq_trans(a, b, c),
+            (_, _, _) => {},
+        }
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.req_type() == b.req_type(),
+            a.spec_message().spec_eq(b.spec_message()),
+            a.req_type() is EchoAfter ==> a->EchoAfter_0.spec_delay_ms()
+                == b->EchoAfter_0.spec_delay_ms(),
+    {
+        match (a, b) {
+            (RequestInner::Echo(a), RequestInner::Echo(b)) => EchoRequest::lemma_spec_eq(a, b),
+            (
+                RequestInner::EchoAfter(a),
+                RequestInner::EchoAfter(b),
+            ) => EchoAfterRequest::lemma_spec_eq(a, b),
+            (
+                RequestInner::EchoError(a),
+                RequestInner::EchoError(b),
+            ) => EchoErrorRequest::lemma_spec_eq(a, b),
+            (RequestInner::Sink(a), RequestInner::Sink(b)) => SinkRequest::lemma_spec_eq(a, b),
+            (_, _) => {},
         }
     }
 }
 
-impl Request {
+impl<V: EchoPayload> Request<V> {
     pub closed spec fn request_id(self) -> Loc {
         self.request.id()
     }
@@ -101,7 +232,7 @@ impl Request {
         self.request@.key()
     }
 
-    pub closed spec fn request(self) -> RequestInner {
+    pub closed spec fn request(self) -> RequestInner<V> {
         self.request@.value()
     }
 
@@ -109,7 +240,7 @@ impl Request {
         self.inner.req_type()
     }
 
-    pub closed spec fn echo(self) -> EchoRequest
+    pub closed spec fn echo(self) -> EchoRequest<V>
         recommends
             self.req_type() is Echo,
     {
@@ -130,8 +261,8 @@ impl Request {
         #[allow(unused_variables)]
         client_id: u64,
         request_id: u64,
-        request_inner: RequestInner,
-        request_proof: Tracked<RequestProof>,
+        request_inner: RequestInner<V>,
+        request_proof: Tracked<RequestProof<V>>,
     ) -> (r: Self)
         requires
             request_proof@.key() == (client_id, request_id),
@@ -140,6 +271,7 @@ impl Request {
             r.req_type() == request_inner.req_type(),
             r.request_key() == (r.client_id(), r.spec_tag()),
             r.request_id() == request_proof@.id(),
+            r.request() == request_proof@.value(),
             r.client_id() == client_id,
             r.spec_tag() == request_id,
             r.req_type() is Echo ==> r.echo() == request_inner->Echo_0,
@@ -147,7 +279,7 @@ impl Request {
         Request { request_id, inner: request_inner, request: request_proof }
     }
 
-    pub fn destruct(self) -> (r: (u64, RequestInner, Tracked<RequestProof>))
+    pub fn destruct(self) -> (r: (u64, RequestInner<V>, Tracked<RequestProof<V>>))
         ensures
             r.0 == self.spec_tag(),
             r.2@.value().spec_eq(r.1),
@@ -167,6 +299,19 @@ impl Request {
         (self.request_id, self.inner, self.request)
     }
 
+    /// Gives back the message carried by the request, e.g., once it failed to go through
+    pub fn into_message(self) -> (r: V)
+        ensures
+            self.request().spec_message().spec_eq(r),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(&self);
+            RequestInner::lemma_spec_eq(self.request@.value(), self.inner);
+        }
+        self.inner.into_message()
+    }
+
     pub closed spec fn spec_eq(self, other: Self) -> bool {
         &&& self.request_id == other.request_id
         &&& self.inner.spec_eq(other.inner)
@@ -201,7 +346,7 @@ impl Request {
     }
 }
 
-impl Clone for Request {
+impl<V: EchoPayload> Clone for Request<V> {
     #[allow(unused_variables)]
     fn clone(&self) -> (r: Self)
         ensures
@@ -223,7 +368,7 @@ impl Clone for Request {
     }
 }
 
-impl Clone for RequestInner {
+impl<V: EchoPayload> Clone for RequestInner<V> {
     #[allow(unused_variables)]
     fn clone(&self) -> (r: Self)
         ensures
@@ -232,20 +377,82 @@ impl Clone for RequestInner {
     {
         match self {
             RequestInner::Echo(echo) => { RequestInner::Echo(echo.clone()) },
+            RequestInner::EchoAfter(echo_after) => { RequestInner::EchoAfter(echo_after.clone()) },
+            RequestInner::EchoError(echo_error) => { RequestInner::EchoError(echo_error.clone()) },
+            RequestInner::Sink(sink) => { RequestInner::Sink(sink.clone()) },
+        }
+    }
+}
+
+impl<V: Codec> Codec for RequestInner<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            RequestInner::Echo(req) => {
+                enc.put_u8(0);
+                req.encode(enc);
+            },
+            RequestInner::EchoAfter(req) => {
+                enc.put_u8(1);
+                req.encode(enc);
+            },
+            RequestInner::EchoError(req) => {
+                enc.put_u8(2);
+                req.encode(enc);
+            },
+            RequestInner::Sink(req) => {
+                enc.put_u8(3);
+                req.encode(enc);
+            },
         }
     }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        match dec.get_u8()? {
+            0 => Ok(RequestInner::Echo(EchoRequest::decode(dec)?)),
+            1 => Ok(RequestInner::EchoAfter(EchoAfterRequest::decode(dec)?)),
+            2 => Ok(RequestInner::EchoError(EchoErrorRequest::decode(dec)?)),
+            3 => Ok(RequestInner::Sink(SinkRequest::decode(dec)?)),
+            _ => Err(DecodeError),
+        }
+    }
+}
+
+impl<V: EchoPayload + Codec> Codec for Request<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.request_id.encode(enc);
+        self.inner.encode(enc);
+    }
+
+    /// The request proof does not cross the wire: it is assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let request_id = u64::decode(dec)?;
+        let inner = RequestInner::decode(dec)?;
+        Ok(Request { request_id, inner, request: dec.tracked()? })
+    }
 }
 
 } // verus!
-impl std::fmt::Debug for RequestInner {
+impl<V: std::fmt::Debug> std::fmt::Debug for RequestInner<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             RequestInner::Echo(echo) => f.debug_tuple("Echo").field(&echo).finish(),
+            RequestInner::EchoAfter(echo_after) => {
+                f.debug_tuple("EchoAfter").field(&echo_after).finish()
+            }
+            RequestInner::EchoError(echo_error) => {
+                f.debug_tuple("EchoError").field(&echo_error).finish()
+            }
+            RequestInner::Sink(sink) => f.debug_tuple("Sink").field(&sink).finish(),
         }
     }
 }
 
-impl std::fmt::Debug for Request {
+impl<V: EchoPayload> std::fmt::Debug for Request<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Request")
             .field("request_id", &self.request_id)
@@ -1,30 +1,39 @@
 use crate::invariants::requests::RequestProof;
+use crate::proto::echo::EchoPayload;
 use crate::proto::echo::EchoResponse;
+use crate::proto::echo_error::EchoErrorResponse;
 #[cfg(verus_only)]
 use crate::proto::request::RequestInner;
 #[cfg(verus_only)]
 use crate::proto::ReqType;
 
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
 use verdist::rpc::proto::TaggedMessage;
 
+use vstd::pervasive::unreached;
 use vstd::prelude::*;
 #[cfg(verus_only)]
 use vstd::resource::Loc;
 
 verus! {
 
-pub struct Response {
+pub struct Response<V: EchoPayload> {
     request_id: u64,
-    inner: ResponseInner,
+    inner: ResponseInner<V>,
     #[allow(unused)]
-    request: Tracked<RequestProof>,
+    request: Tracked<RequestProof<V>>,
 }
 
-pub enum ResponseInner {
-    Echo(EchoResponse),
+pub enum ResponseInner<V> {
+    Echo(EchoResponse<V>),
+    EchoAfter(EchoResponse<V>),
+    EchoError(EchoErrorResponse<V>),
 }
 
-impl TaggedMessage for Response {
+impl<V: EchoPayload> TaggedMessage for Response<V> {
     fn tag(&self) -> u64 {
         self.request_id
     }
@@ -34,21 +43,22 @@ impl TaggedMessage for Response {
     }
 }
 
-impl Response {
-    pub fn new(request_id: u64, inner: ResponseInner, request: Tracked<RequestProof>) -> (r: Self)
+impl<V: EchoPayload> Response<V> {
+    pub fn new(request_id: u64, inner: ResponseInner<V>, request: Tracked<RequestProof<V>>) -> (r:
+        Self)
         requires
             request@.key().1 == request_id,
             request@.value().req_type() is Echo <==> inner is Echo,
-            request@.value().req_type() is Echo ==> {
-                let echo_req = request@.value()->Echo_0;
-                let echo_resp = inner->Echo_0;
-                &&& echo_resp.spec_message() == echo_req.spec_message()
-            },
+            request@.value().req_type() is EchoAfter <==> inner is EchoAfter,
+            request@.value().req_type() is EchoError <==> inner is EchoError,
+            request@.value().spec_message().spec_eq(inner.spec_message()),
         ensures
             r.spec_tag() == request_id,
             r.request_id() == request.id(),
             r.request_key() == request@.key(),
             r.request() == request@.value(),
+            r.req_type() == inner.req_type(),
+            r.spec_message() == inner.spec_message(),
             inner is Echo ==> {
                 &&& r.req_type() is Echo
                 &&& inner->Echo_0 == r.echo()
@@ -61,11 +71,7 @@ impl Response {
     spec fn inv(self) -> bool {
         &&& self.request_key().1 == self.spec_tag()
         &&& self.request().req_type() == self.req_type()
-        &&& self.req_type() is Echo ==> {
-            let echo_req = self.request()->Echo_0;
-            let echo_resp = self.echo();
-            &&& echo_resp.spec_message() == echo_req.spec_message()
-        }
+        &&& self.request().spec_message().spec_eq(self.spec_message())
     }
 
     pub closed spec fn request_id(self) -> Loc {
@@ -76,24 +82,26 @@ impl Response {
         self.request@.key()
     }
 
-    pub closed spec fn request(self) -> RequestInner {
+    pub closed spec fn request(self) -> RequestInner<V> {
         self.request@.value()
     }
 
     pub closed spec fn req_type(self) -> ReqType {
-        match self.inner {
-            ResponseInner::Echo(_) => ReqType::Echo,
-        }
+        self.inner.req_type()
     }
 
-    pub closed spec fn echo(self) -> EchoResponse
+    pub closed spec fn spec_message(self) -> V {
+        self.inner.spec_message()
+    }
+
+    pub closed spec fn echo(self) -> EchoResponse<V>
         recommends
             self.req_type() is Echo,
     {
         self.inner->Echo_0
     }
 
-    pub fn destruct_echo(self) -> (r: EchoResponse)
+    pub fn destruct_echo(self) -> (r: EchoResponse<V>)
         requires
             self.req_type() is Echo,
         ensures
@@ -101,7 +109,7 @@ impl Response {
             ({
                 let echo_req = self.request()->Echo_0;
                 let echo_resp = self.echo();
-                &&& echo_req.spec_message() == echo_resp.spec_message()
+                &&& echo_req.spec_message().spec_eq(echo_resp.spec_message())
             }),
         no_unwind
     {
@@ -110,6 +118,27 @@ impl Response {
         }
         match self.inner {
             ResponseInner::Echo(g) => g,
+            _ => {
+                assert(false);
+                unreached()
+            },
+        }
+    }
+
+    /// Returns the message of the response: a copy of the one the request carried
+    pub fn into_message(self) -> (r: V)
+        ensures
+            r == self.spec_message(),
+            self.request().spec_message().spec_eq(r),
+        no_unwind
+    {
+        proof {
+            use_type_invariant(&self);
+        }
+        match self.inner {
+            ResponseInner::Echo(g) => g.message(),
+            ResponseInner::EchoAfter(g) => g.message(),
+            ResponseInner::EchoError(g) => g.message(),
         }
     }
 
@@ -155,14 +184,16 @@ impl Response {
             a.request_key() == b.request_key(),
             a.request() == b.request(),
             a.req_type() == b.req_type(),
+            a.spec_message().spec_eq(b.spec_message()),
             a.req_type() is Echo ==> EchoResponse::spec_eq(a.echo(), b.echo()),
     {
+        ResponseInner::lemma_spec_eq(a.inner, b.inner);
     }
 
     pub proof fn agree_request(
         tracked &self,
         #[allow(unused_variables)]
-        tracked request_proof: &mut RequestProof,
+        tracked request_proof: &mut RequestProof<V>,
     )
         requires
             self.request_id() == old(request_proof).id(),
@@ -179,6 +210,7 @@ impl Response {
         ensures
             self.request_key().1 == self.spec_tag(),
             self.request().req_type() == self.req_type(),
+            self.request().spec_message().spec_eq(self.spec_message()),
         no_unwind
     {
         proof {
@@ -187,10 +219,33 @@ impl Response {
     }
 }
 
-impl ResponseInner {
+impl<V> ResponseInner<V> {
+    pub open spec fn req_type(self) -> ReqType {
+        match self {
+            ResponseInner::Echo(_) => ReqType::Echo,
+            ResponseInner::EchoAfter(_) => ReqType::EchoAfter,
+            ResponseInner::EchoError(_) => ReqType::EchoError,
+        }
+    }
+
+    /// The message carried by the response, whatever its type
+    pub open spec fn spec_message(self) -> V {
+        match self {
+            ResponseInner::Echo(resp) => resp.spec_message(),
+            ResponseInner::EchoAfter(resp) => resp.spec_message(),
+            ResponseInner::EchoError(resp) => resp.spec_message(),
+        }
+    }
+
+}
+
+impl<V: EchoPayload> ResponseInner<V> {
     pub open spec fn spec_eq(self, other: Self) -> bool {
         match (self, other) {
             (ResponseInner::Echo(a), ResponseInner::Echo(b)) => a.spec_eq(b),
+            (ResponseInner::EchoAfter(a), ResponseInner::EchoAfter(b)) => a.spec_eq(b),
+            (ResponseInner::EchoError(a), ResponseInner::EchoError(b)) => a.spec_eq(b),
+            (_, _) => false,
         }
     }
 
@@ -200,6 +255,8 @@ impl ResponseInner {
     {
         match a {
             ResponseInner::Echo(a) => EchoResponse::spec_eq_refl(a),
+            ResponseInner::EchoAfter(a) => EchoResponse::spec_eq_refl(a),
+            ResponseInner::EchoError(a) => EchoErrorResponse::spec_eq_refl(a),
         }
     }
 
@@ -211,6 +268,15 @@ impl ResponseInner {
     {
         match (a, b) {
             (ResponseInner::Echo(a), ResponseInner::Echo(b)) => EchoResponse::spec_eq_symm(a, b),
+            (
+                ResponseInner::EchoAfter(a),
+                ResponseInner::EchoAfter(b),
+            ) => EchoResponse::spec_eq_symm(a, b),
+            (
+                ResponseInner::EchoError(a),
+                ResponseInner::EchoError(b),
+            ) => EchoErrorResponse::spec_eq_symm(a, b),
+            (_, _) => {},
         }
     }
 
@@ -227,11 +293,43 @@ impl ResponseInner {
                 ResponseInner::Echo(b),
                 ResponseInner::Echo(c),
             ) => EchoResponse::spec_eq_trans(a, b, c),
+            (
+                ResponseInner::EchoAfter(a),
+                ResponseInner::EchoAfter(b),
+                ResponseInner::EchoAfter(c),
+            ) => EchoResponse::spec_eq_trans(a, b, c),
+            (
+                ResponseInner::EchoError(a),
+                ResponseInner::EchoError(b),
+                ResponseInner::EchoError(c),
+            ) => EchoErrorResponse::spec_eq_trans(a, b, c),
+            (_, _, _) => {},
+        }
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.req_type() == b.req_type(),
+            a.spec_message().spec_eq(b.spec_message()),
+    {
+        match (a, b) {
+            (ResponseInner::Echo(a), ResponseInner::Echo(b)) => EchoResponse::lemma_spec_eq(a, b),
+            (
+                ResponseInner::EchoAfter(a),
+                ResponseInner::EchoAfter(b),
+            ) => EchoResponse::lemma_spec_eq(a, b),
+            (
+                ResponseInner::EchoError(a),
+                ResponseInner::EchoError(b),
+            ) => EchoErrorResponse::lemma_spec_eq(a, b),
+            (_, _) => {},
         }
     }
 }
 
-impl Clone for Response {
+impl<V: EchoPayload> Clone for Response<V> {
     #[allow(unused_variables)]
     fn clone(&self) -> (r: Self)
         ensures
@@ -243,15 +341,18 @@ impl Clone for Response {
         let inner = self.inner.clone();
         let request = Tracked(self.request.borrow().duplicate());
         proof {
-            if inner is Echo {
-                EchoResponse::lemma_spec_eq(self.inner->Echo_0, inner->Echo_0);
-            }
+            ResponseInner::lemma_spec_eq(self.inner, inner);
+            V::spec_eq_trans(
+                self.request().spec_message(),
+                self.spec_message(),
+                inner.spec_message(),
+            );
         }
         Response { request_id: self.request_id, inner, request }
     }
 }
 
-impl Clone for ResponseInner {
+impl<V: EchoPayload> Clone for ResponseInner<V> {
     #[allow(unused_variables)]
     fn clone(&self) -> (r: Self)
         ensures
@@ -259,20 +360,75 @@ impl Clone for ResponseInner {
     {
         match self {
             ResponseInner::Echo(echo) => { ResponseInner::Echo(echo.clone()) },
+            ResponseInner::EchoAfter(echo) => { ResponseInner::EchoAfter(echo.clone()) },
+            ResponseInner::EchoError(echo_error) => {
+                ResponseInner::EchoError(echo_error.clone())
+            },
+        }
+    }
+}
+
+impl<V: Codec> Codec for ResponseInner<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            ResponseInner::Echo(resp) => {
+                enc.put_u8(0);
+                resp.encode(enc);
+            },
+            ResponseInner::EchoAfter(resp) => {
+                enc.put_u8(1);
+                resp.encode(enc);
+            },
+            ResponseInner::EchoError(resp) => {
+                enc.put_u8(2);
+                resp.encode(enc);
+            },
+        }
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        match dec.get_u8()? {
+            0 => Ok(ResponseInner::Echo(EchoResponse::decode(dec)?)),
+            1 => Ok(ResponseInner::EchoAfter(EchoResponse::decode(dec)?)),
+            2 => Ok(ResponseInner::EchoError(EchoErrorResponse::decode(dec)?)),
+            _ => Err(DecodeError),
         }
     }
 }
 
+impl<V: EchoPayload + Codec> Codec for Response<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.request_id.encode(enc);
+        self.inner.encode(enc);
+    }
+
+    /// The request proof does not cross the wire: it is assumed back (see
+    /// [`Decoder::tracked`])
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        let request_id = u64::decode(dec)?;
+        let inner = ResponseInner::decode(dec)?;
+        Ok(Response { request_id, inner, request: dec.tracked()? })
+    }
+}
+
 } // verus!
-impl std::fmt::Debug for ResponseInner {
+impl<V: std::fmt::Debug> std::fmt::Debug for ResponseInner<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ResponseInner::Echo(echo) => f.debug_tuple("Echo").field(&echo).finish(),
+            ResponseInner::EchoAfter(echo) => f.debug_tuple("EchoAfter").field(&echo).finish(),
+            ResponseInner::EchoError(echo_error) => {
+                f.debug_tuple("EchoError").field(&echo_error).finish()
+            }
         }
     }
 }
 
-impl std::fmt::Debug for Response {
+impl<V: EchoPayload> std::fmt::Debug for Response<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Response")
             .field("request_id", &self.request_id)
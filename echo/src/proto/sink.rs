@@ -0,0 +1,114 @@
+use crate::proto::echo::EchoPayload;
+
+use verdist::network::codec::Codec;
+use verdist::network::codec::Decoder;
+use verdist::network::codec::Encoder;
+use verdist::network::error::DecodeError;
+
+use vstd::prelude::*;
+
+verus! {
+
+/// Sends a message the server does not answer
+///
+/// Useful to exercise the logic which gives up on a request
+pub struct SinkRequest<V> {
+    #[allow(unused)]
+    message: V,
+}
+
+#[allow(unused)]
+impl<V> SinkRequest<V> {
+    pub fn new(message: V) -> (r: Self)
+        ensures
+            r.spec_message() == message,
+    {
+        SinkRequest { message }
+    }
+
+    pub closed spec fn spec_message(self) -> V {
+        self.message
+    }
+
+    pub fn message(self) -> V
+        returns
+            self.spec_message(),
+    {
+        self.message
+    }
+}
+
+impl<V: EchoPayload> SinkRequest<V> {
+    pub closed spec fn spec_eq(self, other: Self) -> bool {
+        self.spec_message().spec_eq(other.spec_message())
+    }
+
+    pub broadcast proof fn spec_eq_refl(a: Self)
+        ensures
+            #[trigger] a.spec_eq(a),
+    {
+        V::spec_eq_refl(a.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    {
+        V::spec_eq_symm(a.spec_message(), b.spec_message());
+    }
+
+    pub broadcast proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+            #[trigger] b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    {
+        V::spec_eq_trans(a.spec_message(), b.spec_message(), c.spec_message());
+    }
+
+    pub broadcast proof fn lemma_spec_eq(a: Self, b: Self)
+        requires
+            #[trigger] a.spec_eq(b),
+        ensures
+            a.spec_message().spec_eq(b.spec_message()),
+    {
+    }
+}
+
+impl<V: EchoPayload> Clone for SinkRequest<V> {
+    fn clone(&self) -> (r: Self)
+        ensures
+            self.spec_eq(r),
+            r.spec_eq(*self),
+    {
+        let message = self.message.clone_payload();
+        proof {
+            V::spec_eq_symm(self.message, message);
+        }
+        SinkRequest { message }
+    }
+}
+
+impl<V: Codec> Codec for SinkRequest<V> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        self.message.encode(enc);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        Ok(SinkRequest { message: V::decode(dec)? })
+    }
+}
+
+} // verus!
+impl<V: std::fmt::Debug> std::fmt::Debug for SinkRequest<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SinkRequest")
+            .field("message", &self.message)
+            .finish()
+    }
+}
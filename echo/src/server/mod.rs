@@ -1,7 +1,13 @@
+#[cfg(verus_only)]
+use crate::channel::chan_response_inv;
 use crate::channel::ChannelInv;
 #[cfg(verus_only)]
 use crate::invariants;
 use crate::invariants::StateInvariant;
+use crate::proto::EchoAfterRequest;
+use crate::proto::EchoErrorRequest;
+use crate::proto::EchoErrorResponse;
+use crate::proto::EchoPayload;
 use crate::proto::EchoRequest;
 use crate::proto::EchoResponse;
 use crate::proto::Request;
@@ -13,14 +19,22 @@ use verdist::network::channel::Channel;
 #[cfg(verus_only)]
 use verdist::network::channel::ChannelInvariant;
 use verdist::network::channel::Listener;
+use verdist::network::codec::Codec;
 use verdist::network::modelled::ModelledConnector;
 #[cfg(verus_only)]
 use verdist::network::modelled::ModelledListener;
-#[cfg(verus_only)]
+use verdist::network::uds::UdsConnector;
 use verdist::rpc::proto::TaggedMessage;
+use verdist::server::reply_cache::Lookup;
+use verdist::server::reply_cache::ReplyCache;
+use verdist::server::reply_cache::ReplyCacheInv;
 
 use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::OnceLock;
+use std::time::Instant;
 
 #[cfg(verus_only)]
 use vstd::invariant::InvariantPredicate;
@@ -29,15 +43,59 @@ use vstd::rwlock::RwLock;
 #[cfg(verus_only)]
 use vstd::rwlock::RwLockPredicate;
 
+/// Milliseconds since the first call, to time [`EchoAfterRequest`]s
+fn now_ms() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
 verus! {
 
+pub assume_specification[ now_ms ]() -> u64
+;
+
 pub struct ServerInv {
     pub channel_inv: ChannelInv,
     pub server_id: u64,
 }
 
-impl<C> vstd::rwlock::RwLockPredicate<Vec<C>> for ServerInv where
-    C: Channel<Id = (u64, u64), R = Request, S = Response, K = ChannelInv>,
+/// The replies cached by the server can be sent back to their clients
+pub open spec fn cached_reply_inv<V: EchoPayload>(
+    channel_inv: ChannelInv,
+    server_id: u64,
+) -> spec_fn(u64, Response<V>) -> bool {
+    |client_id: u64, r: Response<V>| chan_response_inv(channel_inv, client_id, server_id, r)
+}
+
+/// An [`EchoAfterRequest`] which was answered, but whose reply is held back until its deadline
+pub struct Delayed<V: EchoPayload> {
+    pub client_id: u64,
+    /// When to send the reply (see `now_ms`)
+    pub deadline_ms: u64,
+    pub response: Response<V>,
+}
+
+impl<V: EchoPayload> Delayed<V> {
+    pub open spec fn inv(self, channel_inv: ChannelInv, server_id: u64) -> bool {
+        chan_response_inv(channel_inv, self.client_id, server_id, self.response)
+    }
+}
+
+pub struct DelayedInv {
+    pub channel_inv: ChannelInv,
+    pub server_id: u64,
+}
+
+impl<V: EchoPayload> vstd::rwlock::RwLockPredicate<Vec<Delayed<V>>> for DelayedInv {
+    open spec fn inv(self, v: Vec<Delayed<V>>) -> bool {
+        forall|idx: int|
+            0 <= idx < v@.len() ==> #[trigger] v@[idx].inv(self.channel_inv, self.server_id)
+    }
+}
+
+impl<C, V> vstd::rwlock::RwLockPredicate<Vec<C>> for ServerInv where
+    C: Channel<Id = (u64, u64), R = Request<V>, S = Response<V>, K = ChannelInv>,
+    V: EchoPayload,
  {
     open spec fn inv(self, v: Vec<C>) -> bool {
         forall|idx: int|
@@ -50,9 +108,10 @@ impl<C> vstd::rwlock::RwLockPredicate<Vec<C>> for ServerInv where
 }
 
 #[verifier::reject_recursive_types(C)]
-pub struct EchoServer<L, C> where
+pub struct EchoServer<L, C, V> where
     L: Listener<C>,
-    C: Channel<R = Request, S = Response, Id = (u64, u64), K = ChannelInv>,
+    C: Channel<R = Request<V>, S = Response<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
  {
     /// ID of the server
     id: u64,
@@ -60,14 +119,21 @@ pub struct EchoServer<L, C> where
     listener: L,
     /// Connected clients
     connected: RwLock<Vec<C>, ServerInv>,
+    /// Replies to [`EchoAfterRequest`]s which are not due yet, by decreasing deadline (the next
+    /// one due is the last one)
+    delayed: RwLock<Vec<Delayed<V>>, DelayedInv>,
+    /// Last reply to each client, to answer duplicate requests
+    replies: RwLock<ReplyCache<Response<V>>, ReplyCacheInv<Response<V>>>,
+    _marker: PhantomData<V>,
 }
 
-impl<L, C> EchoServer<L, C> where
+impl<L, C, V> EchoServer<L, C, V> where
     L: Listener<C>,
-    C: Channel<R = Request, S = Response, Id = (u64, u64), K = ChannelInv>,
+    C: Channel<R = Request<V>, S = Response<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
  {
     #[allow(unused)]
-    pub fn new(listener: L, id: u64, state_inv: Tracked<Arc<StateInvariant>>) -> (r: Self)
+    pub fn new(listener: L, id: u64, state_inv: Tracked<Arc<StateInvariant<V>>>) -> (r: Self)
         requires
             state_inv@.namespace() == invariants::state_inv_id(),
     {
@@ -75,12 +141,29 @@ impl<L, C> EchoServer<L, C> where
         let ghost channel_inv = ChannelInv::from_state_pred(state_inv@.constant());
         let ghost server_inv = ServerInv { channel_inv, server_id: id };
         assert(server_inv.inv(empty));
-        EchoServer { id, connected: RwLock::new(empty, Ghost(server_inv)), listener }
+        let ghost delayed_inv = DelayedInv { channel_inv, server_id: id };
+        let ghost replies_inv = ReplyCacheInv { reply_inv: cached_reply_inv(channel_inv, id) };
+        let replies = ReplyCache::new();
+        assert(replies_inv.inv(replies));
+        EchoServer {
+            id,
+            connected: RwLock::new(empty, Ghost(server_inv)),
+            delayed: RwLock::new(Vec::new(), Ghost(delayed_inv)),
+            replies: RwLock::new(replies, Ghost(replies_inv)),
+            listener,
+            _marker: PhantomData,
+        }
     }
 
     #[verifier::type_invariant]
     closed spec fn inv(self) -> bool {
         &&& self.connected.pred().server_id == self.id
+        &&& self.delayed.pred().channel_inv == self.connected.pred().channel_inv
+        &&& self.delayed.pred().server_id == self.id
+        &&& self.replies.pred().reply_inv == cached_reply_inv::<V>(
+            self.connected.pred().channel_inv,
+            self.id,
+        )
     }
 
     fn accept(&self, channel: C)
@@ -97,7 +180,7 @@ impl<L, C> EchoServer<L, C> where
         handle.release_write(guard);
     }
 
-    fn handle_echo(&self, req: EchoRequest) -> (r: ResponseInner)
+    fn handle_echo(&self, req: EchoRequest<V>) -> (r: ResponseInner<V>)
         ensures
             r is Echo,
             ({
@@ -111,46 +194,252 @@ impl<L, C> EchoServer<L, C> where
         ResponseInner::Echo(EchoResponse::new(req.message()))
     }
 
+    /// Echoes the message back
+    ///
+    /// The reply is only sent once the delay has passed (see `delay`)
+    fn handle_echo_after(&self, req: EchoAfterRequest<V>) -> (r: ResponseInner<V>)
+        ensures
+            r is EchoAfter,
+            ({
+                let resp = r->EchoAfter_0;
+                &&& resp.spec_message() == req.spec_message()
+            }),
+    {
+        ResponseInner::EchoAfter(EchoResponse::new(req.message()))
+    }
+
+    fn handle_echo_error(&self, req: EchoErrorRequest<V>) -> (r: ResponseInner<V>)
+        ensures
+            r is EchoError,
+            ({
+                let resp = r->EchoError_0;
+                &&& resp.spec_message() == req.spec_message()
+            }),
+    {
+        ResponseInner::EchoError(EchoErrorResponse::new(req.message()))
+    }
+
+    /// Holds back `response` for `delay_ms`: it is sent by a later `poll`
+    fn delay(&self, response: Response<V>, client_id: u64, delay_ms: u64)
+        requires
+            chan_response_inv(self.connected.pred().channel_inv, client_id, self.id, response),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        let now = now_ms();
+        let deadline_ms = if delay_ms > u64::MAX - now {
+            u64::MAX
+        } else {
+            now + delay_ms
+        };
+
+        let ghost delayed_inv = self.delayed.pred();
+        let (mut delayed, handle) = self.delayed.acquire_write();
+        let mut idx = delayed.len();
+        while idx > 0 && delayed[idx - 1].deadline_ms < deadline_ms
+            invariant
+                0 <= idx <= delayed@.len(),
+            decreases idx,
+        {
+            idx -= 1;
+        }
+        let entry = Delayed { client_id, deadline_ms, response };
+        assert(entry.inv(delayed_inv.channel_inv, delayed_inv.server_id));
+        delayed.insert(idx, entry);
+        assert forall|i: int| 0 <= i < delayed@.len() implies #[trigger] delayed@[i].inv(
+            delayed_inv.channel_inv,
+            delayed_inv.server_id,
+        ) by {}
+        handle.release_write(delayed);
+    }
+
+    /// Sends the delayed replies which are now due
+    ///
+    /// A reply whose client is gone is dropped
+    fn send_due(&self, connected: &Vec<C>)
+        requires
+            forall|idx|
+                0 <= idx < connected@.len() ==> {
+                    let chan = #[trigger] connected@[idx];
+                    &&& self.connected.pred().channel_inv == chan.constant()
+                    &&& self.id == chan.spec_id().0
+                },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        let ghost delayed_inv = self.delayed.pred();
+        let (mut delayed, handle) = self.delayed.acquire_write();
+        let now = now_ms();
+        while delayed.len() > 0 && delayed[delayed.len() - 1].deadline_ms <= now
+            invariant
+                forall|idx: int|
+                    0 <= idx < delayed@.len() ==> #[trigger] delayed@[idx].inv(
+                        delayed_inv.channel_inv,
+                        delayed_inv.server_id,
+                    ),
+                forall|idx|
+                    0 <= idx < connected@.len() ==> {
+                        let chan = #[trigger] connected@[idx];
+                        &&& self.connected.pred().channel_inv == chan.constant()
+                        &&& self.id == chan.spec_id().0
+                    },
+                delayed_inv == self.delayed.pred(),
+                delayed_inv.channel_inv == self.connected.pred().channel_inv,
+                delayed_inv.server_id == self.id,
+            decreases delayed@.len(),
+        {
+            let entry = delayed.pop().unwrap();
+            assert(entry.inv(delayed_inv.channel_inv, delayed_inv.server_id));
+            for channel in it: connected.iter()
+                invariant
+                    forall|idx|
+                        0 <= idx < connected@.len() ==> {
+                            let chan = #[trigger] connected@[idx];
+                            &&& self.connected.pred().channel_inv == chan.constant()
+                            &&& self.id == chan.spec_id().0
+                        },
+                    connected@ == it.elements,
+                    chan_response_inv(
+                        self.connected.pred().channel_inv,
+                        entry.client_id,
+                        self.id,
+                        entry.response,
+                    ),
+            {
+                if channel.id().1 == entry.client_id {
+                    assert(C::K::send_inv(channel.constant(), channel.spec_id(), entry.response));
+                    vlib::veprintln!("[server|{:>3}]: sending delayed resp: {:?}", self.id, entry.response);
+                    // a failed send means the client is gone: the channel is dropped on the next poll
+                    let _ = channel.send(&entry.response);
+                    break ;
+                }
+            }
+        }
+        handle.release_write(delayed);
+    }
+
+    /// Handles a request
+    ///
+    /// Returns `None` if there is no reply to send now: either the request is a sink or its reply
+    /// was delayed (see `delay`)
     fn handle(
         &self,
-        request: Request,
+        request: Request<V>,
         #[allow(unused_variables)]
         client_id: u64,
-    ) -> (r: Response)
+    ) -> (r: Option<Response<V>>)
         requires
             request.request_key() == (client_id, request.spec_tag()),
+            request.request_id() == self.connected.pred().channel_inv.request_map_id,
         ensures
-            r.spec_tag() == request.spec_tag(),
-            r.request_id() == request.request_id(),
-            r.request_key() == request.request_key(),
-            r.request().spec_eq(request.request()),
-            request.req_type() == r.req_type(),
-            r.req_type() is Echo ==> ({
-                let echo_req = request.echo();
-                let resp = r.echo();
-                &&& echo_req.spec_message() == resp.spec_message()
-            }),
+            request.req_type() is Sink ==> r is None,
+            r is None ==> request.req_type() is Sink || request.req_type() is EchoAfter,
+            r is Some ==> {
+                let resp = r->Some_0;
+                &&& resp.spec_tag() == request.spec_tag()
+                &&& resp.request_id() == request.request_id()
+                &&& resp.request_key() == request.request_key()
+                &&& resp.request().spec_eq(request.request())
+                &&& request.req_type() == resp.req_type()
+                &&& request.request().spec_message().spec_eq(resp.spec_message())
+            },
     {
         vlib::veprintln!("[server|{:>3}]: received req: {:?}", self.id, request);
         let (request_id, request_inner, request_proof) = request.destruct();
+        proof {
+            RequestInner::lemma_spec_eq(request_proof@.value(), request_inner);
+        }
+
         let resp_inner = match request_inner {
             RequestInner::Echo(req) => self.handle_echo(req),
+            RequestInner::EchoAfter(req) => {
+                let delay_ms = req.delay_ms();
+                let resp_inner = self.handle_echo_after(req);
+                let r = Response::new(request_id, resp_inner, request_proof);
+                vlib::veprintln!("[server|{:>3}]: delaying resp by {}ms: {:?}", self.id, delay_ms, r);
+                self.delay(r, client_id, delay_ms);
+                return None;
+            },
+            RequestInner::EchoError(req) => self.handle_echo_error(req),
+            RequestInner::Sink(_) => {
+                vlib::veprintln!("[server|{:>3}]: not answering req {}", self.id, request_id);
+                return None;
+            },
         };
 
-        proof {
-            if request_inner is Echo {
-                let echo_req = request_inner->Echo_0;
-                let proof_echo_req = request_proof@.value()->Echo_0;
-                EchoRequest::lemma_spec_eq(proof_echo_req, echo_req);
-            }
-        }
-
         let r = Response::new(request_id, resp_inner, request_proof);
         proof {
             RequestInner::spec_eq_refl(r.request());
         }
         vlib::veprintln!("[server|{:>3}]: sending resp: {:?}", self.id, r);
-        r
+        Some(r)
+    }
+
+    /// Handles a request, unless it was already handled
+    ///
+    /// Duplicates of a request get the reply that was sent to it, and duplicates of a request
+    /// still being handled get none (see [`ReplyCache`])
+    fn handle_once(&self, request: Request<V>, client_id: u64) -> (r: Option<Response<V>>)
+        requires
+            request.request_key() == (client_id, request.spec_tag()),
+            request.request_id() == self.connected.pred().channel_inv.request_map_id,
+        ensures
+            r is Some ==> {
+                let resp = r->Some_0;
+                &&& chan_response_inv(self.connected.pred().channel_inv, client_id, self.id, resp)
+                &&& resp.spec_tag() == request.spec_tag()
+            },
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        let ghost channel_inv = self.connected.pred().channel_inv;
+        let ghost reply_inv = self.replies.pred().reply_inv;
+        let (mut replies, handle) = self.replies.acquire_write();
+        let tag = request.tag();
+        let res = match replies.lookup(client_id, tag, Ghost(reply_inv)) {
+            Lookup::New => {
+                // a client may pipeline its requests (see `EchoClient::echo_many`), so they can
+                // arrive out of order: none acknowledges the others, and the replies are kept
+                // until the client disconnects
+                replies.start(client_id, tag, Ghost(reply_inv));
+                let res = self.handle(request, client_id);
+                if let Some(response) = &res {
+                    let cached = response.clone();
+                    proof {
+                        Response::lemma_spec_eq(*response, cached);
+                    }
+                    replies.record(client_id, cached, Ghost(reply_inv));
+                }
+                res
+            },
+            Lookup::Duplicate => {
+                let cached = replies.reply(client_id, tag).unwrap();
+                let response = cached.clone();
+                proof {
+                    replies.lemma_reply(reply_inv, client_id, tag);
+                    Response::lemma_spec_eq(*cached, response);
+                }
+                vlib::veprintln!("[server|{:>3}]: resending resp: {:?}", self.id, response);
+                Some(response)
+            },
+            Lookup::Stale => {
+                vlib::veprintln!("[server|{:>3}]: dropped stale req: {:?}", self.id, request);
+                None
+            },
+        };
+        handle.release_write(replies);
+        res
+    }
+
+    /// Forgets the replies cached for `client_id` (see [`ReplyCache::evict`])
+    fn evict_replies(&self, client_id: u64) {
+        let ghost reply_inv = self.replies.pred().reply_inv;
+        let (mut replies, handle) = self.replies.acquire_write();
+        replies.evict(client_id, Ghost(reply_inv));
+        handle.release_write(replies);
     }
 
     fn poll(&self) -> bool {
@@ -182,18 +471,15 @@ impl<L, C> EchoServer<L, C> where
         }
 
         let mut drop = HashSet::new();
+        let mut gone = Vec::new();
         let (mut connected, handle) = self.connected.acquire_write();
 
         let ghost connected_pred = self.connected.pred();
         let iterator = connected.iter();
-        #[allow(unused_variables)]
-        let mut idx = 0usize;
-        #[allow(unused_assignments)]
         for channel in it: iterator
             invariant
                 self.connected.pred() == connected_pred,
                 connected_pred.server_id == self.id,
-                idx == it.pos,
                 connected@ == it.elements,
                 forall|idx|
                     0 <= idx < connected@.len() ==> {
@@ -205,19 +491,20 @@ impl<L, C> EchoServer<L, C> where
             match channel.try_recv() {
                 Ok(req) => {
                     assert(C::K::recv_inv(channel.constant(), channel.spec_id(), req));
-                    let response = self.handle(req, channel.id().1);
-                    assert(C::K::send_inv(channel.constant(), channel.spec_id(), response));
-                    if channel.send(&response).is_err() {
-                        drop.insert(channel.id());
+                    if let Some(response) = self.handle_once(req, channel.id().1) {
+                        assert(C::K::send_inv(channel.constant(), channel.spec_id(), response));
+                        if channel.send(&response).is_err() {
+                            drop.insert(channel.id());
+                            gone.push(channel.id().1);
+                        }
                     }
                 },
                 Err(verdist::network::error::TryRecvError::Empty) => {},
                 Err(verdist::network::error::TryRecvError::Disconnected) => {
                     drop.insert(channel.id());
+                    gone.push(channel.id().1);
                 },
             }
-            assume(idx < usize::MAX);  // XXX: overflow
-            idx += 1;
         }
 
         let ghost old_c = connected@;
@@ -234,43 +521,86 @@ impl<L, C> EchoServer<L, C> where
                 old_c.lemma_filter_contains_rev(|c| filter_fn.ensures((&c,), true), chan);
             }
         }
+        for gone_idx in 0..gone.len()
+            invariant
+                forall|idx|
+                    0 <= idx < connected@.len() ==> {
+                        let chan = #[trigger] connected@[idx];
+                        &&& self.connected.pred().channel_inv == chan.constant()
+                        &&& self.id == chan.spec_id().0
+                    },
+        {
+            self.evict_replies(gone[gone_idx]);
+        }
+        self.send_due(&connected);
         handle.release_write(connected);
 
         true
     }
 }
 
-fn create_server<L, C>(server_id: u64, listener: L) -> EchoServer<L, C> where
+fn create_server<L, C, V>(
+    server_id: u64,
+    listener: L,
+    state_inv: &Tracked<Arc<StateInvariant<V>>>,
+) -> EchoServer<L, C, V> where
     L: Listener<C>,
-    C: Channel<R = Request, S = Response, Id = (u64, u64), K = ChannelInv>,
- {
-    let tracked state_inv;
+    C: Channel<R = Request<V>, S = Response<V>, Id = (u64, u64), K = ChannelInv>,
+    V: EchoPayload,
+
+    requires
+        state_inv@.namespace() == invariants::state_inv_id(),
+{
+    let tracked server_inv;
     proof {
-        state_inv = invariants::get_system_state();
+        server_inv = state_inv.borrow().clone();
     }
-    EchoServer::new(listener, server_id, Tracked(state_inv))
+    EchoServer::new(listener, server_id, Tracked(server_inv))
 }
 
 } // verus!
+/// Polls `server` in the background (see [`verdist::server::spawn_pollers`])
 // Why is this unverified:
 // - minor: no support for tracing
 // - major: verus does not support threads
-pub fn run_modelled_server(server_id: u64) -> ModelledConnector<Response, Request>
-// requires
-    // server_ids@.contains(server_id),
+fn spawn_server<L, C, V>(server: EchoServer<L, C, V>) where
+    L: Listener<C> + Send + Sync + 'static,
+    C: Channel<R = Request<V>, S = Response<V>, Id = (u64, u64), K = ChannelInv>
+        + Send
+        + Sync
+        + 'static,
+    V: EchoPayload + Send + Sync + 'static,
 {
-    let (listener, connector) = verdist::network::modelled::listen_channel(server_id);
-    std::thread::spawn(move || {
-        let server = Arc::new(create_server::<_, _>(server_id, listener));
-        vlib::veprintln!("[server|{:>3}]: starting", server.id);
-
-        std::thread::scope(|s| {
-            for _ in 0..5 {
-                let serv = server.clone();
-                s.spawn(move || while serv.poll() {});
-            }
-        });
-    });
+    vlib::veprintln!("[server|{:>3}]: starting", server.id);
+    verdist::server::spawn_pollers(server, |server| server.poll());
+}
 
+// Why is this unverified:
+// - minor: no support for tracing
+// - major: verus does not support threads
+pub fn run_modelled_server<V>(
+    server_id: u64,
+    state_inv: &Tracked<Arc<StateInvariant<V>>>,
+) -> ModelledConnector<Response<V>, Request<V>> where
+    V: EchoPayload + Send + Sync + 'static,
+{
+    let (listener, connector) = verdist::network::modelled::listen_channel(server_id);
+    spawn_server(create_server::<_, _, V>(server_id, listener, state_inv));
     connector
 }
+
+/// Runs a server listening on the Unix domain socket at `path` (see [`verdist::network::uds`])
+// Why is this unverified:
+// - minor: no support for tracing
+// - major: verus does not support threads
+pub fn run_uds_server<V>(
+    server_id: u64,
+    path: impl AsRef<Path>,
+    state_inv: &Tracked<Arc<StateInvariant<V>>>,
+) -> std::io::Result<UdsConnector<Response<V>, Request<V>>> where
+    V: EchoPayload + Codec + Send + Sync + 'static,
+{
+    let (listener, connector) = verdist::network::uds::listen_uds(server_id, path)?;
+    spawn_server(create_server::<_, _, V>(server_id, listener, state_inv));
+    Ok(connector)
+}
@@ -1,3 +1,7 @@
+use std::sync::Arc;
+
+use vstd::invariant::AtomicInvariant;
+use vstd::invariant::InvariantPredicate;
 use vstd::logatom::MutLinearizer;
 use vstd::logatom::MutOperation;
 use vstd::logatom::ReadLinearizer;
@@ -13,6 +17,12 @@ pub trait AbdError<L, Op> {
     spec fn err_ensures(self, op: Op, lin: L) -> bool;
 }
 
+/// Errors for conditional writes: on top of regular write errors they can report a conflict
+pub trait AbdConditionalError<L, Op, Ts>: AbdError<L, Op> {
+    /// The timestamp the register was observed at, if the write was not attempted because of it
+    spec fn conflict(self) -> Option<Ts>;
+}
+
 // NOTE: LIMITATION
 // - The MutLinearizer should be specified in the method
 // - Type problem: the linearization queue is parametrized by the linearizer type
@@ -26,6 +36,8 @@ pub trait AbdRegisterClient<C, ML, RL> where
 
     type WriteErr: AbdError<ML, RegisterWrite>;
 
+    type CondWriteErr: AbdConditionalError<ML, RegisterWrite, Self::Timestamp>;
+
     type Timestamp;
 
     spec fn read_lin_requires(lin: RL) -> bool;
@@ -88,6 +100,57 @@ pub trait AbdRegisterClient<C, ML, RL> where
                 err.err_ensures(op, lin@)
             }),
     ;
+
+    /// Write `value` only if the register is still at timestamp `expected`
+    ///
+    /// NOTE: this is a write-if-unchanged, not a compare-and-set (which would need consensus).
+    /// The condition is checked against the timestamp of a quorum, before the write physically
+    /// starts: if that timestamp is not `expected`, nothing is written and the conflicting
+    /// timestamp is reported. Otherwise, this proceeds as a regular `write`; concurrent writes may
+    /// still be ordered between the check and the write.
+    ///
+    /// The write linearizes as a [`RegisterConditionalWrite`], whose result is the timestamp the
+    /// quorum was observed at: it is `expected`.
+    fn write_if_unchanged(
+        &mut self,
+        expected: Self::Timestamp,
+        value: Option<u64>,
+        lin: Tracked<ML>,
+    ) -> (r: Result<
+        Tracked<<ML as MutLinearizer<RegisterWrite>>::Completion>,
+        Self::CondWriteErr,
+    >) where
+        ML: ConditionalWriteLinearizer<Self::Timestamp>,
+
+        requires
+            old(self).inv(),
+            lin@.pre(
+                RegisterConditionalWrite {
+                    id: Ghost(old(self).register_loc()),
+                    expected,
+                    new_value: value,
+                },
+            ),
+            Self::write_lin_requires(lin@),
+        ensures
+            final(self).inv(),
+            final(self).register_loc() == old(self).register_loc(),
+            r is Ok ==> ({
+                let comp = r->Ok_0;
+                let op = RegisterConditionalWrite {
+                    id: Ghost(final(self).register_loc()),
+                    expected,
+                    new_value: value,
+                };
+                &&& lin@.post(op, expected, comp@)
+            }),
+            r is Err ==> ({
+                let err = r->Err_0;
+                let op = RegisterWrite { id: Ghost(final(self).register_loc()), new_value: value };
+                &&& err.err_ensures(op, lin@)
+                &&& err.conflict() is Some ==> err.conflict()->Some_0 != expected
+            }),
+    ;
 }
 
 pub struct RegisterRead {
@@ -219,4 +282,287 @@ impl MutLinearizer<RegisterWrite> for OwnedWritePerm {
     }
 }
 
+/// Write `new_value` to a register last observed at timestamp `expected`
+///
+/// Registers without consensus cannot compare and write atomically: the condition is on what the
+/// implementation observed before the write, its result (e.g., the timestamp of a quorum), which
+/// has to be `expected` for the write to apply. Concurrent writes may still linearize in between.
+pub struct RegisterConditionalWrite<Ts> {
+    /// resource location
+    pub id: Ghost<Loc>,
+    pub expected: Ts,
+    pub new_value: Option<u64>,
+}
+
+impl<Ts> RegisterConditionalWrite<Ts> {
+    /// The write this turns into
+    pub open spec fn write(self) -> RegisterWrite {
+        RegisterWrite { id: self.id, new_value: self.new_value }
+    }
+}
+
+impl<Ts> MutOperation for RegisterConditionalWrite<Ts> {
+    type Resource = GhostVarAuth<Option<u64>>;
+
+    /// The timestamp the register was observed at
+    type ExecResult = Ts;
+
+    type NewState = ();
+
+    open spec fn requires(
+        self,
+        pre: Self::Resource,
+        new_state: Self::NewState,
+        e: Self::ExecResult,
+    ) -> bool {
+        &&& pre.id() == self.id
+        &&& e == self.expected
+    }
+
+    open spec fn ensures(
+        self,
+        pre: Self::Resource,
+        post: Self::Resource,
+        new_state: Self::NewState,
+    ) -> bool {
+        &&& pre.id() == post.id()
+        &&& post@ == self.new_value
+    }
+}
+
+/// Write linearizers which also linearize conditional writes
+///
+/// A conditional write is carried out as the regular write it turns into: the lemmas carry the
+/// precondition of the conditional write over to the write, and the postcondition back, once the
+/// implementation established that it observed the `expected` timestamp
+pub trait ConditionalWriteLinearizer<Ts>: MutLinearizer<RegisterWrite> + MutLinearizer<
+    RegisterConditionalWrite<Ts>,
+    Completion = <Self as MutLinearizer<RegisterWrite>>::Completion,
+> {
+    proof fn lemma_conditional_pre(self, op: RegisterConditionalWrite<Ts>)
+        requires
+            <Self as MutLinearizer<RegisterConditionalWrite<Ts>>>::pre(self, op),
+        ensures
+            <Self as MutLinearizer<RegisterWrite>>::pre(self, op.write()),
+            <Self as MutLinearizer<RegisterWrite>>::namespaces(self)
+                == <Self as MutLinearizer<RegisterConditionalWrite<Ts>>>::namespaces(self),
+    ;
+
+    proof fn lemma_conditional_post(
+        self,
+        op: RegisterConditionalWrite<Ts>,
+        observed: Ts,
+        completion: <Self as MutLinearizer<RegisterWrite>>::Completion,
+    )
+        requires
+            <Self as MutLinearizer<RegisterConditionalWrite<Ts>>>::pre(self, op),
+            <Self as MutLinearizer<RegisterWrite>>::post(self, op.write(), (), completion),
+            observed == op.expected,
+        ensures
+            <Self as MutLinearizer<RegisterConditionalWrite<Ts>>>::post(
+                self,
+                op,
+                observed,
+                completion,
+            ),
+    ;
+}
+
+impl<Ts> MutLinearizer<RegisterConditionalWrite<Ts>> for OwnedWritePerm {
+    type Completion = GhostVar<Option<u64>>;
+
+    open spec fn namespaces(self) -> Set<int> {
+        Set::empty()
+    }
+
+    open spec fn pre(self, op: RegisterConditionalWrite<Ts>) -> bool {
+        op.id == self.register.id()
+    }
+
+    /// The write applied having observed the `expected` timestamp
+    open spec fn post(
+        self,
+        op: RegisterConditionalWrite<Ts>,
+        exec_res: Ts,
+        completion: Self::Completion,
+    ) -> bool {
+        &&& op.id == self.register.id()
+        &&& op.id == completion.id()
+        &&& op.new_value == completion@
+        &&& exec_res == op.expected
+    }
+
+    proof fn apply(
+        tracked self,
+        op: RegisterConditionalWrite<Ts>,
+        tracked resource: &mut GhostVarAuth<Option<u64>>,
+        new_state: (),
+        exec_res: &Ts,
+    ) -> (tracked result: Self::Completion) {
+        let tracked OwnedWritePerm { value, mut register } = self;
+
+        resource.update(&mut register, op.new_value);
+        register
+    }
+
+    proof fn peek(
+        tracked &self,
+        op: RegisterConditionalWrite<Ts>,
+        tracked resource: &GhostVarAuth<Option<u64>>,
+    ) {
+    }
+}
+
+impl<Ts> ConditionalWriteLinearizer<Ts> for OwnedWritePerm {
+    proof fn lemma_conditional_pre(self, op: RegisterConditionalWrite<Ts>) {
+    }
+
+    proof fn lemma_conditional_post(
+        self,
+        op: RegisterConditionalWrite<Ts>,
+        observed: Ts,
+        completion: GhostVar<Option<u64>>,
+    ) {
+    }
+}
+
+/// The register view lives in the invariant, at the location of the register
+pub struct RegisterViewPredicate {}
+
+impl InvariantPredicate<Loc, GhostVar<Option<u64>>> for RegisterViewPredicate {
+    open spec fn inv(loc: Loc, view: GhostVar<Option<u64>>) -> bool {
+        view.id() == loc
+    }
+}
+
+/// A register view shared by several clients (the constant is the location of the register)
+pub type SharedRegisterView = AtomicInvariant<Loc, GhostVar<Option<u64>>, RegisterViewPredicate>;
+
+/// Read linearizer which goes through a [`SharedRegisterView`]
+///
+/// Unlike [`OwnedReadPerm`], the completion says nothing about the value: other clients may have
+/// changed the register by the time it is handed back
+pub struct SharedReadPerm {
+    pub tracked view: Arc<SharedRegisterView>,
+}
+
+impl ReadLinearizer<RegisterRead> for SharedReadPerm {
+    type Completion = ();
+
+    open spec fn namespaces(self) -> Set<int> {
+        set![self.view.namespace()]
+    }
+
+    open spec fn pre(self, op: RegisterRead) -> bool {
+        op.id == self.view.constant()
+    }
+
+    open spec fn post(self, op: RegisterRead, exec_res: Option<u64>, completion: ()) -> bool {
+        op.id == self.view.constant()
+    }
+
+    proof fn apply(
+        tracked self,
+        op: RegisterRead,
+        tracked resource: &GhostVarAuth<Option<u64>>,
+        exec_res: &Option<u64>,
+    ) -> (tracked result: ()) {
+    }
+
+    proof fn peek(tracked &self, op: RegisterRead, tracked resource: &GhostVarAuth<Option<u64>>) {
+    }
+}
+
+/// Write linearizer which goes through a [`SharedRegisterView`]
+pub struct SharedWritePerm {
+    pub value: Option<u64>,
+    pub tracked view: Arc<SharedRegisterView>,
+}
+
+impl MutLinearizer<RegisterWrite> for SharedWritePerm {
+    type Completion = ();
+
+    open spec fn namespaces(self) -> Set<int> {
+        set![self.view.namespace()]
+    }
+
+    open spec fn pre(self, op: RegisterWrite) -> bool {
+        op.id == self.view.constant()
+    }
+
+    open spec fn post(self, op: RegisterWrite, exec_res: (), completion: ()) -> bool {
+        op.id == self.view.constant()
+    }
+
+    proof fn apply(
+        tracked self,
+        op: RegisterWrite,
+        tracked resource: &mut GhostVarAuth<Option<u64>>,
+        new_state: (),
+        exec_res: &(),
+    ) -> (tracked result: ()) {
+        vstd::open_atomic_invariant_in_proof!(&self.view => register => {
+            resource.update(&mut register, op.new_value);
+        });
+    }
+
+    proof fn peek(tracked &self, op: RegisterWrite, tracked resource: &GhostVarAuth<Option<u64>>) {
+    }
+}
+
+impl<Ts> MutLinearizer<RegisterConditionalWrite<Ts>> for SharedWritePerm {
+    type Completion = ();
+
+    open spec fn namespaces(self) -> Set<int> {
+        set![self.view.namespace()]
+    }
+
+    open spec fn pre(self, op: RegisterConditionalWrite<Ts>) -> bool {
+        op.id == self.view.constant()
+    }
+
+    /// The write applied having observed the `expected` timestamp
+    open spec fn post(
+        self,
+        op: RegisterConditionalWrite<Ts>,
+        exec_res: Ts,
+        completion: (),
+    ) -> bool {
+        &&& op.id == self.view.constant()
+        &&& exec_res == op.expected
+    }
+
+    proof fn apply(
+        tracked self,
+        op: RegisterConditionalWrite<Ts>,
+        tracked resource: &mut GhostVarAuth<Option<u64>>,
+        new_state: (),
+        exec_res: &Ts,
+    ) -> (tracked result: ()) {
+        vstd::open_atomic_invariant_in_proof!(&self.view => register => {
+            resource.update(&mut register, op.new_value);
+        });
+    }
+
+    proof fn peek(
+        tracked &self,
+        op: RegisterConditionalWrite<Ts>,
+        tracked resource: &GhostVarAuth<Option<u64>>,
+    ) {
+    }
+}
+
+impl<Ts> ConditionalWriteLinearizer<Ts> for SharedWritePerm {
+    proof fn lemma_conditional_pre(self, op: RegisterConditionalWrite<Ts>) {
+    }
+
+    proof fn lemma_conditional_post(
+        self,
+        op: RegisterConditionalWrite<Ts>,
+        observed: Ts,
+        completion: (),
+    ) {
+    }
+}
+
 } // verus!
@@ -2,27 +2,162 @@ use vstd::prelude::*;
 
 verus! {
 
-pub trait EchoError {
-    type Val;
+/// A value which can be echoed
+///
+/// What comes back is a copy of the value sent, which is only known to be equivalent to it (e.g.,
+/// a `Vec` with the same contents)
+pub trait EchoVal: Sized {
+    spec fn spec_eq(self, other: Self) -> bool;
 
-    spec fn err_ensures(self, v: Self::Val) -> bool;
+    proof fn spec_eq_refl(a: Self)
+        ensures
+            a.spec_eq(a),
+    ;
+
+    proof fn spec_eq_symm(a: Self, b: Self)
+        requires
+            a.spec_eq(b),
+        ensures
+            b.spec_eq(a),
+    ;
+
+    proof fn spec_eq_trans(a: Self, b: Self, c: Self)
+        requires
+            a.spec_eq(b),
+            b.spec_eq(c),
+        ensures
+            a.spec_eq(c),
+    ;
+}
+
+impl EchoVal for String {
+    open spec fn spec_eq(self, other: Self) -> bool {
+        self == other
+    }
+
+    proof fn spec_eq_refl(a: Self) {
+    }
+
+    proof fn spec_eq_symm(a: Self, b: Self) {
+    }
+
+    proof fn spec_eq_trans(a: Self, b: Self, c: Self) {
+    }
+}
+
+impl EchoVal for Vec<u8> {
+    open spec fn spec_eq(self, other: Self) -> bool {
+        self@ == other@
+    }
+
+    proof fn spec_eq_refl(a: Self) {
+    }
+
+    proof fn spec_eq_symm(a: Self, b: Self) {
+    }
+
+    proof fn spec_eq_trans(a: Self, b: Self, c: Self) {
+    }
+}
+
+pub trait EchoError<V> {
+    /// What the error guarantees about `v`, the message which failed to be echoed
+    ///
+    /// e.g., that the error hands it back
+    spec fn err_ensures(self, v: V) -> bool;
+
+    /// Whether the server answered the request with an error
+    ///
+    /// Servers only do that when asked to (see [`EchoClient::echo_error`])
+    spec fn rejected(self) -> bool;
 }
 
 #[allow(dead_code)]
 pub trait EchoClient<C> where  {
-    type Error: EchoError<Val = Self::Val>;
+    type Error: EchoError<Self::Val>;
 
-    type Val;
+    type Val: EchoVal;
 
     fn echo(&mut self, v: Self::Val) -> (r: Result<Self::Val, Self::Error>)
         ensures
             r is Ok ==> ({
                 let r_v = r->Ok_0;
-                v == r_v
+                v.spec_eq(r_v)
+            }),
+            r is Err ==> ({
+                let err = r->Err_0;
+                &&& err.err_ensures(v)
+                &&& !err.rejected()
+            }),
+    ;
+
+    /// Echoes a message, which the server holds on to for `delay_ms` first
+    ///
+    /// The delay is not part of the spec: it is there to exercise timeouts
+    fn echo_after(&mut self, v: Self::Val, delay_ms: u64) -> (r: Result<Self::Val, Self::Error>)
+        ensures
+            r is Ok ==> ({
+                let r_v = r->Ok_0;
+                v.spec_eq(r_v)
+            }),
+            r is Err ==> ({
+                let err = r->Err_0;
+                &&& err.err_ensures(v)
+                &&& !err.rejected()
+            }),
+    ;
+
+    /// Asks the server to answer with an error: this never succeeds
+    fn echo_error(&mut self, v: Self::Val) -> (r: Result<Self::Val, Self::Error>)
+        ensures
+            r is Err,
+            r->Err_0.err_ensures(v),
+    ;
+
+    /// Sends a message the server does not answer
+    ///
+    /// Succeeds once the message is sent
+    fn sink(&mut self, v: Self::Val) -> (r: Result<(), Self::Error>)
+        ensures
+            r is Err ==> ({
+                let err = r->Err_0;
+                &&& err.err_ensures(v)
+                &&& !err.rejected()
+            }),
+    ;
+}
+
+/// An echo client which broadcasts to several servers
+///
+/// An echo only completes once `quorum_size` servers echoed the message back
+#[allow(dead_code)]
+pub trait BroadcastEchoClient<C> where  {
+    type Error: EchoError<Self::Val>;
+
+    type Val: EchoVal;
+
+    spec fn spec_quorum_size(self) -> nat;
+
+    fn quorum_size(&self) -> (r: usize)
+        ensures
+            r == self.spec_quorum_size(),
+    ;
+
+    /// Echoes a message through a quorum of servers
+    ///
+    /// Returns the echoed message, along with how many servers echoed it
+    fn echo(&mut self, v: Self::Val) -> (r: Result<(Self::Val, usize), Self::Error>)
+        ensures
+            final(self).spec_quorum_size() == old(self).spec_quorum_size(),
+            r is Ok ==> ({
+                let (r_v, n_echoed) = r->Ok_0;
+                &&& v.spec_eq(r_v)
+                &&& n_echoed >= old(self).spec_quorum_size()
             }),
             r is Err ==> ({
                 let err = r->Err_0;
-                err.err_ensures(v)
+                &&& err.err_ensures(v)
+                &&& !err.rejected()
             }),
     ;
 }
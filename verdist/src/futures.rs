@@ -0,0 +1,408 @@
+//! Futures over the (polling) verified cores
+//!
+//! Everything in verdist is non-blocking at heart: channels are polled with `try_recv`, and
+//! requests make progress with [`RequestContext::poll_step`]. The futures here drive those same
+//! operations, and when there is nothing to do yet they ask to be polled again after a backoff
+//! (the channels have no way to signal that a message arrived). They do not depend on any
+//! particular runtime (e.g., they can be awaited under tokio); [`block_on`] is a minimal local
+//! executor for when there is none.
+//!
+//! These are trusted shims: verus does not reason about futures, so the preconditions of the
+//! wrapped operations (e.g., the `send_inv` of [`RpcChannel::async_invoke`]) are on the caller.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::future::poll_fn;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Wake;
+use std::task::Waker;
+use std::thread::Thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::network::channel::BufChannel;
+use crate::network::channel::Channel;
+use crate::network::error::InvokeError;
+use crate::network::error::TryRecvError;
+use crate::pool::ChannelId;
+use crate::pool::ChannelResp;
+use crate::pool::ConnectionPool;
+use crate::pool::PoolChannel;
+use crate::rpc::proto::TaggedMessage;
+use crate::rpc::replies::ReplyAccumulator;
+use crate::rpc::request_context::WaitStatus;
+use crate::rpc::rpc_channel::RpcChannel;
+use crate::rpc::rpc_channel::RpcContext;
+use crate::rpc::Replies;
+use crate::rpc::RequestContext;
+
+use vstd::invariant::InvariantPredicate;
+
+/// Delay before retrying an operation which was not ready
+const MIN_BACKOFF: Duration = Duration::from_micros(20);
+
+/// Longest delay between two attempts at an operation
+const MAX_BACKOFF: Duration = Duration::from_millis(5);
+
+/// How long a thread running blocking operations (see [`unblock`]) waits for more work
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exponential backoff between the attempts at a non-blocking operation
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Backoff { delay: MIN_BACKOFF }
+    }
+
+    /// Turns the result of a non-blocking attempt into a poll
+    ///
+    /// If the attempt is not ready yet, the task is woken up to try again once the backoff
+    /// elapses, and the backoff doubles (up to [`MAX_BACKOFF`])
+    fn retry_later<T>(&mut self, cx: &mut Context<'_>, attempt: Option<T>) -> Poll<T> {
+        match attempt {
+            Some(t) => Poll::Ready(t),
+            None => {
+                timer().wake_after(self.delay, cx.waker().clone());
+                self.delay = (self.delay * 2).min(MAX_BACKOFF);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A task to wake up at some point
+struct Deadline {
+    at: Instant,
+    waker: Waker,
+}
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at
+    }
+}
+
+impl Eq for Deadline {}
+
+impl PartialOrd for Deadline {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Deadline {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.at.cmp(&other.at)
+    }
+}
+
+/// Wakes up tasks once their deadline passes, from a thread of its own
+struct Timer {
+    deadlines: Mutex<BinaryHeap<Reverse<Deadline>>>,
+    cvar: Condvar,
+}
+
+impl Timer {
+    fn wake_after(&self, delay: Duration, waker: Waker) {
+        let deadline = Deadline { at: Instant::now() + delay, waker };
+        let mut deadlines = self.deadlines.lock().expect("timer lock should not be poisoned");
+        let earliest = deadlines.peek().is_none_or(|Reverse(next)| deadline.at < next.at);
+        deadlines.push(Reverse(deadline));
+        if earliest {
+            self.cvar.notify_one();
+        }
+    }
+
+    fn run(&self) {
+        let mut deadlines = self.deadlines.lock().expect("timer lock should not be poisoned");
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            while let Some(Reverse(next)) = deadlines.peek() {
+                if next.at > now {
+                    break;
+                }
+                if let Some(Reverse(deadline)) = deadlines.pop() {
+                    expired.push(deadline.waker);
+                }
+            }
+
+            if !expired.is_empty() {
+                drop(deadlines);
+                for waker in expired {
+                    waker.wake();
+                }
+                deadlines = self.deadlines.lock().expect("timer lock should not be poisoned");
+                continue;
+            }
+
+            deadlines = match deadlines.peek() {
+                Some(Reverse(next)) => {
+                    let timeout = next.at.saturating_duration_since(now);
+                    self.cvar
+                        .wait_timeout(deadlines, timeout)
+                        .expect("timer lock should not be poisoned")
+                        .0
+                }
+                None => self.cvar.wait(deadlines).expect("timer lock should not be poisoned"),
+            };
+        }
+    }
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<&'static Timer> = OnceLock::new();
+    *TIMER.get_or_init(|| {
+        let timer: &'static Timer = Box::leak(Box::new(Timer {
+            deadlines: Mutex::new(BinaryHeap::new()),
+            cvar: Condvar::new(),
+        }));
+        std::thread::spawn(move || timer.run());
+        timer
+    })
+}
+
+/// Receives the next message from the channel
+pub async fn recv<C: Channel>(channel: &C) -> Result<C::R, TryRecvError> {
+    let mut backoff = Backoff::new();
+    poll_fn(|cx| {
+        let attempt = match channel.try_recv() {
+            Err(TryRecvError::Empty) => None,
+            res => Some(res),
+        };
+        backoff.retry_later(cx, attempt)
+    })
+    .await
+}
+
+/// Receives the message tagged with `tag` from the channel (see [`BufChannel::try_recv_tag`])
+pub async fn recv_tag<C>(channel: &BufChannel<C>, tag: u64) -> Result<C::R, TryRecvError>
+where
+    C: Channel,
+    C::Id: std::fmt::Debug,
+    C::R: TaggedMessage,
+{
+    let mut backoff = Backoff::new();
+    poll_fn(|cx| {
+        let attempt = channel.try_recv_tag(tag).transpose();
+        backoff.retry_later(cx, attempt)
+    })
+    .await
+}
+
+/// Waits for the reply to an outstanding request (see [`RpcContext::wait`])
+pub async fn wait<C>(ctx: &RpcContext<'_, C>) -> Result<C::R, TryRecvError>
+where
+    C: Channel,
+    C::Id: std::fmt::Debug,
+    C::R: TaggedMessage,
+    C::S: TaggedMessage,
+{
+    let mut backoff = Backoff::new();
+    poll_fn(|cx| {
+        let attempt = ctx.try_poll().transpose();
+        backoff.retry_later(cx, attempt)
+    })
+    .await
+}
+
+/// Sends a request and waits for its reply (see [`RpcChannel::invoke`])
+pub async fn invoke<C>(channel: &RpcChannel<C>, req: &C::S) -> Result<C::R, InvokeError<C::S>>
+where
+    C: Channel,
+    C::Id: std::fmt::Debug,
+    C::R: TaggedMessage,
+    C::S: TaggedMessage,
+{
+    let ctx = channel.async_invoke(req)?;
+    Ok(wait(&ctx).await?)
+}
+
+/// Waits for the termination condition of a request (see [`RequestContext::wait_for`])
+#[allow(clippy::type_complexity)]
+pub async fn wait_for<Pool, Pred, A, F>(
+    ctx: RequestContext<'_, Pool, Pred, A>,
+    termination_cond: F,
+) -> Result<Replies<PoolChannel<Pool>, Pred, A>, Replies<PoolChannel<Pool>, Pred, A>>
+where
+    Pool: ConnectionPool,
+    ChannelId<Pool>: std::fmt::Debug + Clone,
+    ChannelResp<Pool>: TaggedMessage,
+    Pred: InvariantPredicate<Pred, A>,
+    A: ReplyAccumulator<PoolChannel<Pool>, Pred>,
+    F: Fn(&Replies<PoolChannel<Pool>, Pred, A>) -> bool,
+{
+    let mut ctx = Some(ctx);
+    let mut backoff = Backoff::new();
+    poll_fn(|cx| {
+        let status = ctx
+            .as_mut()
+            .expect("should not poll after completion")
+            .poll_step(&termination_cond);
+        let attempt = match status {
+            WaitStatus::Pending => None,
+            WaitStatus::Done => ctx.take().map(|ctx| Ok(ctx.into_replies())),
+            WaitStatus::GaveUp => ctx.take().map(|ctx| Err(ctx.into_replies())),
+        };
+        backoff.retry_later(cx, attempt)
+    })
+    .await
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct BlockingPoolState {
+    jobs: VecDeque<Job>,
+    /// Threads waiting for a job
+    idle: usize,
+}
+
+/// Threads running the blocking operations handed to [`unblock`]
+///
+/// Threads are reused across operations: a new one only starts when every thread is busy, and
+/// threads exit once they are idle for [`IDLE_TIMEOUT`]
+struct BlockingPool {
+    state: Mutex<BlockingPoolState>,
+    cvar: Condvar,
+}
+
+impl BlockingPool {
+    fn run(&'static self, job: Job) {
+        let mut state = self.state.lock().expect("blocking pool lock should not be poisoned");
+        state.jobs.push_back(job);
+        if state.jobs.len() > state.idle {
+            std::thread::spawn(move || self.work());
+        } else {
+            self.cvar.notify_one();
+        }
+    }
+
+    fn work(&self) {
+        let mut state = self.state.lock().expect("blocking pool lock should not be poisoned");
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().expect("blocking pool lock should not be poisoned");
+                continue;
+            }
+
+            state.idle += 1;
+            let (new_state, timeout) = self
+                .cvar
+                .wait_timeout(state, IDLE_TIMEOUT)
+                .expect("blocking pool lock should not be poisoned");
+            state = new_state;
+            state.idle -= 1;
+            if timeout.timed_out() && state.jobs.is_empty() {
+                return;
+            }
+        }
+    }
+}
+
+fn blocking_pool() -> &'static BlockingPool {
+    static POOL: OnceLock<BlockingPool> = OnceLock::new();
+    POOL.get_or_init(|| BlockingPool {
+        state: Mutex::new(BlockingPoolState { jobs: VecDeque::new(), idle: 0 }),
+        cvar: Condvar::new(),
+    })
+}
+
+struct UnblockState<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Runs a blocking operation on a separate thread, resolving when it is done
+///
+/// This is how whole client operations (e.g., reads and writes on an `AbdPool`, which move the
+/// client into and back out of `f`) can be awaited concurrently from a single runtime. The
+/// threads are shared between operations (see [`BlockingPool`]).
+pub async fn unblock<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(UnblockState { result: None, waker: None }));
+    let job_state = state.clone();
+    blocking_pool().run(Box::new(move || {
+        let result = f();
+        let mut state = job_state.lock().expect("unblock lock should not be poisoned");
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }));
+
+    poll_fn(|cx| {
+        let mut state = state.lock().expect("unblock lock should not be poisoned");
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    })
+    .await
+}
+
+/// Runs futures concurrently, resolving to their outputs (in the same order)
+pub async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(|fut| Some(Box::pin(fut))).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        let mut done = true;
+        for (slot, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if let Some(fut) = slot {
+                match fut.as_mut().poll(cx) {
+                    Poll::Ready(out) => {
+                        *output = Some(out);
+                        *slot = None;
+                    }
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(
+                outputs.iter_mut().map(|out| out.take().expect("future should be done")).collect(),
+            )
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future to completion on the current thread
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
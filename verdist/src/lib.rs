@@ -1,3 +1,6 @@
+#[cfg(feature = "async")]
+pub mod futures;
 pub mod network;
 pub mod pool;
 pub mod rpc;
+pub mod server;
@@ -6,34 +6,14 @@ use crate::network::error::ConnectError;
 use crate::network::error::SendError;
 use crate::network::error::TryListenError;
 use crate::network::error::TryRecvError;
+use crate::network::latency::Latency;
 use crate::rpc::proto::TaggedMessage;
 
-use rand_distr::{Distribution, Normal};
-
 use vstd::prelude::*;
 use vstd::rwlock::RwLock;
 
-fn park_thread(mean: Duration, std_dev: Duration) {
-    let normal = Normal::new(mean.as_secs_f64(), std_dev.as_secs_f64())
-        .expect("should be able to construct normal distribution");
-    let wait = normal.sample(&mut rand::rng());
-    if wait.is_sign_positive() {
-        std::thread::sleep(Duration::from_secs_f64(wait));
-    }
-}
-
-fn default_delay() -> (Duration, Duration) {
-    Default::default()
-}
-
 verus! {
 
-pub assume_specification[ park_thread ](mean: Duration, std_dev: Duration)
-;
-
-pub assume_specification[ default_delay ]() -> (a: (Duration, Duration))
-;
-
 pub trait ChannelInvariant<K, Id, R, S> {
     spec fn recv_inv(k: K, id: Id, r: R) -> bool;
 
@@ -107,7 +87,8 @@ pub trait Channel {
 
     spec fn spec_id(self) -> Self::Id;
 
-    fn add_latency(&mut self, _avg: Duration, _stddev: Duration)
+    /// Models the latency of the messages on this channel (see [`crate::network::latency`])
+    fn set_latency(&mut self, _latency: Latency)
         ensures
             final(self).spec_id() == old(self).spec_id(),
             final(self).constant() == old(self).constant(),
@@ -115,13 +96,14 @@ pub trait Channel {
     {
     }
 
-    fn delay(&self) -> (Duration, Duration) {
-        default_delay()
-    }
-
-    fn wait(&self) {
-        let (mean, std_dev) = self.delay();
-        park_thread(mean, std_dev);
+    /// Models normally distributed latency
+    fn add_latency(&mut self, avg: Duration, stddev: Duration)
+        ensures
+            final(self).spec_id() == old(self).spec_id(),
+            final(self).constant() == old(self).constant(),
+        no_unwind
+    {
+        self.set_latency(Latency::normal(avg, stddev));
     }
 
     spec fn constant(self) -> Self::K;
@@ -143,6 +125,216 @@ pub trait Connector<C> where C: Channel {
     ;
 }
 
+/// How many replies with unexpected tags a [`BufChannel`] keeps around
+///
+/// Replies are always dropped once their tag is finished (see [`BufChannel::finish`]); the policy
+/// bounds what is kept until then.
+#[derive(Clone, Copy)]
+pub enum BufferPolicy {
+    /// Keep every reply
+    Unbounded,
+    /// Finishing a tag also finishes every tag below it, dropping their replies (even those
+    /// arriving later)
+    ///
+    /// For clients which have a single request in flight, with increasing tags
+    LowWater,
+    /// Keep at most this many replies, evicting the least recently buffered (`Lru(0)` keeps
+    /// none)
+    Lru(usize),
+    /// Keep at most this many replies, dropping new ones when full
+    MaxSize(usize),
+}
+
+/// Replies received with a tag other than the one being waited for
+struct ReplyBuffer<R> {
+    replies: HashMap<u64, R>,
+    /// Buffered tags, oldest first
+    ///
+    /// Only kept for the policies which need it (see [`ReplyBuffer::tracks_order`])
+    order: Vec<u64>,
+    /// Replies with tags below this are dropped
+    low_water: u64,
+    /// Tags which were finished before their reply arrived, oldest first
+    ///
+    /// Their replies are dropped when they arrive (at most [`MAX_FINISHED`] are remembered)
+    finished: Vec<u64>,
+    policy: BufferPolicy,
+}
+
+/// How many tags finished before their reply arrived a [`ReplyBuffer`] remembers
+const MAX_FINISHED: usize = 1024;
+
+impl<R> ReplyBuffer<R> {
+    fn new(policy: BufferPolicy) -> (r: Self)
+        ensures
+            r.replies@.is_empty(),
+    {
+        ReplyBuffer {
+            replies: HashMap::new(),
+            order: Vec::new(),
+            low_water: 0,
+            finished: Vec::new(),
+            policy,
+        }
+    }
+
+    /// Whether the policy needs the order in which replies were buffered: to evict the oldest
+    /// ([`BufferPolicy::Lru`]) or to find the ones below the low water mark
+    /// ([`BufferPolicy::LowWater`])
+    fn tracks_order(&self) -> bool {
+        match self.policy {
+            BufferPolicy::Lru(_) | BufferPolicy::LowWater => true,
+            BufferPolicy::Unbounded | BufferPolicy::MaxSize(_) => false,
+        }
+    }
+
+    /// Takes the reply tagged with `tag` out of the buffer
+    fn take(&mut self, tag: u64) -> (r: Option<R>)
+        ensures
+            final(self).replies@ == old(self).replies@.remove(tag),
+            r is Some <==> old(self).replies@.contains_key(tag),
+            r is Some ==> r->Some_0 == old(self).replies@[tag],
+    {
+        let r = self.replies.remove(&tag);
+        if r.is_some() && self.tracks_order() {
+            let mut idx = 0;
+            while idx < self.order.len()
+                invariant
+                    self.replies@ == old(self).replies@.remove(tag),
+                decreases self.order.len() - idx,
+            {
+                if self.order[idx] == tag {
+                    self.order.remove(idx);
+                    break ;
+                }
+                idx += 1;
+            }
+        }
+        r
+    }
+
+    /// Keeps `r` (with tag `tag`) around, unless the policy says otherwise
+    fn buffer(&mut self, tag: u64, r: R)
+        ensures
+            forall|t: u64| #[trigger]
+                final(self).replies@.contains_key(t) ==> {
+                    ||| old(self).replies@.contains_key(t) && final(self).replies@[t] == old(
+                        self,
+                    ).replies@[t]
+                    ||| t == tag && final(self).replies@[t] == r
+                },
+    {
+        if tag < self.low_water {
+            return ;
+        }
+        for idx in 0..self.finished.len()
+            invariant
+                self.finished@ == old(self).finished@,
+                self.replies@ == old(self).replies@,
+        {
+            if self.finished[idx] == tag {
+                // late reply: the one we were expecting
+                self.finished.remove(idx);
+                return ;
+            }
+        }
+        match self.policy {
+            BufferPolicy::MaxSize(max) => {
+                if self.replies.len() >= max {
+                    return ;
+                }
+            },
+            BufferPolicy::Lru(max) => {
+                if max == 0 {
+                    return ;
+                }
+                while self.replies.len() >= max && self.order.len() > 0
+                    invariant
+                        forall|t: u64| #[trigger]
+                            self.replies@.contains_key(t) ==> {
+                                &&& old(self).replies@.contains_key(t)
+                                &&& self.replies@[t] == old(self).replies@[t]
+                            },
+                    decreases self.order.len(),
+                {
+                    let oldest = self.order.remove(0);
+                    self.replies.remove(&oldest);
+                }
+            },
+            BufferPolicy::Unbounded | BufferPolicy::LowWater => {},
+        }
+        self.replies.insert(tag, r);
+        if self.tracks_order() {
+            self.order.push(tag);
+        }
+    }
+
+    /// Whether `tag` was finished before its reply arrived
+    fn is_finished(&self, tag: u64) -> bool {
+        for idx in 0..self.finished.len() {
+            if self.finished[idx] == tag {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Drops the reply tagged with `tag`, now or when it arrives (and, with
+    /// [`BufferPolicy::LowWater`], the replies with lower tags)
+    ///
+    /// Finishing a tag twice is the same as finishing it once.
+    fn finish(&mut self, tag: u64)
+        ensures
+            forall|t: u64| #[trigger]
+                final(self).replies@.contains_key(t) ==> {
+                    &&& old(self).replies@.contains_key(t)
+                    &&& final(self).replies@[t] == old(self).replies@[t]
+                },
+    {
+        let buffered = self.take(tag).is_some();
+        match self.policy {
+            BufferPolicy::LowWater => {},
+            _ => {
+                if !buffered && !self.is_finished(tag) {
+                    if self.finished.len() >= MAX_FINISHED {
+                        self.finished.remove(0);
+                    }
+                    self.finished.push(tag);
+                }
+                return ;
+            },
+        }
+        if tag < self.low_water {
+            return ;
+        }
+        self.low_water = if tag < u64::MAX {
+            tag + 1
+        } else {
+            tag
+        };
+
+        let ghost buffered_order = self.order@;
+        let mut order = Vec::new();
+        for idx in 0..self.order.len()
+            invariant
+                self.order@ == buffered_order,
+                forall|t: u64| #[trigger]
+                    self.replies@.contains_key(t) ==> {
+                        &&& old(self).replies@.contains_key(t)
+                        &&& self.replies@[t] == old(self).replies@[t]
+                    },
+        {
+            let t = self.order[idx];
+            if t < self.low_water {
+                self.replies.remove(&t);
+            } else {
+                order.push(t);
+            }
+        }
+        self.order = order;
+    }
+}
+
 #[allow(dead_code)]
 struct BufChannelInv<K, Id, S> {
     ghost channel_inv: K,
@@ -150,15 +342,15 @@ struct BufChannelInv<K, Id, S> {
     _marker: PhantomData<S>,
 }
 
-impl<K, Id, R, S> vstd::rwlock::RwLockPredicate<HashMap<u64, R>> for BufChannelInv<K, Id, S> where
+impl<K, Id, R, S> vstd::rwlock::RwLockPredicate<ReplyBuffer<R>> for BufChannelInv<K, Id, S> where
     K: ChannelInvariant<K, Id, R, S>,
     R: TaggedMessage,
  {
-    closed spec fn inv(self, v: HashMap<u64, R>) -> bool {
+    closed spec fn inv(self, v: ReplyBuffer<R>) -> bool {
         forall|tag: u64| #[trigger]
-            v@.contains_key(tag) ==> {
-                &&& v@[tag].spec_tag() == tag
-                &&& K::recv_inv(self.channel_inv, self.channel_id, v@[tag])
+            v.replies@.contains_key(tag) ==> {
+                &&& v.replies@[tag].spec_tag() == tag
+                &&& K::recv_inv(self.channel_inv, self.channel_id, v.replies@[tag])
             }
     }
 }
@@ -166,7 +358,7 @@ impl<K, Id, R, S> vstd::rwlock::RwLockPredicate<HashMap<u64, R>> for BufChannelI
 pub struct BufChannel<C> where C: Channel, C::R: TaggedMessage {
     channel: C,
     #[allow(dead_code)]
-    buffered: RwLock<HashMap<u64, C::R>, BufChannelInv<C::K, C::Id, C::S>>,
+    buffered: RwLock<ReplyBuffer<C::R>, BufChannelInv<C::K, C::Id, C::S>>,
 }
 
 impl<C> BufChannel<C> where C: Channel, C::R: TaggedMessage {
@@ -174,13 +366,21 @@ impl<C> BufChannel<C> where C: Channel, C::R: TaggedMessage {
         ensures
             r.spec_id() == channel.spec_id(),
             r.constant() == channel.constant(),
+    {
+        Self::with_policy(channel, BufferPolicy::Unbounded)
+    }
+
+    pub fn with_policy(channel: C, policy: BufferPolicy) -> (r: Self)
+        ensures
+            r.spec_id() == channel.spec_id(),
+            r.constant() == channel.constant(),
     {
         let ghost lock_pred = BufChannelInv {
             channel_inv: channel.constant(),
             channel_id: channel.spec_id(),
             _marker: PhantomData,
         };
-        BufChannel { channel, buffered: RwLock::new(HashMap::new(), Ghost(lock_pred)) }
+        BufChannel { channel, buffered: RwLock::new(ReplyBuffer::new(policy), Ghost(lock_pred)) }
     }
 
     #[verifier::type_invariant]
@@ -188,6 +388,13 @@ impl<C> BufChannel<C> where C: Channel, C::R: TaggedMessage {
         &&& self.buffered.pred().channel_inv == self.constant()
         &&& self.buffered.pred().channel_id == self.spec_id()
     }
+
+    /// Signals that no one will wait for replies tagged with `tag`, so they need not be kept
+    pub fn finish(&self, tag: u64) {
+        let (mut guard, handle) = self.buffered.acquire_write();
+        guard.finish(tag);
+        handle.release_write(guard);
+    }
 }
 
 impl<C> BufChannel<C> where C: Channel, C::R: TaggedMessage, C::Id: std::fmt::Debug {
@@ -203,7 +410,7 @@ impl<C> BufChannel<C> where C: Channel, C::R: TaggedMessage, C::Id: std::fmt::De
             use_type_invariant(self);
         }
         let (mut guard, handle) = self.buffered.acquire_write();
-        if let Some(r) = guard.remove(&tag) {
+        if let Some(r) = guard.take(tag) {
             handle.release_write(guard);
             return Ok(Some(r));
         }
@@ -220,7 +427,7 @@ impl<C> BufChannel<C> where C: Channel, C::R: TaggedMessage, C::Id: std::fmt::De
             Ok(r) => {
                 // vlib::veprintln!("[client]: received message on channel {:?} (wrong tag)", self.id());
                 let (mut guard, handle) = self.buffered.acquire_write();
-                guard.insert(r.tag(), r);
+                guard.buffer(r.tag(), r);
                 handle.release_write(guard);
                 Ok(None)
             },
@@ -261,19 +468,11 @@ impl<C> Channel for BufChannel<C> where C: Channel, C::R: TaggedMessage {
         self.channel.send(v)
     }
 
-    fn wait(&self) {
-        self.channel.wait();
-    }
-
-    fn delay(&self) -> (Duration, Duration) {
-        self.channel.delay()
-    }
-
-    fn add_latency(&mut self, avg: Duration, stddev: Duration) {
+    fn set_latency(&mut self, latency: Latency) {
         proof {
             use_type_invariant(&*self);
         }
-        self.channel.add_latency(avg, stddev);
+        self.channel.set_latency(latency);
     }
 }
 
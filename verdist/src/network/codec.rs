@@ -0,0 +1,415 @@
+//! Wire format of the messages sent over sockets
+//!
+//! A message is encoded by its [`Codec`] and sent as a frame: a big-endian `u32` length followed
+//! by that many bytes. Byte streams (e.g., [`crate::network::uds`]) write frames with a
+//! [`FrameWriter`] and read them back with a [`FrameReader`].
+//!
+//! Only the exec part of a message goes on the wire. Its ghost and tracked parts are assumed back
+//! when decoding ([`Decoder::tracked`]): a socket is outside of what is verified, so the channel
+//! invariants of a real transport are trusted, just like the ones of the modelled network. This
+//! is only allowed for frames received by a transport (see [`Trusted`]): that is the single trust
+//! boundary of a transport.
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+
+use crate::network::error::DecodeError;
+use crate::network::error::TryRecvError;
+
+use vstd::prelude::*;
+
+/// Largest frame that is sent or accepted
+pub const MAX_FRAME_LEN: usize = 64 << 20;
+
+/// How many bytes of frames a [`FrameWriter`] holds before refusing more
+const MAX_PENDING_LEN: usize = MAX_FRAME_LEN;
+
+const FRAME_HEADER_LEN: usize = 4;
+
+const READ_CHUNK_LEN: usize = 4096;
+
+/// Buffer a message is encoded into
+#[derive(Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn put_u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    /// Puts `v`, prefixed by its length
+    pub fn put_bytes(&mut self, v: &[u8]) {
+        self.put_u64(v.len() as u64);
+        self.buf.extend_from_slice(v);
+    }
+
+    /// The encoded message, as a frame
+    ///
+    /// Fails if the message is larger than [`MAX_FRAME_LEN`]
+    pub fn into_frame(self) -> std::io::Result<Vec<u8>> {
+        if self.buf.len() > MAX_FRAME_LEN {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + self.buf.len());
+        frame.extend_from_slice(&(self.buf.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&self.buf);
+        Ok(frame)
+    }
+}
+
+/// Permission to assume back the ghost and tracked parts of the messages in a frame
+///
+/// Only the receive path of a transport (e.g., [`crate::network::uds::UdsChannel`]) creates
+/// these: it is where the invariants of the channel are trusted.
+pub struct Trusted {
+    _private: (),
+}
+
+impl Trusted {
+    pub(crate) fn assume() -> Self {
+        Trusted { _private: () }
+    }
+}
+
+/// Frame a message is decoded from
+pub struct Decoder {
+    buf: Vec<u8>,
+    pos: usize,
+    trusted: bool,
+}
+
+impl Decoder {
+    /// Decoder for a frame whose messages have no ghost or tracked parts
+    pub fn new(buf: Vec<u8>) -> Self {
+        Decoder { buf, pos: 0, trusted: false }
+    }
+
+    /// Decoder for a frame received by a transport
+    pub(crate) fn trusted(buf: Vec<u8>, _trust: &Trusted) -> Self {
+        Decoder { buf, pos: 0, trusted: true }
+    }
+
+    /// Assumes back a tracked part of the message, which does not cross the wire
+    ///
+    /// Fails unless the frame was received by a transport (see [`Trusted`])
+    pub fn tracked<T>(&self) -> Result<Tracked<T>, DecodeError> {
+        if self.trusted {
+            Ok(Tracked::assume_new())
+        } else {
+            Err(DecodeError)
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], DecodeError> {
+        let end = self.pos.checked_add(len).ok_or(DecodeError)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(DecodeError)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, DecodeError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().map_err(|_| DecodeError)?))
+    }
+
+    /// Gets bytes put by [`Encoder::put_bytes`]
+    pub fn get_bytes(&mut self) -> Result<Vec<u8>, DecodeError> {
+        let len = usize::try_from(self.get_u64()?).map_err(|_| DecodeError)?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// Checks the whole frame was decoded
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if self.pos == self.buf.len() {
+            Ok(())
+        } else {
+            Err(DecodeError)
+        }
+    }
+}
+
+/// Encodes `msg` as a frame
+pub fn encode_frame<T: Codec>(msg: &T) -> std::io::Result<Vec<u8>> {
+    let mut enc = Encoder::new();
+    msg.encode(&mut enc);
+    enc.into_frame()
+}
+
+/// Decodes a frame (without its header) into a message
+///
+/// Without `trust`, the message cannot have ghost or tracked parts
+pub fn decode_frame<T: Codec>(frame: Vec<u8>, trust: Option<&Trusted>) -> Result<T, DecodeError> {
+    let mut dec = match trust {
+        Some(trust) => Decoder::trusted(frame, trust),
+        None => Decoder::new(frame),
+    };
+    let msg = T::decode(&mut dec)?;
+    dec.finish()?;
+    Ok(msg)
+}
+
+/// Writes `msg` as a frame to a blocking stream
+///
+/// Non-blocking streams go through a [`FrameWriter`] instead
+pub fn write_frame<W: Write, T: Codec>(w: &mut W, msg: &T) -> std::io::Result<()> {
+    w.write_all(&encode_frame(msg)?)?;
+    w.flush()
+}
+
+/// Writes frames to a non-blocking stream
+///
+/// What the stream does not take right away is held back, and written by later calls (to
+/// [`FrameWriter::send`] or [`FrameWriter::flush`]), so that the caller never waits on the peer
+#[derive(Default)]
+pub struct FrameWriter {
+    pending: Vec<u8>,
+}
+
+impl FrameWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends `msg` as a frame, after the frames which are held back
+    ///
+    /// Fails with `WouldBlock` if too much is held back already: the peer is not reading
+    pub fn send<W: Write, T: Codec>(&mut self, w: &mut W, msg: &T) -> std::io::Result<()> {
+        let frame = encode_frame(msg)?;
+        if !self.pending.is_empty() && self.pending.len() + frame.len() > MAX_PENDING_LEN {
+            self.flush(w)?;
+            if !self.pending.is_empty() && self.pending.len() + frame.len() > MAX_PENDING_LEN {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+        self.pending.extend_from_slice(&frame);
+        self.flush(w)
+    }
+
+    /// Writes what was held back, until the stream would block
+    pub fn flush<W: Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        let mut written = 0;
+        let res = loop {
+            if written == self.pending.len() {
+                break w.flush();
+            }
+            match w.write(&self.pending[written..]) {
+                Ok(0) => break Err(ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.pending.drain(..written);
+        res
+    }
+}
+
+/// Reassembles the frames read from a byte stream
+///
+/// Once the stream ends (or carries something which is not a frame) the reader reports
+/// `Disconnected`, after handing out the frames which were complete.
+#[derive(Default)]
+pub struct FrameReader {
+    buf: Vec<u8>,
+    broken: bool,
+}
+
+impl FrameReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the next complete frame out of the buffer
+    fn next_frame(&mut self) -> Option<Vec<u8>> {
+        if self.buf.len() < FRAME_HEADER_LEN {
+            return None;
+        }
+        let header = self.buf[..FRAME_HEADER_LEN].try_into().expect("header should be 4 bytes");
+        let len = u32::from_be_bytes(header) as usize;
+        if len > MAX_FRAME_LEN {
+            self.broken = true;
+            return None;
+        }
+        if self.buf.len() < FRAME_HEADER_LEN + len {
+            return None;
+        }
+        let frame = self.buf[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len].to_vec();
+        self.buf.drain(..FRAME_HEADER_LEN + len);
+        Some(frame)
+    }
+
+    fn decode_next<T: Codec>(
+        &mut self,
+        trust: Option<&Trusted>,
+    ) -> Option<Result<T, TryRecvError>> {
+        let frame = self.next_frame()?;
+        Some(decode_frame(frame, trust).map_err(|_| {
+            self.broken = true;
+            TryRecvError::Disconnected
+        }))
+    }
+
+    /// Reads from `r` until it would block, and returns the next message, if it is complete
+    ///
+    /// The messages are decoded with `trust` (see [`decode_frame`])
+    pub fn try_recv<R: Read, T: Codec>(
+        &mut self,
+        r: &mut R,
+        trust: Option<&Trusted>,
+    ) -> Result<T, TryRecvError> {
+        if let Some(msg) = self.decode_next(trust) {
+            return msg;
+        }
+        let mut chunk = [0; READ_CHUNK_LEN];
+        while !self.broken {
+            match r.read(&mut chunk) {
+                Ok(0) => self.broken = true,
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.broken = true,
+            }
+        }
+        match self.decode_next(trust) {
+            Some(msg) => msg,
+            None if self.broken => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Reads from `r` until the next message is complete
+    ///
+    /// Meant for blocking streams: a read timeout (or a non-blocking stream running dry) is
+    /// reported as `Disconnected`. The messages are decoded with `trust` (see [`decode_frame`])
+    pub fn recv<R: Read, T: Codec>(
+        &mut self,
+        r: &mut R,
+        trust: Option<&Trusted>,
+    ) -> Result<T, TryRecvError> {
+        let mut chunk = [0; READ_CHUNK_LEN];
+        loop {
+            if let Some(msg) = self.decode_next(trust) {
+                return msg;
+            }
+            if self.broken {
+                return Err(TryRecvError::Disconnected);
+            }
+            match r.read(&mut chunk) {
+                Ok(0) => self.broken = true,
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.broken = true,
+            }
+        }
+    }
+}
+
+verus! {
+
+#[verifier::external_type_specification]
+#[verifier::external_body]
+pub struct ExEncoder(Encoder);
+
+#[verifier::external_type_specification]
+#[verifier::external_body]
+pub struct ExDecoder(Decoder);
+
+/// Encoding of a message on the wire
+pub trait Codec: Sized {
+    /// Appends the encoding of `self` to `enc`
+    fn encode(&self, enc: &mut Encoder);
+
+    /// Decodes a value off the front of `dec`
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError>;
+}
+
+impl Codec for u64 {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        enc.put_u64(*self);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        dec.get_u64()
+    }
+}
+
+impl Codec for bool {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        enc.put_u8(*self as u8);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        match dec.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError),
+        }
+    }
+}
+
+impl Codec for String {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        enc.put_bytes(self.as_bytes());
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        String::from_utf8(dec.get_bytes()?).map_err(|_| DecodeError)
+    }
+}
+
+impl Codec for Vec<u8> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        enc.put_bytes(self);
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        dec.get_bytes()
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    #[verifier::external_body]
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            None => enc.put_u8(0),
+            Some(v) => {
+                enc.put_u8(1);
+                v.encode(enc);
+            },
+        }
+    }
+
+    #[verifier::external_body]
+    fn decode(dec: &mut Decoder) -> Result<Self, DecodeError> {
+        match dec.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(dec)?)),
+            _ => Err(DecodeError),
+        }
+    }
+}
+
+} // verus!
@@ -33,6 +33,10 @@ pub enum InvokeError<S> {
 #[derive(Debug)]
 pub struct ConnectError;
 
+/// A message could not be decoded (see [`crate::network::codec`])
+#[derive(Debug)]
+pub struct DecodeError;
+
 impl Error for TryListenError {
 
 }
@@ -49,6 +53,10 @@ impl Error for ConnectError {
 
 }
 
+impl Error for DecodeError {
+
+}
+
 impl<S: Display + Debug> Error for InvokeError<S> {
 
 }
@@ -125,6 +133,12 @@ impl Display for ConnectError {
     }
 }
 
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Error decoding")
+    }
+}
+
 impl<S: Display> Display for InvokeError<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -0,0 +1,215 @@
+//! Modelled message latency
+//!
+//! Rather than sleeping in the sender (or receiver) thread, each message is stamped with a
+//! delivery time when it is sent, sampled from the [`Latency`] of its link. The receiving end
+//! holds messages in a [`DelayQueue`] and only hands them out once they are due, so a slow message
+//! neither blocks its sender nor the messages queued behind it.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use crossbeam_channel::Receiver;
+
+use crate::network::error::TryRecvError;
+
+use rand_distr::{Distribution, LogNormal, Normal, Pareto};
+
+use vstd::prelude::*;
+
+/// Distribution of the one-way latency of a link
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Latency {
+    /// Messages are delivered immediately
+    #[default]
+    None,
+    /// Every message takes the same time
+    Constant(Duration),
+    /// Normally distributed (negative samples are delivered immediately)
+    Normal { mean: Duration, stddev: Duration },
+    /// Log-normally distributed, with the given mean and standard deviation
+    LogNormal { mean: Duration, stddev: Duration },
+    /// Pareto distributed: at least `scale`, with a heavy tail (lower `shape` is heavier)
+    Pareto { scale: Duration, shape: f64 },
+}
+
+impl Latency {
+    /// Normally distributed latency
+    pub fn normal(mean: Duration, stddev: Duration) -> Self {
+        Latency::Normal { mean, stddev }
+    }
+
+    /// Samples the latency of a single message
+    ///
+    /// Degenerate parameters (e.g., a pareto distribution with a zero scale or a shape which is not
+    /// positive) deliver messages immediately
+    pub fn sample(&self) -> Duration {
+        let rng = &mut rand::rng();
+        let secs = match *self {
+            Latency::None => return Duration::ZERO,
+            Latency::Constant(latency) => return latency,
+            Latency::Normal { mean, stddev } => {
+                match Normal::new(mean.as_secs_f64(), stddev.as_secs_f64()) {
+                    Ok(normal) => normal.sample(rng),
+                    Err(_) => return Duration::ZERO,
+                }
+            }
+            Latency::LogNormal { mean, stddev } => {
+                if mean.is_zero() {
+                    return Duration::ZERO;
+                }
+                let cv = stddev.as_secs_f64() / mean.as_secs_f64();
+                match LogNormal::from_mean_cv(mean.as_secs_f64(), cv) {
+                    Ok(log_normal) => log_normal.sample(rng),
+                    Err(_) => return Duration::ZERO,
+                }
+            }
+            Latency::Pareto { scale, shape } => match Pareto::new(scale.as_secs_f64(), shape) {
+                Ok(pareto) => pareto.sample(rng),
+                Err(_) => return Duration::ZERO,
+            },
+        };
+        if secs.is_sign_positive() && secs.is_finite() {
+            Duration::from_secs_f64(secs)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// A message in flight
+pub struct Stamped<T> {
+    deliver_at: Instant,
+    msg: T,
+}
+
+impl<T> Stamped<T> {
+    pub fn into_inner(self) -> T {
+        self.msg
+    }
+}
+
+/// Latency of a link, shared by both of its ends
+///
+/// Configuring either end of a link (e.g., with [`crate::network::channel::Channel::set_latency`])
+/// changes the latency in both directions.
+#[derive(Clone, Default)]
+pub struct LinkLatency {
+    latency: Arc<RwLock<Latency>>,
+}
+
+impl LinkLatency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, latency: Latency) {
+        *self.latency.write().expect("latency lock should not be poisoned") = latency;
+    }
+
+    pub fn get(&self) -> Latency {
+        *self.latency.read().expect("latency lock should not be poisoned")
+    }
+
+    /// Stamps a message being sent now
+    pub fn stamp<T>(&self, msg: T) -> Stamped<T> {
+        Stamped { deliver_at: Instant::now() + self.get().sample(), msg }
+    }
+}
+
+/// Entry of the delay queue: ordered by delivery time, then by arrival
+struct Pending<T> {
+    deliver_at: Instant,
+    seq: u64,
+    msg: T,
+}
+
+impl<T> PartialEq for Pending<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Pending<T> {}
+
+impl<T> PartialOrd for Pending<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Pending<T> {
+    // reversed: the heap pops the earliest delivery first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.deliver_at.cmp(&self.deliver_at).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Receiving end of a link: holds messages until they are due
+pub struct DelayQueue<T> {
+    rx: Receiver<Stamped<T>>,
+    pending: Mutex<(u64, BinaryHeap<Pending<T>>)>,
+}
+
+impl<T> DelayQueue<T> {
+    pub fn new(rx: Receiver<Stamped<T>>) -> Self {
+        DelayQueue { rx, pending: Mutex::new((0, BinaryHeap::new())) }
+    }
+
+    /// Returns the earliest message which is due
+    ///
+    /// Only reports `Disconnected` once the messages in flight were delivered
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut guard = self.pending.lock().expect("delay queue lock should not be poisoned");
+        let (seq, pending) = &mut *guard;
+        let disconnected = loop {
+            match self.rx.try_recv() {
+                Ok(Stamped { deliver_at, msg }) => {
+                    pending.push(Pending { deliver_at, seq: *seq, msg });
+                    *seq += 1;
+                }
+                Err(crossbeam_channel::TryRecvError::Empty) => break false,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        match pending.peek() {
+            Some(next) if next.deliver_at <= Instant::now() => {
+                Ok(pending.pop().expect("peeked entry should be there").msg)
+            }
+            None if disconnected => Err(TryRecvError::Disconnected),
+            _ => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+verus! {
+
+#[verifier::external_type_specification]
+#[verifier::external_body]
+pub struct ExLatency(Latency);
+
+#[verifier::external_type_specification]
+#[verifier::external_body]
+#[verifier::reject_recursive_types_in_ground_variants(T)]
+#[allow(dead_code)]
+pub struct ExStamped<T>(Stamped<T>);
+
+#[verifier::external_type_specification]
+#[verifier::external_body]
+pub struct ExLinkLatency(LinkLatency);
+
+#[verifier::external_type_specification]
+#[verifier::external_body]
+#[verifier::reject_recursive_types_in_ground_variants(T)]
+#[allow(dead_code)]
+pub struct ExDelayQueue<T>(DelayQueue<T>);
+
+pub assume_specification[ Latency::normal ](mean: Duration, stddev: Duration) -> Latency
+    no_unwind
+;
+
+} // verus!
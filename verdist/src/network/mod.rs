@@ -1,3 +1,7 @@
 pub mod channel;
+pub mod codec;
 pub mod error;
+pub mod latency;
 pub mod modelled;
+pub mod reconnecting;
+pub mod uds;
@@ -12,6 +12,10 @@ use crate::network::error::ConnectError;
 use crate::network::error::SendError;
 use crate::network::error::TryListenError;
 use crate::network::error::TryRecvError;
+use crate::network::latency::DelayQueue;
+use crate::network::latency::Latency;
+use crate::network::latency::LinkLatency;
+use crate::network::latency::Stamped;
 
 use vstd::prelude::*;
 
@@ -35,7 +39,7 @@ pub struct ExSender<T>(Sender<T>);
 pub struct ModelledListener<R, S> {
     id: u64,
     registering_rx: Receiver<u64>,
-    connection_tx: Sender<(u64, Sender<R>, Receiver<S>)>,
+    connection_tx: Sender<(u64, Sender<Stamped<R>>, Receiver<Stamped<S>>, LinkLatency)>,
 }
 
 #[verifier::external_body]
@@ -43,7 +47,7 @@ pub struct ModelledListener<R, S> {
 #[verifier::reject_recursive_types(S)]
 pub struct ModelledConnector<R, S> {
     registering_tx: Sender<u64>,
-    connection_rx: Receiver<(u64, Sender<S>, Receiver<R>)>,
+    connection_rx: Receiver<(u64, Sender<Stamped<S>>, Receiver<Stamped<R>>, LinkLatency)>,
 }
 
 /// Channel TO Client
@@ -54,13 +58,12 @@ pub struct ModelledConnector<R, S> {
 pub struct ClientChannel<K, R, S> {
     #[allow(dead_code)]
     pred: Ghost<K>,
-    tx: Sender<S>,
-    rx: Receiver<R>,
+    tx: Sender<Stamped<S>>,
+    rx: DelayQueue<R>,
     client_id: u64,
     server_id: u64,
     faulty: AtomicBool,
-    avg_latency: std::time::Duration,
-    stddev_latency: std::time::Duration,
+    latency: LinkLatency,
 }
 
 /// Channel TO Server
@@ -71,13 +74,12 @@ pub struct ClientChannel<K, R, S> {
 pub struct ServerChannel<K, R, S> {
     #[allow(dead_code)]
     pred: Ghost<K>,
-    tx: Sender<S>,
-    rx: Receiver<R>,
+    tx: Sender<Stamped<S>>,
+    rx: DelayQueue<R>,
     client_id: u64,
     server_id: u64,
     faulty: AtomicBool,
-    avg_latency: std::time::Duration,
-    stddev_latency: std::time::Duration,
+    latency: LinkLatency,
 }
 
 impl<K, R, S> ClientChannel<K, R, S> {
@@ -86,18 +88,18 @@ impl<K, R, S> ClientChannel<K, R, S> {
         client_id: u64,
         server_id: u64,
         pred: Ghost<K>,
-        tx: Sender<S>,
-        rx: Receiver<R>,
+        tx: Sender<Stamped<S>>,
+        rx: Receiver<Stamped<R>>,
+        latency: LinkLatency,
     ) -> Self {
         ClientChannel {
             pred,
             tx,
-            rx,
+            rx: DelayQueue::new(rx),
             client_id,
             server_id,
             faulty: AtomicBool::new(false),
-            avg_latency: Default::default(),
-            stddev_latency: Default::default(),
+            latency,
         }
     }
 }
@@ -108,18 +110,18 @@ impl<K, R, S> ServerChannel<K, R, S> {
         server_id: u64,
         client_id: u64,
         pred: Ghost<K>,
-        tx: Sender<S>,
-        rx: Receiver<R>,
+        tx: Sender<Stamped<S>>,
+        rx: Receiver<Stamped<R>>,
+        latency: LinkLatency,
     ) -> Self {
         ServerChannel {
             pred,
             tx,
-            rx,
+            rx: DelayQueue::new(rx),
             server_id,
             client_id,
             faulty: AtomicBool::new(false),
-            avg_latency: Default::default(),
-            stddev_latency: Default::default(),
+            latency,
         }
     }
 }
@@ -156,9 +158,7 @@ impl<K, R, S> Channel for ClientChannel<K, R, S> where
     #[verifier::external_body]
     fn try_recv(&self) -> Result<R, crate::network::error::TryRecvError> {
         if !self.faulty.load(std::sync::atomic::Ordering::SeqCst) {
-            self.wait();
-            let r = self.rx.try_recv()?;
-            Ok(r)
+            self.rx.try_recv()
         } else {
             Err(crate::network::error::TryRecvError::Empty)
         }
@@ -167,8 +167,9 @@ impl<K, R, S> Channel for ClientChannel<K, R, S> where
     #[verifier::external_body]
     fn send(&self, v: &S) -> Result<(), crate::network::error::SendError<S>> {
         if !self.faulty.load(std::sync::atomic::Ordering::SeqCst) {
-            self.wait();
-            self.tx.send(v.clone())?;
+            self.tx.send(self.latency.stamp(v.clone())).map_err(
+                |e| crate::network::error::SendError(e.into_inner().into_inner()),
+            )?;
         }
         Ok(())
     }
@@ -184,14 +185,8 @@ impl<K, R, S> Channel for ClientChannel<K, R, S> where
     }
 
     #[verifier::external_body]
-    fn add_latency(&mut self, avg: std::time::Duration, stddev: std::time::Duration) {
-        self.avg_latency = avg;
-        self.stddev_latency = stddev;
-    }
-
-    #[verifier::external_body]
-    fn delay(&self) -> (std::time::Duration, std::time::Duration) {
-        (self.avg_latency, self.stddev_latency)
+    fn set_latency(&mut self, latency: Latency) {
+        self.latency.set(latency);
     }
 }
 
@@ -215,8 +210,7 @@ impl<K, R, S> Channel for ServerChannel<K, R, S> where
     #[verifier::external_body]
     fn try_recv(&self) -> Result<R, crate::network::error::TryRecvError> {
         if !self.faulty.load(std::sync::atomic::Ordering::SeqCst) {
-            self.wait();
-            self.rx.try_recv().map_err(|e| e.into())
+            self.rx.try_recv()
         } else {
             Err(crate::network::error::TryRecvError::Empty)
         }
@@ -225,8 +219,9 @@ impl<K, R, S> Channel for ServerChannel<K, R, S> where
     #[verifier::external_body]
     fn send(&self, v: &S) -> Result<(), crate::network::error::SendError<S>> {
         if !self.faulty.load(std::sync::atomic::Ordering::SeqCst) {
-            self.wait();
-            self.tx.send(v.clone())?;
+            self.tx.send(self.latency.stamp(v.clone())).map_err(
+                |e| crate::network::error::SendError(e.into_inner().into_inner()),
+            )?;
         }
         Ok(())
     }
@@ -242,14 +237,8 @@ impl<K, R, S> Channel for ServerChannel<K, R, S> where
     }
 
     #[verifier::external_body]
-    fn add_latency(&mut self, avg: std::time::Duration, stddev: std::time::Duration) {
-        self.avg_latency = avg;
-        self.stddev_latency = stddev;
-    }
-
-    #[verifier::external_body]
-    fn delay(&self) -> (std::time::Duration, std::time::Duration) {
-        (self.avg_latency, self.stddev_latency)
+    fn set_latency(&mut self, latency: Latency) {
+        self.latency.set(latency);
     }
 }
 
@@ -272,13 +261,14 @@ impl<K, R, S> Listener<ClientChannel<K, R, S>> for ModelledListener<R, S> where
         let (resp_tx, resp_rx) = unbounded();
         let (req_tx, req_rx) = unbounded();
 
-        self.connection_tx.send((self.id, req_tx, resp_rx)).map_err(
+        let latency = LinkLatency::new();
+        self.connection_tx.send((self.id, req_tx, resp_rx, latency.clone())).map_err(
             |_x| TryListenError::Disconnected,
         )?;
 
         let pred = Ghost(gen_pred@(self));
 
-        let chan = ClientChannel::new(client_id, self.id, pred, resp_tx, req_rx);
+        let chan = ClientChannel::new(client_id, self.id, pred, resp_tx, req_rx, latency);
 
         vlib::veprintln!("[server|{:>3}]: accepted connection from client {client_id} (channel_id: {:?})", self.id, chan.id());
 
@@ -299,9 +289,9 @@ impl<K, R, S> Connector<ServerChannel<K, R, S>> for ModelledConnector<R, S> wher
             "[client|{:>3}]: connecting to server", local_id,
         );
         self.registering_tx.send(local_id).map_err(|_e| ConnectError)?;
-        let (server_id, tx, rx) = self.connection_rx.recv().map_err(|_e| ConnectError)?;
+        let (server_id, tx, rx, latency) = self.connection_rx.recv().map_err(|_e| ConnectError)?;
         let pred = gen_pred(self, local_id);
-        let chan = ServerChannel::new(server_id, local_id, pred, tx, rx);
+        let chan = ServerChannel::new(server_id, local_id, pred, tx, rx, latency);
         vlib::veprintln!(
             "[client|{:>3}]: connected to server {server_id}  (channel_id: {:?})", local_id, chan.id()
         );
@@ -0,0 +1,281 @@
+//! Channels which reconnect when they break
+//!
+//! A [`ReconnectingChannel`] keeps the [`Connector`] it was created from. When the underlying
+//! channel reports `Disconnected`, it is dropped and a background thread connects again (backing
+//! off between attempts), swapping the new channel in once it is up. The thread gives up after
+//! [`RECONNECT_MAX_ATTEMPTS`] attempts (the next error on the channel starts over), or as soon as
+//! the channel itself is dropped.
+//!
+//! The replacement is checked to have the same id and obtains the same constant, so the id and
+//! constant of a [`ReconnectingChannel`] never change. Pools of them (see
+//! [`crate::pool::ReconnectingPool`]) have stable `spec_channels()`, and whatever invariants were
+//! established on the channels survive reconnections.
+use std::sync::Arc;
+use std::sync::Weak;
+use std::time::Duration;
+
+use crate::network::channel::Channel;
+#[cfg(verus_only)]
+use crate::network::channel::ChannelInvariant;
+use crate::network::channel::Connector;
+use crate::network::error::SendError;
+use crate::network::error::TryRecvError;
+use crate::network::latency::Latency;
+
+use vstd::prelude::*;
+use vstd::rwlock::RwLock;
+
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(10);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_ATTEMPTS: u32 = 16;
+
+verus! {
+
+/// State of the underlying connection
+struct Slot<C> {
+    /// Missing while reconnecting
+    channel: Option<C>,
+    /// Latency to apply to new connections (see [`Channel::set_latency`])
+    latency: Option<Latency>,
+    /// Whether a thread is trying to reconnect
+    reconnecting: bool,
+}
+
+#[allow(dead_code)]
+struct SlotInv<K> {
+    k: K,
+    id: (u64, u64),
+}
+
+impl<C> vstd::rwlock::RwLockPredicate<Slot<C>> for SlotInv<C::K> where
+    C: Channel<Id = (u64, u64)>,
+ {
+    closed spec fn inv(self, v: Slot<C>) -> bool {
+        v.channel is Some ==> {
+            &&& v.channel->Some_0.constant() == self.k
+            &&& v.channel->Some_0.spec_id() == self.id
+        }
+    }
+}
+
+/// Everything needed to reconnect, shared with the reconnecting thread
+pub struct Reconnector<C, Conn> where C: Channel<Id = (u64, u64)>, Conn: Connector<C> {
+    id: (u64, u64),
+    k: Ghost<C::K>,
+    local_id: u64,
+    connector: Conn,
+    slot: RwLock<Slot<C>, SlotInv<C::K>>,
+}
+
+impl<C, Conn> Reconnector<C, Conn> where C: Channel<Id = (u64, u64)>, Conn: Connector<C> {
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& self.slot.pred().k == self.k@
+        &&& self.slot.pred().id == self.id
+    }
+
+    /// Drops the channel after an error
+    ///
+    /// Returns whether the caller should start reconnecting (i.e., no one else has)
+    fn disconnected(&self) -> bool {
+        proof {
+            use_type_invariant(self);
+        }
+        let (mut slot, handle) = self.slot.acquire_write();
+        let start = !slot.reconnecting;
+        slot.channel = None;
+        slot.reconnecting = true;
+        handle.release_write(slot);
+        start
+    }
+
+    /// Stops reconnecting: the channel stays disconnected until its next error
+    fn give_up(&self) {
+        proof {
+            use_type_invariant(self);
+        }
+        let (mut slot, handle) = self.slot.acquire_write();
+        slot.reconnecting = false;
+        handle.release_write(slot);
+    }
+
+    /// Attempts to connect again, installing the new channel on success
+    pub fn try_reconnect(&self) -> bool {
+        proof {
+            use_type_invariant(self);
+        }
+        let ghost k = self.k@;
+        let res = self.connector.connect(
+            self.local_id,
+            |_connector: &Conn, _local_id: u64| -> (r: Ghost<C::K>)
+                ensures
+                    r@ == k,
+                { Ghost(k) },
+        );
+        let mut channel = match res {
+            Ok(channel) => channel,
+            Err(_) => return false,
+        };
+        // connected to someone else
+        if channel.id() != self.id {
+            return false;
+        }
+
+        let (mut slot, handle) = self.slot.acquire_write();
+        if let Some(latency) = slot.latency {
+            channel.set_latency(latency);
+        }
+        slot.channel = Some(channel);
+        slot.reconnecting = false;
+        handle.release_write(slot);
+        vlib::veprintln!("[reconnect]: reconnected channel {:?}", self.id);
+        true
+    }
+}
+
+/// A channel which reconnects in the background when it breaks
+pub struct ReconnectingChannel<C, Conn> where C: Channel<Id = (u64, u64)>, Conn: Connector<C> {
+    reconnector: Arc<Reconnector<C, Conn>>,
+}
+
+impl<C, Conn> ReconnectingChannel<C, Conn> where
+    C: Channel<Id = (u64, u64)> + Send + Sync + 'static,
+    Conn: Connector<C> + Send + Sync + 'static,
+ {
+    /// Wraps `channel`, which was connected to by `connector` from `local_id`
+    pub fn new(channel: C, connector: Conn, local_id: u64) -> (r: Self)
+        ensures
+            r.spec_id() == channel.spec_id(),
+            r.constant() == channel.constant(),
+    {
+        let id = channel.id();
+        let ghost k = channel.constant();
+        let ghost pred = SlotInv { k, id };
+        let slot = Slot { channel: Some(channel), latency: None, reconnecting: false };
+        assert(<SlotInv<C::K> as vstd::rwlock::RwLockPredicate<Slot<C>>>::inv(pred, slot));
+        let reconnector = Reconnector {
+            id,
+            k: Ghost(k),
+            local_id,
+            connector,
+            slot: RwLock::new(slot, Ghost(pred)),
+        };
+        ReconnectingChannel { reconnector: Arc::new(reconnector) }
+    }
+
+    /// Handles an error of the underlying channel
+    fn disconnected(&self) {
+        if self.reconnector.disconnected() {
+            vlib::veprintln!("[reconnect]: channel {:?} disconnected", self.reconnector.id);
+            spawn_reconnect(&self.reconnector);
+        }
+    }
+}
+
+impl<C, Conn> Channel for ReconnectingChannel<C, Conn> where
+    C: Channel<Id = (u64, u64)> + Send + Sync + 'static,
+    Conn: Connector<C> + Send + Sync + 'static,
+ {
+    type R = C::R;
+
+    type S = C::S;
+
+    type Id = (u64, u64);
+
+    type K = C::K;
+
+    closed spec fn constant(self) -> Self::K {
+        self.reconnector.k@
+    }
+
+    fn id(&self) -> Self::Id {
+        self.reconnector.id
+    }
+
+    closed spec fn spec_id(self) -> Self::Id {
+        self.reconnector.id
+    }
+
+    fn try_recv(&self) -> Result<Self::R, TryRecvError> {
+        proof {
+            use_type_invariant(&*self.reconnector);
+        }
+        let handle = self.reconnector.slot.acquire_read();
+        let res = match &handle.borrow().channel {
+            Some(channel) => channel.try_recv(),
+            None => Err(TryRecvError::Disconnected),
+        };
+        handle.release_read();
+
+        match &res {
+            Err(TryRecvError::Disconnected) => self.disconnected(),
+            _ => {},
+        }
+        res
+    }
+
+    fn send(&self, s: &Self::S) -> Result<(), SendError<Self::S>> {
+        proof {
+            use_type_invariant(&*self.reconnector);
+        }
+        let handle = self.reconnector.slot.acquire_read();
+        let res = match &handle.borrow().channel {
+            Some(channel) => channel.send(s),
+            None => Err(SendError(s.clone())),
+        };
+        handle.release_read();
+
+        if res.is_err() {
+            self.disconnected();
+        }
+        res
+    }
+
+    fn set_latency(&mut self, latency: Latency) {
+        proof {
+            use_type_invariant(&*self.reconnector);
+        }
+        let (mut slot, handle) = self.reconnector.slot.acquire_write();
+        slot.channel = match slot.channel.take() {
+            Some(mut channel) => {
+                channel.set_latency(latency);
+                Some(channel)
+            },
+            None => None,
+        };
+        slot.latency = Some(latency);
+        handle.release_write(slot);
+    }
+}
+
+/// Reconnects in the background, without keeping the channel alive
+// Why is this unverified: verus does not support threads
+#[verifier::external_body]
+fn spawn_reconnect<C, Conn>(reconnector: &Arc<Reconnector<C, Conn>>) where
+    C: Channel<Id = (u64, u64)> + Send + Sync + 'static,
+    Conn: Connector<C> + Send + Sync + 'static,
+ {
+    let reconnector: Weak<Reconnector<C, Conn>> = Arc::downgrade(reconnector);
+    std::thread::spawn(move || {
+        let mut delay = RECONNECT_BASE_DELAY;
+        for _ in 0..RECONNECT_MAX_ATTEMPTS {
+            // the channel was dropped: there is no one left to reconnect for
+            let Some(reconnector) = reconnector.upgrade() else {
+                return;
+            };
+            if reconnector.try_reconnect() {
+                return;
+            }
+            drop(reconnector);
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+        }
+
+        if let Some(reconnector) = reconnector.upgrade() {
+            vlib::veprintln!("[reconnect]: giving up on channel {:?}", reconnector.id);
+            reconnector.give_up();
+        }
+    });
+}
+
+} // verus!
@@ -0,0 +1,330 @@
+//! Unix domain socket transport
+//!
+//! For servers running as sidecars on the same host as their clients: a [`UdsListener`] binds a
+//! socket path and [`UdsConnector`]s connect to it. Messages are framed and encoded as described
+//! in [`crate::network::codec`].
+//!
+//! When connecting, the client sends its id and the server answers with its own, so both ends
+//! agree on the id of the channel: `(client_id, server_id)` on the client side and
+//! `(server_id, client_id)` on the server side, as with the modelled network. The server never
+//! waits on a handshake: accepted connections are parked until their id arrives, and dropped if it
+//! does not arrive within [`HANDSHAKE_TIMEOUT`].
+//!
+//! As for any real transport, the channel invariants are trusted rather than verified: the
+//! ghost and tracked parts of the messages are assumed back in [`UdsChannel`]'s `try_recv`, and
+//! nowhere else (see [`crate::network::codec::Trusted`]).
+//!
+//! NOTE: the handshake is not authenticated. Whichever id a client sends becomes the id of its
+//! channel, so a local process can impersonate any client (the socket file permissions are the
+//! only access control).
+use std::marker::PhantomData;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::network::channel::Channel;
+use crate::network::channel::ChannelInvariant;
+use crate::network::channel::Connector;
+use crate::network::channel::Listener;
+use crate::network::codec::write_frame;
+use crate::network::codec::Codec;
+use crate::network::codec::FrameReader;
+use crate::network::codec::FrameWriter;
+use crate::network::codec::Trusted;
+use crate::network::error::ConnectError;
+use crate::network::error::SendError;
+use crate::network::error::TryListenError;
+use crate::network::error::TryRecvError;
+
+use vstd::prelude::*;
+
+/// How long a peer has to answer when setting up a connection
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+verus! {
+
+#[verifier::external_body]
+#[verifier::reject_recursive_types(R)]
+#[verifier::reject_recursive_types(S)]
+pub struct UdsListener<R, S> {
+    id: u64,
+    listener: UnixListener,
+    path: PathBuf,
+    /// Accepted connections whose client id did not arrive yet
+    handshakes: Mutex<Vec<Handshake>>,
+    _marker: PhantomData<(R, S)>,
+}
+
+#[verifier::external_body]
+#[verifier::reject_recursive_types(R)]
+#[verifier::reject_recursive_types(S)]
+pub struct UdsConnector<R, S> {
+    path: PathBuf,
+    _marker: PhantomData<(R, S)>,
+}
+
+/// Channel over a Unix domain socket (to a client or to a server)
+#[verifier::external_body]
+#[verifier::reject_recursive_types(K)]
+#[verifier::reject_recursive_types(R)]
+#[verifier::reject_recursive_types(S)]
+pub struct UdsChannel<K, R, S> {
+    #[allow(dead_code)]
+    pred: Ghost<K>,
+    id: (u64, u64),
+    writer: Mutex<(UnixStream, FrameWriter)>,
+    reader: Mutex<(UnixStream, FrameReader)>,
+    _marker: PhantomData<(R, S)>,
+}
+
+impl<K, R, S> Channel for UdsChannel<K, R, S> where
+    K: ChannelInvariant<K, (u64, u64), R, S>,
+    R: Codec,
+    S: Codec + Clone,
+ {
+    type R = R;
+
+    type S = S;
+
+    type Id = (u64, u64);
+
+    type K = K;
+
+    #[verifier::external_body]
+    closed spec fn constant(self) -> Self::K {
+        self.pred@
+    }
+
+    #[verifier::external_body]
+    fn try_recv(&self) -> Result<R, TryRecvError> {
+        // push out what earlier sends held back, unless someone is sending already
+        if let Ok(mut writer) = self.writer.try_lock() {
+            let (stream, frames) = &mut *writer;
+            let _ = frames.flush(stream);
+        }
+        let mut reader = self.reader.lock().expect("reader lock should not be poisoned");
+        let (stream, frames) = &mut *reader;
+        // the trust boundary: what comes out of the socket is assumed to satisfy the invariants
+        frames.try_recv(stream, Some(&Trusted::assume()))
+    }
+
+    #[verifier::external_body]
+    fn send(&self, v: &S) -> Result<(), SendError<S>> {
+        let mut writer = self.writer.lock().expect("writer lock should not be poisoned");
+        let (stream, frames) = &mut *writer;
+        frames.send(stream, v).map_err(|_e| SendError(v.clone()))
+    }
+
+    #[verifier::external_body]
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    #[verifier::external_body]
+    closed spec fn spec_id(self) -> Self::Id {
+        self.id
+    }
+}
+
+impl<K, R, S> Listener<UdsChannel<K, R, S>> for UdsListener<R, S> where
+    K: ChannelInvariant<K, (u64, u64), R, S>,
+    R: Codec,
+    S: Codec + Clone,
+ {
+    #[allow(unused_variables)]
+    #[verifier::external_body]
+    fn try_accept(&self, gen_pred: Ghost<spec_fn(&Self) -> K>) -> (r: Result<
+        UdsChannel<K, R, S>,
+        TryListenError,
+    >) {
+        match self.listener.accept() {
+            Ok((stream, _addr)) => {
+                if let Some(handshake) = Handshake::start(stream, self.id) {
+                    self.handshakes
+                        .lock()
+                        .expect("handshake lock should not be poisoned")
+                        .push(handshake);
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {},
+            Err(_e) => return Err(TryListenError::Disconnected),
+        }
+
+        // a client which fails the handshake is dropped: the listener itself is fine
+        // NOTE: the client id is taken on faith, and becomes the id of the channel
+        let (client_id, handshake) = match self.finish_handshake() {
+            Some(done) => done,
+            None => return Err(TryListenError::Empty),
+        };
+        vlib::veprintln!(
+            "[server|{:>3}]: accepting a connection from client {client_id}", self.id
+        );
+
+        let pred = Ghost(gen_pred@(self));
+        let Handshake { stream, reader, writer, .. } = handshake;
+        let chan = UdsChannel::new((self.id, client_id), pred, stream, reader, writer)
+            .map_err(|_e| TryListenError::Empty)?;
+
+        vlib::veprintln!("[server|{:>3}]: accepted connection from client {client_id} (channel_id: {:?})", self.id, chan.id());
+
+        Ok(chan)
+    }
+}
+
+impl<K, R, S> Connector<UdsChannel<K, R, S>> for UdsConnector<R, S> where
+    K: ChannelInvariant<K, (u64, u64), R, S>,
+    R: Codec,
+    S: Codec + Clone,
+ {
+    #[verifier::external_body]
+    fn connect<F>(&self, local_id: u64, gen_pred: F) -> Result<
+        UdsChannel<K, R, S>,
+        ConnectError,
+    > where F: FnOnce(&Self, u64) -> Ghost<K> {
+        vlib::veprintln!(
+            "[client|{:>3}]: connecting to server at {}", local_id, self.path.display()
+        );
+        let stream = UnixStream::connect(&self.path).map_err(|_e| ConnectError)?;
+        let mut frames = FrameReader::new();
+        let server_id = handshake(&stream, &mut frames, local_id).ok_or(ConnectError)?;
+
+        let pred = gen_pred(self, local_id);
+        let chan = UdsChannel::new((local_id, server_id), pred, stream, frames, FrameWriter::new())
+            .map_err(|_e| ConnectError)?;
+        vlib::veprintln!(
+            "[client|{:>3}]: connected to server {server_id}  (channel_id: {:?})", local_id, chan.id()
+        );
+        Ok(chan)
+    }
+}
+
+} // verus!
+impl<K, R, S> UdsChannel<K, R, S> {
+    /// Wraps a connected stream (and whatever was read past, or held back by, the handshake)
+    fn new(
+        id: (u64, u64),
+        pred: Ghost<K>,
+        stream: UnixStream,
+        reader: FrameReader,
+        writer: FrameWriter,
+    ) -> std::io::Result<Self> {
+        stream.set_read_timeout(None)?;
+        stream.set_nonblocking(true)?;
+        let write_stream = stream.try_clone()?;
+        Ok(UdsChannel {
+            pred,
+            id,
+            writer: Mutex::new((write_stream, writer)),
+            reader: Mutex::new((stream, reader)),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Exchanges ids with the server at the other end of a new connection, returning its id
+///
+/// Blocks the connecting client for up to [`HANDSHAKE_TIMEOUT`]
+fn handshake(stream: &UnixStream, frames: &mut FrameReader, local_id: u64) -> Option<u64> {
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).ok()?;
+    write_frame(&mut &*stream, &local_id).ok()?;
+    frames.recv(&mut &*stream, None).ok()
+}
+
+/// Server side of the handshake of an accepted connection
+struct Handshake {
+    stream: UnixStream,
+    reader: FrameReader,
+    writer: FrameWriter,
+    deadline: Instant,
+}
+
+impl Handshake {
+    /// Starts the handshake by sending the id of the server, without waiting on the client
+    fn start(stream: UnixStream, server_id: u64) -> Option<Self> {
+        stream.set_nonblocking(true).ok()?;
+        let mut writer = FrameWriter::new();
+        writer.send(&mut &stream, &server_id).ok()?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        Some(Handshake { stream, reader: FrameReader::new(), writer, deadline })
+    }
+
+    /// Returns the id of the client, once it arrived
+    ///
+    /// `Empty` while it has not arrived yet, and `Disconnected` if the client went away or ran
+    /// out of time
+    fn try_finish(&mut self) -> Result<u64, TryRecvError> {
+        if self.writer.flush(&mut &self.stream).is_err() {
+            return Err(TryRecvError::Disconnected);
+        }
+        match self.reader.try_recv(&mut &self.stream, None) {
+            Err(TryRecvError::Empty) if Instant::now() >= self.deadline => {
+                Err(TryRecvError::Disconnected)
+            }
+            res => res,
+        }
+    }
+}
+
+impl<R, S> UdsListener<R, S> {
+    /// Binds a listener to `path`, which should not exist yet
+    ///
+    /// The socket file is left behind: whoever picks the path cleans it up
+    pub fn bind(server_id: u64, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(UdsListener {
+            id: server_id,
+            listener,
+            path,
+            handshakes: Mutex::new(Vec::new()),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Moves the pending handshakes along, and returns the first connection done with its own
+    ///
+    /// The connections which failed their handshake are dropped
+    fn finish_handshake(&self) -> Option<(u64, Handshake)> {
+        let mut handshakes = self.handshakes.lock().expect("handshake lock should not be poisoned");
+        let mut idx = 0;
+        while idx < handshakes.len() {
+            match handshakes[idx].try_finish() {
+                Ok(client_id) => return Some((client_id, handshakes.swap_remove(idx))),
+                Err(TryRecvError::Empty) => idx += 1,
+                Err(TryRecvError::Disconnected) => {
+                    handshakes.swap_remove(idx);
+                }
+            }
+        }
+        None
+    }
+
+    /// Connector to this listener
+    pub fn connector(&self) -> UdsConnector<S, R> {
+        UdsConnector::new(&self.path)
+    }
+}
+
+impl<R, S> UdsConnector<R, S> {
+    /// Connector to the listener bound at `path`
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        UdsConnector { path: path.as_ref().to_path_buf(), _marker: PhantomData }
+    }
+}
+
+/// Binds a listener to `path`, along with a connector to it (see
+/// [`crate::network::modelled::listen_channel`])
+pub fn listen_uds<R, S>(
+    server_id: u64,
+    path: impl AsRef<Path>,
+) -> std::io::Result<(UdsListener<R, S>, UdsConnector<S, R>)> {
+    let listener = UdsListener::bind(server_id, path)?;
+    let connector = listener.connector();
+    Ok((listener, connector))
+}
@@ -5,10 +5,13 @@ use crate::network::channel::ChannelInvariant;
 use crate::pool::connection_pool::channel_seq_to_map;
 #[cfg(verus_only)]
 use crate::pool::connection_pool::lemma_channel_seq_to_map;
+use crate::pool::thrifty::Thrifty;
 use crate::pool::ChannelId;
+use crate::pool::ChannelReq;
 use crate::pool::ChannelResp;
 use crate::pool::ConnectionPool;
 use crate::pool::PoolChannel;
+use crate::rpc::proto::Cancellable;
 use crate::rpc::proto::TaggedMessage;
 use crate::rpc::replies::ReplyAccumulator;
 use crate::rpc::RequestContext;
@@ -26,7 +29,7 @@ pub struct BroadcastPool<'a, Pool> {
 impl<'a, Pool, Request> BroadcastPool<'a, Pool> where
     Pool: ConnectionPool,
     ChannelResp<Pool>: TaggedMessage,
-    ChannelId<Pool>: std::fmt::Debug,
+    ChannelId<Pool>: std::fmt::Debug + Clone,
     Pool::C: Channel<S = Request>,
     ChannelResp<Pool>: TaggedMessage + std::fmt::Debug,
     Request: TaggedMessage + Clone + std::fmt::Debug,
@@ -44,7 +47,7 @@ impl<'a, Pool, Request> BroadcastPool<'a, Pool> where
 
     pub fn broadcast_filter<Pred, A, F>(
         self,
-        request: Request,
+        request: &Request,
         pred: Ghost<Pred>,
         accum: A,
         filter_fn: F,
@@ -66,45 +69,17 @@ impl<'a, Pool, Request> BroadcastPool<'a, Pool> where
                     &&& <PoolChannel<Pool> as Channel>::K::send_inv(
                         chan.constant(),
                         chan.spec_id(),
-                        request,
+                        *request,
                     )
                 },
         ensures
             r.pred() == pred@,
     {
-        let channels = self.pool.channels();
-        let ghost g_channels = self.spec_channels();
-        proof {
-            lemma_channel_seq_to_map(channels@, self.spec_channels());
-        }
-        for chan in channels.iter()
-            invariant
-                self.spec_channels() == g_channels,
-                self.spec_channels() == channel_seq_to_map(channels@),
-                channels@.map_values(|c: PoolChannel<Pool>| c.spec_id()).no_duplicates(),
-                forall|id| #[trigger]
-                    self.spec_channels().contains_key(id) ==> {
-                        let c = self.spec_channels()[id];
-                        &&& filter_fn.requires((c.spec_id(),))
-                        &&& <PoolChannel<Pool> as Channel>::K::send_inv(
-                            c.constant(),
-                            c.spec_id(),
-                            request,
-                        )
-                    },
-        {
-            proof {
-                lemma_channel_seq_to_map(channels@, self.spec_channels());
-                assert(self.spec_channels().contains_key(chan.spec_id()));
-            }
-            if filter_fn(chan.id()) {
-                let _res = chan.send(&request);
-            }
-        }
+        send_filter(self.pool, request, filter_fn);
         RequestContext::new(self.pool, request.tag(), pred, accum)
     }
 
-    pub fn broadcast<Pred, A>(self, request: Request, pred: Ghost<Pred>, accum: A) -> (r:
+    pub fn broadcast<Pred, A>(self, request: &Request, pred: Ghost<Pred>, accum: A) -> (r:
         RequestContext<'a, Pool, Pred, A>) where
         Pred: InvariantPredicate<Pred, A>,
         A: ReplyAccumulator<PoolChannel<Pool>, Pred>,
@@ -121,7 +96,7 @@ impl<'a, Pool, Request> BroadcastPool<'a, Pool> where
                     <PoolChannel<Pool> as Channel>::K::send_inv(
                         chan.constant(),
                         chan.spec_id(),
-                        request,
+                        *request,
                     )
                 },
         ensures
@@ -129,6 +104,119 @@ impl<'a, Pool, Request> BroadcastPool<'a, Pool> where
     {
         self.broadcast_filter(request, pred, accum, |_s| true)
     }
+
+    /// Broadcast to a preferred quorum first (see [`Thrifty`])
+    ///
+    /// The `quorum` fastest servers get the request now; the rest get it when the policy decides
+    /// to fall back (see [`RequestContext::wait_for`]). With no policy, this is a regular
+    /// broadcast.
+    pub fn broadcast_thrifty<Pred, A>(
+        self,
+        request: Request,
+        pred: Ghost<Pred>,
+        accum: A,
+        thrifty: Option<&'a Thrifty<ChannelId<Pool>>>,
+        quorum: usize,
+    ) -> (r: RequestContext<'a, Pool, Pred, A>) where
+        Pred: InvariantPredicate<Pred, A>,
+        A: ReplyAccumulator<PoolChannel<Pool>, Pred>,
+
+        requires
+            Pred::inv(pred@, accum),
+            accum.request_tag() == request.spec_tag(),
+            accum.spec_handled_replies().is_empty(),
+            accum.channels() == self.spec_channels(),
+            vstd::laws_cmp::obeys_cmp::<ChannelId<Pool>>(),
+            forall|id| #[trigger]
+                self.spec_channels().contains_key(id) ==> {
+                    let chan = self.spec_channels()[id];
+                    <PoolChannel<Pool> as Channel>::K::send_inv(
+                        chan.constant(),
+                        chan.spec_id(),
+                        request,
+                    )
+                },
+        ensures
+            r.pred() == pred@,
+    {
+        match thrifty {
+            Some(thrifty) => {
+                let round = thrifty.start_round(self.pool.channels(), quorum);
+                send_filter(self.pool, &request, |id: ChannelId<Pool>| round.is_preferred(&id));
+                RequestContext::new_thrifty(self.pool, request, pred, accum, thrifty, round)
+            },
+            None => self.broadcast(&request, pred, accum),
+        }
+    }
+
+    /// Tells every server to drop a request if they have not handled it yet
+    ///
+    /// `cancellation` is the cancellation of that request (see [`Cancellable::cancellation`]).
+    /// Replies which still arrive are discarded (see [`ConnectionPool::finish`])
+    pub fn cancel(self, cancellation: &Request) where Request: Cancellable
+        requires
+            cancellation.spec_is_cancel(),
+            forall|id| #[trigger]
+                self.spec_channels().contains_key(id) ==> {
+                    let chan = self.spec_channels()[id];
+                    <PoolChannel<Pool> as Channel>::K::send_inv(
+                        chan.constant(),
+                        chan.spec_id(),
+                        *cancellation,
+                    )
+                },
+    {
+        send_filter(self.pool, cancellation, |_id| true);
+        self.pool.finish(cancellation.tag());
+    }
+}
+
+/// Sends `request` to the channels of `pool` which pass `filter_fn`
+pub(crate) fn send_filter<Pool, F>(pool: &Pool, request: &ChannelReq<Pool>, filter_fn: F) where
+    Pool: ConnectionPool,
+    F: Fn(ChannelId<Pool>) -> bool,
+
+    requires
+        forall|id| #[trigger]
+            pool.spec_channels().contains_key(id) ==> {
+                let chan = pool.spec_channels()[id];
+                &&& filter_fn.requires((chan.spec_id(),))
+                &&& <PoolChannel<Pool> as Channel>::K::send_inv(
+                    chan.constant(),
+                    chan.spec_id(),
+                    *request,
+                )
+            },
+{
+    let channels = pool.channels();
+    let ghost g_channels = pool.spec_channels();
+    proof {
+        lemma_channel_seq_to_map(channels@, pool.spec_channels());
+    }
+    for chan in channels.iter()
+        invariant
+            pool.spec_channels() == g_channels,
+            pool.spec_channels() == channel_seq_to_map(channels@),
+            channels@.map_values(|c: PoolChannel<Pool>| c.spec_id()).no_duplicates(),
+            forall|id| #[trigger]
+                pool.spec_channels().contains_key(id) ==> {
+                    let c = pool.spec_channels()[id];
+                    &&& filter_fn.requires((c.spec_id(),))
+                    &&& <PoolChannel<Pool> as Channel>::K::send_inv(
+                        c.constant(),
+                        c.spec_id(),
+                        *request,
+                    )
+                },
+    {
+        proof {
+            lemma_channel_seq_to_map(channels@, pool.spec_channels());
+            assert(pool.spec_channels().contains_key(chan.spec_id()));
+        }
+        if filter_fn(chan.id()) {
+            let _res = chan.send(request);
+        }
+    }
 }
 
 } // verus!
@@ -125,6 +125,11 @@ pub trait ConnectionPool {
                 },
     ;
 
+    /// Signals that the request tagged with `request_tag` is over, so its replies (including late
+    /// ones) need not be kept
+    fn finish(&self, _request_tag: u64) {
+    }
+
     proof fn lemma_len(tracked &self)
         ensures
             self.spec_len() == self.spec_channels().len(),
@@ -291,6 +296,12 @@ impl<C> ConnectionPool for FlawlessPool<BufChannel<C>> where
         v
     }
 
+    fn finish(&self, request_tag: u64) {
+        for idx in 0..self.pool.len() {
+            self.pool[idx].finish(request_tag);
+        }
+    }
+
     proof fn lemma_len(tracked &self) {
         self._lemma_len()
     }
@@ -1,14 +1,26 @@
 pub mod broadcast_pool;
 pub mod connection_pool;
+pub mod parallel_pool;
+pub mod thrifty;
 
 pub use broadcast_pool::BroadcastPool;
 pub use connection_pool::ConnectionPool;
 pub use connection_pool::FlawlessPool;
+pub use parallel_pool::ParallelPool;
+pub use thrifty::Thrifty;
 
+use crate::network::channel::BufChannel;
 use crate::network::channel::Channel;
+use crate::network::reconnecting::ReconnectingChannel;
 
 pub type PoolChannel<Pool> = <Pool as ConnectionPool>::C;
 pub type ChannelReq<Pool> = <<Pool as ConnectionPool>::C as Channel>::S;
 pub type ChannelResp<Pool> = <<Pool as ConnectionPool>::C as Channel>::R;
 pub type ChannelId<Pool> = <<Pool as ConnectionPool>::C as Channel>::Id;
 pub type ChannelK<Pool> = <<Pool as ConnectionPool>::C as Channel>::K;
+
+/// Pool of channels which reconnect in the background when they break
+///
+/// The channels themselves never change (they hold the connection), so `spec_channels()` is
+/// stable across reconnections.
+pub type ReconnectingPool<C, Conn> = FlawlessPool<BufChannel<ReconnectingChannel<C, Conn>>>;
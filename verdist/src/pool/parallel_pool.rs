@@ -0,0 +1,439 @@
+//! Connection pool which polls its channels concurrently
+//!
+//! Polling a [`super::FlawlessPool`] visits every channel in turn, and with modelled latency each
+//! `try_recv` sleeps first: a single poll then takes the sum of the delays of all the channels.
+//! A [`ParallelPool`] instead runs a reader thread per channel, feeding the replies into a queue
+//! shared by all of them, where they wait (grouped by tag) until someone polls for them.
+//!
+//! An idle reader backs off (up to [`MAX_BACKOFF`] between attempts) rather than spinning on its
+//! channel, so a reply may wait that long before it reaches the queue.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::network::channel::Channel;
+#[cfg(verus_only)]
+use crate::network::channel::ChannelInvariant;
+use crate::network::error::TryRecvError;
+use crate::rpc::proto::TaggedMessage;
+
+use vstd::prelude::*;
+use vstd::rwlock::RwLock;
+
+use std::sync::Arc;
+
+use super::connection_pool::channel_seq_to_map;
+use super::connection_pool::lemma_channel_seq_to_map;
+use super::ConnectionPool;
+
+/// How long an idle reader first waits before trying its channel again
+const MIN_BACKOFF: Duration = Duration::from_micros(10);
+
+/// The longest an idle reader waits before trying its channel again
+const MAX_BACKOFF: Duration = Duration::from_millis(1);
+
+verus! {
+
+/// How many tags the queue holds replies for: replies for other tags are dropped when full
+const MAX_QUEUED_TAGS: usize = 1024;
+
+/// How many finished tags the queue remembers (to drop their late replies)
+const MAX_FINISHED: usize = 1024;
+
+/// Replies received by the reader threads
+struct TaggedQueue<Id, R> {
+    /// Replies not yet polled for, by tag
+    replies: HashMap<u64, Vec<(Id, R)>>,
+    /// Whether the channel at each index was disconnected
+    disconnected: Vec<bool>,
+    /// Tags which were finished, oldest first
+    ///
+    /// Replies which arrive for them are dropped (at most [`MAX_FINISHED`] are remembered)
+    finished: Vec<u64>,
+    /// Whether the readers should stop
+    closed: bool,
+}
+
+impl<Id, R> TaggedQueue<Id, R> {
+    /// Whether the replies tagged with `tag` are no longer wanted
+    fn is_finished(&self, tag: u64) -> bool {
+        for idx in 0..self.finished.len() {
+            if self.finished[idx] == tag {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether there is room for a reply tagged with `tag`
+    fn has_room(&self, tag: u64) -> bool {
+        self.replies.contains_key(&tag) || self.replies.len() < MAX_QUEUED_TAGS
+    }
+}
+
+#[allow(dead_code)]
+struct TaggedQueueInv<C> {
+    ghost channels: Seq<C>,
+}
+
+impl<C> TaggedQueueInv<C> where C: Channel, C::R: TaggedMessage {
+    spec fn reply_inv(self, tag: u64, id: C::Id, r: C::R) -> bool {
+        let channels = channel_seq_to_map(self.channels);
+        &&& channels.contains_key(id)
+        &&& r.spec_tag() == tag
+        &&& C::K::recv_inv(channels[id].constant(), id, r)
+    }
+}
+
+impl<C> vstd::rwlock::RwLockPredicate<TaggedQueue<C::Id, C::R>> for TaggedQueueInv<C> where
+    C: Channel,
+    C::R: TaggedMessage,
+ {
+    closed spec fn inv(self, v: TaggedQueue<C::Id, C::R>) -> bool {
+        &&& v.disconnected@.len() == self.channels.len()
+        &&& forall|tag: u64| #[trigger]
+            v.replies@.contains_key(tag) ==> forall|idx|
+                0 <= idx < v.replies@[tag]@.len() ==> {
+                    let (id, r) = #[trigger] v.replies@[tag]@[idx];
+                    self.reply_inv(tag, id, r)
+                }
+    }
+}
+
+/// Pool which polls its channels concurrently
+///
+/// Each channel has a reader thread which moves its replies into a shared queue, so a poll
+/// returns whatever has arrived from any server without waiting on the others.
+///
+/// The reader threads stop when their channel disconnects or after [`ParallelPool::shutdown`].
+pub struct ParallelPool<C> where C: Channel, C::R: TaggedMessage {
+    channels: Arc<Vec<C>>,
+    queue: Arc<RwLock<TaggedQueue<C::Id, C::R>, TaggedQueueInv<C>>>,
+}
+
+/// What a reader did in a [`Reader::step`]
+enum Step {
+    /// Moved a reply into the queue (or dropped it)
+    Received,
+    /// Found nothing on the channel
+    Empty,
+    /// Should stop: the channel disconnected or the pool was shut down
+    Stop,
+}
+
+/// Reader thread state: moves the replies from one channel into the queue
+struct Reader<C> where C: Channel, C::R: TaggedMessage {
+    channels: Arc<Vec<C>>,
+    queue: Arc<RwLock<TaggedQueue<C::Id, C::R>, TaggedQueueInv<C>>>,
+    idx: usize,
+}
+
+impl<C> Reader<C> where C: Channel, C::R: TaggedMessage {
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& self.queue.pred().channels == (*self.channels)@
+        &&& (*self.channels)@.map_values(|c: C| c.spec_id()).no_duplicates()
+        &&& self.idx < (*self.channels)@.len()
+    }
+
+    /// Receives (at most) one reply from the channel
+    ///
+    /// The queue is only locked when something arrives: on an empty channel the reader does not
+    /// learn whether it should stop (see [`Reader::closed`])
+    fn step(&self) -> Step {
+        proof {
+            use_type_invariant(self);
+            lemma_channel_seq_to_map((*self.channels)@, channel_seq_to_map((*self.channels)@));
+        }
+        let channel = &self.channels[self.idx];
+        match channel.try_recv() {
+            Ok(r) => {
+                let tag = r.tag();
+                let id = channel.id();
+                assert(self.queue.pred().reply_inv(tag, id, r));
+
+                let (mut queue, handle) = self.queue.acquire_write();
+                let closed = queue.closed;
+                // late replies (and replies which do not fit) are dropped
+                if !closed && !queue.is_finished(tag) && queue.has_room(tag) {
+                    let mut replies = match queue.replies.remove(&tag) {
+                        Some(replies) => replies,
+                        None => Vec::new(),
+                    };
+                    replies.push((id, r));
+                    queue.replies.insert(tag, replies);
+                }
+                handle.release_write(queue);
+                if closed {
+                    Step::Stop
+                } else {
+                    Step::Received
+                }
+            },
+            Err(TryRecvError::Empty) => Step::Empty,
+            Err(TryRecvError::Disconnected) => {
+                let (mut queue, handle) = self.queue.acquire_write();
+                queue.disconnected.set(self.idx, true);
+                handle.release_write(queue);
+                Step::Stop
+            },
+        }
+    }
+
+    /// Whether the pool was shut down
+    fn closed(&self) -> bool {
+        let handle = self.queue.acquire_read();
+        let closed = handle.borrow().closed;
+        handle.release_read();
+        closed
+    }
+}
+
+impl<C> ParallelPool<C> where C: Channel, C::R: TaggedMessage {
+    #[verifier::type_invariant]
+    closed spec fn inv(self) -> bool {
+        &&& self.queue.pred().channels == (*self.channels)@
+        // all channel ids are different
+        &&& (*self.channels)@.map_values(|c: C| c.spec_id()).no_duplicates()
+    }
+
+    /// Stops the reader threads (also done when the pool is dropped)
+    ///
+    /// Replies which already arrived can still be polled for
+    pub fn shutdown(&self) {
+        let (mut queue, handle) = self.queue.acquire_write();
+        queue.closed = true;
+        handle.release_write(queue);
+    }
+
+    fn _channels(&self) -> (r: &[C])
+        ensures
+            self._spec_channels() == channel_seq_to_map(r@),
+            r@.map_values(|c: C| c.spec_id()).no_duplicates(),
+    {
+        proof {
+            use_type_invariant(self);
+        }
+        self.channels.as_slice()
+    }
+
+    closed spec fn _spec_channels(self) -> Map<<C as Channel>::Id, C> {
+        channel_seq_to_map((*self.channels)@)
+    }
+
+    closed spec fn _spec_len(self) -> nat {
+        (*self.channels)@.len()
+    }
+
+    proof fn _lemma_len(tracked &self)
+        ensures
+            self._spec_len() == self._spec_channels().len(),
+    {
+        use_type_invariant(self);
+        lemma_channel_seq_to_map((*self.channels)@, self._spec_channels());
+        let ghost pool_ids = (*self.channels)@.map_values(|c: C| c.spec_id());
+        assert(self._spec_len() == pool_ids.len());
+        assert(self._spec_channels().len() == pool_ids.to_set().len());
+        pool_ids.unique_seq_to_set();
+    }
+}
+
+impl<C> ParallelPool<C> where
+    C: Channel + Send + Sync + 'static,
+    C::Id: Send + Sync,
+    C::R: TaggedMessage + Send + Sync,
+ {
+    /// Creates the pool and spawns a reader thread per channel
+    pub fn new(pool: Vec<C>) -> (r: Self)
+        requires
+            pool@.map_values(|c: C| c.spec_id()).no_duplicates(),
+        ensures
+            r.spec_len() == pool@.len(),
+            r.spec_channels() == channel_seq_to_map(pool@),
+    {
+        let ghost pred = TaggedQueueInv { channels: pool@ };
+        let mut disconnected = Vec::new();
+        for idx in 0..pool.len()
+            invariant
+                disconnected@.len() == idx,
+        {
+            disconnected.push(false);
+        }
+        let queue = TaggedQueue {
+            replies: HashMap::new(),
+            disconnected,
+            finished: Vec::new(),
+            closed: false,
+        };
+        assert(<TaggedQueueInv<C> as vstd::rwlock::RwLockPredicate<_>>::inv(pred, queue));
+
+        let channels = Arc::new(pool);
+        let queue = Arc::new(RwLock::new(queue, Ghost(pred)));
+        for idx in 0..channels.len()
+            invariant
+                queue.pred().channels == (*channels)@,
+                (*channels)@.map_values(|c: C| c.spec_id()).no_duplicates(),
+        {
+            spawn_reader(Reader { channels: channels.clone(), queue: queue.clone(), idx });
+        }
+
+        ParallelPool { channels, queue }
+    }
+}
+
+impl<C> ConnectionPool for ParallelPool<C> where
+    C: Channel + Send + Sync + 'static,
+    C::Id: Send + Sync,
+    C::R: TaggedMessage + Send + Sync,
+ {
+    type R = C::R;
+
+    type C = C;
+
+    fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    closed spec fn spec_len(self) -> nat {
+        self._spec_len()
+    }
+
+    fn channels(&self) -> &[Self::C] {
+        self._channels()
+    }
+
+    closed spec fn spec_channels(&self) -> Map<<Self::C as Channel>::Id, Self::C> {
+        self._spec_channels()
+    }
+
+    fn poll(&self, request_tag: u64) -> Vec<(C::Id, Result<Option<C::R>, TryRecvError>)> {
+        proof {
+            use_type_invariant(self);
+            lemma_channel_seq_to_map((*self.channels)@, self._spec_channels());
+        }
+        let (mut queue, handle) = self.queue.acquire_write();
+        let mut replies = match queue.replies.remove(&request_tag) {
+            Some(replies) => replies,
+            None => Vec::new(),
+        };
+
+        let mut v = Vec::new();
+        for idx in 0..self.channels.len()
+            invariant
+                self.queue.inv(queue),
+                queue.disconnected@.len() == (*self.channels)@.len(),
+                forall|r_idx|
+                    0 <= r_idx < replies@.len() ==> {
+                        let (id, r) = #[trigger] replies@[r_idx];
+                        self.queue.pred().reply_inv(request_tag, id, r)
+                    },
+                forall|v_idx|
+                    0 <= v_idx < v@.len() ==> {
+                        let (id, resp): (C::Id, Result<Option<C::R>, _>) = #[trigger] v@[v_idx];
+                        &&& self.spec_channels().contains_key(id)
+                        &&& resp is Ok && resp->Ok_0 is Some ==> {
+                            let x = resp->Ok_0->Some_0;
+                            &&& x.spec_tag() == request_tag
+                            &&& C::K::recv_inv(self.spec_channels()[id].constant(), id, x)
+                        }
+                    },
+                forall|idx|
+                    0 <= idx < (*self.channels)@.len() ==> {
+                        let channel = #[trigger] (*self.channels)@[idx];
+                        &&& self._spec_channels().contains_key(channel.spec_id())
+                        &&& self._spec_channels()[channel.spec_id()] == channel
+                    },
+        {
+            if queue.disconnected[idx] {
+                v.push((self.channels[idx].id(), Err(TryRecvError::Disconnected)));
+            }
+        }
+        handle.release_write(queue);
+
+        loop
+            invariant
+                forall|r_idx|
+                    0 <= r_idx < replies@.len() ==> {
+                        let (id, r) = #[trigger] replies@[r_idx];
+                        self.queue.pred().reply_inv(request_tag, id, r)
+                    },
+                forall|v_idx|
+                    0 <= v_idx < v@.len() ==> {
+                        let (id, resp): (C::Id, Result<Option<C::R>, _>) = #[trigger] v@[v_idx];
+                        &&& self.spec_channels().contains_key(id)
+                        &&& resp is Ok && resp->Ok_0 is Some ==> {
+                            let x = resp->Ok_0->Some_0;
+                            &&& x.spec_tag() == request_tag
+                            &&& C::K::recv_inv(self.spec_channels()[id].constant(), id, x)
+                        }
+                    },
+                self.queue.pred().channels == (*self.channels)@,
+            decreases replies@.len(),
+        {
+            let ghost old_replies = replies@;
+            match replies.pop() {
+                Some((id, r)) => {
+                    assert(old_replies[old_replies.len() - 1] == (id, r));
+                    v.push((id, Ok(Some(r))));
+                },
+                None => break ,
+            }
+        }
+
+        v
+    }
+
+    fn finish(&self, request_tag: u64) {
+        let (mut queue, handle) = self.queue.acquire_write();
+        queue.replies.remove(&request_tag);
+        // the readers drop the replies which are still to arrive
+        if !queue.is_finished(request_tag) {
+            if queue.finished.len() >= MAX_FINISHED {
+                queue.finished.remove(0);
+            }
+            queue.finished.push(request_tag);
+        }
+        handle.release_write(queue);
+    }
+
+    proof fn lemma_len(tracked &self) {
+        self._lemma_len()
+    }
+
+    proof fn lemma_channels(tracked &self) {
+        use_type_invariant(self);
+        lemma_channel_seq_to_map((*self.channels)@, self._spec_channels());
+    }
+}
+
+// Why is this unverified: verus does not support threads
+#[verifier::external_body]
+fn spawn_reader<C>(reader: Reader<C>) where
+    C: Channel + Send + Sync + 'static,
+    C::Id: Send + Sync,
+    C::R: TaggedMessage + Send + Sync,
+ {
+    std::thread::spawn(move || {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match reader.step() {
+                Step::Received => backoff = MIN_BACKOFF,
+                Step::Empty => {
+                    // only an idle reader checks whether it should stop
+                    if backoff == MAX_BACKOFF && reader.closed() {
+                        break ;
+                    }
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                },
+                Step::Stop => break ,
+            }
+        }
+    });
+}
+
+} // verus!
+impl<C> Drop for ParallelPool<C> where C: Channel, C::R: TaggedMessage {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
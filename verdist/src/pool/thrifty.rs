@@ -0,0 +1,140 @@
+//! Thrifty fan-out: contact a preferred quorum first
+//!
+//! Broadcasting every request to all servers wastes messages when only a quorum of replies is
+//! needed. A [`Thrifty`] policy sends the request to the servers which have been the fastest so
+//! far, and only falls back to the rest if they do not deliver (see
+//! [`crate::pool::BroadcastPool::broadcast_thrifty`]).
+//!
+//! None of this is load bearing for correctness: it only decides *who* gets the request first.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::network::channel::Channel;
+use crate::network::error::TryRecvError;
+
+use vstd::prelude::*;
+
+/// Weight of a new latency sample in the moving average
+const EWMA_ALPHA: f64 = 0.2;
+
+verus! {
+
+/// Fan-out policy which contacts the fastest servers first
+///
+/// Servers are ranked by a moving average of their reply latency. Servers which have not been
+/// heard from rank first, so every server eventually gets measured.
+#[verifier::external_body]
+#[verifier::reject_recursive_types(Id)]
+pub struct Thrifty<Id> {
+    fallback_after: Duration,
+    latencies: Mutex<BTreeMap<Id, f64>>,
+}
+
+/// A single thrifty fan-out: who was contacted first, and when
+#[verifier::external_body]
+#[verifier::reject_recursive_types(Id)]
+pub struct ThriftyRound<Id> {
+    preferred: BTreeSet<Id>,
+    seen: BTreeSet<Id>,
+    started: Instant,
+    fell_back: bool,
+}
+
+impl<Id> Thrifty<Id> where Id: Ord + Clone {
+    /// Fall back to the remaining servers if the preferred ones have not delivered after
+    /// `fallback_after`
+    #[verifier::external_body]
+    pub fn new(fallback_after: Duration) -> Self {
+        Thrifty { fallback_after, latencies: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Starts a round, preferring the `n` fastest channels
+    #[verifier::external_body]
+    pub fn start_round<C>(&self, channels: &[C], n: usize) -> ThriftyRound<Id> where
+        C: Channel<Id = Id>,
+     {
+        let latencies = self.latencies.lock().expect("latency lock should not be poisoned");
+        let mut ranked: Vec<(f64, Id)> = channels
+            .iter()
+            .map(|c| {
+                let id = c.id();
+                (latencies.get(&id).copied().unwrap_or(0.0), id)
+            })
+            .collect();
+        ranked.sort_by(|(a, a_id), (b, b_id)| a.total_cmp(b).then_with(|| a_id.cmp(b_id)));
+
+        ThriftyRound {
+            preferred: ranked.into_iter().take(n).map(|(_, id)| id).collect(),
+            seen: BTreeSet::new(),
+            started: Instant::now(),
+            fell_back: false,
+        }
+    }
+
+    #[verifier::external_body]
+    fn record(&self, id: &Id, latency: Duration) {
+        let mut latencies = self.latencies.lock().expect("latency lock should not be poisoned");
+        let sample = latency.as_secs_f64();
+        latencies
+            .entry(id.clone())
+            .and_modify(|avg| *avg = EWMA_ALPHA * sample + (1.0 - EWMA_ALPHA) * *avg)
+            .or_insert(sample);
+    }
+}
+
+impl<Id> ThriftyRound<Id> where Id: Ord + Clone {
+    /// Whether the channel was contacted in the first place
+    #[verifier::external_body]
+    pub fn is_preferred(&self, id: &Id) -> bool {
+        self.preferred.contains(id)
+    }
+
+    /// Whether the request should now be sent to the remaining servers
+    ///
+    /// This happens once: after a timeout, after an error, or when all the preferred servers
+    /// replied and that was not enough
+    #[verifier::external_body]
+    pub fn should_fall_back(
+        &self,
+        thrifty: &Thrifty<Id>,
+        n_received: usize,
+        errors: &BTreeMap<Id, TryRecvError>,
+    ) -> bool {
+        !self.fell_back && (!errors.is_empty() || n_received >= self.preferred.len()
+            || self.started.elapsed() >= thrifty.fallback_after)
+    }
+
+    #[verifier::external_body]
+    pub fn mark_fell_back(&mut self) {
+        self.fell_back = true;
+    }
+
+    /// Records the latency of the replies (and errors) which were not seen before
+    ///
+    /// Errors count as a reply at the fallback timeout, which sends the server to the back of the
+    /// ranking for a while
+    #[verifier::external_body]
+    pub fn observe(
+        &mut self,
+        thrifty: &Thrifty<Id>,
+        replied: &BTreeSet<Id>,
+        errors: &BTreeMap<Id, TryRecvError>,
+    ) {
+        let elapsed = self.started.elapsed();
+        for id in replied {
+            if self.seen.insert(id.clone()) {
+                thrifty.record(id, elapsed);
+            }
+        }
+        for id in errors.keys() {
+            if self.seen.insert(id.clone()) {
+                thrifty.record(id, elapsed.max(thrifty.fallback_after));
+            }
+        }
+    }
+}
+
+} // verus!
@@ -0,0 +1,205 @@
+//! Server side of request cancellation
+//!
+//! A [`CancellingChannel`] receives everything that is already queued on the underlying channel
+//! before handing out the next request, so that a cancellation (see [`Cancellable`]) can drop the
+//! request it refers to even if it was sent (long) before. The server never sees the
+//! cancellations themselves.
+use std::marker::PhantomData;
+
+use crate::network::channel::Channel;
+#[cfg(verus_only)]
+use crate::network::channel::ChannelInvariant;
+use crate::network::error::SendError;
+use crate::network::error::TryRecvError;
+use crate::network::latency::Latency;
+use crate::rpc::proto::Cancellable;
+
+use vstd::prelude::*;
+use vstd::rwlock::RwLock;
+
+verus! {
+
+/// How many cancellations of requests which have not arrived (yet) are remembered
+const MAX_CANCELLED: usize = 1024;
+
+/// Requests received but not yet handed out
+struct Inbox<R> {
+    queued: Vec<R>,
+    /// Tags cancelled before their request arrived, oldest first
+    cancelled: Vec<u64>,
+    disconnected: bool,
+}
+
+impl<R> Inbox<R> where R: Cancellable {
+    /// Drops the queued requests tagged with `tag`, or the request when it arrives
+    fn cancel(&mut self, tag: u64)
+        ensures
+            forall|m: R| #[trigger] final(self).queued@.contains(m) ==> old(self).queued@.contains(m),
+    {
+        let mut kept = Vec::new();
+        let mut found = false;
+        while self.queued.len() > 0
+            invariant
+                forall|m: R| #[trigger] kept@.contains(m) ==> old(self).queued@.contains(m),
+                forall|m: R| #[trigger] self.queued@.contains(m) ==> old(self).queued@.contains(m),
+            decreases self.queued.len(),
+        {
+            let ghost queued = self.queued@;
+            let m = self.queued.remove(0);
+            assert(queued.contains(m));
+            if m.tag() == tag {
+                found = true;
+            } else {
+                kept.push(m);
+            }
+        }
+        self.queued = kept;
+
+        if !found {
+            if self.cancelled.len() >= MAX_CANCELLED {
+                self.cancelled.remove(0);
+            }
+            self.cancelled.push(tag);
+        }
+    }
+
+    /// Whether a request which just arrived was already cancelled
+    fn take_cancelled(&mut self, tag: u64) -> bool
+        ensures
+            final(self).queued == old(self).queued,
+    {
+        for idx in 0..self.cancelled.len()
+            invariant
+                self.queued == old(self).queued,
+                self.cancelled@ == old(self).cancelled@,
+        {
+            if self.cancelled[idx] == tag {
+                self.cancelled.remove(idx);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[allow(dead_code)]
+struct InboxInv<K, Id, S> {
+    ghost channel_inv: K,
+    ghost channel_id: Id,
+    _marker: PhantomData<S>,
+}
+
+impl<K, Id, R, S> vstd::rwlock::RwLockPredicate<Inbox<R>> for InboxInv<K, Id, S> where
+    K: ChannelInvariant<K, Id, R, S>,
+ {
+    closed spec fn inv(self, v: Inbox<R>) -> bool {
+        forall|m: R| #[trigger]
+            v.queued@.contains(m) ==> K::recv_inv(self.channel_inv, self.channel_id, m)
+    }
+}
+
+/// Server side channel which drops cancelled requests
+pub struct CancellingChannel<C> where C: Channel, C::R: Cancellable {
+    channel: C,
+    inbox: RwLock<Inbox<C::R>, InboxInv<C::K, C::Id, C::S>>,
+}
+
+impl<C> CancellingChannel<C> where C: Channel, C::R: Cancellable {
+    pub fn new(channel: C) -> (r: Self)
+        ensures
+            r.spec_id() == channel.spec_id(),
+            r.constant() == channel.constant(),
+    {
+        let ghost lock_pred = InboxInv {
+            channel_inv: channel.constant(),
+            channel_id: channel.spec_id(),
+            _marker: PhantomData,
+        };
+        let inbox = Inbox { queued: Vec::new(), cancelled: Vec::new(), disconnected: false };
+        CancellingChannel { channel, inbox: RwLock::new(inbox, Ghost(lock_pred)) }
+    }
+
+    #[verifier::type_invariant]
+    spec fn inv(self) -> bool {
+        &&& self.inbox.pred().channel_inv == self.channel.constant()
+        &&& self.inbox.pred().channel_id == self.channel.spec_id()
+    }
+}
+
+impl<C> Channel for CancellingChannel<C> where C: Channel, C::R: Cancellable {
+    type R = C::R;
+
+    type S = C::S;
+
+    type Id = C::Id;
+
+    type K = C::K;
+
+    closed spec fn constant(self) -> Self::K {
+        self.channel.constant()
+    }
+
+    fn id(&self) -> Self::Id {
+        self.channel.id()
+    }
+
+    closed spec fn spec_id(self) -> Self::Id {
+        self.channel.spec_id()
+    }
+
+    #[verifier::exec_allows_no_decreases_clause]
+    fn try_recv(&self) -> Result<Self::R, TryRecvError> {
+        proof {
+            use_type_invariant(self);
+        }
+        let (mut inbox, handle) = self.inbox.acquire_write();
+        while !inbox.disconnected
+            invariant
+                self.inbox.inv(inbox),
+                self.inbox.pred().channel_inv == self.channel.constant(),
+                self.inbox.pred().channel_id == self.channel.spec_id(),
+        {
+            match self.channel.try_recv() {
+                Ok(m) => {
+                    let tag = m.tag();
+                    if m.is_cancel() {
+                        inbox.cancel(tag);
+                    } else if !inbox.take_cancelled(tag) {
+                        inbox.queued.push(m);
+                    }
+                },
+                Err(TryRecvError::Empty) => break ,
+                Err(TryRecvError::Disconnected) => {
+                    inbox.disconnected = true;
+                },
+            }
+        }
+
+        let res = if inbox.queued.len() > 0 {
+            let ghost queued = inbox.queued@;
+            let m = inbox.queued.remove(0);
+            assert(queued[0] == m);
+            assert(queued.contains(m));
+            Ok(m)
+        } else if inbox.disconnected {
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
+        };
+        handle.release_write(inbox);
+        res
+    }
+
+    fn send(&self, s: &Self::S) -> Result<(), SendError<Self::S>> {
+        self.channel.send(s)
+    }
+
+    fn set_latency(&mut self, latency: Latency) {
+        proof {
+            use_type_invariant(&*self);
+        }
+        self.channel.set_latency(latency);
+    }
+}
+
+} // verus!
@@ -1,3 +1,4 @@
+pub mod cancel;
 pub mod proto;
 pub mod replies;
 pub mod request_context;
@@ -5,3 +6,4 @@ pub mod rpc_channel;
 
 pub use replies::Replies;
 pub use request_context::RequestContext;
+pub use request_context::WaitStatus;
@@ -11,4 +11,25 @@ pub trait TaggedMessage {
     ;
 }
 
+/// Requests which can be cancelled
+///
+/// The cancellation of a request is a message of the same type, with the same tag. A client sends
+/// it when it stops waiting for replies (see [`crate::pool::BroadcastPool::cancel`]), and servers
+/// drop the request if they have not handled it yet (see
+/// [`crate::rpc::cancel::CancellingChannel`]).
+pub trait Cancellable: TaggedMessage + Sized {
+    spec fn spec_is_cancel(self) -> bool;
+
+    fn is_cancel(&self) -> (r: bool)
+        ensures
+            r == self.spec_is_cancel(),
+    ;
+
+    fn cancellation(&self) -> (r: Self)
+        ensures
+            r.spec_tag() == self.spec_tag(),
+            r.spec_is_cancel(),
+    ;
+}
+
 } // verus!
@@ -124,9 +124,12 @@ impl<C, Pred, A> Replies<C, Pred, A> where
         self.accum.spec_handled_replies()
     }
 
+    /// Number of replies and errors received (saturating)
     pub fn n_received(&self) -> usize {
-        assume(self.spec_len() + self.errors.len() <= usize::MAX);  // XXX: arithmetic overflow
-        self.len() + self.errors.len()
+        match self.len().checked_add(self.errors.len()) {
+            Some(n) => n,
+            None => usize::MAX,
+        }
     }
 
     pub closed spec fn spec_accumulator(self) -> A {
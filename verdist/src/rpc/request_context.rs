@@ -2,7 +2,11 @@
 use crate::network::channel::Channel;
 #[cfg(verus_only)]
 use crate::network::channel::ChannelInvariant;
+use crate::pool::broadcast_pool::send_filter;
+use crate::pool::thrifty::Thrifty;
+use crate::pool::thrifty::ThriftyRound;
 use crate::pool::ChannelId;
+use crate::pool::ChannelReq;
 use crate::pool::ChannelResp;
 use crate::pool::ConnectionPool;
 use crate::pool::PoolChannel;
@@ -16,6 +20,16 @@ use super::proto::TaggedMessage;
 
 verus! {
 
+/// Progress of a [`RequestContext`] (see [`RequestContext::poll_step`])
+pub enum WaitStatus {
+    /// Still waiting for replies
+    Pending,
+    /// The termination condition holds
+    Done,
+    /// Every server was heard from, and the termination condition does not hold
+    GaveUp,
+}
+
 pub struct RequestContext<'a, Pool, Pred, A> where
     Pool: ConnectionPool,
     ChannelResp<Pool>: TaggedMessage,
@@ -25,6 +39,14 @@ pub struct RequestContext<'a, Pool, Pred, A> where
     pool: &'a Pool,
     request_tag: u64,
     replies: Replies<PoolChannel<Pool>, Pred, A>,
+    /// Servers left out of a thrifty broadcast, which get the request on fall back
+    fallback: Option<Fallback<'a, Pool>>,
+}
+
+struct Fallback<'a, Pool> where Pool: ConnectionPool {
+    request: ChannelReq<Pool>,
+    thrifty: &'a Thrifty<ChannelId<Pool>>,
+    round: ThriftyRound<ChannelId<Pool>>,
 }
 
 impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
@@ -37,12 +59,21 @@ impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
     closed spec fn inv(self) -> bool {
         &&& self.pool.spec_channels() == self.replies.channels()
         &&& self.request_tag == self.replies.request_tag()
+        &&& self.fallback is Some ==> forall|id| #[trigger]
+            self.pool.spec_channels().contains_key(id) ==> {
+                let chan = self.pool.spec_channels()[id];
+                <PoolChannel<Pool> as Channel>::K::send_inv(
+                    chan.constant(),
+                    chan.spec_id(),
+                    self.fallback->Some_0.request,
+                )
+            }
     }
 }
 
 impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
     Pool: ConnectionPool,
-    ChannelId<Pool>: std::fmt::Debug,
+    ChannelId<Pool>: std::fmt::Debug + Clone,
     ChannelResp<Pool>: TaggedMessage,
     Pred: InvariantPredicate<Pred, A>,
     A: ReplyAccumulator<PoolChannel<Pool>, Pred>,
@@ -58,7 +89,45 @@ impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
             r.pred() == pred@,
             r.channels() == pool.spec_channels(),
     {
-        RequestContext { pool, request_tag, replies: Replies::new(pred, accum) }
+        RequestContext { pool, request_tag, replies: Replies::new(pred, accum), fallback: None }
+    }
+
+    /// Context for a request which was only sent to the preferred servers of `round`
+    ///
+    /// The request is kept to send it to the others on fall back
+    pub fn new_thrifty(
+        pool: &'a Pool,
+        request: ChannelReq<Pool>,
+        pred: Ghost<Pred>,
+        accum: A,
+        thrifty: &'a Thrifty<ChannelId<Pool>>,
+        round: ThriftyRound<ChannelId<Pool>>,
+    ) -> (r: Self) where ChannelReq<Pool>: TaggedMessage
+        requires
+            Pred::inv(pred@, accum),
+            accum.request_tag() == request.spec_tag(),
+            accum.spec_handled_replies().is_empty(),
+            accum.channels() == pool.spec_channels(),
+            vstd::laws_cmp::obeys_cmp::<ChannelId<Pool>>(),
+            forall|id| #[trigger]
+                pool.spec_channels().contains_key(id) ==> {
+                    let chan = pool.spec_channels()[id];
+                    <PoolChannel<Pool> as Channel>::K::send_inv(
+                        chan.constant(),
+                        chan.spec_id(),
+                        request,
+                    )
+                },
+        ensures
+            r.pred() == pred@,
+            r.channels() == pool.spec_channels(),
+    {
+        RequestContext {
+            pool,
+            request_tag: request.tag(),
+            replies: Replies::new(pred, accum),
+            fallback: Some(Fallback { request, thrifty, round }),
+        }
     }
 
     pub fn tag(&self) -> u64 {
@@ -77,6 +146,133 @@ impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
         self.pool.spec_channels()
     }
 
+    pub closed spec fn spec_replies(self) -> Replies<PoolChannel<Pool>, Pred, A> {
+        self.replies
+    }
+
+    /// Makes one round of progress on the request, without blocking
+    ///
+    /// Polls the pool once, unless the termination condition already holds or there is no one
+    /// left to hear from. See [`RequestContext::wait_for`] for the blocking version.
+    pub fn poll_step<F>(&mut self, termination_cond: &F) -> (r: WaitStatus) where
+        F: Fn(&Replies<PoolChannel<Pool>, Pred, A>) -> bool,
+
+        requires
+            forall|replies| termination_cond.requires((&replies,)),
+        ensures
+            final(self).pred() == old(self).pred(),
+            final(self).channels() == old(self).channels(),
+            r is Done ==> call_ensures(*termination_cond, (&final(self).spec_replies(),), true),
+    {
+        proof {
+            use_type_invariant(&*self);
+        }
+        let ghost pred = self.pred();
+        let ghost channels = self.channels();
+        let ghost request_tag = self.request_tag;
+        if termination_cond(&self.replies) {
+            vlib::veprintln!("termination condition triggered");
+            return WaitStatus::Done;
+        }
+        match self.fallback.take() {
+            Some(mut fallback) => {
+                let replied = self.replies.accumulator().handled_replies();
+                fallback.round.observe(fallback.thrifty, &replied, self.replies.errors());
+                if fallback.round.should_fall_back(
+                    fallback.thrifty,
+                    self.replies.n_received(),
+                    self.replies.errors(),
+                ) {
+                    vlib::veprintln!("thrifty fall back triggered");
+                    let round = &fallback.round;
+                    send_filter(
+                        self.pool,
+                        &fallback.request,
+                        |id: ChannelId<Pool>| !round.is_preferred(&id),
+                    );
+                    fallback.round.mark_fell_back();
+                }
+                self.fallback = Some(fallback);
+            },
+            None => {},
+        }
+
+        // TODO: we can try to figure out a better "give up" condition
+
+        if self.replies.n_received() >= self.n_nodes() {
+            vlib::veprintln!("failsafe give up triggered");
+            return WaitStatus::GaveUp;
+        }
+        let resps = self.pool.poll(self.request_tag);
+        if resps.is_empty() {
+            return WaitStatus::Pending;
+        }
+        for (id, r) in it: resps
+            invariant
+                pred == self.pred(),
+                channels == self.channels(),
+                self.pool.spec_channels() == channels,
+                self.replies.channels() == channels,
+                forall|idx|
+                    0 <= idx < resps@.len() ==> {
+                        let (id, r) = #[trigger] resps@[idx];
+                        &&& self.pool.spec_channels().contains_key(id)
+                        &&& {
+                            let chan = self.pool.spec_channels()[id];
+                            r is Ok && r->Ok_0 is Some ==> {
+                                let resp = r->Ok_0->Some_0;
+                                &&& <<Pool as ConnectionPool>::C as Channel>::K::recv_inv(
+                                    chan.constant(),
+                                    id,
+                                    resp,
+                                )
+                                &&& resp.spec_tag() == request_tag
+                            }
+                        }
+                    },
+                self.request_tag == request_tag,
+                self.replies.request_tag() == request_tag,
+        {
+            proof {
+                use_type_invariant(&*self);
+                self.pool.lemma_channels();
+            }
+            let ghost chan = self.pool.spec_channels()[id];
+
+            match r {
+                Ok(Some(resp)) => {
+                    assert(chan.spec_id() == id);
+                    assert(<<Pool as ConnectionPool>::C as Channel>::K::recv_inv(
+                        chan.constant(),
+                        id,
+                        resp,
+                    ));
+                    self.replies.insert_reply(id, resp)
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    self.replies.insert_error(id, e);
+                },
+            }
+        }
+        WaitStatus::Pending
+    }
+
+    /// Ends the request, returning the replies gathered so far
+    ///
+    /// Replies arriving after this are not kept (see [`ConnectionPool::finish`])
+    pub fn into_replies(self) -> (r: Replies<PoolChannel<Pool>, Pred, A>)
+        ensures
+            r == self.spec_replies(),
+            Pred::inv(self.pred(), r.spec_accumulator()),
+    {
+        self.pool.finish(self.request_tag);
+        let replies = self.replies;
+        replies.lemma_pred();
+        assert(Pred::inv(replies.pred(), replies.spec_accumulator()));
+        replies
+    }
+
     #[verifier::exec_allows_no_decreases_clause]
     // TODO: a mechanism to ensure that the Replies we get back is the same we put in (i.e., same
     // identity, not same value, would be useful, maybe)
@@ -95,12 +291,8 @@ impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
                 &&& Pred::inv(self.pred(), r->Err_0.spec_accumulator())
             },
     {
-        proof {
-            use_type_invariant(&self);
-        }
         let ghost pred = self.pred();
         let ghost channels = self.channels();
-        let ghost request_tag = self.request_tag;
         let mut self_mut = self;
         loop
             invariant
@@ -109,86 +301,11 @@ impl<'a, Pool, Pred, A> RequestContext<'a, Pool, Pred, A> where
                 pred == self.pred(),
                 channels == self_mut.channels(),
                 channels == self.channels(),
-                self_mut.pool.spec_channels() == channels,
-                self_mut.replies.channels() == channels,
-                self_mut.request_tag == request_tag,
-                self_mut.replies.request_tag() == request_tag,
         {
-            if termination_cond(&self_mut.replies) {
-                vlib::veprintln!("termination condition triggered");
-                self_mut.replies.lemma_pred();
-                assert(Pred::inv(self_mut.replies.pred(), self_mut.replies.spec_accumulator()));
-                assert(self_mut.replies.pred() == pred);
-                return Ok(self_mut.replies);
-            }
-            // TODO: we can try to figure out a better "give up" condition
-
-            if self_mut.replies.n_received() >= self_mut.n_nodes() {
-                vlib::veprintln!("failsafe give up triggered");
-                let replies = self_mut.replies;
-                replies.lemma_pred();
-
-                assert(Pred::inv(replies.pred(), replies.spec_accumulator()));
-                assert(replies.pred() == pred);
-                assert(Pred::inv(pred, replies.spec_accumulator()));
-                assert(Pred::inv(self.pred(), replies.spec_accumulator()));
-                return Err(replies);
-            }
-            let resps = self_mut.pool.poll(self_mut.request_tag);
-            if resps.is_empty() {
-                continue ;
-            }
-            let mut idx = 0usize;
-            for (id, r) in it: resps
-                invariant
-                    pred == self_mut.pred(),
-                    channels == self_mut.channels(),
-                    self_mut.pool.spec_channels() == channels,
-                    self_mut.replies.channels() == channels,
-                    idx == it.pos,
-                    forall|idx|
-                        0 <= idx < resps@.len() ==> {
-                            let (id, r) = #[trigger] resps@[idx];
-                            &&& self_mut.pool.spec_channels().contains_key(id)
-                            &&& {
-                                let chan = self_mut.pool.spec_channels()[id];
-                                r is Ok && r->Ok_0 is Some ==> {
-                                    let resp = r->Ok_0->Some_0;
-                                    &&& <<Pool as ConnectionPool>::C as Channel>::K::recv_inv(
-                                        chan.constant(),
-                                        id,
-                                        resp,
-                                    )
-                                    &&& resp.spec_tag() == request_tag
-                                }
-                            }
-                        },
-                    self_mut.request_tag == request_tag,
-                    self_mut.replies.request_tag() == request_tag,
-            {
-                proof {
-                    self_mut.pool.lemma_channels();
-                }
-                let ghost chan = self_mut.pool.spec_channels()[id];
-
-                match r {
-                    Ok(Some(resp)) => {
-                        assert(chan.spec_id() == id);
-                        assert(<<Pool as ConnectionPool>::C as Channel>::K::recv_inv(
-                            chan.constant(),
-                            id,
-                            resp,
-                        ));
-                        self_mut.replies.insert_reply(id, resp)
-                    },
-                    Ok(None) => {},
-                    Err(e) => {
-                        self_mut.replies.insert_error(id, e);
-                    },
-                }
-
-                assume(idx < usize::MAX);  // XXX: overflow
-                idx = idx + 1;
+            match self_mut.poll_step(&termination_cond) {
+                WaitStatus::Done => return Ok(self_mut.into_replies()),
+                WaitStatus::GaveUp => return Err(self_mut.into_replies()),
+                WaitStatus::Pending => {},
             }
         }
     }
@@ -101,7 +101,7 @@ impl<'a, C> RpcContext<'a, C> where
     }
 
     #[verifier::exec_allows_no_decreases_clause]
-    pub fn wait(self) -> (r: Result<C::R, TryRecvError>)
+    pub fn wait(&self) -> (r: Result<C::R, TryRecvError>)
         ensures
             r is Ok ==> {
                 let resp = r->Ok_0;
@@ -117,4 +117,86 @@ impl<'a, C> RpcContext<'a, C> where
     }
 }
 
+/// Waits for the first of `ctxs` to be answered, removing it
+///
+/// Returns the index the answered context had, alongside its reply
+#[verifier::exec_allows_no_decreases_clause]
+pub fn wait_any<'a, C>(ctxs: &mut Vec<RpcContext<'a, C>>) -> (r: Result<
+    (usize, C::R),
+    TryRecvError,
+>) where
+    C: Channel,
+    C::Id: std::fmt::Debug,
+    C::R: TaggedMessage,
+    C::S: TaggedMessage,
+
+    requires
+        old(ctxs)@.len() > 0,
+    ensures
+        r is Ok ==> {
+            let (idx, resp) = r->Ok_0;
+            let ctx = old(ctxs)@[idx as int];
+            &&& idx < old(ctxs)@.len()
+            &&& final(ctxs)@ == old(ctxs)@.remove(idx as int)
+            &&& resp.spec_tag() == ctx.spec_tag()
+            &&& C::K::recv_inv(ctx.channel().constant(), ctx.channel().spec_id(), resp)
+        },
+{
+    loop
+        invariant
+            ctxs@ == old(ctxs)@,
+    {
+        for idx in 0..ctxs.len()
+            invariant
+                ctxs@ == old(ctxs)@,
+        {
+            if let Some(resp) = ctxs[idx].try_poll()? {
+                ctxs.remove(idx);
+                return Ok((idx, resp));
+            }
+        }
+    }
+}
+
+/// Waits for all of `ctxs` to be answered
+///
+/// The replies may arrive in any order (the channel buffers them by tag), but are returned in the
+/// order of the contexts
+pub fn wait_all<'a, C>(ctxs: &Vec<RpcContext<'a, C>>) -> (r: Result<Vec<C::R>, TryRecvError>) where
+    C: Channel,
+    C::Id: std::fmt::Debug,
+    C::R: TaggedMessage,
+    C::S: TaggedMessage,
+
+    ensures
+        r is Ok ==> {
+            let replies = r->Ok_0;
+            &&& replies@.len() == ctxs@.len()
+            &&& forall|idx|
+                0 <= idx < replies@.len() ==> {
+                    let ctx = ctxs@[idx];
+                    let resp = #[trigger] replies@[idx];
+                    &&& resp.spec_tag() == ctx.spec_tag()
+                    &&& C::K::recv_inv(ctx.channel().constant(), ctx.channel().spec_id(), resp)
+                }
+        },
+{
+    let mut replies = Vec::with_capacity(ctxs.len());
+    for idx in 0..ctxs.len()
+        invariant
+            replies@.len() == idx,
+            forall|j|
+                0 <= j < idx ==> {
+                    let ctx = ctxs@[j];
+                    let resp = #[trigger] replies@[j];
+                    &&& resp.spec_tag() == ctx.spec_tag()
+                    &&& C::K::recv_inv(ctx.channel().constant(), ctx.channel().spec_id(), resp)
+                },
+    {
+        let resp = ctxs[idx].wait()?;
+        replies.push(resp);
+    }
+    Ok(replies)
+}
+
 } // verus!
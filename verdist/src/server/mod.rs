@@ -0,0 +1,25 @@
+//! Running servers
+//!
+//! A server is a `poll` function over some shared state, called over and over by a few threads.
+//! Verus does not support threads, so this part is unverified.
+
+pub mod reply_cache;
+
+/// Number of threads polling a server
+pub const POLL_THREADS: usize = 5;
+
+/// Polls `server` from [`POLL_THREADS`] threads in the background, until `poll` returns false
+pub fn spawn_pollers<S, F>(server: S, poll: F) -> std::thread::JoinHandle<()>
+where
+    S: Send + Sync + 'static,
+    F: Fn(&S) -> bool + Send + Sync + 'static,
+{
+    std::thread::spawn(move || {
+        std::thread::scope(|s| {
+            for _ in 0..POLL_THREADS {
+                let (server, poll) = (&server, &poll);
+                s.spawn(move || while poll(server) {});
+            }
+        });
+    })
+}
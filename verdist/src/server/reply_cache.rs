@@ -0,0 +1,272 @@
+//! Replies sent by a server, kept to answer duplicate requests
+//!
+//! If the transport retries or duplicates messages, the same `(client_id, tag)` request can arrive
+//! more than once. Handling it again is not harmless in general, so the server answers duplicates
+//! with the reply it already sent.
+//!
+//! The cache keeps the reply to every request of a client, until the server tells it the client is
+//! done with them:
+//! - [`ReplyCache::acknowledge`] drops the replies to the requests tagged below some tag (clients
+//!   with a single request in flight acknowledge the earlier ones with each new request, see
+//!   [`crate::network::channel::BufferPolicy::LowWater`]). Clients which pipeline their requests
+//!   must not be acknowledged this way: their requests can arrive out of order;
+//! - [`ReplyCache::evict`] forgets a client altogether, once it is gone.
+use crate::rpc::proto::TaggedMessage;
+
+use std::collections::HashMap;
+
+use vstd::prelude::*;
+
+verus! {
+
+/// What the server remembers about a client
+struct ClientEntry<S> {
+    /// The requests tagged below this were acknowledged
+    low_water: u64,
+    /// Requests which were seen, by tag, with their reply once they were handled
+    replies: HashMap<u64, Option<S>>,
+}
+
+/// What to do with a request
+pub enum Lookup {
+    /// The request was not seen before: it should be handled
+    New,
+    /// The request was already handled: its reply is cached (see [`ReplyCache::reply`])
+    Duplicate,
+    /// The request was already acknowledged, or is still being handled (e.g., a reply which is
+    /// held back): it should be dropped
+    Stale,
+}
+
+/// Replies to the requests of each client
+pub struct ReplyCache<S> {
+    clients: HashMap<u64, ClientEntry<S>>,
+}
+
+/// The replies cached for a client satisfy `reply_inv(client_id, reply)`
+pub struct ReplyCacheInv<S> {
+    pub reply_inv: spec_fn(u64, S) -> bool,
+}
+
+impl<S> vstd::rwlock::RwLockPredicate<ReplyCache<S>> for ReplyCacheInv<S> where
+    S: TaggedMessage,
+ {
+    open spec fn inv(self, v: ReplyCache<S>) -> bool {
+        v.inv(self.reply_inv)
+    }
+}
+
+impl<S> ReplyCache<S> where S: TaggedMessage {
+    pub fn new() -> (r: Self)
+        ensures
+            forall|reply_inv| #[trigger] r.inv(reply_inv),
+    {
+        ReplyCache { clients: HashMap::new() }
+    }
+
+    /// Reply cached for the request of `client_id` tagged `tag`
+    pub closed spec fn spec_reply(self, client_id: u64, tag: u64) -> Option<S> {
+        if self.clients@.contains_key(client_id) && self.clients@[client_id].replies@.contains_key(
+            tag,
+        ) {
+            self.clients@[client_id].replies@[tag]
+        } else {
+            None
+        }
+    }
+
+    /// The cached replies can be sent (back) to their clients
+    pub closed spec fn inv(self, reply_inv: spec_fn(u64, S) -> bool) -> bool {
+        forall|client_id: u64, tag: u64| #[trigger]
+            self.spec_reply(client_id, tag) is Some ==> {
+                let reply = self.spec_reply(client_id, tag)->Some_0;
+                &&& reply_inv(client_id, reply)
+                &&& reply.spec_tag() == tag
+            }
+    }
+
+    /// The cached replies satisfy the invariant
+    pub proof fn lemma_reply(self, reply_inv: spec_fn(u64, S) -> bool, client_id: u64, tag: u64)
+        requires
+            self.inv(reply_inv),
+            self.spec_reply(client_id, tag) is Some,
+        ensures
+            reply_inv(client_id, self.spec_reply(client_id, tag)->Some_0),
+            self.spec_reply(client_id, tag)->Some_0.spec_tag() == tag,
+    {
+    }
+
+    /// Looks up the request of `client_id` tagged `tag`
+    pub fn lookup(
+        &self,
+        client_id: u64,
+        tag: u64,
+        Ghost(reply_inv): Ghost<spec_fn(u64, S) -> bool>,
+    ) -> (r: Lookup)
+        requires
+            self.inv(reply_inv),
+        ensures
+            r is Duplicate ==> {
+                &&& self.spec_reply(client_id, tag) is Some
+                &&& self.spec_reply(client_id, tag)->Some_0.spec_tag() == tag
+            },
+    {
+        let entry = match self.clients.get(&client_id) {
+            Some(entry) => entry,
+            None => return Lookup::New,
+        };
+        if tag < entry.low_water {
+            return Lookup::Stale;
+        }
+        match entry.replies.get(&tag) {
+            Some(Some(_)) => Lookup::Duplicate,
+            Some(None) => Lookup::Stale,
+            None => Lookup::New,
+        }
+    }
+
+    /// The reply cached for the request of `client_id` tagged `tag` (see [`Lookup::Duplicate`])
+    pub fn reply(&self, client_id: u64, tag: u64) -> (r: Option<&S>)
+        ensures
+            r is Some <==> self.spec_reply(client_id, tag) is Some,
+            r is Some ==> *r->Some_0 == self.spec_reply(client_id, tag)->Some_0,
+    {
+        match self.clients.get(&client_id) {
+            Some(entry) => match entry.replies.get(&tag) {
+                Some(reply) => reply.as_ref(),
+                None => None,
+            },
+            None => None,
+        }
+    }
+
+    /// Takes the entry of `client_id` out of the cache (an empty one if there is none)
+    fn take(&mut self, client_id: u64) -> (r: ClientEntry<S>)
+        ensures
+            final(self).clients@ == old(self).clients@.remove(client_id),
+            old(self).clients@.contains_key(client_id) ==> r == old(self).clients@[client_id],
+            !old(self).clients@.contains_key(client_id) ==> r.replies@.is_empty(),
+    {
+        match self.clients.remove(&client_id) {
+            Some(entry) => entry,
+            None => ClientEntry { low_water: 0, replies: HashMap::new() },
+        }
+    }
+
+    /// Marks the request of `client_id` tagged `tag` as being handled
+    ///
+    /// Until its reply is recorded, copies of the request are stale
+    pub fn start(
+        &mut self,
+        client_id: u64,
+        tag: u64,
+        Ghost(reply_inv): Ghost<spec_fn(u64, S) -> bool>,
+    )
+        requires
+            old(self).inv(reply_inv),
+        ensures
+            final(self).inv(reply_inv),
+    {
+        let mut entry = self.take(client_id);
+        if tag >= entry.low_water && !entry.replies.contains_key(&tag) {
+            entry.replies.insert(tag, None);
+        }
+        self.clients.insert(client_id, entry);
+        assert forall|id: u64, t: u64| #[trigger] self.spec_reply(id, t) is Some implies {
+            let reply = self.spec_reply(id, t)->Some_0;
+            &&& reply_inv(id, reply)
+            &&& reply.spec_tag() == t
+        } by {
+            assert(old(self).spec_reply(id, t) == self.spec_reply(id, t));
+        }
+    }
+
+    /// Drops the replies to the requests of `client_id` tagged below `tag`
+    ///
+    /// Requests with those tags are stale from now on
+    pub fn acknowledge(
+        &mut self,
+        client_id: u64,
+        tag: u64,
+        Ghost(reply_inv): Ghost<spec_fn(u64, S) -> bool>,
+    )
+        requires
+            old(self).inv(reply_inv),
+        ensures
+            final(self).inv(reply_inv),
+    {
+        let mut entry = self.take(client_id);
+        if tag > entry.low_water {
+            retain_from(&mut entry.replies, tag);
+            entry.low_water = tag;
+        }
+        self.clients.insert(client_id, entry);
+        assert forall|id: u64, t: u64| #[trigger] self.spec_reply(id, t) is Some implies {
+            let reply = self.spec_reply(id, t)->Some_0;
+            &&& reply_inv(id, reply)
+            &&& reply.spec_tag() == t
+        } by {
+            assert(old(self).spec_reply(id, t) == self.spec_reply(id, t));
+        }
+    }
+
+    /// Keeps the reply to a request of `client_id`
+    ///
+    /// The reply is not kept if its request was acknowledged in the meantime
+    pub fn record(
+        &mut self,
+        client_id: u64,
+        reply: S,
+        Ghost(reply_inv): Ghost<spec_fn(u64, S) -> bool>,
+    )
+        requires
+            old(self).inv(reply_inv),
+            reply_inv(client_id, reply),
+        ensures
+            final(self).inv(reply_inv),
+    {
+        let tag = reply.tag();
+        let mut entry = self.take(client_id);
+        if tag >= entry.low_water {
+            entry.replies.insert(tag, Some(reply));
+        }
+        self.clients.insert(client_id, entry);
+        assert forall|id: u64, t: u64| #[trigger] self.spec_reply(id, t) is Some implies {
+            let reply = self.spec_reply(id, t)->Some_0;
+            &&& reply_inv(id, reply)
+            &&& reply.spec_tag() == t
+        } by {
+            if id != client_id || t != tag {
+                assert(old(self).spec_reply(id, t) == self.spec_reply(id, t));
+            }
+        }
+    }
+
+    /// Forgets `client_id` (e.g., because it disconnected)
+    pub fn evict(&mut self, client_id: u64, Ghost(reply_inv): Ghost<spec_fn(u64, S) -> bool>)
+        requires
+            old(self).inv(reply_inv),
+        ensures
+            final(self).inv(reply_inv),
+    {
+        self.clients.remove(&client_id);
+        assert forall|id: u64, t: u64| #[trigger] self.spec_reply(id, t) is Some implies {
+            let reply = self.spec_reply(id, t)->Some_0;
+            &&& reply_inv(id, reply)
+            &&& reply.spec_tag() == t
+        } by {
+            assert(old(self).spec_reply(id, t) == self.spec_reply(id, t));
+        }
+    }
+}
+
+/// Keeps the entries of `replies` tagged at or above `tag`
+#[verifier::external_body]
+fn retain_from<S>(replies: &mut HashMap<u64, Option<S>>, tag: u64)
+    ensures
+        final(replies)@ == old(replies)@.restrict(old(replies)@.dom().filter(|t: u64| t >= tag)),
+{
+    replies.retain(|t, _| *t >= tag);
+}
+
+} // verus!
@@ -0,0 +1,191 @@
+//! Allocation of client ids
+//!
+//! Client ids are handed out by a single counter (an AtomicU64) whose permission lives in the
+//! shared invariant of the system using them. Every allocated id comes with an exclusive token,
+//! which the client trades in when logging in: this is what makes ids unique.
+use std::error::Error;
+use std::fmt::Display;
+
+use vstd::atomic::PermissionU64;
+use vstd::resource::map::GhostMapAuth;
+use vstd::resource::map::GhostPointsTo;
+use vstd::resource::Loc;
+
+use vstd::prelude::*;
+
+verus! {
+
+/// Proof that a client id was allocated and has not been used to log in yet
+pub type ClientIdToken = GhostPointsTo<u64, ()>;
+
+/// Every client id was handed out
+#[derive(Debug)]
+pub struct ExhaustedError;
+
+impl Error for ExhaustedError {
+
+}
+
+/// Client id allocator
+///
+/// The expected workflow is as follows
+///     - [`ClientIds::take_permission`] to extract the permission to update the AtomicU64
+///     - [`ClientIds::allocate`] to return the advanced permission, getting a token for the id
+///       just below the new value (ids skipped over are never handed out)
+///     - or [`ClientIds::return_permission`] to return it untouched
+///     - [`ClientIds::claim`] to trade the token in when logging in
+#[allow(unused)]
+pub struct ClientIds {
+    /// Permission of the next id generator (missing while an id is being allocated)
+    next_perm: Option<PermissionU64>,
+    /// Next id to be allocated
+    next_id: Ghost<u64>,
+    /// Id of the permission of the next id generator
+    next_perm_id: Ghost<int>,
+    /// Ids which were allocated but not claimed
+    fresh_auth: GhostMapAuth<u64, ()>,
+}
+
+pub struct ClientIdsIds {
+    pub next_perm_id: int,
+    pub fresh_id: Loc,
+}
+
+impl ClientIds {
+    #[verifier::type_invariant]
+    pub closed spec fn inv(self) -> bool {
+        &&& 0 < self.next_id@
+        &&& self.next_perm is Some ==> {
+            &&& self.next_perm->Some_0.id() == self.next_perm_id@
+            &&& self.next_perm->Some_0.value() == self.next_id@
+        }
+        &&& forall|id: u64| #[trigger]
+            self.fresh_auth@.contains_key(id) ==> 0 < id < self.next_id@
+    }
+
+    pub open spec fn ids(self) -> ClientIdsIds {
+        ClientIdsIds { next_perm_id: self.next_perm_id(), fresh_id: self.fresh_id() }
+    }
+
+    pub closed spec fn is_full(self) -> bool {
+        self.next_perm is Some
+    }
+
+    pub closed spec fn next_id(self) -> u64 {
+        self.next_id@
+    }
+
+    pub closed spec fn next_perm_id(self) -> int {
+        self.next_perm_id@
+    }
+
+    pub closed spec fn fresh_id(self) -> Loc {
+        self.fresh_auth.id()
+    }
+
+    pub closed spec fn fresh(self) -> Set<u64> {
+        self.fresh_auth@.dom()
+    }
+
+    /// Client 0 is reserved, so the generator should start at 1
+    pub proof fn new(tracked next_perm: PermissionU64) -> (tracked r: ClientIds)
+        requires
+            next_perm.value() == 1,
+        ensures
+            r.is_full(),
+            r.next_id() == 1,
+            r.next_perm_id() == next_perm.id(),
+            r.fresh().is_empty(),
+    {
+        let tracked (fresh_auth, _empty) = GhostMapAuth::new(Map::empty());
+        let ghost next_perm_id = next_perm.id();
+        ClientIds {
+            next_perm: Some(next_perm),
+            next_id: Ghost(1),
+            next_perm_id: Ghost(next_perm_id),
+            fresh_auth,
+        }
+    }
+
+    pub proof fn take_permission(tracked &mut self) -> (tracked r: PermissionU64)
+        requires
+            old(self).is_full(),
+        ensures
+            !final(self).is_full(),
+            final(self).ids() == old(self).ids(),
+            final(self).next_id() == old(self).next_id(),
+            final(self).fresh() == old(self).fresh(),
+            r.id() == old(self).next_perm_id(),
+            r.value() == old(self).next_id(),
+    {
+        use_type_invariant(&*self);
+        self.next_perm.tracked_take()
+    }
+
+    pub proof fn allocate(tracked &mut self, tracked next_perm: PermissionU64) -> (tracked r:
+        ClientIdToken)
+        requires
+            !old(self).is_full(),
+            next_perm.id() == old(self).next_perm_id(),
+            old(self).next_id() < next_perm.value(),
+        ensures
+            final(self).is_full(),
+            final(self).ids() == old(self).ids(),
+            final(self).next_id() == next_perm.value(),
+            final(self).fresh() == old(self).fresh().insert(r.key()),
+            !old(self).fresh().contains(r.key()),
+            r.id() == final(self).fresh_id(),
+            r.key() == next_perm.value() - 1,
+    {
+        use_type_invariant(&*self);
+        let ghost id = (next_perm.value() - 1) as u64;
+        self.next_id = Ghost(next_perm.value());
+        self.next_perm = Some(next_perm);
+        self.fresh_auth.insert(id, ())
+    }
+
+    pub proof fn return_permission(tracked &mut self, tracked next_perm: PermissionU64)
+        requires
+            !old(self).is_full(),
+            next_perm.id() == old(self).next_perm_id(),
+            next_perm.value() == old(self).next_id(),
+        ensures
+            final(self).is_full(),
+            final(self).ids() == old(self).ids(),
+            final(self).next_id() == old(self).next_id(),
+            final(self).fresh() == old(self).fresh(),
+    {
+        use_type_invariant(&*self);
+        self.next_perm = Some(next_perm);
+    }
+
+    pub proof fn claim(tracked &mut self, tracked token: ClientIdToken)
+        requires
+            token.id() == old(self).fresh_id(),
+        ensures
+            final(self).is_full() == old(self).is_full(),
+            final(self).ids() == old(self).ids(),
+            final(self).next_id() == old(self).next_id(),
+            final(self).fresh() == old(self).fresh().remove(token.key()),
+            old(self).fresh().contains(token.key()),
+            0 < token.key() < old(self).next_id(),
+    {
+        use_type_invariant(&*self);
+        token.agree(&self.fresh_auth);
+        self.fresh_auth.delete_points_to(token);
+    }
+
+    pub proof fn lemma_fresh(tracked &self)
+        ensures
+            forall|id: u64| #[trigger] self.fresh().contains(id) ==> 0 < id < self.next_id(),
+    {
+        use_type_invariant(self);
+    }
+}
+
+} // verus!
+impl Display for ExhaustedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ran out of client ids")
+    }
+}
@@ -1,3 +1,4 @@
+pub mod client_ids;
 pub mod map;
 pub mod monotonic;
 pub mod print;
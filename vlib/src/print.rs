@@ -1,5 +1,10 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
 use vstd::prelude::*;
 
+static QUIET: AtomicBool = AtomicBool::new(false);
+
 verus! {
 
 #[macro_export]
@@ -21,7 +26,7 @@ macro_rules! vprint {
 macro_rules! veprint {
     ($($arg:tt)*) => {
         #[cfg(not(verus_only))]
-        {
+        if !$crate::print::quiet() {
         use $crate::print::*;
         let s = format!($($arg)*);
         eprint(&s)
@@ -51,7 +56,7 @@ macro_rules! vprintln {
 macro_rules! veprintln {
     ($($arg:tt)*) => {
         #[cfg(not(verus_only))]
-        {
+        if !$crate::print::quiet() {
         use $crate::print::*;
         let s = format!($($arg)*);
         eprintln(&s)
@@ -62,6 +67,18 @@ macro_rules! veprintln {
     };
 }
 
+/// Silences `veprint!` and `veprintln!` (e.g., to keep the diagnostics off the hot paths of a
+/// benchmark): their arguments are not even formatted
+#[verifier::external_body]
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+#[verifier::external_body]
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
 #[verifier::external_body]
 pub fn print(s: &str) {
     print!("{s}");